@@ -272,7 +272,9 @@ if __name__ == "__main__":
     import time
     
     parser = argparse.ArgumentParser(description="Chatterbox TTS Inference (CLI)")
-    parser.add_argument("--text", type=str, required=True, help="Text to speak")
+    text_group = parser.add_mutually_exclusive_group(required=True)
+    text_group.add_argument("--text", type=str, help="Text to speak")
+    text_group.add_argument("--text-file", type=str, help="UTF-8 file with the text to speak (for text too long for the command line)")
     parser.add_argument("--output", type=str, default="output.wav", help="Output WAV file path")
     parser.add_argument("--ref_wav", type=str, help="Path to reference WAV file for cloning (optional)")
     parser.add_argument("--seed", type=int, default=123, help="Random seed")
@@ -283,6 +285,9 @@ if __name__ == "__main__":
     parser.add_argument("--model_gguf", type=str, default="s3gen-bf16.gguf", help="Path to S3Gen/Model GGUF file")
     
     args = parser.parse_args()
+    if args.text_file:
+        with open(args.text_file, encoding="utf-8") as f:
+            args.text = f.read()
     
     set_seed(args.seed)
     
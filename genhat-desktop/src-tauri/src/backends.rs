@@ -0,0 +1,255 @@
+// Facts about the resolved llama-server binary, probed by running it with
+// `--list-devices` (compute backends), `--version` (build info) and `--help` (features).
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::{AppHandle, Manager, State};
+
+use crate::{llama_exe, AppState};
+
+#[derive(Clone, serde::Serialize)]
+pub struct DeviceInfo {
+    /// llama.cpp device id, e.g. `CUDA0`, `Vulkan1`, `Metal`.
+    pub id: String,
+    pub description: String,
+    pub total_vram_mb: Option<u64>,
+    pub free_vram_mb: Option<u64>,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct BackendInfo {
+    /// `CPU`, `CUDA`, `Vulkan`, `Metal`, ... (device id without its index).
+    pub name: String,
+    pub devices: Vec<DeviceInfo>,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct LlamaVersion {
+    /// llama.cpp build number, e.g. `4567`.
+    pub build: Option<u32>,
+    /// Short commit hash of the build.
+    pub commit: Option<String>,
+    /// Compiler and target, e.g. `cc (GCC) 13.2.0 for x86_64-linux-gnu`.
+    pub built_with: Option<String>,
+    /// Backends the binary loaded or reported while starting (`CPU`, `CUDA`, ...).
+    pub features: Vec<String>,
+    /// The full `--version` output.
+    pub raw: String,
+}
+
+/// Server features, detected from the flags `--help` lists.
+#[derive(Clone, serde::Serialize)]
+pub struct ServerFeatures {
+    /// `--embedding`: `/v1/embeddings` for embedding models.
+    pub embeddings: bool,
+    /// `--reranking`: `/rerank` for reranker models.
+    pub reranking: bool,
+    /// `/infill` fill-in-the-middle for code models (builds with `--spm-infill`).
+    pub infill: bool,
+    /// `--mmproj`: image input through a multimodal projector.
+    pub multimodal: bool,
+}
+
+/// Last probe result, keyed by the binary it came from.
+pub type ProbeCache<T> = std::sync::Mutex<Option<(PathBuf, T)>>;
+
+/// Parses a device line such as `CUDA0: NVIDIA GeForce RTX 3080 (10240 MiB, 9010 MiB free)`.
+fn parse_device(line: &str) -> Option<DeviceInfo> {
+    let (id, rest) = line.trim().split_once(": ")?;
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let (description, memory) = match rest.rsplit_once(" (") {
+        Some((desc, mem)) if mem.ends_with(')') => (desc, Some(mem.trim_end_matches(')'))),
+        _ => (rest, None),
+    };
+    let mib = |part: &str| part.split_whitespace().next()?.parse::<u64>().ok();
+    let mut parts = memory.map(|m| m.split(", ")).into_iter().flatten();
+    Some(DeviceInfo {
+        id: id.to_string(),
+        description: description.trim().to_string(),
+        total_vram_mb: parts.next().and_then(mib),
+        free_vram_mb: parts.next().and_then(mib),
+    })
+}
+
+/// Groups the devices listed after `Available devices:` by backend. CPU is always present.
+fn parse_devices(output: &str) -> Vec<BackendInfo> {
+    let mut backends = vec![BackendInfo {
+        name: "CPU".to_string(),
+        devices: Vec::new(),
+    }];
+    let listing = output.lines().skip_while(|l| !l.trim_start().starts_with("Available devices"));
+    for device in listing.skip(1).filter_map(parse_device) {
+        let name = device.id.trim_end_matches(|c: char| c.is_ascii_digit()).to_string();
+        match backends.iter_mut().find(|b| b.name == name) {
+            Some(backend) => backend.devices.push(device),
+            None => backends.push(BackendInfo {
+                name,
+                devices: vec![device],
+            }),
+        }
+    }
+    backends
+}
+
+/// Parses `version: 4567 (1a2b3c4)` / `built with ... for ...` plus any backend-loading
+/// lines (`load_backend: loaded CUDA backend from ...`, `ggml_cuda_init: ...`).
+fn parse_version(output: &str) -> LlamaVersion {
+    let mut version = LlamaVersion {
+        build: None,
+        commit: None,
+        built_with: None,
+        features: Vec::new(),
+        raw: output.trim().to_string(),
+    };
+    for line in output.lines().map(str::trim) {
+        let feature = if let Some(rest) = line.strip_prefix("version:") {
+            let mut parts = rest.split_whitespace();
+            version.build = parts.next().and_then(|b| b.parse().ok());
+            version.commit = parts.next().map(|c| c.trim_matches(|ch| ch == '(' || ch == ')').to_string());
+            None
+        } else if let Some(rest) = line.strip_prefix("built with ") {
+            version.built_with = Some(rest.to_string());
+            None
+        } else if let Some(rest) = line.strip_prefix("load_backend: loaded ") {
+            rest.split(" backend").next()
+        } else if line.starts_with("ggml_cuda_init:") {
+            Some("CUDA")
+        } else if line.starts_with("ggml_metal") {
+            Some("Metal")
+        } else if line.starts_with("ggml_vulkan:") {
+            Some("Vulkan")
+        } else {
+            None
+        };
+        if let Some(name) = feature.filter(|n| !version.features.iter().any(|f| f == n)) {
+            version.features.push(name.to_string());
+        }
+    }
+    version
+}
+
+/// Runs the binary with one flag and returns stdout and stderr together:
+/// `--list-devices` prints to stdout, but ggml logs (and some builds' version info) go to stderr.
+fn run_probe(exe: &Path, flag: &str) -> Result<String, String> {
+    let output = Command::new(exe)
+        .arg(flag)
+        .current_dir(exe.parent().unwrap_or(Path::new(".")))
+        .output()
+        .map_err(|e| format!("Failed to run '{}': {}", exe.display(), e))?;
+    Ok(format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ))
+}
+
+/// Backends (with devices and VRAM where reported) the bundled llama-server supports.
+/// Probing spawns the binary, so the result is cached until the resolved binary changes.
+/// Falls back to CPU only if the binary can't be probed.
+#[tauri::command]
+pub async fn list_backends(state: State<'_, AppState>) -> Result<Vec<BackendInfo>, String> {
+    let exe = llama_exe(&state)?;
+    if let Some((cached_exe, backends)) = state.backends.lock().unwrap().as_ref() {
+        if *cached_exe == exe {
+            return Ok(backends.clone());
+        }
+    }
+
+    let probe_exe = exe.clone();
+    let probed = tauri::async_runtime::spawn_blocking(move || run_probe(&probe_exe, "--list-devices"))
+        .await
+        .map_err(|e| format!("Backend probe failed: {e}"))?;
+    match probed.map(|out| parse_devices(&out)) {
+        Ok(backends) => {
+            *state.backends.lock().unwrap() = Some((exe, backends.clone()));
+            Ok(backends)
+        }
+        Err(e) => {
+            eprintln!("Backend probe failed: {e}");
+            Ok(parse_devices(""))
+        }
+    }
+}
+
+/// `list_backends` for blocking callers: probes the binary when the cache is cold.
+pub(crate) fn backends(state: &AppState) -> Result<Vec<BackendInfo>, String> {
+    let exe = llama_exe(state)?;
+    let mut cache = state.backends.lock().unwrap();
+    match cache.as_ref() {
+        Some((cached_exe, backends)) if *cached_exe == exe => Ok(backends.clone()),
+        _ => {
+            let backends = parse_devices(&run_probe(&exe, "--list-devices")?);
+            *cache = Some((exe, backends.clone()));
+            Ok(backends)
+        }
+    }
+}
+
+/// GPU devices in llama.cpp's index order, which is what `--main-gpu` and `--tensor-split`
+/// refer to. Blocking: probes the binary when the cache is cold. Empty when none are found.
+pub(crate) fn gpu_devices(state: &AppState) -> Result<Vec<DeviceInfo>, String> {
+    Ok(backends(state)?.into_iter().filter(|b| b.name != "CPU").flat_map(|b| b.devices).collect())
+}
+
+/// `gpu_devices` probed afresh, for the VRAM free right now (the cache keeps what was free
+/// at its probe, e.g. while a previous model was loaded). Refreshes the cache. Blocking.
+pub(crate) fn probe_gpu_devices(state: &AppState) -> Result<Vec<DeviceInfo>, String> {
+    let exe = llama_exe(state)?;
+    let backends = parse_devices(&run_probe(&exe, "--list-devices")?);
+    *state.backends.lock().unwrap() = Some((exe, backends.clone()));
+    Ok(backends.into_iter().filter(|b| b.name != "CPU").flat_map(|b| b.devices).collect())
+}
+
+/// The resolved llama-server's `--version` info, cached per binary. Blocking: probes the
+/// binary when the cache is cold.
+pub(crate) fn llama_version(state: &AppState) -> Result<LlamaVersion, String> {
+    let exe = llama_exe(state)?;
+    if let Some((cached_exe, version)) = state.llama_version.lock().unwrap().as_ref() {
+        if *cached_exe == exe {
+            return Ok(version.clone());
+        }
+    }
+
+    let version = parse_version(&run_probe(&exe, "--version")?);
+    if version.build.is_none() {
+        return Err(format!("Could not parse llama-server version from:\n{}", version.raw));
+    }
+    *state.llama_version.lock().unwrap() = Some((exe, version.clone()));
+    Ok(version)
+}
+
+fn parse_features(help: &str) -> ServerFeatures {
+    let has = |flag: &str| help.split(|c: char| c.is_whitespace() || c == ',').any(|word| word == flag);
+    ServerFeatures {
+        embeddings: has("--embedding") || has("--embeddings"),
+        reranking: has("--reranking") || has("--rerank"),
+        infill: has("--spm-infill"),
+        multimodal: has("--mmproj"),
+    }
+}
+
+/// The resolved llama-server's features from `--help`, cached per binary. Blocking:
+/// probes the binary when the cache is cold.
+pub(crate) fn server_features(state: &AppState) -> Result<ServerFeatures, String> {
+    let exe = llama_exe(state)?;
+    if let Some((cached_exe, features)) = state.server_features.lock().unwrap().as_ref() {
+        if *cached_exe == exe {
+            return Ok(features.clone());
+        }
+    }
+
+    let features = parse_features(&run_probe(&exe, "--help")?);
+    *state.server_features.lock().unwrap() = Some((exe, features.clone()));
+    Ok(features)
+}
+
+/// Build number, commit, compiler and backends of the bundled llama-server, from
+/// `--version`. Cached until the resolved binary changes.
+#[tauri::command]
+pub async fn get_llama_version(app: AppHandle) -> Result<LlamaVersion, String> {
+    tauri::async_runtime::spawn_blocking(move || llama_version(&app.state::<AppState>()))
+        .await
+        .map_err(|e| format!("Version probe failed: {e}"))?
+}
@@ -0,0 +1,355 @@
+// Backup of the user's own data as one ZIP archive: settings.json (pins, aliases, categories,
+// …), <app data>/presets and <app data>/conversations. Models, caches, logs and crash
+// reports are left out. Entries are stored uncompressed (see `zip`), next to a genhat-backup.json
+// manifest; `import_app_data` accepts exactly that layout, so it reads what
+// `export_app_data` writes rather than arbitrary ZIP files.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::conversation::Conversation;
+use crate::presets::Preset;
+use crate::zip::{read_zip, write_zip};
+use crate::{settings, unix_millis, validate_output_path, write_file_atomic, AppState};
+
+/// Bumped when the layout changes incompatibly; archives with a newer format are rejected.
+const BACKUP_FORMAT: u32 = 1;
+
+const MANIFEST: &str = "genhat-backup.json";
+
+/// Largest archive `import_app_data` reads; real backups are far smaller.
+const MAX_IMPORT_BYTES: u64 = 1 << 30;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Manifest {
+    format: u32,
+    /// GenHat version that wrote the archive.
+    app_version: String,
+    /// Unix epoch milliseconds.
+    created_at: u64,
+}
+
+#[derive(serde::Serialize)]
+pub struct ImportSummary {
+    /// Restored files, as archive paths (`settings.json`, `presets/<name>.json`, …).
+    pub restored: Vec<String>,
+    /// Entries this version doesn't know (e.g. written by a newer GenHat), left out.
+    pub skipped: Vec<String>,
+    /// GenHat version that wrote the archive.
+    pub app_version: String,
+}
+
+/// What an archive entry restores.
+#[derive(Clone, Copy)]
+enum Kind {
+    Settings,
+    Preset,
+    Conversation,
+}
+
+/// The kind of a known entry name: `settings.json`, `presets/<name>.json` or
+/// `conversations/<id>.json`, with the character sets those modules accept as names.
+fn classify(name: &str) -> Option<Kind> {
+    if name == "settings.json" {
+        return Some(Kind::Settings);
+    }
+    let (dir, file) = name.split_once('/')?;
+    let (kind, allow_space) = match dir {
+        "presets" => (Kind::Preset, true),
+        "conversations" => (Kind::Conversation, false),
+        _ => return None,
+    };
+    let stem = file.strip_suffix(".json")?;
+    let valid = !stem.is_empty()
+        && stem
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || (allow_space && c == ' '));
+    valid.then_some(kind)
+}
+
+fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Cannot resolve app data directory: {e}"))
+}
+
+/// Blocking body of `export_app_data`.
+fn export(app: &AppHandle, dest: &Path, include_secrets: bool) -> Result<(), String> {
+    let dest = validate_output_path(dest)?;
+    let dir = data_dir(app)?;
+    // The in-memory settings are current even if settings.json was never written
+    let settings = app.state::<AppState>().settings.lock().unwrap().clone();
+    let zip = build_archive(&dir, settings, include_secrets)?;
+    write_file_atomic(&dest, &zip).map_err(|e| format!("Failed to write {}: {}", dest.display(), e))
+}
+
+/// The archive of `settings` and the presets and conversations under the data folder `dir`.
+fn build_archive(dir: &Path, mut settings: settings::Settings, include_secrets: bool) -> Result<Vec<u8>, String> {
+    let manifest = Manifest {
+        format: BACKUP_FORMAT,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: unix_millis(),
+    };
+    let mut entries = vec![(
+        MANIFEST.to_string(),
+        serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?,
+    )];
+    if !include_secrets {
+        if let Some(endpoint) = settings.remote_endpoint.as_mut() {
+            endpoint.api_key = None;
+        }
+    }
+    let settings = serde_json::to_vec_pretty(&settings).map_err(|e| e.to_string())?;
+    entries.push(("settings.json".to_string(), settings));
+    for folder in ["presets", "conversations"] {
+        let Ok(files) = std::fs::read_dir(dir.join(folder)) else {
+            continue;
+        };
+        let mut names: Vec<String> = files
+            .flatten()
+            .filter_map(|f| f.file_name().into_string().ok())
+            .map(|file| format!("{folder}/{file}"))
+            .filter(|name| classify(name).is_some())
+            .collect();
+        names.sort();
+        for name in names {
+            let path = dir.join(&name);
+            let data = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            entries.push((name, data));
+        }
+    }
+    write_zip(&entries)
+}
+
+/// Gives `imported` the API key of `current` when it points at the same endpoint but carries
+/// no key, as archives exported without secrets do, so a restore doesn't log the user out.
+/// A key is never handed to a different server. Returns whether it changed anything.
+fn keep_api_key(imported: &mut settings::Settings, current: &settings::Settings) -> bool {
+    let (Some(imported), Some(current)) = (imported.remote_endpoint.as_mut(), current.remote_endpoint.as_ref()) else {
+        return false;
+    };
+    if imported.api_key.is_some() || current.api_key.is_none() || imported.base_url != current.base_url {
+        return false;
+    }
+    imported.api_key = current.api_key.clone();
+    true
+}
+
+/// Blocking body of `import_app_data`: validates every entry before writing any.
+fn import(app: &AppHandle, src: &Path) -> Result<ImportSummary, String> {
+    let len = std::fs::metadata(src)
+        .map_err(|e| format!("Cannot open {}: {}", src.display(), e))?
+        .len();
+    if len > MAX_IMPORT_BYTES {
+        return Err(format!("{} is too large to be a GenHat backup", src.display()));
+    }
+    let bytes = std::fs::read(src).map_err(|e| format!("Failed to read {}: {}", src.display(), e))?;
+    let dir = data_dir(app)?;
+    let current = app.state::<AppState>().settings.lock().unwrap().clone();
+    let summary = unpack(src, &bytes, &dir, &current)?;
+    if summary.restored.iter().any(|name| name == "settings.json") {
+        *app.state::<AppState>().settings.lock().unwrap() = settings::load(app);
+    }
+    Ok(summary)
+}
+
+/// Restores the archive `bytes` (read from `src`, named in errors) into the data folder
+/// `dir`. `current` supplies the API key an archive exported without secrets lacks.
+fn unpack(src: &Path, bytes: &[u8], dir: &Path, current: &settings::Settings) -> Result<ImportSummary, String> {
+    let entries = read_zip(bytes, |_| true).map_err(|e| format!("{}: {}", src.display(), e))?;
+
+    let manifest = entries
+        .iter()
+        .find(|(name, _)| name == MANIFEST)
+        .ok_or_else(|| format!("{} is not a GenHat backup (no {MANIFEST})", src.display()))?;
+    let manifest: Manifest =
+        serde_json::from_slice(&manifest.1).map_err(|e| format!("Invalid {MANIFEST}: {e}"))?;
+    if manifest.format > BACKUP_FORMAT {
+        return Err(format!(
+            "The backup was made by GenHat {} (format {}); this version reads format {BACKUP_FORMAT} and older",
+            manifest.app_version, manifest.format
+        ));
+    }
+
+    // Unknown fields are ignored and missing ones defaulted, so older and newer files load
+    let mut restore = Vec::new();
+    let mut skipped = Vec::new();
+    for (name, data) in entries.iter().filter(|(name, _)| name != MANIFEST) {
+        let mut data = Cow::Borrowed(data.as_slice());
+        let checked = match classify(name) {
+            Some(Kind::Settings) => serde_json::from_slice::<settings::Settings>(&data).and_then(|mut imported| {
+                if keep_api_key(&mut imported, current) {
+                    data = Cow::Owned(serde_json::to_vec_pretty(&imported)?);
+                }
+                Ok(None)
+            }),
+            Some(Kind::Preset) => serde_json::from_slice::<Preset>(&data).map(|_| None),
+            Some(Kind::Conversation) => serde_json::from_slice::<Conversation>(&data).map(|c| Some(c.id)),
+            None => {
+                skipped.push(name.clone());
+                continue;
+            }
+        };
+        let id = checked.map_err(|e| format!("{name} in the backup is invalid: {e}"))?;
+        // The file name is the id conversations are opened by, so the two must agree
+        if let Some(id) = id.filter(|id| *name != format!("conversations/{id}.json")) {
+            return Err(format!("{name} in the backup holds conversation '{id}', not the one its name says"));
+        }
+        restore.push((name, data));
+    }
+
+    let mut restored = Vec::new();
+    for (name, data) in restore {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        write_file_atomic(&path, &data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        restored.push(name.clone());
+    }
+    Ok(ImportSummary {
+        restored,
+        skipped,
+        app_version: manifest.app_version,
+    })
+}
+
+/// Writes settings (with pins, aliases and categories), presets and conversations to one
+/// ZIP archive at `dest_path`, for a backup or a move to another machine. Models, caches,
+/// logs and crash reports are left out, and so is the remote endpoint's API key unless
+/// `include_secrets` is true: backups tend to end up in cloud folders and mail.
+#[tauri::command]
+pub async fn export_app_data(app: AppHandle, dest_path: String, include_secrets: Option<bool>) -> Result<(), String> {
+    let include_secrets = include_secrets.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || export(&app, Path::new(&dest_path), include_secrets))
+        .await
+        .map_err(|e| format!("Export failed: {e}"))?
+}
+
+/// Restores an `export_app_data` archive: files in it replace those of the same name, other
+/// presets and conversations are kept. Nothing is written unless every entry parses; entries
+/// this version doesn't know are skipped and listed. Archives from a newer backup format are
+/// rejected. The restored settings apply at once, except those read only at launch; a backup
+/// exported without secrets keeps the current API key if it names the same remote endpoint.
+#[tauri::command]
+pub async fn import_app_data(app: AppHandle, src_path: String) -> Result<ImportSummary, String> {
+    tauri::async_runtime::spawn_blocking(move || import(&app, Path::new(&src_path)))
+        .await
+        .map_err(|e| format!("Import failed: {e}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{RemoteEndpoint, Settings};
+
+    /// A fresh, empty folder under the system temp dir.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("genhat-backup-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn with_endpoint(base_url: &str, api_key: Option<&str>) -> Settings {
+        Settings {
+            remote_endpoint: Some(RemoteEndpoint {
+                base_url: base_url.to_string(),
+                api_key: api_key.map(str::to_string),
+                model: None,
+            }),
+            ..Settings::default()
+        }
+    }
+
+    fn archive(entries: &[(&str, &str)]) -> Vec<u8> {
+        let manifest = format!(r#"{{"format":{BACKUP_FORMAT},"app_version":"0.0.0","created_at":0}}"#);
+        let mut all = vec![(MANIFEST.to_string(), manifest.into_bytes())];
+        all.extend(entries.iter().map(|(name, data)| (name.to_string(), data.as_bytes().to_vec())));
+        write_zip(&all).unwrap()
+    }
+
+    #[test]
+    fn entry_names_are_classified() {
+        assert!(matches!(classify("settings.json"), Some(Kind::Settings)));
+        assert!(matches!(classify("presets/Fast chat.json"), Some(Kind::Preset)));
+        assert!(matches!(classify("conversations/abc-123_x.json"), Some(Kind::Conversation)));
+        for name in [
+            "conversations/with space.json",
+            "presets/.json",
+            "presets/x.txt",
+            "presets/../settings.json",
+            "presets/a/b.json",
+            "models/x.json",
+            "settings.json.bak",
+            "/settings.json",
+        ] {
+            assert!(classify(name).is_none(), "{name}");
+        }
+    }
+
+    #[test]
+    fn export_then_import_restores_the_same_files() {
+        let source = temp_dir("export");
+        std::fs::create_dir_all(source.join("presets")).unwrap();
+        std::fs::create_dir_all(source.join("conversations")).unwrap();
+        std::fs::write(source.join("presets/Fast.json"), r#"{"model":"m.gguf"}"#).unwrap();
+        std::fs::write(source.join("conversations/c1.json"), r#"{"id":"c1","messages":[]}"#).unwrap();
+        std::fs::write(source.join("presets/notes.txt"), "not a preset").unwrap();
+        let zip = build_archive(&source, with_endpoint("http://host/v1", Some("secret")), false).unwrap();
+        assert!(!String::from_utf8_lossy(&zip).contains("secret"));
+
+        let target = temp_dir("import");
+        let current = with_endpoint("http://host/v1", Some("kept"));
+        let summary = unpack(Path::new("backup.zip"), &zip, &target, &current).unwrap();
+        assert_eq!(summary.restored, ["settings.json", "presets/Fast.json", "conversations/c1.json"]);
+        assert!(summary.skipped.is_empty());
+        for file in ["presets/Fast.json", "conversations/c1.json"] {
+            assert_eq!(std::fs::read(source.join(file)).unwrap(), std::fs::read(target.join(file)).unwrap());
+        }
+        assert!(!target.join("presets/notes.txt").exists());
+        let settings: Settings = serde_json::from_slice(&std::fs::read(target.join("settings.json")).unwrap()).unwrap();
+        assert_eq!(settings.remote_endpoint.unwrap().api_key.as_deref(), Some("kept"));
+        let _ = std::fs::remove_dir_all(source);
+        let _ = std::fs::remove_dir_all(target);
+    }
+
+    #[test]
+    fn api_keys_stay_with_their_endpoint() {
+        let mut other = with_endpoint("http://other/v1", None);
+        assert!(!keep_api_key(&mut other, &with_endpoint("http://host/v1", Some("key"))));
+        assert!(other.remote_endpoint.unwrap().api_key.is_none());
+        let mut exported = with_endpoint("http://host/v1", Some("new"));
+        assert!(!keep_api_key(&mut exported, &with_endpoint("http://host/v1", Some("old"))));
+        assert_eq!(exported.remote_endpoint.unwrap().api_key.as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn bad_entries_fail_the_whole_import() {
+        let target = temp_dir("bad-entries");
+        let current = Settings::default();
+        let import = |entries: &[(&str, &str)]| unpack(Path::new("backup.zip"), &archive(entries), &target, &current);
+        let good = ("presets/Fast.json", r#"{"model":"m.gguf"}"#);
+        for bad in [
+            ("conversations/c1.json", r#"{"id":"c2","messages":[]}"#),
+            ("conversations/c1.json", r#"{"id":"c1"}"#),
+            ("presets/Slow.json", "{"),
+            ("settings.json", r#"{"autostart":"yes"}"#),
+        ] {
+            assert!(import(&[good, bad]).is_err(), "{}", bad.0);
+        }
+        assert!(std::fs::read_dir(&target).unwrap().next().is_none(), "nothing is written");
+
+        let summary = import(&[good, ("models/x.gguf", "GGUF")]).unwrap();
+        assert_eq!(summary.restored, ["presets/Fast.json"]);
+        assert_eq!(summary.skipped, ["models/x.gguf"]);
+
+        let newer = format!(r#"{{"format":{},"app_version":"9.0.0","created_at":0}}"#, BACKUP_FORMAT + 1);
+        let zip = write_zip(&[(MANIFEST.to_string(), newer.into_bytes())]).unwrap();
+        assert!(unpack(Path::new("backup.zip"), &zip, &target, &current).is_err());
+        let no_manifest = write_zip(&[("settings.json".to_string(), b"{}".to_vec())]).unwrap();
+        assert!(unpack(Path::new("backup.zip"), &no_manifest, &target, &current).is_err());
+        let _ = std::fs::remove_dir_all(target);
+    }
+}
@@ -0,0 +1,67 @@
+// One place to ask what this build can do: which bundled binaries resolve, the compute
+// backends, and the features of the llama-server binary. Built from the cached probes in
+// `backends`, so only the first call after a binary change spawns anything.
+
+use tauri::{AppHandle, Manager};
+
+use crate::backends::{self, ServerFeatures};
+use crate::{llama_exe, tts_exe, whisper_exe, AppState};
+
+#[derive(Clone, serde::Serialize)]
+pub struct Capabilities {
+    /// A llama-server binary resolves (`llm_available`).
+    pub llm: bool,
+    /// tts-inference resolves (`tts_available`).
+    pub tts: bool,
+    /// whisper-cli resolves, for `transcribe_audio`.
+    pub transcription: bool,
+    /// Backend names from `list_backends` (`CPU` first).
+    pub backends: Vec<String>,
+    /// At least one non-CPU device was found.
+    pub gpu: bool,
+    pub llama_build: Option<u32>,
+    /// `None` when there is no llama-server or it couldn't be probed.
+    pub server: Option<ServerFeatures>,
+    /// Probes that failed, so "unsupported" can be told apart from "unknown".
+    pub errors: Vec<String>,
+}
+
+/// The probe's value, or `None` with the error noted.
+fn noted<T>(errors: &mut Vec<String>, result: Result<T, String>) -> Option<T> {
+    result.map_err(|e| errors.push(e)).ok()
+}
+
+/// Blocking: may run the llama-server probes.
+fn gather(state: &AppState) -> Capabilities {
+    let mut errors = Vec::new();
+    let llm = llama_exe(state).is_ok();
+    let (backends, server, llama_build) = if llm {
+        (
+            noted(&mut errors, backends::backends(state)),
+            noted(&mut errors, backends::server_features(state)),
+            noted(&mut errors, backends::llama_version(state)).and_then(|v| v.build),
+        )
+    } else {
+        (None, None, None)
+    };
+    let backends = backends.unwrap_or_default();
+    Capabilities {
+        llm,
+        tts: tts_exe(state).is_ok(),
+        transcription: whisper_exe(state).is_ok(),
+        gpu: backends.iter().any(|b| b.name != "CPU" && !b.devices.is_empty()),
+        backends: backends.into_iter().map(|b| b.name).collect(),
+        llama_build,
+        server,
+        errors,
+    }
+}
+
+/// Feature detection for the frontend and third-party callers: bundled binaries, GPU
+/// backends and llama-server features (embeddings, reranking, infill, multimodal).
+#[tauri::command]
+pub async fn get_capabilities(app: AppHandle) -> Result<Capabilities, String> {
+    tauri::async_runtime::spawn_blocking(move || gather(&app.state::<AppState>()))
+        .await
+        .map_err(|e| format!("Capability probe failed: {e}"))
+}
@@ -0,0 +1,67 @@
+// Best-effort model role detection for grouping the model picker, from GGUF
+// architecture metadata plus filename patterns. Settings can override the result.
+
+use std::path::Path;
+
+use crate::{gguf, gguf_cache, AppState};
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelCategory {
+    Chat,
+    Code,
+    Embedding,
+    Vision,
+    Tts,
+    Unknown,
+}
+
+/// Encoder-only architectures that llama.cpp serves as embedding models.
+const EMBEDDING_ARCHS: &[&str] = &["bert", "nomic-bert", "jina-bert-v2", "t5encoder"];
+const CODE_ARCHS: &[&str] = &["starcoder", "starcoder2", "codeshell"];
+const TTS_ARCHS: &[&str] = &["wavtokenizer-dec", "outetts"];
+
+fn from_name(name: &str) -> Option<ModelCategory> {
+    let name = name.to_lowercase();
+    let has = |patterns: &[&str]| patterns.iter().any(|p| name.contains(p));
+    if name.starts_with("s3gen") || name.starts_with("t3_") || name.starts_with("ve_") || has(&["tts"]) {
+        Some(ModelCategory::Tts)
+    } else if has(&["embed", "bge-", "e5-", "minilm", "rerank"]) {
+        Some(ModelCategory::Embedding)
+    } else if has(&["mmproj", "llava", "vision", "-vl-", "-vl."]) {
+        Some(ModelCategory::Vision)
+    } else if has(&["coder", "codellama", "starcoder", "codegemma", "-code"]) {
+        Some(ModelCategory::Code)
+    } else {
+        None
+    }
+}
+
+fn from_metadata(meta: &gguf::GgufMetadata) -> Option<ModelCategory> {
+    let arch = meta.architecture()?;
+    Some(if EMBEDDING_ARCHS.contains(&arch) || meta.values.contains_key(&format!("{arch}.pooling_type")) {
+        ModelCategory::Embedding
+    } else if arch == "clip" {
+        ModelCategory::Vision
+    } else if CODE_ARCHS.contains(&arch) {
+        ModelCategory::Code
+    } else if TTS_ARCHS.contains(&arch) {
+        ModelCategory::Tts
+    } else {
+        // Any other decoder architecture is a general text model
+        ModelCategory::Chat
+    })
+}
+
+/// Definitive metadata (encoder, projector, code or TTS architecture) wins; otherwise a
+/// filename match refines a generic decoder (e.g. a llama-architecture "coder" model).
+pub fn detect(state: &AppState, path: &Path) -> ModelCategory {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let by_meta = gguf_cache::read_metadata(state, path).ok().and_then(|m| from_metadata(&m));
+    match (by_meta, from_name(&name)) {
+        (Some(meta), _) if meta != ModelCategory::Chat => meta,
+        (_, Some(by_name)) => by_name,
+        (Some(meta), None) => meta,
+        (None, None) => ModelCategory::Unknown,
+    }
+}
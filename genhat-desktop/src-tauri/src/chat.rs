@@ -0,0 +1,1437 @@
+// Chat proxy: forwards chat requests from the frontend to llama-server's
+// OpenAI-compatible /v1/chat/completions endpoint, and raw prompts to its native
+// /completion endpoint (no chat template) for base models and custom prompt formats.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+use crate::error::{self, AppError};
+use crate::metrics::{self, Timings};
+use crate::settings::{self, RemoteEndpoint};
+use crate::{conversation, queue, response_cache, slot_cache};
+use crate::AppState;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// Per-request sampling options. Unset fields fall back to the server's startup defaults.
+/// Field names match llama-server's request JSON, so this serializes straight into the body.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct GenParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<i32>,
+    /// RNG seed. Identical output also requires identical sampling params (and model/prompt);
+    /// the seed alone doesn't make a temperature-sampled request reproducible across setting changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// Drops tokens whose probability is below `min_p` × the top token's probability.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_p: Option<f32>,
+    /// 0 = off, 1 = Mirostat, 2 = Mirostat 2.0. Replaces top-k/top-p/min-p when enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirostat: Option<u8>,
+    /// Mirostat target entropy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirostat_tau: Option<f32>,
+    /// Mirostat learning rate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirostat_eta: Option<f32>,
+}
+
+#[derive(Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamKind {
+    Float,
+    Int,
+}
+
+/// One `GenParams` field as described by `get_param_schema`. The same table drives
+/// `GenParams::validate`, so the UI's ranges and the backend's checks can't drift apart.
+#[derive(Clone, serde::Serialize)]
+pub struct ParamDef {
+    pub name: &'static str,
+    pub kind: ParamKind,
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+    /// Value used when the field is unset; `None` means the sampler is off (or the seed random).
+    pub default: Option<f64>,
+    pub description: &'static str,
+}
+
+const fn def(
+    name: &'static str,
+    kind: ParamKind,
+    (min, max, step): (f64, f64, f64),
+    default: Option<f64>,
+    description: &'static str,
+) -> ParamDef {
+    ParamDef {
+        name,
+        kind,
+        min,
+        max,
+        step,
+        default,
+        description,
+    }
+}
+
+pub const PARAM_DEFS: &[ParamDef] = &[
+    def("temperature", ParamKind::Float, (0.0, 2.0, 0.05), Some(0.7), "Randomness; 0 always picks the most likely token"),
+    def("top_p", ParamKind::Float, (0.0, 1.0, 0.01), Some(0.9), "Sample from the smallest token set whose probability reaches top_p"),
+    def("top_k", ParamKind::Int, (0.0, 200.0, 1.0), Some(40.0), "Sample from the k most likely tokens; 0 disables"),
+    def("repeat_penalty", ParamKind::Float, (0.0, 2.0, 0.05), Some(1.1), "Penalty for repeating recent tokens; 1 disables"),
+    def("max_tokens", ParamKind::Int, (-1.0, 32768.0, 1.0), Some(256.0), "Maximum tokens to generate; -1 is unlimited"),
+    def("seed", ParamKind::Int, (-1.0, 4294967295.0, 1.0), None, "RNG seed for repeatable output; -1 is random"),
+    def("min_p", ParamKind::Float, (0.0, 1.0, 0.01), None, "Drop tokens below min_p times the top token's probability"),
+    def("mirostat", ParamKind::Int, (0.0, 2.0, 1.0), None, "0 = off, 1 = Mirostat, 2 = Mirostat 2.0"),
+    def("mirostat_tau", ParamKind::Float, (0.0, 10.0, 0.1), None, "Mirostat target entropy"),
+    def("mirostat_eta", ParamKind::Float, (0.001, 1.0, 0.001), None, "Mirostat learning rate"),
+];
+
+/// Looks up a parameter definition. Panics on an unknown name, which is a programming error.
+pub fn param_def(name: &str) -> &'static ParamDef {
+    PARAM_DEFS.iter().find(|d| d.name == name).expect("unknown parameter")
+}
+
+impl GenParams {
+    /// Rejects values outside the ranges in `PARAM_DEFS`.
+    pub fn validate(&self) -> Result<(), String> {
+        fn check(name: &str, value: Option<f64>) -> Result<(), String> {
+            let def = param_def(name);
+            match value {
+                Some(v) if !v.is_finite() || v < def.min || v > def.max => {
+                    Err(format!("{name} must be between {} and {} (got {v})", def.min, def.max))
+                }
+                _ => Ok(()),
+            }
+        }
+        check("temperature", self.temperature.map(f64::from))?;
+        check("top_p", self.top_p.map(f64::from))?;
+        check("top_k", self.top_k.map(f64::from))?;
+        check("repeat_penalty", self.repeat_penalty.map(f64::from))?;
+        check("max_tokens", self.max_tokens.map(f64::from))?;
+        check("seed", self.seed.map(|v| v as f64))?;
+        check("min_p", self.min_p.map(f64::from))?;
+        check("mirostat", self.mirostat.map(f64::from))?;
+        check("mirostat_tau", self.mirostat_tau.map(f64::from))?;
+        check("mirostat_eta", self.mirostat_eta.map(f64::from))?;
+        Ok(())
+    }
+
+    /// The `PARAM_DEFS` defaults: what llama-server is started with for unset fields.
+    /// Samplers without a default (seed, min-p, mirostat) stay unset, i.e. off.
+    pub fn builtin() -> GenParams {
+        let default = |name: &str| param_def(name).default;
+        GenParams {
+            temperature: default("temperature").map(|v| v as f32),
+            top_p: default("top_p").map(|v| v as f32),
+            top_k: default("top_k").map(|v| v as i32),
+            repeat_penalty: default("repeat_penalty").map(|v| v as f32),
+            max_tokens: default("max_tokens").map(|v| v as i32),
+            seed: default("seed").map(|v| v as i64),
+            min_p: default("min_p").map(|v| v as f32),
+            mirostat: default("mirostat").map(|v| v as u8),
+            mirostat_tau: default("mirostat_tau").map(|v| v as f32),
+            mirostat_eta: default("mirostat_eta").map(|v| v as f32),
+        }
+    }
+
+    /// Fills fields this request leaves unset from `defaults`.
+    pub fn or_defaults(self, defaults: &GenParams) -> GenParams {
+        GenParams {
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            top_k: self.top_k.or(defaults.top_k),
+            repeat_penalty: self.repeat_penalty.or(defaults.repeat_penalty),
+            max_tokens: self.max_tokens.or(defaults.max_tokens),
+            seed: self.seed.or(defaults.seed),
+            min_p: self.min_p.or(defaults.min_p),
+            mirostat: self.mirostat.or(defaults.mirostat),
+            mirostat_tau: self.mirostat_tau.or(defaults.mirostat_tau),
+            mirostat_eta: self.mirostat_eta.or(defaults.mirostat_eta),
+        }
+    }
+}
+
+/// Effective sampling defaults for a chat request that sets nothing: the live defaults
+/// (`update_generation_defaults`), then the params the running server was started with,
+/// then the built-in values. Before any server has started, the `max_tokens` setting
+/// stands in for the launch params, as it will at the next start.
+#[tauri::command]
+pub fn get_default_params(state: State<AppState>) -> GenParams {
+    let launched = state.server_config.lock().unwrap().as_ref().map(|c| c.params.clone()).unwrap_or_else(|| GenParams {
+        max_tokens: state.settings.lock().unwrap().max_tokens,
+        ..GenParams::default()
+    });
+    let live = state.generation_defaults.lock().unwrap().clone();
+    live.or_defaults(&launched).or_defaults(&GenParams::builtin())
+}
+
+/// Launch options llama-server only reads at startup (`LlamaConfig` fields). Every
+/// `GenParams` field is sent per request, so only these need a restart.
+pub const STARTUP_ONLY_PARAMS: &[&str] = &[
+    "ctx_size",
+    "gpu_layers",
+    "threads",
+    "batch_size",
+    "ubatch_size",
+    "lora",
+    "parallel_slots",
+    "keep_tokens",
+    "context_shift",
+    "main_gpu",
+    "tensor_split",
+    "chat_template",
+    "slot_save_path",
+    "rope_scaling",
+    "rope_freq_base",
+    "rope_freq_scale",
+    "mode",
+];
+
+/// Name, type, range, step and default of every sampling parameter, for generating
+/// settings controls. `GenParams::validate` enforces exactly these ranges.
+#[tauri::command]
+pub fn get_param_schema() -> Vec<ParamDef> {
+    PARAM_DEFS.to_vec()
+}
+
+pub(crate) fn completions_url(state: &AppState) -> String {
+    crate::llama_url(state, "/v1/chat/completions")
+}
+
+pub(crate) fn raw_completion_url(state: &AppState) -> String {
+    crate::llama_url(state, "/completion")
+}
+
+/// Prepends the configured system prompt unless the caller already supplied a system message.
+fn with_system_prompt(mut messages: Vec<ChatMessage>, system_prompt: Option<String>) -> Vec<ChatMessage> {
+    if let Some(prompt) = system_prompt {
+        if !messages.iter().any(|m| m.role == "system") {
+            messages.insert(
+                0,
+                ChatMessage {
+                    role: "system".into(),
+                    content: prompt,
+                },
+            );
+        }
+    }
+    messages
+}
+
+/// Applies a request's `n_predict` on top of its params. Validated as `max_tokens`, so the
+/// same -1..=32768 cap applies.
+fn with_n_predict(mut params: GenParams, n_predict: Option<i32>) -> GenParams {
+    if n_predict.is_some() {
+        params.max_tokens = n_predict;
+    }
+    params
+}
+
+/// Builds the /v1/chat/completions request body from messages and sampling params.
+pub(crate) fn request_body(messages: &[ChatMessage], params: &GenParams, stream: bool) -> serde_json::Value {
+    // Keep the slot's KV cache so a shared prefix (the system prompt) isn't re-evaluated
+    let mut body = serde_json::json!({
+        "messages": messages,
+        "stream": stream,
+        "cache_prompt": true,
+    });
+    if let (Some(body), Ok(serde_json::Value::Object(extra))) = (body.as_object_mut(), serde_json::to_value(params)) {
+        body.extend(extra);
+    }
+    body
+}
+
+/// Builds the native /completion request body for a raw prompt. The native API names the
+/// token limit `n_predict`.
+fn raw_completion_body(prompt: &str, params: &GenParams, stream: bool) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "prompt": prompt,
+        "stream": stream,
+        "cache_prompt": true,
+    });
+    if let (Some(body), Ok(serde_json::Value::Object(extra))) = (body.as_object_mut(), serde_json::to_value(params)) {
+        body.extend(extra);
+    }
+    if let Some(n) = params.max_tokens {
+        body["n_predict"] = serde_json::json!(n);
+    }
+    body
+}
+
+/// Converts `(token_id, bias)` pairs into the `{ "<id>": bias }` map llama-server accepts.
+fn logit_bias_map(bias: &[(i32, f32)]) -> Result<serde_json::Value, AppError> {
+    let mut map = serde_json::Map::new();
+    for &(token, value) in bias {
+        if !value.is_finite() {
+            return Err(AppError::InvalidInput(format!(
+                "logit_bias for token {token} must be a finite number (got {value})"
+            )));
+        }
+        map.insert(token.to_string(), serde_json::json!(value));
+    }
+    Ok(serde_json::Value::Object(map))
+}
+
+/// Why a generation was cancelled.
+#[derive(Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbortReason {
+    /// `abort_generation`.
+    User,
+    /// `force_switch_model` is replacing the server.
+    ModelSwitch,
+    /// The app is shutting down.
+    Shutdown,
+}
+
+/// Cancels one in-flight generation; `cancellable` waits on it.
+#[derive(Default)]
+pub struct AbortHandle {
+    notify: Notify,
+    reason: Mutex<Option<AbortReason>>,
+}
+
+impl AbortHandle {
+    /// The first reason given wins.
+    pub fn abort(&self, reason: AbortReason) {
+        self.reason.lock().unwrap().get_or_insert(reason);
+        self.notify.notify_one();
+    }
+
+    fn reason(&self) -> AbortReason {
+        self.reason.lock().unwrap().unwrap_or(AbortReason::User)
+    }
+}
+
+/// In-flight generations by request id, so `abort_generation` can cancel them.
+pub type Generations = Mutex<HashMap<String, Arc<AbortHandle>>>;
+
+/// Keeps a request id registered for the lifetime of a generation.
+struct GenerationGuard<'a> {
+    generations: &'a Generations,
+    id: Option<String>,
+}
+
+impl Drop for GenerationGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = &self.id {
+            self.generations.lock().unwrap().remove(id);
+        }
+    }
+}
+
+fn register_generation(
+    generations: &Generations,
+    id: Option<String>,
+) -> Result<(GenerationGuard<'_>, Option<Arc<AbortHandle>>), AppError> {
+    let handle = match &id {
+        Some(id) => {
+            let mut map = generations.lock().unwrap();
+            if map.contains_key(id) {
+                return Err(AppError::Busy(format!("A generation with request id {id} is already running")));
+            }
+            let handle = Arc::new(AbortHandle::default());
+            map.insert(id.clone(), handle.clone());
+            Some(handle)
+        }
+        None => None,
+    };
+    Ok((GenerationGuard { generations, id }, handle))
+}
+
+/// Runs `fut` until it completes or `abort` is notified. Aborting drops the future, which
+/// closes the HTTP connection; llama-server notices the disconnect, stops generating and
+/// releases the slot, so the next request starts from a clean slot.
+async fn cancellable<T>(
+    abort: Option<Arc<AbortHandle>>,
+    fut: impl Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    match abort {
+        None => fut.await,
+        Some(abort) => tokio::select! {
+            result = fut => result,
+            _ = abort.notify.notified() => Err(AppError::Cancelled("Generation aborted".to_string())),
+        },
+    }
+}
+
+pub(crate) fn not_reachable(e: reqwest::Error) -> AppError {
+    AppError::ServerNotRunning(format!("llama-server is not reachable: {e}"))
+}
+
+/// Why the server stopped generating, from the OpenAI `finish_reason` field.
+#[derive(Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// End of turn or a stop sequence matched.
+    Stop,
+    /// Hit the token limit (`max_tokens` / `n_predict`) or the context size; the response
+    /// is truncated and can be continued.
+    Length,
+    ToolCalls,
+    /// Any value this build doesn't know.
+    Other,
+}
+
+impl FinishReason {
+    fn from_response(choice: &serde_json::Value) -> Option<FinishReason> {
+        Some(match choice["finish_reason"].as_str()? {
+            "stop" => FinishReason::Stop,
+            "length" => FinishReason::Length,
+            "tool_calls" => FinishReason::ToolCalls,
+            _ => FinishReason::Other,
+        })
+    }
+
+    /// From a native /completion response or final stream chunk: `stop_type`, or the
+    /// `stopped_*` flags of older builds. `None` while generation hasn't stopped.
+    fn from_raw_completion(json: &serde_json::Value) -> Option<FinishReason> {
+        if let Some(stop_type) = json["stop_type"].as_str() {
+            return match stop_type {
+                "none" => None,
+                "eos" | "word" => Some(FinishReason::Stop),
+                "limit" => Some(FinishReason::Length),
+                _ => Some(FinishReason::Other),
+            };
+        }
+        if !json["stop"].as_bool()? {
+            return None;
+        }
+        let flag = |name: &str| json[name].as_bool().unwrap_or(false);
+        Some(if flag("stopped_limit") || flag("truncated") {
+            FinishReason::Length
+        } else if flag("stopped_eos") || flag("stopped_word") {
+            FinishReason::Stop
+        } else {
+            FinishReason::Other
+        })
+    }
+}
+
+/// A finished response as read from llama-server.
+struct Reply {
+    text: String,
+    timings: Option<Timings>,
+    finish_reason: Option<FinishReason>,
+    /// Tokens/sec `relay_stream` measured over the content deltas; `None` when not streamed.
+    measured_tps: Option<f64>,
+}
+
+async fn send_chat(state: &AppState, body: &serde_json::Value) -> Result<Reply, AppError> {
+    let resp = reqwest::Client::new()
+        .post(completions_url(state))
+        .json(body)
+        .send()
+        .await
+        .map_err(not_reachable)?;
+    read_reply(resp, "llama-server").await
+}
+
+/// Whether there is no local model to answer with: none running, starting or switching, and
+/// none in the models dir to start.
+fn no_local_model(state: &AppState) -> bool {
+    if state.llama.lock().unwrap().is_some() || local_starting(state) {
+        return false;
+    }
+    crate::startup_model(&state.settings.lock().unwrap()).is_none()
+}
+
+/// A local model is being started or switched, so a missing server is about to come back.
+fn local_starting(state: &AppState) -> bool {
+    state.switching.load(Ordering::SeqCst) || state.loading.lock().unwrap().is_some()
+}
+
+/// The endpoint to retry a request with after the local model failed with `error`: only when
+/// no local server is there to answer (`ServerNotRunning` with no llama child, and none
+/// starting or switching). Any other local error, such as a 4xx for a prompt over the context,
+/// is returned as it is, so the prompt doesn't leave the machine.
+fn fallback_after(state: &AppState, endpoint: Option<RemoteEndpoint>, error: AppError) -> Result<RemoteEndpoint, AppError> {
+    let server_gone = matches!(error, AppError::ServerNotRunning(_))
+        && state.llama.lock().unwrap().is_none()
+        && !local_starting(state);
+    match endpoint {
+        Some(endpoint) if server_gone => {
+            eprintln!("No local llama-server ({error}); using the remote endpoint");
+            Ok(endpoint)
+        }
+        _ => Err(error),
+    }
+}
+
+/// Answers from the remote endpoint. No local metrics, cache or queue: none of them describe
+/// the remote server.
+async fn complete_remote(
+    state: &AppState,
+    endpoint: &RemoteEndpoint,
+    body: serde_json::Value,
+    request_id: Option<String>,
+) -> Result<VerboseCompletion, AppError> {
+    let (_guard, abort) = register_generation(&state.generations, request_id)?;
+    let reply = cancellable(abort, send_remote(endpoint, body)).await?;
+    Ok(VerboseCompletion {
+        text: reply.text,
+        timings: reply.timings,
+        finish_reason: reply.finish_reason,
+        cached: false,
+        remote: true,
+    })
+}
+
+async fn send_remote(endpoint: &RemoteEndpoint, mut body: serde_json::Value) -> Result<Reply, AppError> {
+    // llama.cpp-only; strict OpenAI-compatible servers reject unknown fields
+    if let Some(body) = body.as_object_mut() {
+        body.remove("cache_prompt");
+    }
+    if let Some(model) = &endpoint.model {
+        body["model"] = serde_json::json!(model);
+    }
+    let url = format!("{}/chat/completions", endpoint.base_url);
+    let mut request = reqwest::Client::new().post(&url).json(&body);
+    if let Some(key) = &endpoint.api_key {
+        request = request.bearer_auth(key);
+    }
+    let resp = request
+        .send()
+        .await
+        .map_err(|e| AppError::ServerNotRunning(format!("Remote endpoint {url} is not reachable: {e}")))?;
+    read_reply(resp, "the remote endpoint").await
+}
+
+/// Parses a non-streamed completion; `server` names the responder in errors.
+async fn read_reply(resp: reqwest::Response, server: &str) -> Result<Reply, AppError> {
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(AppError::ServerError(format!("{server} returned {status}: {text}")));
+    }
+
+    let json: serde_json::Value = resp.json().await.map_err(|e| AppError::ServerError(format!("Invalid response from {server}: {e}")))?;
+    let text = json["choices"][0]["message"]["content"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| AppError::ServerError(format!("{server} response had no message content")))?;
+    Ok(Reply {
+        text,
+        timings: Timings::from_response(&json),
+        finish_reason: FinishReason::from_response(&json["choices"][0]),
+        measured_tps: None,
+    })
+}
+
+async fn send_raw_completion(state: &AppState, body: &serde_json::Value) -> Result<Reply, AppError> {
+    let resp = reqwest::Client::new()
+        .post(raw_completion_url(state))
+        .json(body)
+        .send()
+        .await
+        .map_err(not_reachable)?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(AppError::ServerError(format!("llama-server returned {status}: {text}")));
+    }
+
+    let json: serde_json::Value = resp.json().await.map_err(|e| AppError::ServerError(format!("Invalid response from llama-server: {e}")))?;
+    let text = json["content"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| AppError::ServerError("llama-server response had no content".to_string()))?;
+    Ok(Reply {
+        text,
+        timings: Timings::from_response(&json),
+        finish_reason: FinishReason::from_raw_completion(&json),
+        measured_tps: None,
+    })
+}
+
+/// Result of `chat_completion_verbose`.
+#[derive(serde::Serialize)]
+pub struct VerboseCompletion {
+    pub text: String,
+    /// llama-server's timing breakdown; `None` for cached responses or builds that don't report it.
+    pub timings: Option<Timings>,
+    /// `None` for cached responses or when the server didn't say.
+    pub finish_reason: Option<FinishReason>,
+    /// Served from the response cache without contacting the server.
+    pub cached: bool,
+    /// Answered by the `remote_endpoint` fallback rather than the local llama-server.
+    pub remote: bool,
+}
+
+/// Shared body of `chat_completion` and `chat_completion_verbose`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn complete_chat(
+    app: &AppHandle,
+    state: &AppState,
+    messages: Vec<ChatMessage>,
+    params: Option<GenParams>,
+    cache: Option<bool>,
+    logit_bias: Option<Vec<(i32, f32)>>,
+    request_id: Option<String>,
+    n_predict: Option<i32>,
+) -> Result<VerboseCompletion, AppError> {
+    let system_prompt = state.settings.lock().unwrap().system_prompt.clone();
+    let messages = with_system_prompt(messages, system_prompt);
+    let defaults = state.generation_defaults.lock().unwrap().clone();
+    let params = with_n_predict(params.unwrap_or_default().or_defaults(&defaults), n_predict);
+    params.validate().map_err(AppError::InvalidInput)?;
+    // The remote endpoint only stands in when there's no local model, or no local server runs
+    let endpoint = state.settings.lock().unwrap().remote_endpoint.clone();
+    let mut remote = endpoint.clone().filter(|_| no_local_model(state));
+    if remote.is_none() {
+        if let Err(e) = crate::ensure_llama_started(app).await {
+            remote = Some(fallback_after(state, endpoint.clone(), e)?);
+        }
+    }
+
+    let mut body = request_body(&messages, &params, false);
+    if let Some(bias) = logit_bias.as_deref().filter(|b| !b.is_empty()) {
+        body["logit_bias"] = logit_bias_map(bias)?;
+    }
+
+    if let Some(endpoint) = remote {
+        return complete_remote(state, &endpoint, body, request_id).await;
+    }
+
+    let server_config = state.server_config.lock().unwrap().clone();
+    let extra_args = state.settings.lock().unwrap().llama_extra_args.clone();
+    let cache_key = match state.current_model.lock().unwrap().as_deref() {
+        Some(model) if cache.unwrap_or(false) && response_cache::is_deterministic(&params) => {
+            Some(response_cache::cache_key(model, server_config.as_ref(), &extra_args, &body))
+        }
+        _ => None,
+    };
+    if let Some(hit) = cache_key.as_deref().and_then(|key| response_cache::get(app, key)) {
+        return Ok(VerboseCompletion {
+            text: hit,
+            timings: None,
+            finish_reason: None,
+            cached: true,
+            remote: false,
+        });
+    }
+
+    let reply = {
+        let (_guard, abort) = register_generation(&state.generations, request_id.clone())?;
+        cancellable(abort, async {
+            let _ticket = queue::enter(app, state, request_id.as_deref()).await;
+            send_chat(state, &body).await
+        })
+        .await
+    };
+    let reply = match reply {
+        Ok(reply) => reply,
+        Err(e) => return complete_remote(state, &fallback_after(state, endpoint, e)?, body, request_id).await,
+    };
+    if let Some(t) = &reply.timings {
+        metrics::record(state, false, t);
+    }
+
+    if let Some(key) = &cache_key {
+        if let Err(e) = response_cache::put(app, key, &reply.text) {
+            eprintln!("Failed to cache response: {e}");
+        }
+    }
+    Ok(VerboseCompletion {
+        text: reply.text,
+        timings: reply.timings,
+        finish_reason: reply.finish_reason,
+        cached: false,
+        remote: false,
+    })
+}
+
+/// Non-streaming chat request. With `cache: true` and deterministic sampling
+/// (temperature 0 or a fixed seed), identical requests against the same model are served from disk.
+/// `logit_bias` pairs a token id (see `tokenize`) with a bias; large negative values (e.g. -100) ban it.
+/// Passing a `request_id` makes the request cancelable through `abort_generation`.
+/// `n_predict` caps this response's tokens (-1 = unlimited), overriding `params.max_tokens`
+/// and the server's startup default. With a `remote_endpoint` configured, the request goes
+/// there when there is no local model at all, or when no local llama-server is running to
+/// answer (but not while one is starting or switching); other local errors are returned.
+/// `chat_completion_verbose` reports `remote: true`.
+// Each option is a separate IPC argument so existing callers keep working.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn chat_completion(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    messages: Vec<ChatMessage>,
+    params: Option<GenParams>,
+    cache: Option<bool>,
+    logit_bias: Option<Vec<(i32, f32)>>,
+    request_id: Option<String>,
+    n_predict: Option<i32>,
+) -> Result<String, AppError> {
+    let result = complete_chat(&app, &state, messages, params, cache, logit_bias, request_id, n_predict).await;
+    error::report(&app, "chat_completion", result.map(|c| c.text))
+}
+
+/// `chat_completion` with the same arguments, also returning llama-server's `timings`
+/// (prompt / generation token counts, milliseconds and tokens per second) and why
+/// generation stopped (`finish_reason`; `length` means truncated).
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn chat_completion_verbose(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    messages: Vec<ChatMessage>,
+    params: Option<GenParams>,
+    cache: Option<bool>,
+    logit_bias: Option<Vec<(i32, f32)>>,
+    request_id: Option<String>,
+    n_predict: Option<i32>,
+) -> Result<VerboseCompletion, AppError> {
+    let result = complete_chat(&app, &state, messages, params, cache, logit_bias, request_id, n_predict).await;
+    error::report(&app, "chat_completion_verbose", result)
+}
+
+/// True when `stored` (a conversation's model: a path, or just a file name) names `current`.
+fn same_model(stored: &str, current: &str) -> bool {
+    let (stored, current) = (std::path::Path::new(stored), std::path::Path::new(current));
+    stored == current || (stored.parent().map_or(true, |p| p.as_os_str().is_empty()) && stored.file_name() == current.file_name())
+}
+
+/// Continues a stored conversation whose last message is a truncated assistant reply
+/// (`finish_reason: length`). The history is sent with that partial message last, which
+/// llama-server treats as a prefill and extends; the continuation is returned and also
+/// appended to the stored message, so calling again continues further. Fails with
+/// `model_changed` when another model is loaded than the conversation's `model`.
+#[tauri::command]
+pub async fn continue_generation(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    conversation_id: String,
+    params: Option<GenParams>,
+    request_id: Option<String>,
+) -> Result<VerboseCompletion, AppError> {
+    let result = async {
+        let mut stored = conversation::read_conversation(&app, &conversation_id).map_err(AppError::InvalidInput)?;
+        if stored.messages.last().map_or(true, |m| m.role != "assistant") {
+            return Err(AppError::InvalidInput(format!(
+                "Conversation {conversation_id} doesn't end with an assistant message to continue"
+            )));
+        }
+        crate::ensure_llama_started(&app).await?;
+        let current = state.current_model.lock().unwrap().clone();
+        if let (Some(model), Some(current)) = (&stored.model, &current) {
+            if !same_model(model, current) {
+                return Err(AppError::ModelChanged(format!(
+                    "Conversation {conversation_id} was generated with {model}, but {current} is loaded; switch back to continue it"
+                )));
+            }
+        }
+
+        let continuation = complete_chat(&app, &state, stored.messages.clone(), params, None, None, request_id, None).await?;
+        if let Some(last) = stored.messages.last_mut() {
+            last.content.push_str(&continuation.text);
+        }
+        conversation::write_conversation(&app, stored).map_err(AppError::Io)?;
+        Ok(continuation)
+    }
+    .await;
+    error::report(&app, "continue_generation", result)
+}
+
+/// Forwards an OpenAI-style request body to `/v1/chat/completions` verbatim (no system
+/// prompt, defaults or validation) and returns the server's JSON response as-is. The
+/// escape hatch for fields the typed commands don't wrap; streaming isn't supported.
+#[tauri::command]
+pub async fn chat_completion_raw(app: AppHandle, body: serde_json::Value) -> Result<serde_json::Value, AppError> {
+    let result = async {
+        if !body.is_object() {
+            return Err(AppError::InvalidInput("Request body must be a JSON object".to_string()));
+        }
+        if body.get("stream").and_then(|v| v.as_bool()) == Some(true) {
+            return Err(AppError::InvalidInput(
+                "chat_completion_raw doesn't stream; use chat_completion_stream".to_string(),
+            ));
+        }
+        crate::ensure_llama_started(&app).await?;
+
+        let state = app.state::<AppState>();
+        let _ticket = queue::enter(&app, &state, None).await;
+        let resp = reqwest::Client::new()
+            .post(completions_url(&state))
+            .json(&body)
+            .send()
+            .await
+            .map_err(not_reachable)?;
+        let status = resp.status();
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| AppError::ServerError(format!("Failed to read llama-server response: {e}")))?;
+        if !status.is_success() {
+            return Err(AppError::ServerError(format!("llama-server returned {status}: {text}")));
+        }
+        serde_json::from_str(&text).map_err(|e| AppError::ServerError(format!("Invalid response from llama-server: {e}")))
+    }
+    .await;
+    error::report(&app, "chat_completion_raw", result)
+}
+
+/// Sends `prompt` as-is to llama-server's native `/completion` endpoint and returns the
+/// continuation: no chat template, system prompt or remote fallback, for base models and
+/// custom prompt formats. `params` and the live generation defaults apply as in chat
+/// (`max_tokens` becomes `n_predict`). Passing a `request_id` makes it cancelable through
+/// `abort_generation`.
+#[tauri::command]
+pub async fn complete(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    prompt: String,
+    params: Option<GenParams>,
+    request_id: Option<String>,
+) -> Result<String, AppError> {
+    let result = async {
+        let defaults = state.generation_defaults.lock().unwrap().clone();
+        let params = params.unwrap_or_default().or_defaults(&defaults);
+        params.validate().map_err(AppError::InvalidInput)?;
+        crate::ensure_llama_started(&app).await?;
+        let body = raw_completion_body(&prompt, &params, false);
+
+        let queue_id = request_id.clone();
+        let (_guard, abort) = register_generation(&state.generations, request_id)?;
+        let reply = cancellable(abort, async {
+            let _ticket = queue::enter(&app, &state, queue_id.as_deref()).await;
+            send_raw_completion(&state, &body).await
+        })
+        .await?;
+        if let Some(t) = &reply.timings {
+            metrics::record(&state, false, t);
+        }
+        Ok(reply.text)
+    }
+    .await;
+    error::report(&app, "complete", result)
+}
+
+/// Next `data:` payload among the complete lines in `buffer`; other SSE lines are dropped.
+/// The buffer holds raw bytes and only complete lines are decoded, so a UTF-8 character
+/// split across network chunks stays intact.
+pub(crate) fn next_sse_payload(buffer: &mut Vec<u8>) -> Option<String> {
+    while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+        let line: Vec<u8> = buffer.drain(..=newline).collect();
+        if let Some(payload) = String::from_utf8_lossy(&line).trim().strip_prefix("data:") {
+            return Some(payload.trim().to_string());
+        }
+    }
+    None
+}
+
+/// One SSE chunk of `chat_completion_stream`, keeping the structure of the OpenAI `delta`
+/// instead of only its text. Fields the chunk doesn't carry are `None`.
+#[derive(Clone, serde::Serialize)]
+pub struct StreamDelta {
+    pub request_id: String,
+    /// Sent once, on the first chunk (`assistant`).
+    pub role: Option<String>,
+    pub content: Option<String>,
+    /// Fragments of function calls; a call's `arguments` arrive in pieces across chunks.
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+    /// Set on the last chunk of the choice.
+    pub finish_reason: Option<FinishReason>,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct ToolCallDelta {
+    /// Which call the fragment belongs to, for accumulating parallel calls.
+    pub index: u64,
+    /// Only on a call's first fragment.
+    pub id: Option<String>,
+    /// Only on a call's first fragment.
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+impl StreamDelta {
+    /// `None` for chunks without any delta fields (e.g. the final usage/timings chunk).
+    fn from_chunk(request_id: &str, json: &serde_json::Value) -> Option<StreamDelta> {
+        let choice = &json["choices"][0];
+        let delta = &choice["delta"];
+        let text = |v: &serde_json::Value| v.as_str().map(str::to_string);
+        let tool_calls = delta["tool_calls"].as_array().map(|calls| {
+            calls
+                .iter()
+                .enumerate()
+                .map(|(i, call)| ToolCallDelta {
+                    index: call["index"].as_u64().unwrap_or(i as u64),
+                    id: text(&call["id"]),
+                    name: text(&call["function"]["name"]),
+                    arguments: text(&call["function"]["arguments"]),
+                })
+                .collect()
+        });
+        let parsed = StreamDelta {
+            request_id: request_id.to_string(),
+            role: text(&delta["role"]),
+            // The role chunk often carries an empty `content`
+            content: text(&delta["content"]).filter(|c| !c.is_empty()),
+            tool_calls,
+            finish_reason: FinishReason::from_response(choice),
+        };
+        let empty = parsed.role.is_none()
+            && parsed.content.is_none()
+            && parsed.tool_calls.is_none()
+            && parsed.finish_reason.is_none();
+        (!empty).then_some(parsed)
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+struct ChatDone {
+    request_id: String,
+    text: String,
+    finish_reason: Option<FinishReason>,
+    /// Average generation speed: llama-server's `predicted_per_second`, else the rate
+    /// measured while relaying.
+    tokens_per_sec: Option<f64>,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct GenerationSpeed {
+    request_id: String,
+    /// Content and tool-call deltas so far; llama-server streams one per token.
+    tokens: u64,
+    tokens_per_sec: f64,
+}
+
+/// How often `generation-speed` is emitted while streaming.
+const SPEED_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Live throughput over streamed tokens, timed from the first one so prompt processing
+/// doesn't drag the rate down.
+#[derive(Default)]
+struct SpeedMeter {
+    first: Option<Instant>,
+    last_emit: Option<Instant>,
+    tokens: u64,
+}
+
+impl SpeedMeter {
+    /// Counts a token; returns the current rate when an update is due.
+    fn tick(&mut self) -> Option<f64> {
+        let now = Instant::now();
+        let first = *self.first.get_or_insert(now);
+        self.tokens += 1;
+        let due = self.last_emit.map_or(now - first >= SPEED_INTERVAL, |last| now - last >= SPEED_INTERVAL);
+        let rate = self.rate(now).filter(|_| due)?;
+        self.last_emit = Some(now);
+        Some(rate)
+    }
+
+    /// Tokens after the first over the time since it; `None` before there are two.
+    fn rate(&self, now: Instant) -> Option<f64> {
+        let secs = (now - self.first?).as_secs_f64();
+        (self.tokens > 1 && secs > 0.0).then(|| (self.tokens - 1) as f64 / secs)
+    }
+}
+
+/// Reads llama-server's SSE stream, emitting a `chat-delta` event (`StreamDelta`) per chunk and
+/// a `generation-speed` event every `SPEED_INTERVAL`. Returns the concatenated text with the
+/// `timings` and `finish_reason` of the final chunks. The text is also collected in
+/// `partial`, which outlives the future when an abort drops it.
+async fn relay_stream(
+    app: &AppHandle,
+    request_id: &str,
+    body: &serde_json::Value,
+    partial: &Mutex<String>,
+) -> Result<Reply, AppError> {
+    let mut resp = reqwest::Client::new()
+        .post(completions_url(&app.state::<AppState>()))
+        .json(body)
+        .send()
+        .await
+        .map_err(not_reachable)?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(AppError::ServerError(format!("llama-server returned {status}: {text}")));
+    }
+
+    let mut buffer = Vec::new();
+    let mut reply = Reply {
+        text: String::new(),
+        timings: None,
+        finish_reason: None,
+        measured_tps: None,
+    };
+    let mut meter = SpeedMeter::default();
+    while let Some(chunk) = resp.chunk().await.map_err(|e| AppError::ServerError(format!("Stream interrupted: {e}")))? {
+        buffer.extend_from_slice(&chunk);
+
+        while let Some(payload) = next_sse_payload(&mut buffer) {
+            if payload == "[DONE]" {
+                reply.measured_tps = meter.rate(Instant::now());
+                return Ok(reply);
+            }
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(&payload) else { continue };
+            if let Some(t) = Timings::from_response(&json) {
+                reply.timings = Some(t);
+            }
+            let Some(delta) = StreamDelta::from_chunk(request_id, &json) else { continue };
+            if delta.finish_reason.is_some() {
+                reply.finish_reason = delta.finish_reason;
+            }
+            let generated = delta.content.is_some() || delta.tool_calls.is_some();
+            if let Some(content) = &delta.content {
+                reply.text.push_str(content);
+                partial.lock().unwrap().push_str(content);
+            }
+            let _ = app.emit("chat-delta", delta);
+            if generated {
+                if let Some(tokens_per_sec) = meter.tick() {
+                    let _ = app.emit(
+                        "generation-speed",
+                        GenerationSpeed {
+                            request_id: request_id.to_string(),
+                            tokens: meter.tokens,
+                            tokens_per_sec,
+                        },
+                    );
+                }
+            }
+        }
+    }
+    reply.measured_tps = meter.rate(Instant::now());
+    Ok(reply)
+}
+
+/// Streaming chat request. Chunks arrive as `chat-delta` events (`StreamDelta`: role,
+/// content fragment, tool-call fragments, finish reason) tagged with `request_id`,
+/// followed by `chat-done` with the full text (also the return value), `finish_reason` and
+/// the average `tokens_per_sec`; `generation-speed` events report the live rate meanwhile.
+/// Cancel with `abort_generation(request_id)`: the command rejects with `cancelled` after a
+/// `chat-aborted` event carrying the text generated so far, which is also appended to
+/// conversation `conversation_id` when given (see `save_partial`).
+#[tauri::command]
+pub async fn chat_completion_stream(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    request_id: String,
+    messages: Vec<ChatMessage>,
+    params: Option<GenParams>,
+    n_predict: Option<i32>,
+    conversation_id: Option<String>,
+) -> Result<String, AppError> {
+    let result: Result<String, AppError> = async {
+        let history = messages.clone();
+        let system_prompt = state.settings.lock().unwrap().system_prompt.clone();
+        let messages = with_system_prompt(messages, system_prompt);
+        let defaults = state.generation_defaults.lock().unwrap().clone();
+        let params = with_n_predict(params.unwrap_or_default().or_defaults(&defaults), n_predict);
+        params.validate().map_err(AppError::InvalidInput)?;
+        crate::ensure_llama_started(&app).await?;
+        let body = request_body(&messages, &params, true);
+
+        let (_guard, abort) = register_generation(&state.generations, Some(request_id.clone()))?;
+        let partial = Mutex::new(String::new());
+        let reply = cancellable(abort.clone(), async {
+            let _ticket = queue::enter(&app, &state, Some(&request_id)).await;
+            relay_stream(&app, &request_id, &body, &partial).await
+        })
+        .await;
+        let reply = match reply {
+            Err(AppError::Cancelled(msg)) => {
+                let text = partial.into_inner().unwrap();
+                let saved = match conversation_id.filter(|_| !text.is_empty()) {
+                    Some(id) => save_partial(&app, &state, id, history, &text)
+                        .map_err(|e| eprintln!("Failed to save the partial response: {e}"))
+                        .ok(),
+                    None => None,
+                };
+                let _ = app.emit(
+                    "chat-aborted",
+                    ChatAborted {
+                        request_id,
+                        text,
+                        reason: abort.map_or(AbortReason::User, |a| a.reason()),
+                        conversation_id: saved,
+                    },
+                );
+                return Err(AppError::Cancelled(msg));
+            }
+            other => other?,
+        };
+        if let Some(t) = &reply.timings {
+            metrics::record(&state, true, t);
+        }
+
+        let _ = app.emit(
+            "chat-done",
+            ChatDone {
+                request_id,
+                text: reply.text.clone(),
+                finish_reason: reply.finish_reason,
+                tokens_per_sec: reply.timings.as_ref().map(|t| t.predicted_per_second).or(reply.measured_tps),
+            },
+        );
+        Ok(reply.text)
+    }
+    .await;
+    error::report(&app, "chat_completion_stream", result)
+}
+
+#[derive(Clone, serde::Serialize)]
+struct ChatAborted {
+    request_id: String,
+    /// Text streamed before the abort.
+    text: String,
+    reason: AbortReason,
+    /// Set when the partial reply was saved to this conversation.
+    conversation_id: Option<String>,
+}
+
+/// Appends an aborted reply to conversation `id` as an assistant message, so it can be
+/// reopened or extended with `continue_generation`. The request's last message is added
+/// first when the stored conversation doesn't end with it yet; a conversation that was
+/// never saved is created from `history`. Returns the conversation id.
+fn save_partial(
+    app: &AppHandle,
+    state: &AppState,
+    id: String,
+    history: Vec<ChatMessage>,
+    text: &str,
+) -> Result<String, String> {
+    // Only a conversation that was never saved is created; an unreadable or corrupt one is
+    // left alone rather than replaced by this partial history
+    let mut stored = match conversation::find_conversation(app, &id)? {
+        Some(mut stored) => {
+            if let Some(last) = history.last() {
+                let saved = stored.messages.last().is_some_and(|m| m.role == last.role && m.content == last.content);
+                if !saved {
+                    stored.messages.push(last.clone());
+                }
+            }
+            stored
+        }
+        None => conversation::Conversation {
+            id,
+            title: String::new(),
+            messages: history,
+            model: None,
+            created_at: 0,
+            updated_at: 0,
+        },
+    };
+    if stored.model.is_none() {
+        stored.model = state.current_model.lock().unwrap().clone();
+    }
+    stored.messages.push(ChatMessage {
+        role: "assistant".into(),
+        content: text.to_string(),
+    });
+    conversation::write_conversation(app, stored).map(|c| c.id)
+}
+
+#[derive(Clone, serde::Serialize)]
+struct CompletionDelta {
+    request_id: String,
+    content: String,
+}
+
+/// `relay_stream` for the native /completion stream: a `completion-delta` event per text
+/// chunk, `generation-speed` as in chat. The stream ends with a chunk carrying `stop: true`
+/// and the timings rather than `[DONE]`.
+async fn relay_raw_stream(app: &AppHandle, request_id: &str, body: &serde_json::Value) -> Result<Reply, AppError> {
+    let mut resp = reqwest::Client::new()
+        .post(raw_completion_url(&app.state::<AppState>()))
+        .json(body)
+        .send()
+        .await
+        .map_err(not_reachable)?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(AppError::ServerError(format!("llama-server returned {status}: {text}")));
+    }
+
+    let mut buffer = Vec::new();
+    let mut reply = Reply {
+        text: String::new(),
+        timings: None,
+        finish_reason: None,
+        measured_tps: None,
+    };
+    let mut meter = SpeedMeter::default();
+    while let Some(chunk) = resp.chunk().await.map_err(|e| AppError::ServerError(format!("Stream interrupted: {e}")))? {
+        buffer.extend_from_slice(&chunk);
+
+        while let Some(payload) = next_sse_payload(&mut buffer) {
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(&payload) else { continue };
+            if let Some(content) = json["content"].as_str().filter(|c| !c.is_empty()) {
+                reply.text.push_str(content);
+                let _ = app.emit(
+                    "completion-delta",
+                    CompletionDelta {
+                        request_id: request_id.to_string(),
+                        content: content.to_string(),
+                    },
+                );
+                if let Some(tokens_per_sec) = meter.tick() {
+                    let _ = app.emit(
+                        "generation-speed",
+                        GenerationSpeed {
+                            request_id: request_id.to_string(),
+                            tokens: meter.tokens,
+                            tokens_per_sec,
+                        },
+                    );
+                }
+            }
+            if json["stop"].as_bool() == Some(true) {
+                reply.timings = Timings::from_response(&json);
+                reply.finish_reason = FinishReason::from_raw_completion(&json);
+                reply.measured_tps = meter.rate(Instant::now());
+                return Ok(reply);
+            }
+        }
+    }
+    reply.measured_tps = meter.rate(Instant::now());
+    Ok(reply)
+}
+
+/// Streaming `complete`. Text chunks arrive as `completion-delta` events
+/// (`{ request_id, content }`), followed by `completion-done` (same shape as `chat-done`);
+/// `generation-speed` events report the live rate. Cancel with `abort_generation(request_id)`.
+#[tauri::command]
+pub async fn complete_stream(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    request_id: String,
+    prompt: String,
+    params: Option<GenParams>,
+) -> Result<String, AppError> {
+    let result: Result<String, AppError> = async {
+        let defaults = state.generation_defaults.lock().unwrap().clone();
+        let params = params.unwrap_or_default().or_defaults(&defaults);
+        params.validate().map_err(AppError::InvalidInput)?;
+        crate::ensure_llama_started(&app).await?;
+        let body = raw_completion_body(&prompt, &params, true);
+
+        let (_guard, abort) = register_generation(&state.generations, Some(request_id.clone()))?;
+        let reply = cancellable(abort, async {
+            let _ticket = queue::enter(&app, &state, Some(&request_id)).await;
+            relay_raw_stream(&app, &request_id, &body).await
+        })
+        .await?;
+        if let Some(t) = &reply.timings {
+            metrics::record(&state, true, t);
+        }
+
+        let _ = app.emit(
+            "completion-done",
+            ChatDone {
+                request_id,
+                text: reply.text.clone(),
+                finish_reason: reply.finish_reason,
+                tokens_per_sec: reply.timings.as_ref().map(|t| t.predicted_per_second).or(reply.measured_tps),
+            },
+        );
+        Ok(reply.text)
+    }
+    .await;
+    error::report(&app, "complete_stream", result)
+}
+
+/// Cancels an in-flight `chat_completion`/`chat_completion_stream` (or `complete`/`complete_stream`) by request id without
+/// touching the server process. Returns false if no such request is running.
+#[tauri::command]
+pub fn abort_generation(state: State<AppState>, request_id: String) -> bool {
+    match state.generations.lock().unwrap().get(&request_id) {
+        Some(abort) => {
+            abort.abort(AbortReason::User);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Resolves text to the loaded model's token ids via llama-server's `/tokenize`.
+#[tauri::command]
+pub async fn tokenize(state: State<'_, AppState>, text: String) -> Result<Vec<i32>, AppError> {
+    let resp = reqwest::Client::new()
+        .post(crate::llama_url(&state, "/tokenize"))
+        .json(&serde_json::json!({ "content": text }))
+        .send()
+        .await
+        .map_err(not_reachable)?;
+
+    if !resp.status().is_success() {
+        return Err(AppError::ServerError(format!("llama-server returned {}", resp.status())));
+    }
+
+    let json: serde_json::Value = resp.json().await.map_err(|e| AppError::ServerError(format!("Invalid response from llama-server: {e}")))?;
+    json["tokens"]
+        .as_array()
+        .map(|tokens| tokens.iter().filter_map(|t| t.as_i64()).map(|t| t as i32).collect())
+        .ok_or_else(|| AppError::ServerError("llama-server response had no tokens".to_string()))
+}
+
+/// Replaces the sampling defaults applied to later chat requests without restarting the
+/// server; fields a request sets itself still win. `{}` clears them. Only the `GenParams`
+/// fields are accepted: names in `STARTUP_ONLY_PARAMS` are rejected with a hint to use
+/// `switch_model`, and anything else as unknown. Kept in memory until the app exits.
+#[tauri::command]
+pub fn update_generation_defaults(
+    state: State<AppState>,
+    params: serde_json::Map<String, serde_json::Value>,
+) -> Result<GenParams, String> {
+    let startup: Vec<&str> = params.keys().map(String::as_str).filter(|k| STARTUP_ONLY_PARAMS.contains(k)).collect();
+    if !startup.is_empty() {
+        return Err(format!(
+            "{} can only be set at startup; pass them to switch_model (restarts llama-server)",
+            startup.join(", ")
+        ));
+    }
+    let unknown: Vec<&str> = params
+        .keys()
+        .map(String::as_str)
+        .filter(|k| !PARAM_DEFS.iter().any(|d| d.name == *k))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!("Unknown sampling parameter(s): {}", unknown.join(", ")));
+    }
+    let defaults: GenParams = serde_json::from_value(serde_json::Value::Object(params)).map_err(|e| e.to_string())?;
+    defaults.validate()?;
+    *state.generation_defaults.lock().unwrap() = defaults.clone();
+    Ok(defaults)
+}
+
+/// Stores a persistent system prompt. An empty (or whitespace-only) string clears it.
+/// A loaded chat server evaluates the new prompt in the background (see `slot_cache`).
+#[tauri::command]
+pub fn set_system_prompt(app: AppHandle, state: State<AppState>, text: String) -> Result<(), String> {
+    let mut settings = state.settings.lock().unwrap();
+    settings.system_prompt = if text.trim().is_empty() { None } else { Some(text) };
+    settings::save(&app, &settings)?;
+    drop(settings);
+    slot_cache::prime_in_background(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_system_prompt(state: State<AppState>) -> Option<String> {
+    state.settings.lock().unwrap().system_prompt.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded() -> GenParams {
+        GenParams {
+            temperature: Some(0.8),
+            top_k: Some(40),
+            seed: Some(42),
+            ..GenParams::default()
+        }
+    }
+
+    fn messages() -> Vec<ChatMessage> {
+        vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Tell me a story".to_string(),
+        }]
+    }
+
+    #[test]
+    fn seeded_request_body_is_fixed() {
+        let body = request_body(&messages(), &seeded(), false);
+        let expected = serde_json::json!({
+            "messages": [{ "role": "user", "content": "Tell me a story" }],
+            "stream": false,
+            "cache_prompt": true,
+            "temperature": 0.8f32,
+            "top_k": 40,
+            "seed": 42,
+        });
+        assert_eq!(body, expected);
+        // The same request always produces the same body, so the server sees the same input
+        assert_eq!(request_body(&messages(), &seeded(), false), body);
+
+        let raw = raw_completion_body("Once upon a time", &seeded(), false);
+        assert_eq!(raw["seed"], 42);
+        assert_eq!(raw["prompt"], "Once upon a time");
+    }
+
+    #[test]
+    fn seed_comes_from_the_request_then_the_defaults() {
+        let defaults = GenParams {
+            seed: Some(7),
+            ..GenParams::default()
+        };
+        assert_eq!(seeded().or_defaults(&defaults).seed, Some(42));
+        assert_eq!(GenParams::default().or_defaults(&defaults).seed, Some(7));
+        // No built-in seed: unseeded requests stay random
+        assert_eq!(GenParams::builtin().seed, None);
+        assert!(request_body(&messages(), &GenParams::default(), false).get("seed").is_none());
+    }
+
+    #[test]
+    fn seed_range_and_determinism() {
+        for (seed, valid) in [(-1, true), (0, true), (4_294_967_295, true), (-2, false), (4_294_967_296, false)] {
+            let params = GenParams {
+                seed: Some(seed),
+                ..GenParams::default()
+            };
+            assert_eq!(params.validate().is_ok(), valid, "seed {seed}");
+        }
+        let with_seed = |seed| GenParams {
+            seed,
+            temperature: Some(0.8),
+            ..GenParams::default()
+        };
+        assert!(crate::response_cache::is_deterministic(&with_seed(Some(42))));
+        assert!(!crate::response_cache::is_deterministic(&with_seed(Some(-1))));
+        assert!(!crate::response_cache::is_deterministic(&with_seed(None)));
+    }
+
+    /// Needs a llama-server with a chat model on 127.0.0.1:8081 (e.g. the app running with a model
+    /// loaded): `cargo test -- --ignored identical_seeded_requests`.
+    #[test]
+    #[ignore]
+    fn identical_seeded_requests_return_identical_text() {
+        let url = format!("http://127.0.0.1:{}/v1/chat/completions", crate::LLAMA_PORT);
+        let params = GenParams {
+            max_tokens: Some(48),
+            ..seeded()
+        };
+        let body = request_body(&messages(), &params, false);
+        let send = || {
+            tauri::async_runtime::block_on(async {
+                let resp = reqwest::Client::new().post(&url).json(&body).send().await;
+                let resp = resp.expect("llama-server not reachable");
+                assert!(resp.status().is_success(), "llama-server returned {}", resp.status());
+                let json: serde_json::Value = resp.json().await.expect("invalid response");
+                json["choices"][0]["message"]["content"].as_str().expect("no content").to_string()
+            })
+        };
+        let first = send();
+        assert!(!first.is_empty());
+        assert_eq!(send(), first);
+    }
+}
@@ -0,0 +1,105 @@
+// Predicts whether the bundled llama-server can load a GGUF, from the file's format
+// version and architecture against the binary's build number, so a too-new model fails
+// with a readable reason instead of a crashed server.
+
+use std::path::Path;
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::{self, AppError};
+use crate::{backends, gguf, gguf_cache, resolve_model_path, AppState};
+
+/// First llama.cpp build (`bNNNN`) that loads each architecture. Only architectures
+/// newer than builds still commonly bundled are listed; anything else is assumed to load.
+const MIN_BUILD_FOR_ARCH: &[(&str, u32)] = &[
+    ("gemma3", 4875),
+    ("llama4", 5074),
+    ("qwen3", 5092),
+    ("qwen3moe", 5092),
+    ("gpt-oss", 6096),
+];
+
+#[derive(Clone, serde::Serialize)]
+pub struct CompatReport {
+    pub path: String,
+    /// False only when loading is predicted to fail; unknowns count as compatible.
+    pub compatible: bool,
+    /// Why the file can't be loaded, when `compatible` is false.
+    pub reason: Option<String>,
+    pub gguf_version: Option<u32>,
+    pub architecture: Option<String>,
+    /// Minimum llama.cpp build for the architecture, if it is in the known list.
+    pub required_build: Option<u32>,
+    /// Build of the resolved llama-server; only probed when `required_build` is set.
+    pub llama_build: Option<u32>,
+}
+
+/// Blocking: may probe `llama-server --version` (cached per binary).
+pub(crate) fn check(state: &AppState, path: &Path) -> CompatReport {
+    let mut report = CompatReport {
+        path: path.to_string_lossy().to_string(),
+        compatible: true,
+        reason: None,
+        gguf_version: None,
+        architecture: None,
+        required_build: None,
+        llama_build: None,
+    };
+    if let Err(reason) = inspect(state, path, &mut report) {
+        report.compatible = false;
+        report.reason = Some(reason);
+    }
+    report
+}
+
+/// Fills in `report` and returns the reason the file won't load, if any.
+fn inspect(state: &AppState, path: &Path, report: &mut CompatReport) -> Result<(), String> {
+    let version = gguf::read_version(path)?;
+    report.gguf_version = Some(version);
+    let supported = gguf::SUPPORTED_VERSIONS;
+    if version > *supported.end() {
+        return Err(format!(
+            "GGUF format v{version} is newer than the bundled llama-server supports (up to v{}); update llama-server",
+            supported.end()
+        ));
+    }
+    if version < *supported.start() {
+        return Err(format!(
+            "GGUF format v{version} is obsolete and no longer loaded by llama-server; re-convert the model"
+        ));
+    }
+
+    let metadata = gguf_cache::read_metadata(state, path).map_err(|e| format!("Unreadable GGUF header: {e}"))?;
+    let arch = metadata.architecture().map(str::to_string);
+    report.required_build = arch
+        .as_deref()
+        .and_then(|arch| MIN_BUILD_FOR_ARCH.iter().find(|(a, _)| *a == arch))
+        .map(|(_, build)| *build);
+    report.architecture = arch;
+    // Without a known requirement there's nothing to compare, so skip the probe
+    if report.required_build.is_some() {
+        report.llama_build = backends::llama_version(state).ok().and_then(|v| v.build);
+    }
+
+    match (report.required_build, report.llama_build) {
+        (Some(required), Some(build)) if build < required => Err(format!(
+            "The '{}' architecture needs llama.cpp build {required} or newer; the bundled llama-server is build {build}",
+            report.architecture.as_deref().unwrap_or_default()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Predicts whether `path` (path, alias or split shard) will load in the bundled
+/// llama-server. `switch_model` runs the same check before spawning.
+#[tauri::command]
+pub async fn check_compatibility(app: AppHandle, state: State<'_, AppState>, path: String) -> Result<CompatReport, AppError> {
+    let result = async {
+        let (path, _) = resolve_model_path(&state, path)?;
+        let handle = app.clone();
+        tauri::async_runtime::spawn_blocking(move || check(&handle.state::<AppState>(), &path))
+        .await
+        .map_err(|e| AppError::Other(format!("Compatibility check failed: {e}")))
+    }
+    .await;
+    error::report(&app, "check_compatibility", result)
+}
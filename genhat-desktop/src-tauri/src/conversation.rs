@@ -0,0 +1,285 @@
+// Durable conversation history: one JSON file per conversation in
+// <app data>/conversations/<id>.json.
+
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+use crate::chat::ChatMessage;
+use crate::{unix_millis, validate_output_path, write_file_atomic};
+
+const TITLE_MAX_CHARS: usize = 60;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct Conversation {
+    /// Left empty by the frontend for a new conversation; assigned on first save.
+    #[serde(default)]
+    pub id: String,
+    /// Generated from the first user message when empty.
+    #[serde(default)]
+    pub title: String,
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Unix epoch milliseconds.
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+#[derive(serde::Serialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    pub model: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub message_count: usize,
+}
+
+fn conversations_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Cannot resolve app data directory: {e}"))?
+        .join("conversations");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// Ids become file names, so only allow a conservative character set (no path separators or dots).
+fn conversation_path(app: &AppHandle, id: &str) -> Result<PathBuf, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid conversation id: {id}"));
+    }
+    Ok(conversations_dir(app)?.join(format!("{id}.json")))
+}
+
+fn new_id() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    format!("{:x}-{:05x}", unix_millis(), nanos & 0xfffff)
+}
+
+/// First line of the first user message, shortened to TITLE_MAX_CHARS.
+fn generate_title(messages: &[ChatMessage]) -> String {
+    let first = messages
+        .iter()
+        .find(|m| m.role == "user")
+        .and_then(|m| m.content.lines().map(str::trim).find(|l| !l.is_empty()));
+
+    match first {
+        None => "New conversation".to_string(),
+        Some(line) if line.chars().count() > TITLE_MAX_CHARS => {
+            let cut: String = line.chars().take(TITLE_MAX_CHARS).collect();
+            format!("{}…", cut.trim_end())
+        }
+        Some(line) => line.to_string(),
+    }
+}
+
+pub fn read_conversation(app: &AppHandle, id: &str) -> Result<Conversation, String> {
+    find_conversation(app, id)?.ok_or_else(|| format!("Conversation not found: {id}"))
+}
+
+/// Like `read_conversation`, but `Ok(None)` when no conversation `id` was saved; a file that
+/// can't be read or parsed is still an error.
+pub fn find_conversation(app: &AppHandle, id: &str) -> Result<Option<Conversation>, String> {
+    let path = conversation_path(app, id)?;
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&json).map(Some).map_err(|e| format!("Conversation {id} is corrupt: {e}"))
+}
+
+pub fn write_conversation(app: &AppHandle, mut conversation: Conversation) -> Result<Conversation, String> {
+    let now = unix_millis();
+    if conversation.id.is_empty() {
+        conversation.id = new_id();
+    }
+    if conversation.created_at == 0 {
+        conversation.created_at = now;
+    }
+    conversation.updated_at = now;
+    if conversation.title.trim().is_empty() {
+        conversation.title = generate_title(&conversation.messages);
+    }
+
+    let path = conversation_path(app, &conversation.id)?;
+    let json = serde_json::to_string_pretty(&conversation).map_err(|e| e.to_string())?;
+    // Through `<id>.json.tmp`, so a crash mid-write can't leave a truncated conversation
+    write_file_atomic(&path, json.as_bytes()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(conversation)
+}
+
+/// Saves (creating or overwriting) a conversation and returns it with id/title/timestamps filled in.
+#[tauri::command]
+pub fn save_conversation(app: AppHandle, conversation: Conversation) -> Result<Conversation, String> {
+    write_conversation(&app, conversation)
+}
+
+#[tauri::command]
+pub fn load_conversation(app: AppHandle, id: String) -> Result<Conversation, String> {
+    read_conversation(&app, &id)
+}
+
+/// Summaries of all stored conversations, most recently updated first. Corrupt files are skipped.
+#[tauri::command]
+pub fn list_conversations(app: AppHandle) -> Result<Vec<ConversationSummary>, String> {
+    let dir = conversations_dir(&app)?;
+    let mut list: Vec<ConversationSummary> = std::fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("json"))
+        .filter_map(|p| std::fs::read_to_string(p).ok())
+        .filter_map(|json| serde_json::from_str::<Conversation>(&json).ok())
+        .map(|c| ConversationSummary {
+            message_count: c.messages.len(),
+            id: c.id,
+            title: c.title,
+            model: c.model,
+            created_at: c.created_at,
+            updated_at: c.updated_at,
+        })
+        .collect();
+    list.sort_by_key(|c| std::cmp::Reverse(c.updated_at));
+    Ok(list)
+}
+
+#[tauri::command]
+pub fn delete_conversation(app: AppHandle, id: String) -> Result<(), String> {
+    let path = conversation_path(&app, &id)?;
+    if !path.exists() {
+        return Err(format!("Conversation not found: {id}"));
+    }
+    std::fs::remove_file(&path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+}
+
+/// Formats epoch milliseconds as `YYYY-MM-DD HH:MM UTC` (no timezone database needed).
+fn format_utc(millis: u64) -> String {
+    let secs = millis / 1000;
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02} UTC", rem / 3600, (rem % 3600) / 60)
+}
+
+fn role_heading(role: &str) -> String {
+    let mut chars = role.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => "Message".to_string(),
+    }
+}
+
+fn render_markdown(conversation: &Conversation) -> String {
+    let mut out = format!("# {}\n\n", conversation.title);
+    if let Some(model) = &conversation.model {
+        out.push_str(&format!("- **Model:** {model}\n"));
+    }
+    out.push_str(&format!("- **Created:** {}\n", format_utc(conversation.created_at)));
+    out.push_str(&format!("- **Exported:** {}\n", format_utc(unix_millis())));
+
+    for message in &conversation.messages {
+        out.push_str(&format!("\n## {}\n\n", role_heading(&message.role)));
+        // Content is written verbatim so fenced code blocks survive; a fence the model
+        // left open is closed so it can't swallow the following headings.
+        let content = message.content.trim_end();
+        out.push_str(content);
+        out.push('\n');
+        let fences = content.lines().filter(|l| l.trim_start().starts_with("```")).count();
+        if fences % 2 == 1 {
+            out.push_str("```\n");
+        }
+    }
+    out
+}
+
+/// Renders a stored conversation to a Markdown file at `path`.
+#[tauri::command]
+pub fn export_conversation(app: AppHandle, id: String, path: String) -> Result<(), String> {
+    let conversation = read_conversation(&app, &id)?;
+    let dest = validate_output_path(std::path::Path::new(&path))?;
+    std::fs::write(&dest, render_markdown(&conversation))
+        .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn title_is_the_first_user_line() {
+        assert_eq!(generate_title(&[]), "New conversation");
+        assert_eq!(generate_title(&[message("system", "Be brief")]), "New conversation");
+        let messages = [message("system", "Be brief"), message("user", "\n  \n  Plan a trip \nto Rome")];
+        assert_eq!(generate_title(&messages), "Plan a trip");
+    }
+
+    #[test]
+    fn long_titles_are_cut_by_characters() {
+        let exact = "é".repeat(TITLE_MAX_CHARS);
+        assert_eq!(generate_title(&[message("user", &exact)]), exact);
+        let long = "日本語 ".repeat(TITLE_MAX_CHARS);
+        let title = generate_title(&[message("user", &long)]);
+        assert!(title.ends_with("語…"), "{title}");
+        assert_eq!(title.chars().count(), TITLE_MAX_CHARS);
+        let emoji = "🙂".repeat(TITLE_MAX_CHARS + 1);
+        assert_eq!(generate_title(&[message("user", &emoji)]), format!("{}…", "🙂".repeat(TITLE_MAX_CHARS)));
+    }
+
+    #[test]
+    fn utc_dates_match_the_calendar() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_utc(1_709_210_040_000), "2024-02-29 12:34 UTC");
+        assert_eq!(format_utc(951_868_740_999), "2000-02-29 23:59 UTC");
+        assert_eq!(format_utc(1_677_628_800_000), "2023-03-01 00:00 UTC");
+        assert_eq!(format_utc(4_107_542_400_000), "2100-03-01 00:00 UTC");
+    }
+
+    #[test]
+    fn markdown_has_role_headings_and_closed_code_blocks() {
+        let conversation = Conversation {
+            id: "c1".to_string(),
+            title: "Sorting".to_string(),
+            messages: vec![
+                message("user", "How do I sort?"),
+                message("assistant", "Like this:\n```rust\nv.sort();\n```\n"),
+                message("tool", "```\nunterminated"),
+                message("", "anonymous"),
+            ],
+            model: Some("qwen.gguf".to_string()),
+            created_at: 0,
+            updated_at: 0,
+        };
+        let markdown = render_markdown(&conversation);
+        assert!(markdown.starts_with("# Sorting\n\n- **Model:** qwen.gguf\n- **Created:** 1970-01-01 00:00 UTC\n"));
+        assert!(markdown.contains("\n## User\n\nHow do I sort?\n"));
+        assert!(markdown.contains("\n## Assistant\n\nLike this:\n```rust\nv.sort();\n```\n\n## Tool\n"));
+        assert!(markdown.contains("\n## Tool\n\n```\nunterminated\n```\n\n## Message\n\nanonymous\n"));
+        assert!(markdown.ends_with("anonymous\n"));
+    }
+}
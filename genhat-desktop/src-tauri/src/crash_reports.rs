@@ -0,0 +1,131 @@
+// Crash reports for llama-server. When the server exits without the app stopping it, the
+// last log lines of that process are saved with the model and exit status to
+// `<app data>/crash-reports/crash-<ms>.json`, something concrete to attach to an issue.
+
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::{unix_millis, AppState};
+
+/// Log lines of the crashed process kept in a report.
+const REPORT_LINES: usize = 50;
+
+/// Reports kept on disk; older ones are deleted when a new one is written.
+const MAX_REPORTS: usize = 20;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CrashReport {
+    model: Option<String>,
+    /// Epoch milliseconds.
+    timestamp: u64,
+    pid: u32,
+    /// Process exit code; `None` when it was killed by a signal.
+    exit_code: Option<i32>,
+    /// Human-readable status, e.g. `exit status: 1` or `signal: 11 (SIGSEGV)`.
+    status: String,
+    /// Last stdout/stderr lines of the process, oldest first.
+    log: Vec<String>,
+}
+
+/// What `list_crash_reports` and the `llama-crash-report` event carry.
+#[derive(Clone, serde::Serialize)]
+pub struct CrashReportSummary {
+    pub path: String,
+    pub model: Option<String>,
+    pub timestamp: u64,
+    pub exit_code: Option<i32>,
+    pub status: String,
+}
+
+impl CrashReport {
+    fn summary(&self, path: &std::path::Path) -> CrashReportSummary {
+        CrashReportSummary {
+            path: path.to_string_lossy().to_string(),
+            model: self.model.clone(),
+            timestamp: self.timestamp,
+            exit_code: self.exit_code,
+            status: self.status.clone(),
+        }
+    }
+}
+
+fn reports_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Cannot resolve app data directory: {e}"))?
+        .join("crash-reports");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// Report files, newest first (names embed the timestamp).
+fn report_files(dir: &std::path::Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
+            name.starts_with("crash-") && name.ends_with(".json")
+        })
+        .collect();
+    files.sort_by(|a, b| b.cmp(a));
+    files
+}
+
+/// Saves a report for the llama-server `pid` that exited with `status`, then emits
+/// `llama-crash-report`. Failures are only logged; this runs on the watcher task.
+pub fn record(app: &AppHandle, pid: u32, model: &str, status: ExitStatus) {
+    let state = app.state::<AppState>();
+    let log = {
+        let buffer = state.llama_log.lock().unwrap();
+        let lines: Vec<String> = buffer
+            .iter()
+            .filter(|l| l.pid == Some(pid) && l.stream != "genhat")
+            .map(|l| format!("[{}] {}", l.stream, l.message))
+            .collect();
+        lines[lines.len().saturating_sub(REPORT_LINES)..].to_vec()
+    };
+    let report = CrashReport {
+        model: Some(model.to_string()).filter(|m| !m.is_empty()),
+        timestamp: unix_millis(),
+        pid,
+        exit_code: status.code(),
+        status: status.to_string(),
+        log,
+    };
+
+    let result = reports_dir(app).and_then(|dir| {
+        let path = dir.join(format!("crash-{:013}.json", report.timestamp));
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        for old in report_files(&dir).into_iter().skip(MAX_REPORTS) {
+            let _ = std::fs::remove_file(old);
+        }
+        Ok(path)
+    });
+    match result {
+        Ok(path) => {
+            eprintln!("llama-server (pid {pid}) exited unexpectedly ({status}); report saved to {}", path.display());
+            let _ = app.emit("llama-crash-report", report.summary(&path));
+        }
+        Err(e) => eprintln!("Failed to save llama-server crash report: {e}"),
+    }
+}
+
+/// Saved crash reports, newest first. Unreadable files are skipped.
+#[tauri::command]
+pub fn list_crash_reports(app: AppHandle) -> Result<Vec<CrashReportSummary>, String> {
+    let dir = reports_dir(&app)?;
+    Ok(report_files(&dir)
+        .into_iter()
+        .filter_map(|path| {
+            let json = std::fs::read_to_string(&path).ok()?;
+            let report: CrashReport = serde_json::from_str(&json).ok()?;
+            Some(report.summary(&path))
+        })
+        .collect())
+}
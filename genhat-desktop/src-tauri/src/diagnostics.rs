@@ -0,0 +1,117 @@
+// Environment snapshot for support requests: every path the app resolves (models folder,
+// bundled binaries, logs, app data) with the candidates each search checked and the
+// environment overrides in effect, so one paste answers "why can't it find my models?".
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+use crate::{
+    first_existing, get_models_dir, llama_candidates, llama_log, tts_candidates, whisper_candidates, AppState,
+};
+
+/// Variables that change where the app looks for things (see the env-var table in the docs).
+const ENV_VARS: &[&str] = &[
+    "GENHAT_MODEL_PATH",
+    "GENHAT_LLAMA_EXE",
+    "GENHAT_TTS_EXE",
+    "GENHAT_WHISPER_EXE",
+    "RUST_BACKTRACE",
+];
+
+#[derive(serde::Serialize)]
+pub struct PathCheck {
+    /// Where the path comes from, e.g. `GENHAT_MODEL_PATH` or `default`.
+    pub source: &'static str,
+    pub path: String,
+    pub exists: bool,
+}
+
+#[derive(serde::Serialize)]
+pub struct BinaryDiagnostics {
+    /// `llama-server`, `tts-inference` or `whisper-cli`.
+    pub name: &'static str,
+    /// The variable that overrides the search.
+    pub override_var: &'static str,
+    /// The path spawns use (cached, or resolved now); `None` when it can't be found.
+    pub resolved: Option<String>,
+    pub error: Option<String>,
+    /// Bundle search candidates in order, up to and including the first that exists
+    /// (all of them when none does). Empty when the app executable can't be located.
+    pub checked: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Diagnostics {
+    pub app_exe: Option<String>,
+    pub app_data_dir: Option<String>,
+    /// The folder models are listed from and loaded at startup.
+    pub models_dir: PathCheck,
+    /// Every models location considered: `GENHAT_MODEL_PATH` when set, then the built-in one.
+    pub models_dirs: Vec<PathCheck>,
+    pub binaries: Vec<BinaryDiagnostics>,
+    pub llama_log: String,
+    /// The `ENV_VARS` that are set, with their values.
+    pub env: BTreeMap<&'static str, String>,
+}
+
+fn path_check(source: &'static str, path: &Path) -> PathCheck {
+    PathCheck {
+        source,
+        path: path.to_string_lossy().to_string(),
+        exists: path.exists(),
+    }
+}
+
+fn binary(
+    name: &'static str,
+    override_var: &'static str,
+    resolved: Result<PathBuf, String>,
+    app_exe: Option<&Path>,
+    candidates: fn(&Path) -> Vec<PathBuf>,
+) -> BinaryDiagnostics {
+    let checked = app_exe.map(|exe| first_existing(candidates(exe)).1).unwrap_or_default();
+    let (resolved, error) = match resolved {
+        Ok(p) => (Some(p.to_string_lossy().to_string()), None),
+        Err(e) => (None, Some(e)),
+    };
+    BinaryDiagnostics {
+        name,
+        override_var,
+        resolved,
+        error,
+        checked: checked.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+    }
+}
+
+/// Every resolved path with the candidates behind it, for pasting into a bug report.
+/// Resolving binaries here also fills the binary cache, as a spawn would.
+#[tauri::command]
+pub fn get_diagnostics(app: AppHandle, state: State<AppState>) -> Diagnostics {
+    let app_exe = std::env::current_exe().ok();
+    let models_dir = get_models_dir();
+    let mut models_dirs = Vec::new();
+    if let Ok(val) = std::env::var("GENHAT_MODEL_PATH") {
+        models_dirs.push(path_check("GENHAT_MODEL_PATH", Path::new(&val)));
+    }
+    let builtin = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../models");
+    models_dirs.push(path_check("default", &builtin.canonicalize().unwrap_or(builtin)));
+
+    let exe = app_exe.as_deref();
+    Diagnostics {
+        app_exe: app_exe.as_ref().map(|p| p.to_string_lossy().to_string()),
+        app_data_dir: app.path().app_data_dir().ok().map(|p| p.to_string_lossy().to_string()),
+        models_dir: path_check("resolved", &models_dir),
+        models_dirs,
+        binaries: vec![
+            binary("llama-server", "GENHAT_LLAMA_EXE", crate::llama_exe(&state), exe, llama_candidates),
+            binary("tts-inference", "GENHAT_TTS_EXE", crate::tts_exe(&state), exe, tts_candidates),
+            binary("whisper-cli", "GENHAT_WHISPER_EXE", crate::whisper_exe(&state), exe, whisper_candidates),
+        ],
+        llama_log: llama_log::log_path(&app).to_string_lossy().to_string(),
+        env: ENV_VARS
+            .iter()
+            .filter_map(|&var| std::env::var(var).ok().map(|val| (var, val)))
+            .collect(),
+    }
+}
@@ -0,0 +1,109 @@
+// Free disk space for the models folder, so the UI can warn before a multi-GB download
+// and `download_model_resumable` can refuse one that won't fit. Queried straight from the
+// OS: `statvfs` on Unix, `GetDiskFreeSpaceExW` on Windows.
+
+use std::path::{Path, PathBuf};
+
+use crate::get_models_dir;
+
+/// Space kept free on top of a download's size, for the rename and everything else on the disk.
+pub const DOWNLOAD_MARGIN_BYTES: u64 = 512 << 20;
+
+#[derive(Clone, serde::Serialize)]
+pub struct DiskSpace {
+    pub path: String,
+    pub total_bytes: u64,
+    /// Space the current user can write (excludes blocks reserved for root on Unix).
+    pub available_bytes: u64,
+}
+
+/// `(total, available)` bytes from `statvfs` block counts. Sizes are in fragments
+/// (`f_frsize`); systems that leave it 0 count in `f_bsize` blocks.
+#[cfg(any(unix, test))]
+fn statvfs_bytes(frsize: u64, bsize: u64, blocks: u64, available_blocks: u64) -> (u64, u64) {
+    let unit = if frsize > 0 { frsize } else { bsize };
+    (blocks.saturating_mul(unit), available_blocks.saturating_mul(unit))
+}
+
+#[cfg(unix)]
+fn query(dir: &Path) -> std::io::Result<(u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: statvfs is plain old data, and all zeroes is a valid value for it
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid, writable statvfs
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // The field widths differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    Ok(statvfs_bytes(stat.f_frsize as u64, stat.f_bsize as u64, stat.f_blocks as u64, stat.f_bavail as u64))
+}
+
+#[cfg(windows)]
+fn query(dir: &Path) -> std::io::Result<(u64, u64)> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+    let (mut available, mut total) = (0u64, 0u64);
+    // SAFETY: `wide` is NUL-terminated and the two out-pointers are valid; the third may be null
+    let ok = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, &mut total, std::ptr::null_mut()) };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok((total, available))
+}
+
+/// Total and available bytes on the disk holding `dir`. Blocking only for as long as the
+/// filesystem takes to answer (a network drive can be slow).
+pub fn disk_space(dir: &Path) -> Result<DiskSpace, String> {
+    let (total_bytes, available_bytes) =
+        query(dir).map_err(|e| format!("Failed to query disk space for {}: {e}", dir.display()))?;
+    Ok(DiskSpace {
+        path: dir.to_string_lossy().to_string(),
+        total_bytes,
+        available_bytes,
+    })
+}
+
+/// Total and available bytes on the disk holding `dir` (default: the models folder).
+#[tauri::command]
+pub async fn get_disk_space(dir: Option<String>) -> Result<DiskSpace, String> {
+    let dir = dir.map(PathBuf::from).unwrap_or_else(get_models_dir);
+    if !dir.is_dir() {
+        return Err(format!("Not a directory: {}", dir.display()));
+    }
+    tauri::async_runtime::spawn_blocking(move || disk_space(&dir))
+        .await
+        .map_err(|e| format!("Disk space query failed: {e}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_counts_become_bytes() {
+        assert_eq!(statvfs_bytes(4096, 4096, 1000, 250), (4096 * 1000, 4096 * 250));
+        // f_frsize wins over f_bsize when both are set
+        assert_eq!(statvfs_bytes(1024, 4096, 10, 5), (10 * 1024, 5 * 1024));
+        assert_eq!(statvfs_bytes(0, 512, 10, 5), (10 * 512, 5 * 512));
+        assert_eq!(statvfs_bytes(4096, 4096, 0, 0), (0, 0));
+        assert_eq!(statvfs_bytes(1 << 20, 0, u64::MAX, u64::MAX / 2), (u64::MAX, u64::MAX));
+    }
+
+    #[test]
+    fn the_temp_dir_has_a_disk() {
+        let space = disk_space(&std::env::temp_dir()).unwrap();
+        assert!(space.total_bytes > 0);
+        assert!(space.available_bytes <= space.total_bytes);
+    }
+
+    #[test]
+    fn a_missing_dir_is_an_error() {
+        let missing = std::env::temp_dir().join("genhat-no-such-dir").join("nested");
+        let error = disk_space(&missing).err().unwrap();
+        assert!(error.contains("Failed to query disk space"), "{error}");
+    }
+}
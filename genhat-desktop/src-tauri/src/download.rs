@@ -0,0 +1,425 @@
+// Resumable model downloads into the models dir. Bytes go to `<name>.part` and through a
+// SHA-256 hasher as they arrive; an interrupted download keeps the `.part` and the next
+// call for the same file resumes it with an HTTP range request. Only a complete (and,
+// when a hash is given, verified) file is renamed to its final name. Each download has an
+// id, so several can run at once and be cancelled independently.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::Notify;
+
+use crate::disk::{self, DOWNLOAD_MARGIN_BYTES};
+use crate::error::{self, AppError};
+use crate::memory::gib;
+use crate::{get_models_dir, AppState};
+
+/// In-flight downloads by download id, so `cancel_download` can stop them.
+pub type Downloads = Mutex<HashMap<String, Arc<DownloadControl>>>;
+
+pub struct DownloadControl {
+    filename: String,
+    /// Checked before every chunk is written and, under the `Downloads` lock, before the rename.
+    cancelled: AtomicBool,
+    /// Wakes a download stuck waiting for the next chunk.
+    wake: Notify,
+}
+
+/// Minimum gap between `model-download-progress` events.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A connection that delivers nothing for this long counts as interrupted.
+const STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, serde::Serialize)]
+struct DownloadProgress {
+    filename: String,
+    downloaded: u64,
+    /// `None` when the server doesn't send a length.
+    total: Option<u64>,
+    /// Bytes already in the `.part` file when this run started.
+    resumed_from: u64,
+}
+
+/// Outcome of the integrity check, also sent as `model-download-finished`.
+#[derive(Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verification {
+    Verified,
+    /// No expected hash was given; `sha256` is still reported.
+    Unverified,
+    Mismatch,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct DownloadCancelled {
+    download_id: String,
+    filename: String,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct DownloadResult {
+    download_id: String,
+    filename: String,
+    /// Final path, or the kept `.part` file on a mismatch.
+    path: String,
+    bytes: u64,
+    sha256: String,
+    resumed_from: u64,
+    verification: Verification,
+}
+
+/// Keeps a download id registered for the lifetime of a download.
+struct DownloadGuard<'a> {
+    downloads: &'a Downloads,
+    id: String,
+    control: Arc<DownloadControl>,
+}
+
+impl Drop for DownloadGuard<'_> {
+    fn drop(&mut self) {
+        let mut downloads = self.downloads.lock().unwrap();
+        // The entry is removed early on success, and the id may have been reused since
+        if downloads.get(&self.id).is_some_and(|c| Arc::ptr_eq(c, &self.control)) {
+            downloads.remove(&self.id);
+        }
+    }
+}
+
+/// Deletes the `.part` file of a cancelled download and announces it.
+fn discard(app: &AppHandle, id: &str, filename: &str, part: &Path) {
+    let _ = std::fs::remove_file(part);
+    let _ = app.emit(
+        "download-cancelled",
+        DownloadCancelled {
+            download_id: id.to_string(),
+            filename: filename.to_string(),
+        },
+    );
+}
+
+/// Plain `.gguf` file names only; the download always lands in the models dir.
+fn validate_filename(filename: &str) -> Result<(), AppError> {
+    let plain = !filename.is_empty()
+        && !filename.starts_with('.')
+        && !filename.contains(['/', '\\'])
+        && Path::new(filename).file_name().and_then(|n| n.to_str()) == Some(filename);
+    if !plain || !filename.to_ascii_lowercase().ends_with(".gguf") {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid file name: {filename} (expected a plain name ending in .gguf)"
+        )));
+    }
+    Ok(())
+}
+
+fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+/// Hashes what's already in the `.part` file (the hasher state isn't saved between runs).
+/// Blocking; reads the whole file.
+fn hash_existing(path: &Path, hasher: &mut Sha256) -> Result<u64, AppError> {
+    let mut file = std::fs::File::open(path).map_err(|e| AppError::Io(format!("Failed to read {}: {}", path.display(), e)))?;
+    let mut buf = vec![0u8; 1 << 20];
+    let mut total = 0;
+    loop {
+        let n = file.read(&mut buf).map_err(|e| AppError::Io(format!("Failed to read {}: {}", path.display(), e)))?;
+        if n == 0 {
+            return Ok(total);
+        }
+        hasher.update(&buf[..n]);
+        total += n as u64;
+    }
+}
+
+/// Total size from a `Content-Range: bytes <range>/<total>` header.
+fn content_range_total(resp: &reqwest::Response) -> Option<u64> {
+    let value = resp.headers().get(reqwest::header::CONTENT_RANGE)?.to_str().ok()?;
+    value.rsplit('/').next()?.parse().ok()
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Refuses a download whose `remaining` bytes plus `DOWNLOAD_MARGIN_BYTES` don't fit on the
+/// disk holding `part`. If free space can't be read, the download goes ahead.
+async fn ensure_space(part: &Path, remaining: u64) -> Result<(), AppError> {
+    let Some(dir) = part.parent().map(Path::to_path_buf) else { return Ok(()) };
+    let space = tauri::async_runtime::spawn_blocking(move || disk::disk_space(&dir))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|r| r);
+    let space = match space {
+        Ok(space) => space,
+        Err(e) => {
+            eprintln!("Skipping free space check: {e}");
+            return Ok(());
+        }
+    };
+    if space.available_bytes < remaining + DOWNLOAD_MARGIN_BYTES {
+        return Err(AppError::InsufficientSpace(format!(
+            "Not enough disk space: the download needs {} (plus {} to spare) but only {} is free on {}",
+            gib(remaining),
+            gib(DOWNLOAD_MARGIN_BYTES),
+            gib(space.available_bytes),
+            space.path
+        )));
+    }
+    Ok(())
+}
+
+/// Streams the rest of `url` into `part`, appending from `offset` (the bytes `hasher` has
+/// seen). Returns the final size and the offset the server actually resumed from.
+async fn fetch(
+    app: &AppHandle,
+    filename: &str,
+    url: &str,
+    part: &Path,
+    mut offset: u64,
+    hasher: &mut Sha256,
+    control: &DownloadControl,
+) -> Result<(u64, u64), AppError> {
+    let cancelled = || AppError::Cancelled(format!("Download of {filename} cancelled"));
+    let client = reqwest::Client::builder()
+        .read_timeout(STALL_TIMEOUT)
+        .build()
+        .map_err(|e| AppError::Other(e.to_string()))?;
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+    }
+    let mut resp = request
+        .send()
+        .await
+        .map_err(|e| AppError::Io(format!("Download failed to start: {e}")))?;
+
+    let status = resp.status();
+    let resumed_from = if offset > 0 && status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // Nothing left past the end: the previous run got every byte but stopped before the rename
+        if content_range_total(&resp) == Some(offset) {
+            return Ok((offset, offset));
+        }
+        return Err(AppError::Other(format!(
+            "The server rejected resuming {filename} at byte {offset}; call cancel_download to discard the partial file"
+        )));
+    } else if status == reqwest::StatusCode::PARTIAL_CONTENT {
+        offset
+    } else if status.is_success() {
+        // The server ignored the range; start over
+        *hasher = Sha256::new();
+        offset = 0;
+        0
+    } else {
+        return Err(AppError::Other(format!("Download failed: {url} returned {status}")));
+    };
+    let total = if status == reqwest::StatusCode::PARTIAL_CONTENT {
+        content_range_total(&resp)
+    } else {
+        resp.content_length()
+    };
+    if let Some(total) = total {
+        ensure_space(part, total.saturating_sub(resumed_from)).await?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed_from > 0)
+        .truncate(resumed_from == 0)
+        .open(part)
+        .map_err(|e| AppError::Io(format!("Failed to open {}: {}", part.display(), e)))?;
+
+    let progress = |downloaded| DownloadProgress {
+        filename: filename.to_string(),
+        downloaded,
+        total,
+        resumed_from,
+    };
+    let _ = app.emit("model-download-progress", progress(offset));
+    let mut last_emit = Instant::now();
+    loop {
+        if control.cancelled.load(Ordering::SeqCst) {
+            return Err(cancelled());
+        }
+        let chunk = tokio::select! {
+            chunk = resp.chunk() => chunk,
+            _ = control.wake.notified() => return Err(cancelled()),
+        };
+        let chunk = match chunk {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                return Err(AppError::Io(format!(
+                    "Download interrupted at {offset} bytes: {e}; call download_model_resumable again to resume"
+                )))
+            }
+        };
+        file.write_all(&chunk).map_err(|e| AppError::Io(format!("Failed to write {}: {}", part.display(), e)))?;
+        hasher.update(&chunk);
+        offset += chunk.len() as u64;
+        if last_emit.elapsed() >= PROGRESS_INTERVAL {
+            let _ = app.emit("model-download-progress", progress(offset));
+            last_emit = Instant::now();
+        }
+    }
+    file.flush().map_err(|e| AppError::Io(format!("Failed to write {}: {}", part.display(), e)))?;
+    let _ = app.emit("model-download-progress", progress(offset));
+
+    if let Some(total) = total.filter(|&t| t != offset) {
+        return Err(AppError::Io(format!(
+            "Download ended early ({offset} of {total} bytes); call download_model_resumable again to resume"
+        )));
+    }
+    Ok((offset, resumed_from))
+}
+
+/// Downloads `url` into the models dir as `filename`, resuming a `.part` file left by an
+/// earlier, interrupted call. Progress arrives as `model-download-progress` events; the
+/// result (also emitted as `model-download-finished`) carries the SHA-256 and whether it
+/// matched `expected_sha256`. The `.part` file is kept on any failure, including a hash
+/// mismatch, and only deleted by `cancel_download(download_id)`; `download_id` defaults to
+/// `filename`.
+#[tauri::command]
+pub async fn download_model_resumable(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    url: String,
+    filename: String,
+    expected_sha256: Option<String>,
+    download_id: Option<String>,
+) -> Result<DownloadResult, AppError> {
+    let result = async {
+        let id = download_id.filter(|id| !id.trim().is_empty()).unwrap_or_else(|| filename.clone());
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            return Err(AppError::InvalidInput(format!("Unsupported download URL: {url}")));
+        }
+        validate_filename(&filename)?;
+        let expected = expected_sha256.map(|h| h.trim().to_ascii_lowercase()).filter(|h| !h.is_empty());
+        if let Some(h) = expected.as_deref().filter(|h| h.len() != 64 || !h.chars().all(|c| c.is_ascii_hexdigit())) {
+            return Err(AppError::InvalidInput(format!("expected_sha256 must be 64 hex characters (got {h})")));
+        }
+        let dest = get_models_dir().join(&filename);
+        if dest.exists() {
+            return Err(AppError::InvalidInput(format!("{} already exists", dest.display())));
+        }
+        let part = part_path(&dest);
+
+        let control = {
+            let mut downloads = state.downloads.lock().unwrap();
+            if downloads.contains_key(&id) {
+                return Err(AppError::Busy(format!("A download with id {id} is already running")));
+            }
+            if downloads.values().any(|c| c.filename == filename) {
+                return Err(AppError::Busy(format!("{filename} is already downloading")));
+            }
+            let control = Arc::new(DownloadControl {
+                filename: filename.clone(),
+                cancelled: AtomicBool::new(false),
+                wake: Notify::new(),
+            });
+            downloads.insert(id.clone(), control.clone());
+            control
+        };
+        let _guard = DownloadGuard {
+            downloads: &state.downloads,
+            id: id.clone(),
+            control: control.clone(),
+        };
+
+        let existing = part.clone();
+        let (mut hasher, offset) = tauri::async_runtime::spawn_blocking(move || {
+            let mut hasher = Sha256::new();
+            let offset = if existing.is_file() { hash_existing(&existing, &mut hasher)? } else { 0 };
+            Ok::<_, AppError>((hasher, offset))
+        })
+        .await
+        .map_err(|e| AppError::Other(format!("Hashing the partial download failed: {e}")))??;
+        let (bytes, resumed_from) = match fetch(&app, &filename, &url, &part, offset, &mut hasher, &control).await {
+            Err(AppError::Cancelled(msg)) => {
+                discard(&app, &id, &filename, &part);
+                return Err(AppError::Cancelled(msg));
+            }
+            other => other?,
+        };
+
+        let sha256 = hex(&hasher.finalize());
+        let verification = match &expected {
+            None => Verification::Unverified,
+            Some(h) if *h == sha256 => Verification::Verified,
+            Some(_) => Verification::Mismatch,
+        };
+        // Cancel and finish are decided under the lock: either the cancel lands first and the
+        // `.part` is discarded, or the download leaves the map (later cancels find nothing)
+        // and the rename goes ahead.
+        let path = {
+            let mut downloads = state.downloads.lock().unwrap();
+            if control.cancelled.load(Ordering::SeqCst) {
+                drop(downloads);
+                discard(&app, &id, &filename, &part);
+                return Err(AppError::Cancelled(format!("Download of {filename} cancelled")));
+            }
+            downloads.remove(&id);
+            if matches!(verification, Verification::Mismatch) {
+                part.clone()
+            } else {
+                std::fs::rename(&part, &dest)
+                    .map_err(|e| AppError::Io(format!("Failed to move {} into place: {}", part.display(), e)))?;
+                dest
+            }
+        };
+        let result = DownloadResult {
+            download_id: id,
+            filename,
+            path: path.to_string_lossy().to_string(),
+            bytes,
+            sha256,
+            resumed_from,
+            verification,
+        };
+        let _ = app.emit("model-download-finished", result.clone());
+        if matches!(verification, Verification::Mismatch) {
+            return Err(AppError::Other(format!(
+                "SHA-256 mismatch for {}: expected {}, got {}; the file was kept as {} (cancel_download discards it)",
+                result.filename,
+                expected.unwrap_or_default(),
+                result.sha256,
+                result.path
+            )));
+        }
+        Ok(result)
+    }
+    .await;
+    error::report(&app, "download_model_resumable", result)
+}
+
+/// Stops the download with `download_id`; it deletes its `.part` file and emits
+/// `download-cancelled` once it has stopped writing. With no such download running, an id
+/// that is a file name deletes that file's leftover `.part`. Returns false if there was
+/// neither, including a download that already finished.
+#[tauri::command]
+pub fn cancel_download(app: AppHandle, state: State<AppState>, download_id: String) -> Result<bool, String> {
+    if let Some(control) = state.downloads.lock().unwrap().get(&download_id) {
+        control.cancelled.store(true, Ordering::SeqCst);
+        control.wake.notify_one();
+        return Ok(true);
+    }
+    if validate_filename(&download_id).is_err() {
+        return Ok(false);
+    }
+    let part = part_path(&get_models_dir().join(&download_id));
+    if !part.exists() {
+        return Ok(false);
+    }
+    std::fs::remove_file(&part).map_err(|e| format!("Failed to delete {}: {}", part.display(), e))?;
+    discard(&app, &download_id, &download_id, &part);
+    Ok(true)
+}
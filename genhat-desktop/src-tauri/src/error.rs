@@ -0,0 +1,135 @@
+// Typed command errors. Serialized to the frontend as `{ code, message }` so the UI can
+// branch on the kind; `Display` yields the same human-readable text commands used to return.
+
+use std::fmt;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Debug)]
+pub enum AppError {
+    ModelNotFound(String),
+    /// The model file exists, but files it needs next to it don't (e.g. the VAE/CLIP
+    /// siblings of a TTS pack), so the UI can offer to download them.
+    MissingComponent(String),
+    /// The model is predicted not to load in the bundled llama-server (see `compat`).
+    Incompatible(String),
+    ServerNotRunning(String),
+    /// llama-server answered, but with an error status or an unusable body.
+    ServerError(String),
+    /// The running llama-server was started in a mode that can't serve this request.
+    WrongMode(String),
+    /// A different model is loaded than the one the request's history was generated with.
+    ModelChanged(String),
+    SpawnFailed(String),
+    /// An external process ran but exited unsuccessfully.
+    ProcessFailed(String),
+    /// An external process reported success but its output is missing or unusable.
+    InvalidOutput(String),
+    PortInUse(u16),
+    Timeout(String),
+    Busy(String),
+    /// Not enough free disk space for the operation.
+    InsufficientSpace(String),
+    Cancelled(String),
+    InvalidInput(String),
+    Io(String),
+    Other(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::ModelNotFound(_) => "model_not_found",
+            AppError::MissingComponent(_) => "missing_component",
+            AppError::Incompatible(_) => "incompatible",
+            AppError::ServerNotRunning(_) => "server_not_running",
+            AppError::ServerError(_) => "server_error",
+            AppError::WrongMode(_) => "wrong_mode",
+            AppError::ModelChanged(_) => "model_changed",
+            AppError::SpawnFailed(_) => "spawn_failed",
+            AppError::ProcessFailed(_) => "process_failed",
+            AppError::InvalidOutput(_) => "invalid_output",
+            AppError::PortInUse(_) => "port_in_use",
+            AppError::Timeout(_) => "timeout",
+            AppError::Busy(_) => "busy",
+            AppError::InsufficientSpace(_) => "insufficient_space",
+            AppError::Cancelled(_) => "cancelled",
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::Io(_) => "io",
+            AppError::Other(_) => "other",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::PortInUse(port) => write!(f, "Port {port} is already in use by another process"),
+            AppError::ModelNotFound(m)
+            | AppError::MissingComponent(m)
+            | AppError::Incompatible(m)
+            | AppError::ServerNotRunning(m)
+            | AppError::ServerError(m)
+            | AppError::WrongMode(m)
+            | AppError::ModelChanged(m)
+            | AppError::SpawnFailed(m)
+            | AppError::ProcessFailed(m)
+            | AppError::InvalidOutput(m)
+            | AppError::Timeout(m)
+            | AppError::Busy(m)
+            | AppError::InsufficientSpace(m)
+            | AppError::Cancelled(m)
+            | AppError::InvalidInput(m)
+            | AppError::Io(m)
+            | AppError::Other(m) => f.write_str(m),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl serde::Serialize for AppError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("AppError", 2)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
+
+/// Untyped helper errors (plain `String`s) surface as `Other`.
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Other(message)
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+struct AppErrorEvent {
+    command: String,
+    code: &'static str,
+    message: String,
+}
+
+/// Mirrors a failed command result onto the `app-error` event channel, so a global UI
+/// handler can show failures without every call site handling them. User-initiated
+/// cancellations aren't failures and are not emitted.
+pub fn report<T>(app: &AppHandle, command: &str, result: Result<T, AppError>) -> Result<T, AppError> {
+    if let Some(e) = result.as_ref().err().filter(|e| !matches!(e, AppError::Cancelled(_))) {
+        let _ = app.emit(
+            "app-error",
+            AppErrorEvent {
+                command: command.to_string(),
+                code: e.code(),
+                message: e.to_string(),
+            },
+        );
+    }
+    result
+}
@@ -0,0 +1,431 @@
+// Plain text from documents for `build_index`: PDF (see `pdf`), DOCX and PPTX (ZIP
+// archives of WordprocessingML / PresentationML parts), and Markdown, text and XML files
+// as they are. The format comes from the file extension.
+
+use std::path::Path;
+
+use crate::{pdf, zip};
+
+/// Largest file `extract_text` reads.
+const MAX_FILE_BYTES: u64 = 256 << 20;
+
+#[derive(Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocFormat {
+    Pdf,
+    Docx,
+    Pptx,
+    Markdown,
+    Text,
+    Xml,
+}
+
+impl DocFormat {
+    fn from_path(path: &Path) -> Result<DocFormat, String> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+        Ok(match ext.as_str() {
+            "pdf" => DocFormat::Pdf,
+            "docx" => DocFormat::Docx,
+            "pptx" => DocFormat::Pptx,
+            "md" | "markdown" => DocFormat::Markdown,
+            "txt" | "text" => DocFormat::Text,
+            "xml" => DocFormat::Xml,
+            "doc" | "ppt" => {
+                return Err(format!(
+                    "Legacy .{ext} files are not supported; save the document as .{ext}x first"
+                ))
+            }
+            "" => return Err(format!("{} has no file extension to tell its type", path.display())),
+            _ => {
+                return Err(format!(
+                    "Unsupported file type '.{ext}'; supported: pdf, docx, pptx, md, txt, xml"
+                ))
+            }
+        })
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct ExtractedDoc {
+    pub path: String,
+    pub format: DocFormat,
+    /// Paragraphs separated by blank lines; pages and slides too.
+    pub text: String,
+    /// PDF pages or PPTX slides; `None` for the other formats.
+    pub pages: Option<usize>,
+    /// 1-based pages or slides without any text (scanned or image-only pages).
+    pub empty_pages: Vec<usize>,
+    /// Paragraphs in `text`.
+    pub sections: usize,
+    pub chars: usize,
+}
+
+/// `&amp;`-style entities and `&#…;` character references in XML text.
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let decoded = rest.find(';').filter(|&end| end <= 12).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => {
+                    let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => entity.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+enum XmlEvent<'a> {
+    /// Element name and whether it is self-closing (`<w:br/>`).
+    Open(&'a str, bool),
+    Close(&'a str),
+    Text(&'a str),
+}
+
+/// Calls `on` for each tag and text run of `xml`. Comments, processing instructions and
+/// declarations are skipped; CDATA sections are passed on as text.
+fn walk_xml<'a>(xml: &'a str, mut on: impl FnMut(XmlEvent<'a>)) {
+    let mut rest = xml;
+    while let Some(at) = rest.find('<') {
+        if at > 0 {
+            on(XmlEvent::Text(&rest[..at]));
+        }
+        rest = &rest[at..];
+        let skip_to = |rest: &'a str, end: &str| rest.find(end).map_or("", |i| &rest[i + end.len()..]);
+        if let Some(body) = rest.strip_prefix("<![CDATA[") {
+            let end = body.find("]]>").unwrap_or(body.len());
+            on(XmlEvent::Text(&body[..end]));
+            rest = skip_to(body, "]]>");
+        } else if rest.starts_with("<!--") {
+            rest = skip_to(rest, "-->");
+        } else if rest.starts_with("<?") {
+            rest = skip_to(rest, "?>");
+        } else if rest.starts_with("<!") {
+            rest = skip_to(rest, ">");
+        } else {
+            // Attribute values may contain '>', so find the end outside quotes
+            let mut quote = None;
+            let end = rest.char_indices().skip(1).find_map(|(i, c)| {
+                match (quote, c) {
+                    (None, '"' | '\'') => quote = Some(c),
+                    (Some(q), c) if c == q => quote = None,
+                    (None, '>') => return Some(i),
+                    _ => {}
+                }
+                None
+            });
+            let Some(end) = end else { break };
+            let tag = &rest[1..end];
+            match tag.strip_prefix('/') {
+                Some(name) => on(XmlEvent::Close(name.trim())),
+                None => {
+                    let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default();
+                    on(XmlEvent::Open(name, tag.ends_with('/')));
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+    }
+    if !rest.is_empty() {
+        on(XmlEvent::Text(rest));
+    }
+}
+
+/// Paragraphs of an Office XML part: the text of `text_tag` elements, split at the end of
+/// each `para_tag`, with `tab_tag` / `break_tags` as tab and line break.
+fn office_paragraphs(xml: &str, text_tag: &str, para_tag: &str, tab_tag: &str, break_tags: &[&str]) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+    let mut in_text = false;
+    walk_xml(xml, |event| match event {
+        XmlEvent::Open(name, closed) if name == text_tag => in_text = !closed,
+        XmlEvent::Open(name, _) if name == tab_tag => current.push('\t'),
+        XmlEvent::Open(name, _) if break_tags.contains(&name) => current.push('\n'),
+        XmlEvent::Close(name) if name == text_tag => in_text = false,
+        XmlEvent::Close(name) if name == para_tag => {
+            let paragraph = current.trim();
+            if !paragraph.is_empty() {
+                paragraphs.push(paragraph.to_string());
+            }
+            current.clear();
+        }
+        XmlEvent::Text(text) if in_text => current.push_str(&decode_entities(text)),
+        _ => {}
+    });
+    if !current.trim().is_empty() {
+        paragraphs.push(current.trim().to_string());
+    }
+    paragraphs
+}
+
+fn read_office(bytes: &[u8], wanted: impl Fn(&str) -> bool, kind: &str) -> Result<Vec<(String, String)>, String> {
+    let entries = zip::read_zip(bytes, wanted).map_err(|e| format!("Not a readable {kind} file: {e}"))?;
+    entries
+        .into_iter()
+        .map(|(name, data)| {
+            String::from_utf8(data)
+                .map(|xml| (name.clone(), xml))
+                .map_err(|_| format!("{name} in the {kind} file is not UTF-8 XML"))
+        })
+        .collect()
+}
+
+fn docx_paragraphs(bytes: &[u8]) -> Result<Vec<String>, String> {
+    let parts = read_office(bytes, |name| name == "word/document.xml", "DOCX")?;
+    let (_, xml) = parts.first().ok_or("Not a Word document (the file has no word/document.xml)")?;
+    Ok(office_paragraphs(xml, "w:t", "w:p", "w:tab", &["w:br", "w:cr"]))
+}
+
+/// Slide number of a `ppt/slides/slideN.xml` part.
+fn slide_number(name: &str) -> Option<usize> {
+    name.strip_prefix("ppt/slides/slide")?.strip_suffix(".xml")?.parse().ok()
+}
+
+/// Text of each slide in order, its paragraphs separated by blank lines.
+fn pptx_slides(bytes: &[u8]) -> Result<Vec<String>, String> {
+    let mut parts = read_office(bytes, |name| slide_number(name).is_some(), "PPTX")?;
+    if parts.is_empty() {
+        return Err("Not a PowerPoint presentation (the file has no slides)".to_string());
+    }
+    parts.sort_by_key(|(name, _)| slide_number(name));
+    Ok(parts
+        .iter()
+        .map(|(_, xml)| office_paragraphs(xml, "a:t", "a:p", "a:tab", &["a:br"]).join("\n\n"))
+        .collect())
+}
+
+/// Text file contents: UTF-8 (BOM optional) or UTF-16 with a BOM; other bytes that aren't
+/// UTF-8 become U+FFFD.
+fn decode_text(bytes: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], be: bool| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|p| if be { u16::from_be_bytes([p[0], p[1]]) } else { u16::from_le_bytes([p[0], p[1]]) })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, false),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, true),
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+/// Text nodes of an XML document, one per line.
+fn xml_text(xml: &str) -> String {
+    let mut lines = Vec::new();
+    walk_xml(xml, |event| {
+        if let XmlEvent::Text(text) = event {
+            let text = decode_entities(text);
+            if !text.trim().is_empty() {
+                lines.push(text.trim().to_string());
+            }
+        }
+    });
+    lines.join("\n")
+}
+
+/// Blank-line-separated paragraphs of plain text.
+fn count_paragraphs(text: &str) -> usize {
+    text.split("\n\n").filter(|p| !p.trim().is_empty()).count()
+}
+
+/// Joins per-page texts into one document, noting the pages without text.
+fn join_pages(pages: &[String]) -> (String, Vec<usize>) {
+    let empty = (1..=pages.len()).filter(|&n| pages[n - 1].trim().is_empty()).collect();
+    let text = pages.iter().map(|p| p.trim()).filter(|p| !p.is_empty()).collect::<Vec<_>>().join("\n\n");
+    (text, empty)
+}
+
+/// Blocking body of `extract_text`.
+fn extract(path: &Path) -> Result<ExtractedDoc, String> {
+    let format = DocFormat::from_path(path)?;
+    let meta = std::fs::metadata(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    if !meta.is_file() {
+        return Err(format!("Not a file: {}", path.display()));
+    }
+    if meta.len() > MAX_FILE_BYTES {
+        return Err(format!(
+            "{} is larger than {} MiB",
+            path.display(),
+            MAX_FILE_BYTES >> 20
+        ));
+    }
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let (text, pages, empty_pages) = match format {
+        DocFormat::Pdf => {
+            let texts = pdf::page_texts(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let (text, empty) = join_pages(&texts);
+            (text, Some(texts.len()), empty)
+        }
+        DocFormat::Pptx => {
+            let slides = pptx_slides(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let (text, empty) = join_pages(&slides);
+            (text, Some(slides.len()), empty)
+        }
+        DocFormat::Docx => {
+            let paragraphs = docx_paragraphs(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+            (paragraphs.join("\n\n"), None, Vec::new())
+        }
+        DocFormat::Markdown | DocFormat::Text => (decode_text(&bytes).replace("\r\n", "\n"), None, Vec::new()),
+        DocFormat::Xml => (xml_text(&decode_text(&bytes)), None, Vec::new()),
+    };
+
+    if text.trim().is_empty() {
+        let hint = match format {
+            DocFormat::Pdf => " (scanned or image-only PDFs need OCR, which isn't supported)",
+            _ => "",
+        };
+        return Err(format!("{} contains no extractable text{hint}", path.display()));
+    }
+    Ok(ExtractedDoc {
+        path: path.to_string_lossy().to_string(),
+        format,
+        sections: count_paragraphs(&text),
+        chars: text.chars().count(),
+        text,
+        pages,
+        empty_pages,
+    })
+}
+
+/// Extracts the plain text of a PDF, DOCX, PPTX, Markdown, text or XML file (by
+/// extension) for `build_index`. Pages or slides without text are listed in
+/// `empty_pages`; a file with no text at all, a legacy `.doc` / `.ppt`, an encrypted PDF
+/// or an unknown extension is an error.
+#[tauri::command]
+pub async fn extract_text(path: String) -> Result<ExtractedDoc, String> {
+    tauri::async_runtime::spawn_blocking(move || extract(Path::new(&path)))
+        .await
+        .map_err(|e| format!("Text extraction failed: {e}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `extract` of a temporary file `name` holding `bytes`.
+    fn extract_file(name: &str, bytes: &[u8]) -> Result<ExtractedDoc, String> {
+        let dir = std::env::temp_dir().join(format!("genhat-extract-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        let result = extract(&path);
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    fn extract_error(name: &str, bytes: &[u8]) -> String {
+        extract_file(name, bytes).err().expect("extraction should fail")
+    }
+
+    fn office_file(parts: &[(&str, &str)]) -> Vec<u8> {
+        let entries: Vec<(String, Vec<u8>)> =
+            parts.iter().map(|(name, xml)| (name.to_string(), xml.as_bytes().to_vec())).collect();
+        zip::write_zip(&entries).unwrap()
+    }
+
+    const DOCUMENT_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<w:document xmlns:w="x"><w:body>
+<w:p><w:r><w:t>Fish &amp; chips</w:t></w:r><w:r><w:tab/><w:t xml:space="preserve"> &#163;5</w:t></w:r></w:p>
+<w:p></w:p>
+<w:p><w:r><w:t>line one</w:t><w:br/><w:t>line &lt;two&gt;</w:t></w:r></w:p>
+</w:body></w:document>"#;
+
+    #[test]
+    fn docx_paragraphs_entities_tabs_and_breaks() {
+        let docx = office_file(&[("[Content_Types].xml", "<Types/>"), ("word/document.xml", DOCUMENT_XML)]);
+        let doc = extract_file("report.docx", &docx).unwrap();
+        assert_eq!(doc.text, "Fish & chips\t £5\n\nline one\nline <two>");
+        assert_eq!(doc.sections, 2);
+        assert_eq!(doc.pages, None);
+        assert_eq!(doc.chars, doc.text.chars().count());
+    }
+
+    #[test]
+    fn pptx_slides_in_number_order() {
+        let slide = |text: &str| format!("<p:sld><a:p><a:r><a:t>{text}</a:t></a:r></a:p></p:sld>");
+        let pptx = office_file(&[
+            ("ppt/slides/slide10.xml", &slide("ten")),
+            ("ppt/slides/slide2.xml", "<p:sld><p:pic/></p:sld>"),
+            ("ppt/slides/slide1.xml", &slide("one")),
+            ("ppt/slides/_rels/slide1.xml.rels", "<Relationships/>"),
+            ("ppt/notesSlides/notesSlide1.xml", &slide("notes")),
+        ]);
+        let doc = extract_file("deck.PPTX", &pptx).unwrap();
+        assert_eq!(doc.text, "one\n\nten");
+        assert_eq!(doc.pages, Some(3));
+        assert_eq!(doc.empty_pages, vec![2]);
+    }
+
+    #[test]
+    fn malformed_office_files() {
+        assert!(extract_error("a.docx", &office_file(&[("word/other.xml", "<x/>")])).contains("no word/document.xml"));
+        assert!(extract_error("a.pptx", &office_file(&[("ppt/presentation.xml", "<x/>")])).contains("no slides"));
+        assert!(extract_error("a.docx", b"plain text, not a zip").contains("Not a readable DOCX file"));
+        let docx = office_file(&[("word/document.xml", DOCUMENT_XML)]);
+        for len in (0..docx.len()).step_by(13) {
+            assert!(extract_file("a.docx", &docx[..len]).is_err(), "{len} bytes");
+        }
+        let latin1 = zip::write_zip(&[("word/document.xml".to_string(), b"<w:t>caf\xe9</w:t>".to_vec())]).unwrap();
+        assert!(extract_error("a.docx", &latin1).contains("not UTF-8"));
+    }
+
+    #[test]
+    fn text_markdown_and_xml() {
+        let doc = extract_file("notes.md", b"\xEF\xBB\xBF# Title\r\n\r\nBody").unwrap();
+        assert_eq!(doc.text, "# Title\n\nBody");
+        assert_eq!(doc.sections, 2);
+
+        let utf16: Vec<u8> =
+            [0xFF, 0xFE].into_iter().chain("héllo".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        assert_eq!(extract_file("a.txt", &utf16).unwrap().text, "héllo");
+
+        let xml = extract_file("feed.xml", b"<a><b> one </b><c/>\n<d>two &amp; three</d></a>").unwrap();
+        assert_eq!(xml.text, "one\ntwo & three");
+    }
+
+    #[test]
+    fn unsupported_and_empty_files() {
+        assert!(extract_error("a.doc", b"x").contains("Legacy .doc"));
+        assert!(extract_error("a.ppt", b"x").contains("save the document as .pptx"));
+        assert!(extract_error("a.exe", b"x").contains("Unsupported file type '.exe'"));
+        assert!(extract_error("README", b"x").contains("no file extension"));
+        assert!(extract_error("blank.txt", b" \n\t\n").contains("no extractable text"));
+        assert!(extract_error("a.pdf", b"not a pdf").contains("no %PDF- header"));
+        let folder = std::env::temp_dir().join(format!("genhat-extract-test-{}", std::process::id())).join("dir.txt");
+        std::fs::create_dir_all(&folder).unwrap();
+        assert!(extract(&folder).err().unwrap().starts_with("Not a file"));
+        std::fs::remove_dir(&folder).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+// Minimal GGUF header reader: parses the metadata key/value section without touching
+// tensor data. Array values (tokenizer vocabularies etc.) are skipped and only their
+// length is kept, so reading a multi-GB model costs a few hundred KB of I/O.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum GgufValue {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    /// Element count of a skipped array.
+    Array(u64),
+}
+
+pub struct GgufMetadata {
+    pub values: HashMap<String, GgufValue>,
+}
+
+impl GgufMetadata {
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.values.get(key) {
+            Some(GgufValue::Str(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// An integer value of either signedness; `None` for negatives and non-integers.
+    pub fn get_uint(&self, key: &str) -> Option<u64> {
+        match self.values.get(key) {
+            Some(GgufValue::UInt(v)) => Some(*v),
+            Some(GgufValue::Int(v)) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// `general.architecture`, e.g. `llama`, `qwen2`, `bert`, `clip`.
+    pub fn architecture(&self) -> Option<&str> {
+        self.get_str("general.architecture")
+    }
+}
+
+/// Strings longer than this are treated as a corrupt header rather than allocated.
+const MAX_STRING_LEN: u64 = 1 << 24;
+
+struct Reader<R> {
+    inner: BufReader<R>,
+}
+
+impl<R: Read + std::io::Seek> Reader<R> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut buf = [0u8; N];
+        self.inner.read_exact(&mut buf).map_err(|e| format!("Truncated GGUF header: {e}"))?;
+        Ok(buf)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u64()?;
+        if len > MAX_STRING_LEN {
+            return Err(format!("GGUF string length {len} is implausible"));
+        }
+        let mut buf = vec![0u8; len as usize];
+        self.inner.read_exact(&mut buf).map_err(|e| format!("Truncated GGUF header: {e}"))?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    fn skip(&mut self, n: i64) -> Result<(), String> {
+        self.inner.seek_relative(n).map_err(|e| format!("Truncated GGUF header: {e}"))
+    }
+
+    fn value(&mut self, ty: u32) -> Result<GgufValue, String> {
+        Ok(match ty {
+            0 => GgufValue::UInt(self.bytes::<1>()?[0] as u64),
+            1 => GgufValue::Int(self.bytes::<1>()?[0] as i8 as i64),
+            2 => GgufValue::UInt(u16::from_le_bytes(self.bytes()?) as u64),
+            3 => GgufValue::Int(i16::from_le_bytes(self.bytes()?) as i64),
+            4 => GgufValue::UInt(self.u32()? as u64),
+            5 => GgufValue::Int(i32::from_le_bytes(self.bytes()?) as i64),
+            6 => GgufValue::Float(f32::from_le_bytes(self.bytes()?) as f64),
+            7 => GgufValue::Bool(self.bytes::<1>()?[0] != 0),
+            8 => GgufValue::Str(self.string()?),
+            9 => {
+                let elem = self.u32()?;
+                let count = self.u64()?;
+                self.skip_array(elem, count)?;
+                GgufValue::Array(count)
+            }
+            10 => GgufValue::UInt(self.u64()?),
+            11 => GgufValue::Int(i64::from_le_bytes(self.bytes()?)),
+            12 => GgufValue::Float(f64::from_le_bytes(self.bytes()?)),
+            other => return Err(format!("Unknown GGUF value type {other}")),
+        })
+    }
+
+    fn skip_array(&mut self, elem: u32, count: u64) -> Result<(), String> {
+        let fixed = match elem {
+            0 | 1 | 7 => Some(1),
+            2 | 3 => Some(2),
+            4..=6 => Some(4),
+            10..=12 => Some(8),
+            _ => None,
+        };
+        match fixed {
+            Some(size) => {
+                let total = count.checked_mul(size).filter(|&t| t <= i64::MAX as u64);
+                self.skip(total.ok_or("GGUF array is implausibly large")? as i64)
+            }
+            None => {
+                for _ in 0..count {
+                    match elem {
+                        8 => {
+                            let len = self.u64()?;
+                            if len > MAX_STRING_LEN {
+                                return Err(format!("GGUF string length {len} is implausible"));
+                            }
+                            self.skip(len as i64)?;
+                        }
+                        _ => {
+                            self.value(elem)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Format versions `read_metadata` understands (and llama.cpp currently loads).
+pub const SUPPORTED_VERSIONS: std::ops::RangeInclusive<u32> = 2..=3;
+
+/// Checks the magic and returns the GGUF format version, without reading any further.
+pub fn read_version(path: &Path) -> Result<u32, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut r = Reader {
+        inner: BufReader::new(file),
+    };
+    if &r.bytes::<4>()? != b"GGUF" {
+        return Err(format!("{} is not a GGUF file", path.display()));
+    }
+    r.u32()
+}
+
+/// Reads the metadata section of a GGUF file. Only GGUF v2 and v3 are supported.
+pub fn read_metadata(path: &Path) -> Result<GgufMetadata, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut r = Reader {
+        inner: BufReader::new(file),
+    };
+
+    if &r.bytes::<4>()? != b"GGUF" {
+        return Err(format!("{} is not a GGUF file", path.display()));
+    }
+    let version = r.u32()?;
+    if !SUPPORTED_VERSIONS.contains(&version) {
+        return Err(format!("Unsupported GGUF version {version}"));
+    }
+    let _tensor_count = r.u64()?;
+    let kv_count = r.u64()?;
+
+    let mut values = HashMap::new();
+    for _ in 0..kv_count {
+        let key = r.string()?;
+        let ty = r.u32()?;
+        let value = r.value(ty)?;
+        values.insert(key, value);
+    }
+
+    Ok(GgufMetadata { values })
+}
+
+/// A model split into `<base>-00001-of-00003.gguf`, `...-00002-of-00003.gguf`, ...
+pub struct SplitSet {
+    /// Shard 1, the only path llama-server needs (it loads the rest itself).
+    pub first: PathBuf,
+    pub count: u32,
+    /// Every shard's path in order, present or not.
+    pub shards: Vec<PathBuf>,
+    /// 1-based indices of shards not present next to the given file.
+    pub missing: Vec<u32>,
+}
+
+/// Parses the split-file suffix: `(base, index, count)`.
+fn split_name(file_name: &str) -> Option<(&str, u32, u32)> {
+    let stem = file_name.strip_suffix(".gguf")?;
+    let (rest, count) = stem.rsplit_once("-of-")?;
+    let (base, index) = rest.rsplit_once('-')?;
+    let number = |s: &str| Some(s).filter(|s| s.len() == 5 && s.bytes().all(|b| b.is_ascii_digit()))?.parse().ok();
+    let (index, count): (u32, u32) = (number(index)?, number(count)?);
+    (index >= 1 && index <= count).then_some((base, index, count))
+}
+
+/// Returns the split set `path` belongs to, or `None` for a single-file model.
+pub fn split_set(path: &Path) -> Option<SplitSet> {
+    let (base, _, count) = split_name(path.file_name()?.to_str()?)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let shard = |i: u32| dir.join(format!("{base}-{i:05}-of-{count:05}.gguf"));
+    Some(SplitSet {
+        first: shard(1),
+        count,
+        shards: (1..=count).map(shard).collect(),
+        missing: (1..=count).filter(|&i| !shard(i).is_file()).collect(),
+    })
+}
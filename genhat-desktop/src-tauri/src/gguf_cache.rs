@@ -0,0 +1,121 @@
+// Parsed GGUF headers, persisted in <app data>/cache/gguf-metadata.json so `list_models`
+// (category detection), `check_model_compatibility` and the memory estimate don't re-read
+// every header on each call or after a restart. Entries are keyed by path and only reused
+// while the file's size and modification time still match.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager};
+
+use crate::gguf::{self, GgufMetadata, GgufValue};
+use crate::{scan_models, AppState};
+
+#[derive(Default)]
+pub struct CacheState {
+    /// Where the cache is persisted; `None` until `load` (or without an app data directory).
+    file: Option<PathBuf>,
+    entries: HashMap<String, Entry>,
+    /// Entries changed since the last `save`.
+    dirty: bool,
+}
+
+pub type MetadataCache = std::sync::Mutex<CacheState>;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct Entry {
+    len: u64,
+    modified: u64,
+    /// Parse failures are kept too, so a non-GGUF file isn't re-read every scan.
+    metadata: Result<HashMap<String, GgufValue>, String>,
+}
+
+fn cache_file(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join("cache").join("gguf-metadata.json"))
+}
+
+/// Reads the persisted cache (empty if it is missing or unreadable).
+pub fn load(app: &AppHandle) -> CacheState {
+    let file = cache_file(app);
+    let entries = file
+        .as_ref()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    CacheState { file, entries, dirty: false }
+}
+
+/// Size and modification time (ms since the epoch) identifying this version of the file.
+fn stamp(path: &Path) -> Result<(u64, u64), String> {
+    let meta = std::fs::metadata(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    Ok((meta.len(), modified))
+}
+
+/// `gguf::read_metadata`, answered from the cache while the file is unchanged.
+pub fn read_metadata(state: &AppState, path: &Path) -> Result<GgufMetadata, String> {
+    let (len, modified) = stamp(path)?;
+    let key = path.to_string_lossy().to_string();
+    if let Some(entry) = state.gguf_metadata.lock().unwrap().entries.get(&key) {
+        if entry.len == len && entry.modified == modified {
+            return entry.metadata.clone().map(|values| GgufMetadata { values });
+        }
+    }
+    // Parse without the lock held; a concurrent read of the same file just parses twice
+    let metadata = gguf::read_metadata(path).map(|m| m.values);
+    let mut cache = state.gguf_metadata.lock().unwrap();
+    cache.entries.insert(key, Entry { len, modified, metadata: metadata.clone() });
+    cache.dirty = true;
+    metadata.map(|values| GgufMetadata { values })
+}
+
+/// Writes the cache to disk if it changed. Failures are logged; the cache is only an
+/// optimization.
+pub fn save(state: &AppState) {
+    let mut cache = state.gguf_metadata.lock().unwrap();
+    let Some(file) = cache.file.clone().filter(|_| cache.dirty) else {
+        return;
+    };
+    let result = file
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::to_string(&cache.entries).map_err(|e| e.to_string()))
+        .and_then(|json| std::fs::write(&file, json).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => cache.dirty = false,
+        Err(e) => eprintln!("Failed to save GGUF metadata cache {}: {}", file.display(), e),
+    }
+}
+
+/// Drops every cached header and re-reads those of the models in the models folder, so
+/// entries for deleted files are pruned and later `list_models` calls are answered from the
+/// cache. Returns the number of files read.
+#[tauri::command]
+pub async fn refresh_model_metadata(app: AppHandle) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        {
+            let mut cache = state.gguf_metadata.lock().unwrap();
+            cache.entries.clear();
+            cache.dirty = true;
+        }
+        // Models with a category override skip detection, so read their headers here
+        for model in scan_models(&state) {
+            let _ = read_metadata(&state, Path::new(&model.path));
+        }
+        save(&state);
+        let count = state.gguf_metadata.lock().unwrap().entries.len();
+        count
+    })
+    .await
+    .map_err(|e| format!("Metadata refresh failed: {e}"))
+}
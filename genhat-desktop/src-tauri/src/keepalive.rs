@@ -0,0 +1,118 @@
+// Keep-alive for a loaded llama-server. The crash watcher only sees a process that exits;
+// one that is alive but wedged (deadlocked, stuck on a bad request) never answers again.
+// While no chat request is in flight, the watcher pings the readiness probe (`/health`
+// unless `health_path` is set) every `KeepAlive::interval_secs`; after `failure_threshold`
+// consecutive misses the server is marked unresponsive, `server-unresponsive` is emitted
+// and, if enabled, it is restarted.
+
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::{reload_llama, AppState};
+
+/// How long a single `/health` ping may take before it counts as a miss.
+const PING_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Clone, serde::Serialize)]
+struct UnresponsiveEvent {
+    model: Option<String>,
+    pid: u32,
+    /// Consecutive pings that went unanswered.
+    failures: u32,
+    /// A restart with the same model and config follows (`KeepAlive::restart`).
+    restarting: bool,
+}
+
+/// Ping state for one llama-server; owned by its watcher task.
+pub struct Monitor {
+    pid: u32,
+    health_url: String,
+    client: reqwest::Client,
+    last_ping: Instant,
+    failures: u32,
+}
+
+impl Monitor {
+    pub fn new(pid: u32, health_url: String) -> Self {
+        Monitor {
+            pid,
+            health_url,
+            client: reqwest::Client::new(),
+            last_ping: Instant::now(),
+            failures: 0,
+        }
+    }
+
+    /// Pings the server if the interval has passed and it is idle. Called from the watcher
+    /// loop every `CRASH_POLL_SECS`, so the effective interval is rounded up to that.
+    pub async fn tick(&mut self, app: &AppHandle) {
+        let state = app.state::<AppState>();
+        let keepalive = state.settings.lock().unwrap().keepalive.clone();
+        if !keepalive.enabled || self.last_ping.elapsed() < Duration::from_secs(keepalive.interval_secs) {
+            return;
+        }
+        self.last_ping = Instant::now();
+        // A generating server is busy, not hung; its request has its own timeout
+        if !state.chat_queue.is_idle() {
+            self.failures = 0;
+            return;
+        }
+        let answered = self
+            .client
+            .get(&self.health_url)
+            .timeout(Duration::from_secs(PING_TIMEOUT_SECS))
+            .send()
+            .await
+            .is_ok();
+        if answered {
+            if self.failures >= keepalive.failure_threshold {
+                eprintln!("llama-server (pid {}) is responding again", self.pid);
+            }
+            self.failures = 0;
+            let mut unresponsive = state.unresponsive.lock().unwrap();
+            if *unresponsive == Some(self.pid) {
+                *unresponsive = None;
+            }
+            return;
+        }
+        self.failures += 1;
+        if self.failures != keepalive.failure_threshold {
+            return;
+        }
+
+        eprintln!(
+            "llama-server (pid {}) missed {} health checks in a row; marking it unresponsive",
+            self.pid, self.failures
+        );
+        *state.unresponsive.lock().unwrap() = Some(self.pid);
+        let _ = app.emit(
+            "server-unresponsive",
+            UnresponsiveEvent {
+                model: state.current_model.lock().unwrap().clone(),
+                pid: self.pid,
+                failures: self.failures,
+                restarting: keepalive.restart,
+            },
+        );
+        if keepalive.restart {
+            let handle = app.clone();
+            let pid = self.pid;
+            let result = tauri::async_runtime::spawn_blocking(move || restart(&handle, pid)).await;
+            match result {
+                Ok(Err(e)) => eprintln!("Keep-alive restart failed: {e}"),
+                Err(e) => eprintln!("Keep-alive restart task failed: {e}"),
+                Ok(Ok(())) => {}
+            }
+        }
+    }
+}
+
+/// Replaces the hung server `pid` with a fresh one for the same model and config, unless
+/// it was stopped or replaced in the meantime.
+fn restart(app: &AppHandle, pid: u32) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    if state.llama.lock().unwrap().as_ref().map(|c| c.id()) != Some(pid) {
+        return Ok(());
+    }
+    reload_llama(app, &state).map(|_| ()).map_err(|e| e.to_string())
+}
@@ -0,0 +1,540 @@
+// llama-server launch configuration. `LlamaConfig` is what `switch_model` receives from
+// the frontend (and presets store); every field is optional, so `{}` is a valid config
+// that starts the server with the built-in defaults.
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::chat::GenParams;
+use crate::error::AppError;
+use crate::{backends, AppState, ServerMode};
+
+/// Default KV-cache size passed as `--ctx-size`, shared by all parallel slots.
+pub const CTX_SIZE: u32 = 4096;
+
+/// Bounds for `ctx_size`. llama.cpp refuses tiny contexts, and beyond 256k the KV cache
+/// alone outgrows any desktop.
+const CTX_SIZE_RANGE: std::ops::RangeInclusive<u32> = 256..=262_144;
+
+/// llama-server's `--batch-size` when none is given.
+const DEFAULT_BATCH_SIZE: u32 = 2048;
+
+/// Templates built into llama.cpp, accepted by name for `chat_template`.
+pub const CHAT_TEMPLATES: &[&str] = &[
+    "chatml", "llama2", "llama2-sys", "llama2-sys-bos", "llama2-sys-strip", "llama3", "llama4", "mistral-v1",
+    "mistral-v3", "mistral-v3-tekken", "mistral-v7", "mistral-v7-tekken", "phi3", "phi4", "falcon3", "zephyr",
+    "monarch", "gemma", "orion", "openchat", "vicuna", "vicuna-orca", "deepseek", "deepseek2", "deepseek3",
+    "command-r", "chatglm3", "chatglm4", "glm4", "minicpm", "exaone3", "rwkv-world", "granite", "gigachat",
+    "megrez",
+];
+
+/// Upper bound for `parallel_slots`; beyond this each slot's share of the context is uselessly small.
+const MAX_PARALLEL_SLOTS: u32 = 16;
+
+/// Upper bound for `rope_freq_base`; trained bases top out around 1e7 (long-context Qwen).
+const MAX_ROPE_FREQ_BASE: f32 = 1e9;
+
+/// RoPE frequency scaling method (`--rope-scaling`).
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RopeScaling {
+    None,
+    Linear,
+    Yarn,
+}
+
+impl RopeScaling {
+    fn flag_value(self) -> &'static str {
+        match self {
+            RopeScaling::None => "none",
+            RopeScaling::Linear => "linear",
+            RopeScaling::Yarn => "yarn",
+        }
+    }
+}
+
+/// Optional llama-server startup settings. Unset fields keep the built-in defaults (or the
+/// binary's own, where GenHat has none). The port is fixed (`LLAMA_PORT`); the host comes
+/// from the `bind_host` setting.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct LlamaConfig {
+    /// Server-wide sampling defaults; requests can still override them per call.
+    pub params: GenParams,
+    /// `--ctx-size`; `None` = `CTX_SIZE`.
+    pub ctx_size: Option<u32>,
+    /// Layers offloaded to the GPU (`-ngl`). `None` leaves it to `llama_extra_args` / the binary.
+    pub gpu_layers: Option<i32>,
+    /// Picks `gpu_layers` at launch: as many layers as the memory estimate says fit in the
+    /// free VRAM, with headroom (see `memory::fit_gpu_layers`). Overrides `gpu_layers`.
+    pub auto_gpu_layers: bool,
+    /// CPU threads for generation (`--threads`). `None` lets llama.cpp pick.
+    pub threads: Option<u32>,
+    /// Logical batch for prompt processing (`-b`). `None` keeps llama-server's 2048.
+    pub batch_size: Option<u32>,
+    /// Physical batch actually computed at once (`-ub`). `None` keeps llama-server's 512.
+    pub ubatch_size: Option<u32>,
+    /// LoRA adapter files applied on top of the model (`--lora`, repeated).
+    pub lora: Vec<String>,
+    /// Concurrent request slots (`--parallel`). `None` keeps llama-server's single slot.
+    pub parallel_slots: Option<u32>,
+    /// Prompt tokens kept when the context shifts (`--keep`; -1 = the whole prompt).
+    pub keep_tokens: Option<i32>,
+    /// `--context-shift` / `--no-context-shift`; `None` keeps the binary's default.
+    pub context_shift: Option<bool>,
+    /// GPU index for the model (or its scratch buffers when split) (`--main-gpu`).
+    pub main_gpu: Option<u32>,
+    /// Proportion of the model offloaded to each GPU, in device order (`--tensor-split`).
+    pub tensor_split: Option<Vec<f32>>,
+    /// Replaces the model's embedded chat template: a `CHAT_TEMPLATES` name
+    /// (`--chat-template`) or Jinja source (written to a temp file, `--chat-template-file`).
+    pub chat_template: Option<String>,
+    /// Folder llama-server may save and restore slot KV caches in (`--slot-save-path`);
+    /// enables saving the primed system prompt (see `slot_cache`).
+    pub slot_save_path: Option<String>,
+    /// RoPE scaling for running past the trained context (`--rope-scaling`); `None` keeps
+    /// the model's own. Wrong values degrade output quality rather than fail.
+    pub rope_scaling: Option<RopeScaling>,
+    /// RoPE base frequency (`--rope-freq-base`); `None` = from the model.
+    pub rope_freq_base: Option<f32>,
+    /// RoPE frequency scale factor (`--rope-freq-scale`); 1/N extends the context N times.
+    pub rope_freq_scale: Option<f32>,
+    pub mode: ServerMode,
+}
+
+impl LlamaConfig {
+    pub fn ctx_size(&self) -> u32 {
+        self.ctx_size.unwrap_or(CTX_SIZE)
+    }
+
+    /// Checks every option before the running server is killed. Blocking when GPU options
+    /// are set (probes `--list-devices`, cached).
+    pub fn validate(&self, state: &AppState) -> Result<(), AppError> {
+        self.validate_options()?;
+        if self.main_gpu.is_some() || self.tensor_split.is_some() {
+            validate_gpu_selection(state, self.main_gpu, self.tensor_split.as_deref())?;
+        }
+        Ok(())
+    }
+
+    /// The checks of `validate` that don't depend on the machine's GPUs.
+    fn validate_options(&self) -> Result<(), AppError> {
+        self.params.validate().map_err(AppError::InvalidInput)?;
+        let ctx = self.ctx_size();
+        if !CTX_SIZE_RANGE.contains(&ctx) {
+            return Err(AppError::InvalidInput(format!(
+                "ctx_size must be between {} and {} (got {ctx})",
+                CTX_SIZE_RANGE.start(),
+                CTX_SIZE_RANGE.end()
+            )));
+        }
+        if let Some(n) = self.parallel_slots {
+            if n == 0 || n > MAX_PARALLEL_SLOTS {
+                return Err(AppError::InvalidInput(format!(
+                    "parallel_slots must be between 1 and {MAX_PARALLEL_SLOTS} (got {n})"
+                )));
+            }
+        }
+        // The kept prefix has to fit in one slot's share of the context with room to spare.
+        if let Some(keep) = self.keep_tokens {
+            let slot_ctx = (ctx / self.parallel_slots.unwrap_or(1)) as i32;
+            if keep < -1 || keep >= slot_ctx {
+                return Err(AppError::InvalidInput(format!(
+                    "keep_tokens must be -1 or between 0 and {} (got {keep})",
+                    slot_ctx - 1
+                )));
+            }
+        }
+        if let Some(layers) = self.gpu_layers.filter(|&l| l < 0) {
+            return Err(AppError::InvalidInput(format!("gpu_layers must not be negative (got {layers})")));
+        }
+        if self.threads == Some(0) {
+            return Err(AppError::InvalidInput("threads must be at least 1".to_string()));
+        }
+        if self.batch_size == Some(0) || self.ubatch_size == Some(0) {
+            return Err(AppError::InvalidInput("batch_size and ubatch_size must be at least 1".to_string()));
+        }
+        if let Some(missing) = self.lora.iter().find(|p| !Path::new(p).is_file()) {
+            return Err(AppError::ModelNotFound(format!("LoRA adapter not found: {missing}")));
+        }
+        if let Some(name) = self.chat_template.as_deref().filter(|t| !is_jinja(t)) {
+            if !CHAT_TEMPLATES.contains(&name) {
+                return Err(AppError::InvalidInput(format!(
+                    "Unknown chat template '{name}'; use one of {} or pass Jinja template source",
+                    CHAT_TEMPLATES.join(", ")
+                )));
+            }
+        }
+        if let Some(base) = self.rope_freq_base.filter(|b| !b.is_finite() || *b <= 0.0 || *b > MAX_ROPE_FREQ_BASE) {
+            return Err(AppError::InvalidInput(format!(
+                "rope_freq_base must be greater than 0 and at most {MAX_ROPE_FREQ_BASE} (got {base})"
+            )));
+        }
+        if let Some(scale) = self.rope_freq_scale.filter(|s| !s.is_finite() || *s <= 0.0 || *s > 1.0) {
+            return Err(AppError::InvalidInput(format!(
+                "rope_freq_scale must be greater than 0 and at most 1 (got {scale})"
+            )));
+        }
+        if let Some(dir) = self.slot_save_path.as_deref().filter(|d| !Path::new(d).is_dir()) {
+            return Err(AppError::InvalidInput(format!("slot_save_path is not a folder: {dir}")));
+        }
+        Ok(())
+    }
+
+    /// Writes a Jinja `chat_template` to the file `to_args` points llama-server at. A no-op
+    /// for named templates.
+    pub fn write_chat_template(&self) -> Result<(), String> {
+        if let Some(source) = self.chat_template.as_deref().filter(|t| is_jinja(t)) {
+            let path = template_file(source);
+            std::fs::write(&path, source).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Settings that are valid but probably not what the caller meant.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let batch = self.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+        if let Some(ubatch) = self.ubatch_size.filter(|&u| u > batch) {
+            warnings.push(format!(
+                "ubatch_size {ubatch} exceeds batch_size {batch}; llama-server caps it at batch_size"
+            ));
+        }
+        if self.auto_gpu_layers && self.gpu_layers.is_some() {
+            warnings.push("gpu_layers is replaced by the count auto_gpu_layers picks".to_string());
+        }
+        if matches!(self.rope_scaling, Some(RopeScaling::None)) && self.rope_freq_scale.is_some() {
+            warnings.push("rope_freq_scale has no effect with rope_scaling \"none\"".to_string());
+        }
+        warnings
+    }
+
+    /// Option args: caller values where set, otherwise the built-in defaults
+    /// (`GenParams::builtin`, shared with `get_default_params`). Samplers without a
+    /// default (seed, min-p, mirostat) are only passed when set.
+    pub fn to_args(&self) -> Vec<String> {
+        let p = self.params.clone().or_defaults(&GenParams::builtin());
+        let mut args = vec!["--ctx-size".to_string(), self.ctx_size().to_string()];
+        let flags = [
+            ("-n", p.max_tokens.map(|v| v.to_string())),
+            ("--temp", p.temperature.map(|v| v.to_string())),
+            ("--top-p", p.top_p.map(|v| v.to_string())),
+            ("--top-k", p.top_k.map(|v| v.to_string())),
+            ("--repeat-penalty", p.repeat_penalty.map(|v| v.to_string())),
+            ("--seed", p.seed.map(|v| v.to_string())),
+            ("--min-p", p.min_p.map(|v| v.to_string())),
+            ("--mirostat", p.mirostat.map(|v| v.to_string())),
+            ("--mirostat-ent", p.mirostat_tau.map(|v| v.to_string())),
+            ("--mirostat-lr", p.mirostat_eta.map(|v| v.to_string())),
+            ("-ngl", self.gpu_layers.map(|v| v.to_string())),
+            ("--threads", self.threads.map(|v| v.to_string())),
+            ("-b", self.batch_size.map(|v| v.to_string())),
+            ("-ub", self.ubatch_size.map(|v| v.to_string())),
+        ];
+        for (flag, value) in flags {
+            if let Some(value) = value {
+                args.extend([flag.to_string(), value]);
+            }
+        }
+        for adapter in &self.lora {
+            args.extend(["--lora".to_string(), adapter.clone()]);
+        }
+        // Slots share the --ctx-size KV cache, so each of N slots gets ctx-size / N tokens.
+        if let Some(n) = self.parallel_slots.filter(|&n| n > 1) {
+            args.extend(["--parallel".to_string(), n.to_string(), "--cont-batching".to_string()]);
+        }
+        if let Some(keep) = self.keep_tokens {
+            args.extend(["--keep".to_string(), keep.to_string()]);
+        }
+        match self.context_shift {
+            Some(true) => args.push("--context-shift".to_string()),
+            Some(false) => args.push("--no-context-shift".to_string()),
+            None => {}
+        }
+        if let Some(gpu) = self.main_gpu {
+            args.extend(["--main-gpu".to_string(), gpu.to_string()]);
+        }
+        if let Some(split) = &self.tensor_split {
+            let split: Vec<String> = split.iter().map(|v| v.to_string()).collect();
+            args.extend(["--tensor-split".to_string(), split.join(",")]);
+        }
+        match self.chat_template.as_deref() {
+            Some(source) if is_jinja(source) => {
+                let path = template_file(source).to_string_lossy().to_string();
+                args.extend(["--jinja".to_string(), "--chat-template-file".to_string(), path]);
+            }
+            Some(name) => args.extend(["--chat-template".to_string(), name.to_string()]),
+            None => {}
+        }
+        if let Some(dir) = &self.slot_save_path {
+            args.extend(["--slot-save-path".to_string(), dir.clone()]);
+        }
+        if let Some(scaling) = self.rope_scaling {
+            args.extend(["--rope-scaling".to_string(), scaling.flag_value().to_string()]);
+        }
+        if let Some(base) = self.rope_freq_base {
+            args.extend(["--rope-freq-base".to_string(), base.to_string()]);
+        }
+        if let Some(scale) = self.rope_freq_scale {
+            args.extend(["--rope-freq-scale".to_string(), scale.to_string()]);
+        }
+        match self.mode {
+            ServerMode::Chat => {}
+            ServerMode::Embedding => args.push("--embedding".to_string()),
+            ServerMode::Rerank => args.push("--reranking".to_string()),
+        }
+        args
+    }
+}
+
+/// Template source rather than a built-in name.
+fn is_jinja(template: &str) -> bool {
+    template.contains("{%") || template.contains("{{")
+}
+
+/// Temp file for a Jinja template, named by its hash so identical templates share one.
+fn template_file(source: &str) -> PathBuf {
+    let digest = Sha256::digest(source.as_bytes());
+    let hash: String = digest.iter().take(8).map(|b| format!("{b:02x}")).collect();
+    std::env::temp_dir().join(format!("genhat-chat-template-{hash}.jinja"))
+}
+
+/// Checks `main_gpu` / `tensor_split` against the GPUs the bundled llama-server reports
+/// (see `list_backends`), so a bad index fails here rather than as a crashed server.
+fn validate_gpu_selection(state: &AppState, main_gpu: Option<u32>, tensor_split: Option<&[f32]>) -> Result<(), AppError> {
+    let count = backends::gpu_devices(state).map_err(AppError::Other)?.len();
+    if count == 0 {
+        return Err(AppError::InvalidInput("No GPU devices detected; main_gpu and tensor_split need one".into()));
+    }
+    if let Some(gpu) = main_gpu.filter(|&g| g as usize >= count) {
+        return Err(AppError::InvalidInput(format!(
+            "main_gpu {gpu} is out of range: {count} GPU device(s) detected (indices 0-{})",
+            count - 1
+        )));
+    }
+    if let Some(split) = tensor_split {
+        if split.is_empty() || split.len() > count {
+            return Err(AppError::InvalidInput(format!(
+                "tensor_split must have between 1 and {count} entries (got {})",
+                split.len()
+            )));
+        }
+        if split.iter().any(|v| !v.is_finite() || *v < 0.0) || split.iter().all(|v| *v == 0.0) {
+            return Err(AppError::InvalidInput(
+                "tensor_split entries must be non-negative and not all zero".into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(config: &LlamaConfig) -> Vec<String> {
+        config.to_args()
+    }
+
+    /// The value following `flag`, if the flag is present.
+    fn value_of<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+        args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).map(String::as_str)
+    }
+
+    fn is_invalid(config: LlamaConfig) -> bool {
+        matches!(config.validate_options(), Err(AppError::InvalidInput(_)))
+    }
+
+    #[test]
+    fn default_args() {
+        let expected = [
+            "--ctx-size", "4096", "-n", "256", "--temp", "0.7", "--top-p", "0.9", "--top-k", "40",
+            "--repeat-penalty", "1.1",
+        ];
+        assert_eq!(args(&LlamaConfig::default()), expected);
+    }
+
+    #[test]
+    fn optional_flags_absent_by_default() {
+        let args = args(&LlamaConfig::default());
+        for flag in [
+            "-ngl",
+            "--threads",
+            "-b",
+            "-ub",
+            "--lora",
+            "--parallel",
+            "--cont-batching",
+            "--keep",
+            "--context-shift",
+            "--no-context-shift",
+            "--main-gpu",
+            "--tensor-split",
+            "--chat-template",
+            "--chat-template-file",
+            "--jinja",
+            "--slot-save-path",
+            "--rope-scaling",
+            "--rope-freq-base",
+            "--rope-freq-scale",
+            "--embedding",
+            "--reranking",
+            "--seed",
+            "--min-p",
+            "--mirostat",
+        ] {
+            assert!(!args.iter().any(|a| a == flag), "{flag} should not be passed by default");
+        }
+    }
+
+    #[test]
+    fn optional_flags_present_when_set() {
+        let config = LlamaConfig {
+            ctx_size: Some(8192),
+            gpu_layers: Some(33),
+            threads: Some(8),
+            batch_size: Some(1024),
+            ubatch_size: Some(256),
+            lora: vec!["a.gguf".to_string(), "b.gguf".to_string()],
+            parallel_slots: Some(4),
+            keep_tokens: Some(-1),
+            context_shift: Some(false),
+            main_gpu: Some(1),
+            tensor_split: Some(vec![3.0, 1.0]),
+            chat_template: Some("chatml".to_string()),
+            slot_save_path: Some("slots".to_string()),
+            rope_scaling: Some(RopeScaling::Yarn),
+            rope_freq_base: Some(1_000_000.0),
+            rope_freq_scale: Some(0.25),
+            mode: ServerMode::Embedding,
+            params: GenParams {
+                seed: Some(42),
+                ..GenParams::default()
+            },
+            ..LlamaConfig::default()
+        };
+        let args = args(&config);
+        assert_eq!(value_of(&args, "--ctx-size"), Some("8192"));
+        assert_eq!(value_of(&args, "-ngl"), Some("33"));
+        assert_eq!(value_of(&args, "--threads"), Some("8"));
+        assert_eq!(value_of(&args, "-b"), Some("1024"));
+        assert_eq!(value_of(&args, "-ub"), Some("256"));
+        let loras: Vec<&str> = args
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| *a == "--lora")
+            .map(|(i, _)| args[i + 1].as_str())
+            .collect();
+        assert_eq!(loras, ["a.gguf", "b.gguf"]);
+        assert_eq!(value_of(&args, "--parallel"), Some("4"));
+        assert!(args.iter().any(|a| a == "--cont-batching"));
+        assert_eq!(value_of(&args, "--keep"), Some("-1"));
+        assert!(args.iter().any(|a| a == "--no-context-shift"));
+        assert_eq!(value_of(&args, "--main-gpu"), Some("1"));
+        assert_eq!(value_of(&args, "--tensor-split"), Some("3,1"));
+        assert_eq!(value_of(&args, "--chat-template"), Some("chatml"));
+        assert_eq!(value_of(&args, "--slot-save-path"), Some("slots"));
+        assert_eq!(value_of(&args, "--rope-scaling"), Some("yarn"));
+        assert_eq!(value_of(&args, "--rope-freq-base"), Some("1000000"));
+        assert_eq!(value_of(&args, "--rope-freq-scale"), Some("0.25"));
+        assert_eq!(value_of(&args, "--seed"), Some("42"));
+        assert_eq!(args.last().map(String::as_str), Some("--embedding"));
+    }
+
+    #[test]
+    fn single_slot_and_modes() {
+        let one_slot = LlamaConfig {
+            parallel_slots: Some(1),
+            context_shift: Some(true),
+            mode: ServerMode::Rerank,
+            ..LlamaConfig::default()
+        };
+        let args = args(&one_slot);
+        assert!(!args.iter().any(|a| a == "--parallel" || a == "--cont-batching"));
+        assert!(args.iter().any(|a| a == "--context-shift"));
+        assert_eq!(args.last().map(String::as_str), Some("--reranking"));
+    }
+
+    #[test]
+    fn jinja_template_goes_to_a_file() {
+        let source = "{% for m in messages %}{{ m.content }}{% endfor %}";
+        let config = LlamaConfig {
+            chat_template: Some(source.to_string()),
+            ..LlamaConfig::default()
+        };
+        let args = args(&config);
+        assert!(args.iter().any(|a| a == "--jinja"));
+        assert!(!args.iter().any(|a| a == "--chat-template"));
+        let file = value_of(&args, "--chat-template-file").expect("template file flag");
+        assert_eq!(Path::new(file), template_file(source));
+        assert!(config.validate_options().is_ok());
+    }
+
+    #[test]
+    fn valid_options_pass() {
+        assert!(LlamaConfig::default().validate_options().is_ok());
+        let config = LlamaConfig {
+            ctx_size: Some(2048),
+            parallel_slots: Some(2),
+            keep_tokens: Some(1023),
+            gpu_layers: Some(0),
+            chat_template: Some("llama3".to_string()),
+            rope_freq_base: Some(10_000.0),
+            rope_freq_scale: Some(1.0),
+            ..LlamaConfig::default()
+        };
+        assert!(config.validate_options().is_ok());
+    }
+
+    #[test]
+    fn invalid_options_rejected() {
+        let base = LlamaConfig::default;
+        assert!(is_invalid(LlamaConfig { ctx_size: Some(255), ..base() }));
+        assert!(is_invalid(LlamaConfig { ctx_size: Some(262_145), ..base() }));
+        assert!(is_invalid(LlamaConfig { parallel_slots: Some(0), ..base() }));
+        assert!(is_invalid(LlamaConfig { parallel_slots: Some(MAX_PARALLEL_SLOTS + 1), ..base() }));
+        assert!(is_invalid(LlamaConfig { keep_tokens: Some(-2), ..base() }));
+        // 4096 / 2 slots leaves each slot 2048 tokens, so keeping 2048 leaves no room
+        assert!(is_invalid(LlamaConfig { parallel_slots: Some(2), keep_tokens: Some(2048), ..base() }));
+        assert!(is_invalid(LlamaConfig { gpu_layers: Some(-1), ..base() }));
+        assert!(is_invalid(LlamaConfig { threads: Some(0), ..base() }));
+        assert!(is_invalid(LlamaConfig { batch_size: Some(0), ..base() }));
+        assert!(is_invalid(LlamaConfig { ubatch_size: Some(0), ..base() }));
+        assert!(is_invalid(LlamaConfig { chat_template: Some("no-such-template".to_string()), ..base() }));
+        assert!(is_invalid(LlamaConfig { rope_freq_base: Some(0.0), ..base() }));
+        assert!(is_invalid(LlamaConfig { rope_freq_base: Some(f32::NAN), ..base() }));
+        assert!(is_invalid(LlamaConfig { rope_freq_base: Some(2e9), ..base() }));
+        assert!(is_invalid(LlamaConfig { rope_freq_scale: Some(0.0), ..base() }));
+        assert!(is_invalid(LlamaConfig { rope_freq_scale: Some(1.5), ..base() }));
+        assert!(is_invalid(LlamaConfig {
+            slot_save_path: Some("/no/such/genhat/folder".to_string()),
+            ..base()
+        }));
+        let bad_params = GenParams {
+            temperature: Some(-1.0),
+            ..GenParams::default()
+        };
+        assert!(is_invalid(LlamaConfig { params: bad_params, ..base() }));
+        let missing_lora = LlamaConfig {
+            lora: vec!["/no/such/adapter.gguf".to_string()],
+            ..base()
+        };
+        assert!(matches!(missing_lora.validate_options(), Err(AppError::ModelNotFound(_))));
+    }
+
+    #[test]
+    fn warnings_for_suspicious_combinations() {
+        assert!(LlamaConfig::default().warnings().is_empty());
+        let config = LlamaConfig {
+            batch_size: Some(256),
+            ubatch_size: Some(512),
+            auto_gpu_layers: true,
+            gpu_layers: Some(10),
+            rope_scaling: Some(RopeScaling::None),
+            rope_freq_scale: Some(0.5),
+            ..LlamaConfig::default()
+        };
+        assert_eq!(config.warnings().len(), 3);
+    }
+}
@@ -0,0 +1,148 @@
+// llama-server log file: plain `[stream] message` lines by default, or one JSON
+// object per line (JSONL) when `structured_logs` is enabled in settings. The most recent
+// entries are also kept in memory so the UI can catch up without re-reading the file.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+use crate::{unix_millis, AppState};
+
+/// Default number of lines returned by `get_llama_log`.
+const DEFAULT_TAIL_LINES: usize = 200;
+
+/// Entries kept in the in-memory buffer; older ones are only in the log file.
+const BUFFER_LINES: usize = 1000;
+
+/// Most recent log entries of this session, oldest first, capped at `BUFFER_LINES`.
+pub type LogBuffer = Mutex<VecDeque<LogLine>>;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct LogLine {
+    /// Epoch milliseconds; `None` for entries read back from plain-text logs.
+    pub timestamp: Option<u64>,
+    /// `stdout`, `stderr`, or `genhat` for markers written by the app itself.
+    pub stream: String,
+    pub pid: Option<u32>,
+    pub message: String,
+}
+
+impl LogLine {
+    pub fn new(stream: &str, pid: Option<u32>, message: impl Into<String>) -> Self {
+        LogLine {
+            timestamp: Some(unix_millis()),
+            stream: stream.to_string(),
+            pid,
+            message: message.into(),
+        }
+    }
+
+    /// Parses a line in either format back into an entry.
+    fn parse(line: &str) -> LogLine {
+        if let Ok(entry) = serde_json::from_str::<LogLine>(line) {
+            return entry;
+        }
+        for stream in ["stdout", "stderr"] {
+            if let Some(message) = line.strip_prefix(&format!("[{stream}] ")) {
+                return LogLine {
+                    timestamp: None,
+                    stream: stream.to_string(),
+                    pid: None,
+                    message: message.to_string(),
+                };
+            }
+        }
+        LogLine {
+            timestamp: None,
+            stream: "genhat".to_string(),
+            pid: None,
+            message: line.to_string(),
+        }
+    }
+}
+
+/// `<app data>/logs/`, created on demand. Falls back to the system temp dir only if the
+/// app data dir can't be resolved or created.
+pub fn logs_dir(app: &AppHandle) -> PathBuf {
+    if let Ok(dir) = app.path().app_data_dir() {
+        let logs = dir.join("logs");
+        if std::fs::create_dir_all(&logs).is_ok() {
+            return logs;
+        }
+    }
+    std::env::temp_dir()
+}
+
+pub fn log_path(app: &AppHandle) -> PathBuf {
+    logs_dir(app).join("genhat-llama-server.log")
+}
+
+/// Appends entries to the llama log in the configured format, and to the in-memory buffer.
+pub struct LogWriter {
+    app: AppHandle,
+    file: File,
+    structured: bool,
+}
+
+impl LogWriter {
+    pub fn open(app: &AppHandle, structured: bool) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(log_path(app))?;
+        Ok(LogWriter {
+            app: app.clone(),
+            file,
+            structured,
+        })
+    }
+
+    pub fn write(&mut self, entry: &LogLine) {
+        {
+            let state = self.app.state::<AppState>();
+            let mut buffer = state.llama_log.lock().unwrap();
+            if buffer.len() == BUFFER_LINES {
+                buffer.pop_front();
+            }
+            buffer.push_back(entry.clone());
+        }
+
+        if self.structured {
+            if let Ok(json) = serde_json::to_string(entry) {
+                let _ = writeln!(self.file, "{json}");
+            }
+        } else if entry.stream == "genhat" {
+            let _ = writeln!(self.file, "{}", entry.message);
+        } else {
+            let _ = writeln!(self.file, "[{}] {}", entry.stream, entry.message);
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_llama_log_path(app: AppHandle) -> String {
+    log_path(&app).to_string_lossy().to_string()
+}
+
+/// Last `lines` entries of the llama log (default 200). Served from the in-memory buffer
+/// when it holds that many; deeper history is read from the file, parsed from either format.
+#[tauri::command]
+pub fn get_llama_log(app: AppHandle, state: State<AppState>, lines: Option<usize>) -> Result<Vec<LogLine>, String> {
+    let lines = lines.unwrap_or(DEFAULT_TAIL_LINES);
+    {
+        let buffer = state.llama_log.lock().unwrap();
+        if buffer.len() >= lines {
+            return Ok(buffer.iter().skip(buffer.len() - lines).cloned().collect());
+        }
+    }
+
+    let path = log_path(&app);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let all: Vec<&str> = content.lines().collect();
+    let start = all.len().saturating_sub(lines);
+    Ok(all[start..].iter().map(|l| LogLine::parse(l)).collect())
+}
@@ -0,0 +1,131 @@
+// Recognizable llama-server warnings, picked out of its output by the log reader and
+// emitted as `llama-warning`, so degradations that only reach the log (a prompt truncated
+// to fit the context, a context shift dropping earlier messages) can be explained in the
+// UI. Patterns follow llama.cpp's server messages; unknown lines are never reported.
+
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// The prompt didn't fit; its start was cut off (`input truncated`).
+    PromptTruncated,
+    /// The context filled up mid-conversation and older tokens were discarded.
+    ContextShift,
+    /// The request was rejected for exceeding the context (no context shift).
+    ContextExceeded,
+    /// No room left in the KV cache for the batch.
+    KvCacheFull,
+    /// `llama_decode` failed for a slot.
+    DecodeFailed,
+    /// A grammar or JSON schema could not be parsed or applied.
+    Grammar,
+    /// Any other error llama-server returned for a request (`send_error`).
+    RequestError,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct LlamaWarning {
+    pub kind: WarningKind,
+    /// Explanation for end users.
+    pub message: String,
+    /// Numbers the line carries (`n_ctx`, `n_prompt_tokens`, `n_discard`, `task`, `id`: the
+    /// slot, or the task in `send_error` lines, …).
+    pub values: BTreeMap<String, i64>,
+    /// The log line as printed.
+    pub line: String,
+    pub pid: u32,
+}
+
+/// `key = 123` and `key 123` pairs in a line such as
+/// `slot update_slots: id  0 | task 3 | input truncated, n_ctx = 4096, n_keep = 0, n_left = 4096`.
+fn numbers(line: &str) -> BTreeMap<String, i64> {
+    let mut values = BTreeMap::new();
+    let words: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '|' || c == ':')
+        .filter(|w| !w.is_empty())
+        .collect();
+    for (i, word) in words.iter().enumerate() {
+        let Ok(value) = word.parse::<i64>() else { continue };
+        let key = match i.checked_sub(1).map(|k| words[k]) {
+            Some("=") => i.checked_sub(2).map(|k| words[k]),
+            key => key,
+        };
+        let Some(key) = key else { continue };
+        if key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') && key.parse::<i64>().is_err() {
+            values.entry(key.to_string()).or_insert(value);
+        }
+    }
+    values
+}
+
+fn classify_kind(line: &str) -> Option<WarningKind> {
+    let lower = line.to_ascii_lowercase();
+    let kind = if lower.contains("input truncated") || lower.contains("prompt is too long") {
+        WarningKind::PromptTruncated
+    } else if lower.contains("context shift") && lower.contains("n_discard") {
+        // "slot context shift, n_keep = 0, n_left = 4094, n_discard = 2047"
+        WarningKind::ContextShift
+    } else if lower.contains("exceeds the available context size") {
+        WarningKind::ContextExceeded
+    } else if lower.contains("failed to find free space in the kv cache")
+        || lower.contains("failed to find a memory slot")
+        || lower.contains("kv cache is full")
+    {
+        WarningKind::KvCacheFull
+    } else if lower.contains("grammar") && (lower.contains("error") || lower.contains("failed")) {
+        WarningKind::Grammar
+    } else if (lower.contains("llama_decode") && lower.contains("fail")) || lower.contains("decode: failed") {
+        WarningKind::DecodeFailed
+    } else if lower.contains("send_error") {
+        WarningKind::RequestError
+    } else {
+        return None;
+    };
+    Some(kind)
+}
+
+fn explain(kind: WarningKind, values: &BTreeMap<String, i64>, line: &str) -> String {
+    let ctx = values.get("n_ctx").map(|n| format!(" ({n} tokens)")).unwrap_or_default();
+    match kind {
+        WarningKind::PromptTruncated => match values.get("n_prompt_tokens") {
+            Some(n) => format!(
+                "Your message was truncated to fit the context window{ctx}: the prompt had {n} tokens, so its start was dropped"
+            ),
+            None => format!("Your message was truncated to fit the context window{ctx}"),
+        },
+        WarningKind::ContextShift => match values.get("n_discard") {
+            Some(n) => format!("The context window filled up; the oldest {n} tokens of the conversation were dropped"),
+            None => "The context window filled up; the oldest part of the conversation was dropped".to_string(),
+        },
+        WarningKind::ContextExceeded => {
+            format!("The request is longer than the context window{ctx}; shorten it or load the model with a larger ctx_size")
+        }
+        WarningKind::KvCacheFull => {
+            "The model ran out of context memory for this request; try a shorter prompt or a larger ctx_size".to_string()
+        }
+        WarningKind::DecodeFailed => "The model failed to process the request".to_string(),
+        WarningKind::Grammar => "The grammar or JSON schema for this request could not be used".to_string(),
+        WarningKind::RequestError => {
+            // "send_error: task id = 3, error: <message>"
+            let detail = line.split_once("error:").map(|(_, e)| e.trim()).filter(|e| !e.is_empty());
+            match detail {
+                Some(detail) => format!("The server rejected a request: {detail}"),
+                None => "The server rejected a request".to_string(),
+            }
+        }
+    }
+}
+
+/// The warning `line` of llama-server `pid` reports, if it is a known one.
+pub fn classify(line: &str, pid: u32) -> Option<LlamaWarning> {
+    let kind = classify_kind(line)?;
+    let values = numbers(line);
+    Some(LlamaWarning {
+        kind,
+        message: explain(kind, &values, line),
+        values,
+        line: line.to_string(),
+        pid,
+    })
+}
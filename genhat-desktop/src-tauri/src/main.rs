@@ -1,23 +1,344 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command, Stdio};
-use std::sync::Mutex;
-use tauri::{Manager, State};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use error::AppError;
+use llama_config::LlamaConfig;
+use llama_log::{LogLine, LogWriter};
+
+mod backends;
+mod backup;
+mod capabilities;
+mod category;
+mod chat;
+mod compat;
+mod conversation;
+mod crash_reports;
+mod diagnostics;
+mod disk;
+mod download;
+mod error;
+mod extract;
+mod gguf;
+mod gguf_cache;
+mod keepalive;
+mod llama_config;
+mod llama_log;
+mod llama_warnings;
+mod markup;
+mod memory;
+mod metrics;
+mod model_params;
+mod model_test;
+mod orphans;
+mod pdf;
+mod preflight;
+mod presets;
+mod queue;
+mod rag;
+mod rag_index;
+mod rerank;
+mod response_cache;
+mod settings;
+mod slot_cache;
+mod tts_cache;
+mod wav;
+mod zip;
 
 #[derive(serde::Serialize)]
 struct ModelFile {
     name: String,
     path: String,
+    /// Favorited via `pin_model`; pinned models are listed first.
+    pinned: bool,
+    /// Display name set with `set_model_alias`.
+    alias: Option<String>,
+    /// Detected role, or the override from `set_model_category`.
+    category: category::ModelCategory,
+    /// Shard count for split GGUFs (`path` is then shard 1).
+    shards: Option<u32>,
+    /// Why the model can't be loaded (e.g. a missing shard); `None` when usable.
+    unusable_reason: Option<String>,
+}
+
+/// Human-readable reason a split set can't be loaded, if any shard is missing.
+fn missing_shards_reason(split: &gguf::SplitSet) -> Option<String> {
+    if split.missing.is_empty() {
+        return None;
+    }
+    let missing: Vec<String> = split.missing.iter().map(|i| i.to_string()).collect();
+    Some(format!("Missing shard(s) {} of {}", missing.join(", "), split.count))
+}
+
+#[derive(serde::Serialize)]
+struct AudioModel {
+    name: String,
+    path: String,
+    /// All sibling files `generate_speech` needs are present.
+    usable: bool,
+    /// File names of the absent siblings.
+    missing: Vec<String>,
+    /// Reference clips in the model's `voices/` folder; `None` when it has no such folder.
+    voice_count: Option<usize>,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct SpeechResult {
+    path: String,
+    duration_secs: f64,
+    sample_rate: u32,
+    channels: u16,
+    /// Copied from the TTS cache instead of synthesized (see `tts_cache`).
+    cached: bool,
+}
+
+/// Port the managed llama-server listens on.
+const LLAMA_PORT: u16 = 8081;
+/// How long to wait for `/health` to report ready after spawning llama-server, unless the
+/// `ready_timeout_secs` setting overrides it. `ready_timeout` adds time for large models.
+const READY_TIMEOUT_SECS: u64 = 120;
+/// Readiness time added per GiB of model weights, so large models on slow disks aren't
+/// reported as failed while still loading.
+const READY_SECS_PER_GIB: u64 = 15;
+/// Readiness probe path, unless the `health_path` setting overrides it.
+const HEALTH_PATH: &str = "/health";
+/// How often a loaded llama-server is checked for an unexpected exit.
+const CRASH_POLL_SECS: u64 = 2;
+/// How long a killed llama-server gets to exit before a restart gives up on it.
+const KILL_TIMEOUT_SECS: u64 = 10;
+/// How long `force_switch_model` and a lazy start wait for a switch already in progress to let go.
+const FORCE_SWITCH_WAIT_SECS: u64 = 15;
+
+/// Spawn attempts before `spawn_llama_process` gives up on a transient failure.
+const SPAWN_ATTEMPTS: u32 = 3;
+/// Delay before the first retry; doubles on each further attempt.
+const SPAWN_RETRY_BASE_MS: u64 = 500;
+
+/// Coarse model-loading stage, reported through `model-loading` events.
+/// Stages only ever move forward, in declaration order.
+#[derive(Clone, Copy, PartialEq, PartialOrd, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum LoadStage {
+    Starting,
+    ReadingMetadata,
+    LoadingTensors,
+    WarmingUp,
+    Ready,
+    Failed,
+}
+
+impl LoadStage {
+    /// Rough completion fraction; `None` while still indeterminate.
+    fn progress(self) -> Option<f32> {
+        match self {
+            LoadStage::Starting => None,
+            LoadStage::ReadingMetadata => Some(0.1),
+            LoadStage::LoadingTensors => Some(0.4),
+            LoadStage::WarmingUp => Some(0.8),
+            LoadStage::Ready => Some(1.0),
+            LoadStage::Failed => None,
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+struct ModelLoadingEvent {
+    model: String,
+    stage: LoadStage,
+    progress: Option<f32>,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct SpawnRetryEvent {
+    model: String,
+    /// The attempt that just failed (1-based).
+    attempt: u32,
+    max_attempts: u32,
+    delay_ms: u64,
+    error: String,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct AutoGpuLayersEvent {
+    model: String,
+    #[serde(flatten)]
+    fit: memory::GpuLayerFit,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct LoadCancelledEvent {
+    /// Path the cancelled server was loading, if known.
+    model: Option<String>,
+    pid: u32,
+}
+
+/// Step of a `force_switch_model`, in order; `waiting` only when another switch held the lock.
+#[derive(Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ForceSwitchPhase {
+    Waiting,
+    Aborting,
+    Stopping,
+    Started,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct ForceSwitchEvent {
+    /// The model being switched to.
+    model: String,
+    phase: ForceSwitchPhase,
+    /// `stopping`: the server being killed; `started`: the new one.
+    pid: Option<u32>,
+    /// `stopping`: the killed server hadn't finished loading.
+    was_loading: bool,
+    /// `aborting`: chat requests told to stop.
+    aborted: usize,
+}
+
+/// How the launch-time auto-start ended.
+#[derive(Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum StartupOutcome {
+    /// The startup model answered `/health`.
+    Ready,
+    /// It failed to spawn, exited or timed out while loading; see `error`.
+    Failed,
+    /// The `autostart` setting is off.
+    Disabled,
+    /// No LLM GGUF in the models dir; the UI can offer a download.
+    NoModels,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct StartupStatus {
+    outcome: StartupOutcome,
+    /// The model auto-start picked, if any.
+    model: Option<String>,
+    error: Option<String>,
+    models_dir: String,
+    /// Set when the expected model was missing and another one was picked.
+    fallback: Option<ModelFallback>,
+}
+
+/// `startup_model` couldn't use the model the user expects and picked another one.
+#[derive(Clone, serde::Serialize)]
+struct ModelFallback {
+    /// `default_model`, else `last_model`, else the bundled `LFM-1.2B-INT8.gguf` (resolved path).
+    requested: String,
+    /// The model started instead.
+    loaded: String,
+}
+
+/// The model `startup_model` picked.
+struct StartupPick {
+    path: PathBuf,
+    fallback: Option<ModelFallback>,
+}
+
+/// What llama-server is launched to serve. Only one mode is active per server process.
+#[derive(Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ServerMode {
+    #[default]
+    Chat,
+    /// `--embedding`: pooled embeddings for embedding GGUFs.
+    Embedding,
+    /// `--reranking`: query/document relevance scores from a reranker GGUF (`/rerank`).
+    Rerank,
 }
 
 struct AppState {
     llama: Mutex<Option<Child>>,
+    /// Path of the model the current llama-server was started with.
+    current_model: Mutex<Option<String>>,
+    /// Mode the current llama-server was started in.
+    server_mode: Mutex<ServerMode>,
+    /// The current model was loaded with `load_model_from_path` (outside the model dirs).
+    model_external: Mutex<bool>,
+    whisper: Mutex<Option<Child>>,
+    tts: Mutex<Option<Child>>,
+    /// A speech job holds tts-inference for all of its runs (see `reserve_tts`).
+    tts_reserved: AtomicBool,
+    /// A `generate_speech_batch` is running; a second one is rejected.
+    speech_batch: AtomicBool,
+    /// Set by `stop_speech_batch`: the running batch ends after its current item.
+    speech_batch_stop: AtomicBool,
+    /// Embedding llama-server on `rag::EMBED_PORT`, next to the chat model (see `rag`).
+    rag_embed: Mutex<Option<rag::EmbedServer>>,
+    settings: Mutex<settings::Settings>,
+    generations: chat::Generations,
+    /// Sampling defaults set with `update_generation_defaults`, merged under each chat request's params.
+    generation_defaults: Mutex<chat::GenParams>,
+    /// Config the last llama-server was launched with (sampling params before built-in defaults).
+    server_config: Mutex<Option<LlamaConfig>>,
+    /// Config the last llama-server was requested with, before the profile and settings fill
+    /// in params; `reload_current_model` re-applies it so their latest values take effect.
+    launch_config: Mutex<Option<LlamaConfig>>,
+    /// Full command line of the last llama-server spawn (see `command_line`).
+    server_command: Mutex<Option<String>>,
+    backends: backends::ProbeCache<Vec<backends::BackendInfo>>,
+    llama_version: backends::ProbeCache<backends::LlamaVersion>,
+    server_features: backends::ProbeCache<backends::ServerFeatures>,
+    /// Recent llama-server log entries (see `llama_log::LogBuffer`).
+    llama_log: llama_log::LogBuffer,
+    /// Cached `resolve_*_exe` results (see `BinaryPaths`).
+    binaries: Mutex<BinaryPaths>,
+    /// Timings of recent chat requests (see `metrics::MetricsHistory`).
+    metrics: metrics::MetricsHistory,
+    /// Token totals since launch or the last model switch (see `metrics::Session`).
+    session: metrics::Session,
+    /// `--host` the current (or last) llama-server was started with.
+    llama_host: Mutex<IpAddr>,
+    /// In-flight `download_model_resumable` runs (see `download::Downloads`).
+    downloads: download::Downloads,
+    /// Serializes chat requests on a single-slot server (see `queue`).
+    chat_queue: queue::RequestQueue,
+    /// A `restart_llama` is replacing the server; overlapping restarts are rejected.
+    switching: AtomicBool,
+    /// Pid of a llama-server that hasn't answered `/health` yet. Changed only with
+    /// `llama` locked (lock `llama` first), so a cancel and a finishing load can't interleave.
+    loading: Mutex<Option<u32>>,
+    /// Result of the launch auto-start; `None` until it resolves (see `app-ready`).
+    startup: Mutex<Option<StartupStatus>>,
+    /// Pid of a llama-server that stopped answering keep-alive pings (see `keepalive`).
+    unresponsive: Mutex<Option<u32>>,
+    /// Parsed GGUF headers, persisted across launches (see `gguf_cache`).
+    gguf_metadata: gguf_cache::MetadataCache,
+    /// Held for a whole `probe_load_time` run, so probes queue instead of sharing RAM.
+    probe_lock: tokio::sync::Mutex<()>,
+    /// Held across each load-modify-save of the orphan registry (see `orphans`).
+    orphans_lock: Mutex<()>,
 }
 
 // ---- Helpers ----
 
+/// Milliseconds since the Unix epoch, used for timestamps in persisted app data.
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Writes `data` to `<path>.tmp` and renames it over `path`, so a crash or full disk
+/// mid-write leaves the old file intact instead of a truncated one.
+fn write_file_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let result = std::fs::write(&tmp, data).and_then(|_| std::fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
 fn get_models_dir() -> PathBuf {
     if let Ok(val) = std::env::var("GENHAT_MODEL_PATH") {
         let p = PathBuf::from(val);
@@ -40,15 +361,105 @@ fn get_models_dir() -> PathBuf {
 }
 
 
-fn resolve_llama_exe() -> PathBuf {
-    // Determine OS-specific folder name
-    let os_folder = if cfg!(windows) {
+#[derive(serde::Serialize)]
+struct CpuFeatures {
+    arch: String,
+    avx: bool,
+    avx2: bool,
+    fma: bool,
+    f16c: bool,
+    avx512f: bool,
+    /// Whether runtime detection was possible on this architecture at all.
+    detected: bool,
+    /// Binary variant folders in the order resolve_llama_exe tries them.
+    variants: Vec<String>,
+}
+
+fn detect_cpu_features() -> CpuFeatures {
+    let mut features = CpuFeatures {
+        arch: std::env::consts::ARCH.to_string(),
+        avx: false,
+        avx2: false,
+        fma: false,
+        f16c: false,
+        avx512f: false,
+        detected: false,
+        variants: Vec::new(),
+    };
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        features.avx = std::arch::is_x86_feature_detected!("avx");
+        features.avx2 = std::arch::is_x86_feature_detected!("avx2");
+        features.fma = std::arch::is_x86_feature_detected!("fma");
+        features.f16c = std::arch::is_x86_feature_detected!("f16c");
+        features.avx512f = std::arch::is_x86_feature_detected!("avx512f");
+        features.detected = true;
+    }
+
+    features.variants = llama_variant_folders(llama_os_folder(), &features);
+    features
+}
+
+fn llama_os_folder() -> &'static str {
+    if cfg!(windows) {
         "llama-win"
     } else if cfg!(target_os = "macos") {
         "llama-mac"
     } else {
         "llama-lin"
-    };
+    }
+}
+
+/// Orders the candidate binary folders for this CPU.
+/// `llama-<os>-avx2` is only tried when AVX2 + FMA are confirmed; otherwise the
+/// conservative `llama-<os>-noavx` build comes first. The plain `llama-<os>`
+/// folder is always kept as a fallback for bundles that ship a single build.
+fn llama_variant_folders(os_folder: &str, features: &CpuFeatures) -> Vec<String> {
+    let avx2 = format!("{os_folder}-avx2");
+    let noavx = format!("{os_folder}-noavx");
+    let base = os_folder.to_string();
+
+    if !features.detected {
+        // Not x86 (e.g. Apple Silicon): AVX variants don't apply.
+        vec![base]
+    } else if features.avx2 && features.fma {
+        vec![avx2, base, noavx]
+    } else {
+        vec![noavx, base]
+    }
+}
+
+/// Reads a binary-path override from `var`. Only honored when it points to an existing file;
+/// otherwise the normal bundle search runs (with a warning, since the override was likely a typo).
+fn exe_override(var: &str) -> Option<PathBuf> {
+    let val = std::env::var(var).ok()?;
+    let p = PathBuf::from(&val);
+    if p.is_file() {
+        Some(p)
+    } else {
+        eprintln!("{var} is set to {val}, which is not a file; ignoring it");
+        None
+    }
+}
+
+/// The app's own executable path, which the bundled-binary searches walk up from.
+/// `current_exe()` can fail in sandboxes or unusual launchers; that's reported as an error
+/// (and logged) pointing at the env-var override instead of panicking.
+fn app_exe_path(override_var: &str) -> Result<PathBuf, String> {
+    std::env::current_exe().map_err(|e| {
+        let msg = format!("Cannot locate the app executable ({e}); set {override_var} to the binary's path");
+        eprintln!("{msg}");
+        msg
+    })
+}
+
+/// Candidate llama-server paths in search order: for each CPU variant folder (best first),
+/// walking up from the app executable, `src-tauri/bin` (dev), `bin` (release) and
+/// `resources/bin` for every platform binary name.
+fn llama_candidates(exe_path: &Path) -> Vec<PathBuf> {
+    // Determine OS-specific folder names, most suitable CPU variant first
+    let os_folders = detect_cpu_features().variants;
 
     // Build a list of candidate executable names depending on platform.
     let exe_names: Vec<&str> = if cfg!(windows) {
@@ -67,50 +478,21 @@ fn resolve_llama_exe() -> PathBuf {
         vec!["llama-server"]
     };
 
-    let exe_path = std::env::current_exe().unwrap();
-    let mut checked = Vec::new();
-
-    exe_path
-        .ancestors()
-        // Walk upward to find src-tauri/bin/{os_folder} in dev builds or bin/{os_folder} in release.
-        .find_map(|dir| {
+    let mut candidates = Vec::new();
+    for os_folder in &os_folders {
+        for dir in exe_path.ancestors() {
             for &exe_name in &exe_names {
-                // Check for dev path
-                let dev = dir.join("src-tauri/bin").join(os_folder).join(exe_name);
-                checked.push(dev.clone());
-                if dev.exists() {
-                    return Some(dev);
-                }
-
-                // Check for release path (typically in bundled resources)
-                let rel = dir.join("bin").join(os_folder).join(exe_name);
-                checked.push(rel.clone());
-                if rel.exists() {
-                    return Some(rel);
-                }
-
-                // Check for resources directory structure
-                let res = dir.join("resources/bin").join(os_folder).join(exe_name);
-                checked.push(res.clone());
-                if res.exists() {
-                    return Some(res);
+                for base in ["src-tauri/bin", "bin", "resources/bin"] {
+                    candidates.push(dir.join(base).join(os_folder).join(exe_name));
                 }
             }
-            None
-        })
-        .unwrap_or_else(|| {
-            let checked_list = checked
-                .into_iter()
-                .map(|p| p.display().to_string())
-                .collect::<Vec<_>>()
-                .join("\n");
-            panic!(
-                "llama-server not found. Checked the following paths:\n{checked_list}",
-            );
-        })
+        }
+    }
+    candidates
 }
 
-fn resolve_tts_exe() -> PathBuf {
+/// Candidate tts-inference paths in search order, walking up from the app executable.
+fn tts_candidates(exe_path: &Path) -> Vec<PathBuf> {
     let os_folder = if cfg!(windows) {
         "tts-win"
     } else if cfg!(target_os = "macos") {
@@ -120,7 +502,6 @@ fn resolve_tts_exe() -> PathBuf {
     };
 
     let exe_name = if cfg!(windows) { "tts-inference.exe" } else { "tts-inference" };
-    let exe_path = std::env::current_exe().unwrap();
 
     // Since we switched to --onedir, the executable is inside a folder of the same name
     // e.g. bin/tts-lin/tts-inference/tts-inference
@@ -129,298 +510,2969 @@ fn resolve_tts_exe() -> PathBuf {
 
     let relative_path = PathBuf::from("tts-inference").join(exe_name);
 
-    exe_path.ancestors().find_map(|dir| {
-         // Dev path (src-tauri/bin/tts-lin/tts-inference/tts-inference)
-         let dev = dir.join("src-tauri/bin").join(os_folder).join(&relative_path);
-         if dev.exists() { return Some(dev); }
-         
-         // Release path
-         let rel = dir.join("bin").join(os_folder).join(&relative_path);
-         if rel.exists() { return Some(rel); }
-         
-         // Resources path
-         let res = dir.join("resources/bin").join(os_folder).join(&relative_path);
-         if res.exists() { return Some(res); }
-
-         // Fallback for older --onefile structure (just in case)
-         let old_onefile = dir.join("src-tauri/bin").join(os_folder).join(exe_name);
-         if old_onefile.exists() && old_onefile.parent().unwrap().file_name().unwrap() != "tts-inference" {
-            return Some(old_onefile);
-         }
-
-         None
-    }).expect("TTS executable not found")
-}
-
-fn spawn_llama_process(model_path: PathBuf) -> Child {
-    let exe = resolve_llama_exe();
-    
-    // Logging setup
-    let log_path = std::env::temp_dir().join("genhat-llama-server.log");
-    let mut log_file = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_path)
-        .expect("Failed to open llama log file");
-    
-    let _ = writeln!(log_file, "--- llama-server start ---");
-    let _ = writeln!(log_file, "exe: {}", exe.display());
-    let _ = writeln!(log_file, "model: {}", model_path.display());
+    let mut candidates = Vec::new();
+    for dir in exe_path.ancestors() {
+        // Dev, release and resources paths (src-tauri/bin/tts-lin/tts-inference/tts-inference)
+        for base in ["src-tauri/bin", "bin", "resources/bin"] {
+            candidates.push(dir.join(base).join(os_folder).join(&relative_path));
+        }
+        // Fallback for older --onefile structure (just in case)
+        candidates.push(dir.join("src-tauri/bin").join(os_folder).join(exe_name));
+    }
+    candidates
+}
 
-    // IMPORTANT: Set current_dir to the binary's folder so it finds sibling DLLs (llama.dll, etc.)
-    let work_dir = exe.parent().expect("Exe has no parent");
-
-    let mut child = Command::new(&exe)
-        .args([
-            "-m",
-            model_path.to_str().unwrap(),
-            "--ctx-size",
-            "4096",
-            "--port",
-            "8081",
-            "--host",
-            "127.0.0.1",
-            "-n", // max_tokens
-            "256",
-            "--temp",
-            "0.7",
-            "--top-p",
-            "0.9",
-            "--top-k",
-            "40",
-            "--repeat-penalty",
-            "1.1",
-        ])
-        .current_dir(work_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .expect("Failed to start llama-server");
+/// Candidate whisper-cli paths in search order, walking up from the app executable.
+fn whisper_candidates(exe_path: &Path) -> Vec<PathBuf> {
+    let os_folder = whisper_folder();
 
-    let _ = writeln!(log_file, "spawned pid: {}", child.id());
+    // whisper.cpp renamed its CLI from `main` to `whisper-cli`; accept both.
+    let exe_names: Vec<&str> = if cfg!(windows) {
+        vec!["whisper-cli.exe", "main.exe"]
+    } else {
+        vec!["whisper-cli", "main"]
+    };
 
-    // Redirect stdout to log file
-    if let Some(stdout) = child.stdout.take() {
-        let log_path_clone = log_path.clone();
-        std::thread::spawn(move || {
-            if let Ok(mut file) = std::fs::OpenOptions::new().create(true).append(true).open(&log_path_clone) {
-                 let reader = BufReader::new(stdout);
-                 for line in reader.lines().flatten() {
-                     let _ = writeln!(file, "[stdout] {line}");
-                 }
+    let mut candidates = Vec::new();
+    for dir in exe_path.ancestors() {
+        for &exe_name in &exe_names {
+            for base in ["src-tauri/bin", "bin", "resources/bin"] {
+                candidates.push(dir.join(base).join(os_folder).join(exe_name));
             }
-        });
+        }
     }
+    candidates
+}
 
-    // Redirect stderr to log file
-    if let Some(stderr) = child.stderr.take() {
-        let log_path_clone = log_path.clone();
-        std::thread::spawn(move || {
-            if let Ok(mut file) = std::fs::OpenOptions::new().create(true).append(true).open(&log_path_clone) {
-                 let reader = BufReader::new(stderr);
-                 for line in reader.lines().flatten() {
-                     let _ = writeln!(file, "[stderr] {line}");
-                 }
-            }
-        });
+fn whisper_folder() -> &'static str {
+    if cfg!(windows) {
+        "whisper-win"
+    } else if cfg!(target_os = "macos") {
+        "whisper-mac"
+    } else {
+        "whisper-lin"
     }
+}
 
-    child
+/// The first of `candidates` that exists, and the paths checked up to and including it.
+fn first_existing(candidates: Vec<PathBuf>) -> (Option<PathBuf>, Vec<PathBuf>) {
+    let mut checked = Vec::new();
+    for candidate in candidates {
+        let found = candidate.exists();
+        checked.push(candidate);
+        if found {
+            return (checked.last().cloned(), checked);
+        }
+    }
+    (None, checked)
 }
 
-// ---- Commands ----
+fn resolve_llama_exe() -> Result<PathBuf, String> {
+    if let Some(p) = exe_override("GENHAT_LLAMA_EXE") {
+        return Ok(p);
+    }
+    let exe_path = app_exe_path("GENHAT_LLAMA_EXE")?;
+    let (found, checked) = first_existing(llama_candidates(&exe_path));
+    found.ok_or_else(|| {
+        let checked_list = checked
+            .into_iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("llama-server not found. Checked the following paths:\n{checked_list}")
+    })
+}
+
+fn resolve_tts_exe() -> Result<PathBuf, String> {
+    if let Some(p) = exe_override("GENHAT_TTS_EXE") {
+        return Ok(p);
+    }
+    let exe_path = app_exe_path("GENHAT_TTS_EXE")?;
+    first_existing(tts_candidates(&exe_path))
+        .0
+        .ok_or_else(|| "TTS executable not found".to_string())
+}
+
+fn resolve_whisper_exe() -> Result<PathBuf, String> {
+    if let Some(p) = exe_override("GENHAT_WHISPER_EXE") {
+        return Ok(p);
+    }
+    let exe_path = app_exe_path("GENHAT_WHISPER_EXE")?;
+    first_existing(whisper_candidates(&exe_path)).0.ok_or_else(|| {
+        format!("Whisper executable not found (looked for bin/{}/whisper-cli)", whisper_folder())
+    })
+}
+
+/// Binary paths resolved by the `resolve_*_exe` searches, kept so the filesystem walk runs
+/// once per binary rather than on every spawn. Cleared by `refresh_binary_paths`.
+#[derive(Clone, Default, serde::Serialize)]
+struct BinaryPaths {
+    llama: Option<PathBuf>,
+    tts: Option<PathBuf>,
+    whisper: Option<PathBuf>,
+}
+
+/// Returns the cached path if the file still exists, otherwise resolves again and caches
+/// the result on success. Failures aren't cached, so a binary installed later is found.
+fn cached_exe(slot: &mut Option<PathBuf>, resolve: fn() -> Result<PathBuf, String>) -> Result<PathBuf, String> {
+    if let Some(p) = slot.as_ref().filter(|p| p.is_file()) {
+        return Ok(p.clone());
+    }
+    let p = resolve()?;
+    *slot = Some(p.clone());
+    Ok(p)
+}
+
+fn llama_exe(state: &AppState) -> Result<PathBuf, String> {
+    cached_exe(&mut state.binaries.lock().unwrap().llama, resolve_llama_exe)
+}
+
+fn tts_exe(state: &AppState) -> Result<PathBuf, String> {
+    cached_exe(&mut state.binaries.lock().unwrap().tts, resolve_tts_exe)
+}
+
+fn whisper_exe(state: &AppState) -> Result<PathBuf, String> {
+    cached_exe(&mut state.binaries.lock().unwrap().whisper, resolve_whisper_exe)
+}
 
+/// Forgets the cached binary paths and probe results and resolves every binary again,
+/// e.g. after the bundled binaries were updated. Returns the paths found (`null` = missing).
 #[tauri::command]
-fn list_models() -> Vec<ModelFile> {
+fn refresh_binary_paths(state: State<AppState>) -> BinaryPaths {
+    *state.binaries.lock().unwrap() = BinaryPaths::default();
+    *state.backends.lock().unwrap() = None;
+    *state.llama_version.lock().unwrap() = None;
+    BinaryPaths {
+        llama: llama_exe(&state).ok(),
+        tts: tts_exe(&state).ok(),
+        whisper: whisper_exe(&state).ok(),
+    }
+}
+
+/// Finds a whisper.cpp model (`ggml-*.bin`) in the models dir or its `whisper/` subfolder.
+fn find_whisper_model() -> Option<PathBuf> {
     let dir = get_models_dir();
-    let mut models = Vec::new();
+    [dir.clone(), dir.join("whisper")].into_iter().find_map(|d| {
+        let mut found: Vec<PathBuf> = std::fs::read_dir(d)
+            .ok()?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
+                name.starts_with("ggml-") && name.ends_with(".bin")
+            })
+            .collect();
+        found.sort();
+        found.into_iter().next()
+    })
+}
 
-    if let Ok(entries) = std::fs::read_dir(&dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            // Check for .gguf extension
-            if path.extension().and_then(|s| s.to_str()) == Some("gguf") {
-                if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
-                    // Exclude TTS models
-                    if name.starts_with("t3_") || name.starts_with("s3gen") || name.starts_with("ve_") {
-                        continue;
+/// Default upper bound for a single tts-inference run.
+const TTS_TIMEOUT_SECS: u64 = 120;
+
+/// Waits for the child held in `slot` to exit.
+/// The child stays in the slot while running so another command can take and kill it;
+/// if the slot is emptied before the process exits, the run counts as cancelled.
+/// With a `timeout`, the child is killed once it has been waited on for that long.
+fn wait_for_slot(slot: &Mutex<Option<Child>>, timeout: Option<std::time::Duration>) -> Result<ExitStatus, SlotError> {
+    let deadline = timeout.map(|t| std::time::Instant::now() + t);
+    loop {
+        {
+            let mut guard = slot.lock().unwrap();
+            match guard.as_mut() {
+                None => return Err(SlotError::Cancelled),
+                Some(child) => {
+                    if let Some(status) = child.try_wait().map_err(|e| SlotError::Wait(e.to_string()))? {
+                        guard.take();
+                        return Ok(status);
+                    }
+                    if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+                        if let Some(mut child) = guard.take() {
+                            let _ = child.kill();
+                            let _ = child.wait();
+                        }
+                        return Err(SlotError::Timeout);
                     }
-                    models.push(ModelFile {
-                        name: name.to_string(),
-                        path: path.to_string_lossy().to_string(),
-                    });
                 }
             }
         }
+        std::thread::sleep(std::time::Duration::from_millis(100));
     }
-    models
 }
 
-#[tauri::command]
-fn list_audio_models() -> Vec<ModelFile> {
-    let dir = get_models_dir();
-    // Check main dir and specific subdir
-    let search_dirs = vec![dir.clone(), dir.join("tts-chatterbox-q4-k-m")];
-    let mut models = Vec::new();
-
-    for d in search_dirs {
-        if let Ok(entries) = std::fs::read_dir(d) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().and_then(|s| s.to_str()) == Some("gguf") {
-                     if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
-                         // We treat 's3gen' files as the selectable "Model" for TTS
-                         if name.starts_with("s3gen") {
-                             models.push(ModelFile {
-                                 name: name.to_string(),
-                                 path: path.to_string_lossy().to_string(),
-                             });
-                         }
-                     }
+/// Captured result of a process run through `run_in_slot`.
+struct SlotOutput {
+    status: ExitStatus,
+    stdout: String,
+    stderr: String,
+}
+
+enum SlotError {
+    /// The slot already holds a running child.
+    Busy,
+    Spawn(std::io::Error),
+    Cancelled,
+    Timeout,
+    Wait(String),
+}
+
+/// Called with each line a `run_in_slot` child prints, as it is printed.
+type LineObserver = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Reads `pipe` to the end. With `on_line`, each line is also passed on as it arrives; `\r`
+/// ends a line too, so a progress bar redrawn in place is seen at every update.
+fn drain(mut pipe: impl std::io::Read, on_line: Option<LineObserver>) -> String {
+    let mut all = Vec::new();
+    let mut line = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = match pipe.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        all.extend_from_slice(&buf[..n]);
+        let Some(on_line) = &on_line else { continue };
+        for &byte in &buf[..n] {
+            if byte == b'\n' || byte == b'\r' {
+                if !line.is_empty() {
+                    on_line(&String::from_utf8_lossy(&line));
+                    line.clear();
                 }
+            } else {
+                line.push(byte);
             }
         }
     }
-    models
+    if let (Some(on_line), false) = (&on_line, line.is_empty()) {
+        on_line(&String::from_utf8_lossy(&line));
+    }
+    String::from_utf8_lossy(&all).into_owned()
 }
 
-#[tauri::command]
-fn switch_model(state: State<AppState>, model_path: String) -> Result<String, String> {
-    let path = PathBuf::from(&model_path);
-    if !path.exists() {
-        return Err(format!("Model file not found: {}", model_path));
+/// Spawns `cmd` into `slot` (rejecting with `Busy` if occupied), drains its output and waits for it,
+/// killing it after `timeout` if given.
+/// Check, spawn and store happen under one lock so two callers can never both start a process.
+/// `on_spawn` sees the child right after it starts (used to record it in `orphans`);
+/// `on_line` sees its stdout and stderr lines while it runs.
+fn run_in_slot(
+    slot: &Mutex<Option<Child>>,
+    mut cmd: Command,
+    timeout: Option<std::time::Duration>,
+    on_spawn: impl FnOnce(&Child),
+    on_line: Option<LineObserver>,
+) -> Result<SlotOutput, SlotError> {
+    let mut guard = slot.lock().unwrap();
+    if guard.is_some() {
+        return Err(SlotError::Busy);
     }
 
-    {
-        let mut guard = state.llama.lock().unwrap();
-        // Kill existing
-        if let Some(mut child) = guard.take() {
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(SlotError::Spawn)?;
+    on_spawn(&child);
+
+    // Drain pipes on threads so a chatty process can't block on a full pipe while we poll.
+    let stdout = child.stdout.take().map(|out| {
+        let on_line = on_line.clone();
+        std::thread::spawn(move || drain(out, on_line))
+    });
+    let stderr = child.stderr.take().map(|err| std::thread::spawn(move || drain(err, on_line)));
+
+    *guard = Some(child);
+    drop(guard);
+
+    let status = wait_for_slot(slot, timeout)?;
+
+    Ok(SlotOutput {
+        status,
+        stdout: stdout.and_then(|h| h.join().ok()).unwrap_or_default(),
+        stderr: stderr.and_then(|h| h.join().ok()).unwrap_or_default(),
+    })
+}
+
+/// Kills the child held in `slot`, if any. Returns whether something was running.
+fn kill_slot(slot: &Mutex<Option<Child>>) -> bool {
+    match slot.lock().unwrap().take() {
+        Some(mut child) => {
             let _ = child.kill();
+            let _ = child.wait();
+            true
         }
-        // Spawn new
-        let child = spawn_llama_process(path);
-        // Store
-        *guard = Some(child);
+        None => false,
     }
-    
-    Ok("server started".into())
 }
 
-#[tauri::command]
-fn stop_llama(state: State<AppState>) {
-    if let Some(mut child) = state.llama.lock().unwrap().take() {
-        let _ = child.kill();
+/// Checks that `path` names a file whose parent directory exists and is writable.
+fn validate_output_path(path: &Path) -> Result<PathBuf, String> {
+    if path.is_dir() {
+        return Err(format!("Output path is a directory: {}", path.display()));
     }
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => return Err(format!("Output path has no parent directory: {}", path.display())),
+    };
+    if !parent.is_dir() {
+        return Err(format!("Output directory does not exist: {}", parent.display()));
+    }
+
+    // Permission bits don't tell the whole story (ACLs, read-only mounts), so probe with a real file.
+    let probe = parent.join(format!(".genhat-write-test-{}", std::process::id()));
+    std::fs::File::create(&probe)
+        .map_err(|e| format!("Output directory is not writable: {} ({})", parent.display(), e))?;
+    let _ = std::fs::remove_file(&probe);
+
+    Ok(path.to_path_buf())
 }
 
-#[tauri::command]
-async fn generate_speech(
-    model_path: String,
-    input: String,
-) -> Result<String, String> {
-    // Resolve Exe
-    let exe = resolve_tts_exe();
-
-    // Resolve model files
-    let s3_path = PathBuf::from(&model_path);
-    if !s3_path.exists() {
-        return Err(format!("Model path not found: {:?}", s3_path));
-    }
-    let parent = s3_path.parent().unwrap_or(Path::new(""));
-    
-    // We expect siblings: ve_fp32-f16.gguf and t3_cfg-q4_k_m.gguf
-    let vae_path = parent.join("ve_fp32-f16.gguf");
-    let clip_path = parent.join("t3_cfg-q4_k_m.gguf");
-    
-    if !vae_path.exists() {
-        return Err(format!("Sibling VAE model (ve_fp32-f16.gguf) not found in {:?}", parent));
-    }
-    if !clip_path.exists() {
-         return Err(format!("Sibling CLIP model (t3_cfg-q4_k_m.gguf) not found in {:?}", parent));
-    }
-
-    // Prepare Output Path
-    let mut temp = std::env::temp_dir();
-    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis();
-    let filename = format!("genhat_tts_{}.wav", timestamp);
-    temp.push(&filename);
-    let output_str = temp.to_string_lossy().to_string();
-
-    // Run Exe
-    // work dir should be the exe's dir so it finds its internal libs (it's a one-dir bundle)
-    let cwd = exe.parent().unwrap_or(&std::path::Path::new("."));
-
-    let output = Command::new(&exe)
-        .current_dir(cwd)
-        .arg("--text")
-        .arg(&input)
-        .arg("--output")
-        .arg(&output_str)
-        .arg("--model_gguf")
-        .arg(&s3_path)
-        .arg("--vae_gguf")
-        .arg(&vae_path)
-        .arg("--clip_gguf")
-        .arg(&clip_path)
-        .output()
-        .map_err(|e| format!("Failed to spawn tts executable '{}': {}", exe.display(), e))?;
-
-    if !output.status.success() {
-         let stderr = String::from_utf8_lossy(&output.stderr);
-         let stdout = String::from_utf8_lossy(&output.stdout);
-         return Err(format!("TTS process failed: {}\nStdout: {}", stderr, stdout));
-    }
-
-    Ok(output_str) // Return the absolute path to the wav file
+/// Maps recognizable llama-server load log lines to a stage.
+/// Ready is deliberately not detected here; it comes from the `/health` check.
+fn detect_load_stage(line: &str) -> Option<LoadStage> {
+    if line.contains("warming up") || line.contains("llama_context:") || line.contains("llama_init_from_model") {
+        Some(LoadStage::WarmingUp)
+    } else if line.contains("load_tensors") || line.contains("llm_load_tensors") {
+        Some(LoadStage::LoadingTensors)
+    } else if line.contains("loaded meta data") || line.contains("llama_model_loader") || line.contains("loading model") {
+        Some(LoadStage::ReadingMetadata)
+    } else {
+        None
+    }
 }
 
+fn emit_load_stage(app: &AppHandle, model: &str, stage: LoadStage) {
+    let _ = app.emit(
+        "model-loading",
+        ModelLoadingEvent {
+            model: model.to_string(),
+            stage,
+            progress: stage.progress(),
+        },
+    );
+}
 
-fn main() {
-    tauri::Builder::default()
-        .manage(AppState {
-            llama: Mutex::new(None),
-        })
-        .setup(|app| {
-            // Auto-start default model if found
-            let dir = get_models_dir();
-            let default_path = dir.join("LFM-1.2B-INT8.gguf");
-            
-            let model_to_load = if default_path.exists() {
-                Some(default_path)
-            } else {
-                // Find first available that isn't a TTS model
-                std::fs::read_dir(&dir).ok().and_then(|mut entries| {
-                    entries.find_map(|e| {
-                         e.ok().map(|ent| ent.path())
-                          .filter(|p| {
-                            let is_gguf = p.extension().map(|s| s == "gguf").unwrap_or(false);
-                            let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
-                            is_gguf && !name.starts_with("t3_") && !name.starts_with("s3gen") && !name.starts_with("ve_")
-                          })
-                    })
-                })
-            };
+/// Advances `current` to `stage` (never backwards) and emits an event if it moved.
+fn advance_load_stage(app: &AppHandle, model: &str, current: &AtomicU8, stage: LoadStage) {
+    let prev = current.fetch_max(stage as u8, Ordering::SeqCst);
+    if (stage as u8) > prev {
+        emit_load_stage(app, model, stage);
+    }
+}
 
-            if let Some(p) = model_to_load {
-                let child = spawn_llama_process(p);
-                app.state::<AppState>().llama.lock().unwrap().replace(child);
-            } else {
-                println!("No valid LLM models found in {}, server not started automatically.", dir.display());
+/// URL of `path` on the llama-server, reached through loopback unless it was bound to a
+/// specific address (which loopback can't reach).
+fn llama_url(state: &AppState, path: &str) -> String {
+    let host = match *state.llama_host.lock().unwrap() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        host => host,
+    };
+    format!("http://{}{path}", SocketAddr::new(host, LLAMA_PORT))
+}
+
+/// URL of the readiness probe: `/health`, or the `health_path` setting for builds that
+/// report readiness elsewhere.
+fn health_url(state: &AppState) -> String {
+    let path = state.settings.lock().unwrap().health_path.clone();
+    llama_url(state, path.as_deref().unwrap_or(HEALTH_PATH))
+}
+
+/// How long `model` gets to become ready: the `ready_timeout_secs` setting (default
+/// `READY_TIMEOUT_SECS`) plus `READY_SECS_PER_GIB` for every started GiB of weights,
+/// counting all shards of a split model.
+fn ready_timeout(state: &AppState, model: &Path) -> std::time::Duration {
+    let base = state.settings.lock().unwrap().ready_timeout_secs.unwrap_or(READY_TIMEOUT_SECS);
+    let files = gguf::split_set(model).map(|s| s.shards).unwrap_or_else(|| vec![model.to_path_buf()]);
+    let bytes: u64 = files.iter().filter_map(|f| std::fs::metadata(f).ok()).map(|m| m.len()).sum();
+    std::time::Duration::from_secs(base + bytes.div_ceil(1 << 30) * READY_SECS_PER_GIB)
+}
+
+/// Polls llama-server's readiness probe (`url`) until it answers 200 or `timeout` elapses.
+async fn wait_for_ready(url: &str, timeout: std::time::Duration) -> bool {
+    let client = reqwest::Client::new();
+    let deadline = std::time::Instant::now() + timeout;
+    while std::time::Instant::now() < deadline {
+        if let Ok(resp) = client.get(url).timeout(std::time::Duration::from_secs(2)).send().await {
+            if resp.status().is_success() {
+                return true;
             }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
+    false
+}
 
-            Ok(())
+/// The llama child with `pid`, as seen by the watchers.
+enum LlamaStatus {
+    Running,
+    /// Exited while still the current server. Every intentional stop takes the child out
+    /// of `AppState.llama` first, so this is a crash.
+    Exited(ExitStatus),
+    /// Stopped or replaced by the app.
+    Gone,
+}
+
+fn llama_status(app: &AppHandle, pid: u32) -> LlamaStatus {
+    let state = app.state::<AppState>();
+    let mut guard = state.llama.lock().unwrap();
+    match guard.as_mut() {
+        Some(child) if child.id() == pid => match child.try_wait() {
+            Ok(None) => LlamaStatus::Running,
+            Ok(Some(status)) => LlamaStatus::Exited(status),
+            Err(_) => LlamaStatus::Gone,
+        },
+        _ => LlamaStatus::Gone,
+    }
+}
+
+/// Returns false if the llama child with `pid` has exited or was replaced.
+fn llama_still_running(app: &AppHandle, pid: u32) -> bool {
+    matches!(llama_status(app, pid), LlamaStatus::Running)
+}
+
+/// Copies one llama-server output stream into the log file line by line, emitting each
+/// line as a `llama-log` event and feeding load-stage detection.
+fn spawn_log_reader(
+    app: &AppHandle,
+    stream: &'static str,
+    pipe: impl std::io::Read + Send + 'static,
+    pid: u32,
+    structured: bool,
+    model_name: &str,
+    stage: &Arc<AtomicU8>,
+) {
+    let (app, model_name, stage) = (app.clone(), model_name.to_string(), stage.clone());
+    std::thread::spawn(move || {
+        if let Ok(mut log) = LogWriter::open(&app, structured) {
+            let reader = BufReader::new(pipe);
+            for line in reader.lines().map_while(Result::ok) {
+                if let Some(s) = detect_load_stage(&line) {
+                    advance_load_stage(&app, &model_name, &stage, s);
+                }
+                if let Some(warning) = llama_warnings::classify(&line, pid) {
+                    let _ = app.emit("llama-warning", warning);
+                }
+                let entry = LogLine::new(stream, Some(pid), line);
+                log.write(&entry);
+                let _ = app.emit("llama-log", entry);
+            }
+        }
+    });
+}
+
+/// One spawn attempt. The flag says whether the failure is transient: a missing or
+/// non-executable binary won't fix itself, but a port that hasn't been released yet or a
+/// spawn refused while drivers/antivirus are still initialising on cold boot might.
+fn try_spawn_llama(exe: &Path, host: IpAddr, args: &[String]) -> Result<Child, (AppError, bool)> {
+    // Fail fast if something else (e.g. a stray llama-server) already holds the port,
+    // rather than spawning a server that exits immediately.
+    if std::net::TcpListener::bind((host, LLAMA_PORT)).is_err() {
+        return Err((AppError::PortInUse(LLAMA_PORT), true));
+    }
+
+    // IMPORTANT: Set current_dir to the binary's folder so it finds sibling DLLs (llama.dll, etc.)
+    let work_dir = exe
+        .parent()
+        .ok_or_else(|| (AppError::SpawnFailed(format!("llama-server path has no folder: {}", exe.display())), false))?;
+
+    Command::new(exe)
+        .args(args)
+        .current_dir(work_dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            let permanent = matches!(
+                e.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::InvalidInput
+            );
+            let err = AppError::SpawnFailed(format!("Failed to start llama-server '{}': {}", exe.display(), e));
+            (err, !permanent)
         })
-        .invoke_handler(tauri::generate_handler![stop_llama, list_models, list_audio_models, switch_model, generate_speech])
-        .build(tauri::generate_context!())
-        .expect("error building tauri app")
-        .run(|app_handle, event| match event {
-            tauri::RunEvent::Exit => {
-                let state = app_handle.state::<AppState>();
-                if let Some(mut child) = state.llama.lock().unwrap().take() {
-                    let _ = child.kill();
-                };
-            }
-            _ => {}
-        });
 }
 
+/// Flags GenHat sets itself; `extra_args` may not repeat them. `--flag=value` forms are
+/// matched on the flag part.
+const MANAGED_LLAMA_FLAGS: &[&str] = &[
+    "-m",
+    "--model",
+    "--port",
+    "--host",
+    "-c",
+    "--ctx-size",
+    "-np",
+    "--parallel",
+    "--embedding",
+    "--embeddings",
+    "--reranking",
+    "--rerank",
+];
+
+fn validate_extra_args(args: &[String]) -> Result<(), String> {
+    for arg in args {
+        let flag = arg.split('=').next().unwrap_or(arg);
+        if MANAGED_LLAMA_FLAGS.contains(&flag) {
+            return Err(format!("'{flag}' is managed by GenHat and can't be passed in extra_args"));
+        }
+    }
+    Ok(())
+}
+
+/// `exe` and `args` as one line that can be pasted into a shell: arguments with spaces or
+/// quotes are double-quoted.
+fn command_line(exe: &Path, args: &[String]) -> String {
+    let quote = |arg: &str| {
+        if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+            arg.to_string()
+        } else {
+            format!("\"{}\"", arg.replace('"', "\\\""))
+        }
+    };
+    std::iter::once(quote(&exe.to_string_lossy()))
+        .chain(args.iter().map(|a| quote(a)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Full llama-server argument list: managed args, then the caller's options, then the
+/// user's `extra_args` verbatim. `slot_dir` is the `--slot-save-path` used when the config
+/// doesn't set one.
+fn llama_args(
+    model_path: &Path,
+    host: IpAddr,
+    slot_dir: Option<&Path>,
+    config: &LlamaConfig,
+    extra_args: &[String],
+) -> Vec<String> {
+    let mut args = vec![
+        "-m".to_string(),
+        model_path.to_string_lossy().to_string(),
+        "--port".to_string(),
+        LLAMA_PORT.to_string(),
+        "--host".to_string(),
+        host.to_string(),
+    ];
+    if let Some(dir) = slot_dir.filter(|_| config.slot_save_path.is_none()) {
+        args.extend(["--slot-save-path".to_string(), dir.to_string_lossy().to_string()]);
+    }
+    args.extend(config.to_args());
+    args.extend(extra_args.iter().cloned());
+    args
+}
+
+/// Spawns llama-server, retrying transient failures up to `SPAWN_ATTEMPTS` times with
+/// exponential backoff (a `model-spawn-retry` event precedes each retry). Only the last
+/// error is returned. Takes the caller's `llama` lock, which is released while waiting between
+/// attempts (so status queries and stops aren't stuck behind the backoff) and handed back with
+/// the child.
+fn spawn_llama_process<'a>(
+    app: &AppHandle,
+    state: &'a AppState,
+    mut llama: MutexGuard<'a, Option<Child>>,
+    model_path: PathBuf,
+    config: &LlamaConfig,
+) -> Result<(MutexGuard<'a, Option<Child>>, Child), AppError> {
+    let exe = llama_exe(state).map_err(AppError::SpawnFailed)?;
+
+    // Logging setup
+    let (structured, extra_args, host) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.structured_logs, settings.llama_extra_args.clone(), settings.bind_host())
+    };
+    // Re-checked here since settings.json can be edited by hand
+    validate_extra_args(&extra_args).map_err(AppError::InvalidInput)?;
+    // Fields the caller's params leave unset come from the model's profile, then settings
+    let requested = config.clone();
+    let mut config = config.clone();
+    config.params = model_params::launch_params(state, &model_path, config.params);
+    config.params.validate().map_err(AppError::InvalidInput)?;
+    config.write_chat_template().map_err(AppError::Io)?;
+    if config.auto_gpu_layers {
+        if memory::gpu_layers_from_args(&extra_args).is_some() {
+            eprintln!("auto_gpu_layers ignored: llama_extra_args sets the GPU layer count");
+        } else {
+            match memory::fit_gpu_layers(state, &model_path, config.ctx_size()) {
+                Ok(fit) => {
+                    config.gpu_layers = Some(fit.gpu_layers as i32);
+                    let _ = app.emit(
+                        "auto-gpu-layers",
+                        AutoGpuLayersEvent {
+                            model: model_path.to_string_lossy().to_string(),
+                            fit,
+                        },
+                    );
+                }
+                Err(e) => eprintln!("auto_gpu_layers failed, keeping gpu_layers {:?}: {e}", config.gpu_layers),
+            }
+        }
+    }
+    let slot_dir = slot_cache::default_dir(app);
+    let args = llama_args(&model_path, host, slot_dir.as_deref(), &config, &extra_args);
+    let mut log = LogWriter::open(app, structured)
+        .map_err(|e| AppError::Io(format!("Failed to open llama log file: {e}")))?;
+
+    log.write(&LogLine::new("genhat", None, "--- llama-server start ---"));
+    log.write(&LogLine::new("genhat", None, format!("exe: {}", exe.display())));
+    log.write(&LogLine::new("genhat", None, format!("model: {}", model_path.display())));
+    let command = command_line(&exe, &args);
+    log.write(&LogLine::new("genhat", None, format!("command: {command}")));
+    *state.server_command.lock().unwrap() = Some(command.clone());
+    if !host.is_loopback() {
+        // llama-server has no authentication: anyone who can reach the address can use it
+        let warning = format!(
+            "WARNING: llama-server is listening on {host}:{LLAMA_PORT}{}; it has no authentication, so anyone who can reach this address can use the model",
+            if host.is_unspecified() { " (all network interfaces)" } else { "" }
+        );
+        eprintln!("{warning}");
+        log.write(&LogLine::new("genhat", None, warning));
+    }
+
+    let model_name = model_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut attempt = 1;
+    let mut child = loop {
+        match try_spawn_llama(&exe, host, &args) {
+            Ok(child) => break child,
+            Err((e, true)) if attempt < SPAWN_ATTEMPTS => {
+                let delay_ms = SPAWN_RETRY_BASE_MS << (attempt - 1);
+                log.write(&LogLine::new(
+                    "genhat",
+                    None,
+                    format!("spawn attempt {attempt}/{SPAWN_ATTEMPTS} failed: {e}; retrying in {delay_ms}ms"),
+                ));
+                let _ = app.emit(
+                    "model-spawn-retry",
+                    SpawnRetryEvent {
+                        model: model_name.clone(),
+                        attempt,
+                        max_attempts: SPAWN_ATTEMPTS,
+                        delay_ms,
+                        error: e.to_string(),
+                    },
+                );
+                drop(llama);
+                std::thread::sleep(std::time::Duration::from_millis(delay_ms));
+                llama = state.llama.lock().unwrap();
+                // Spawners after startup hold `switching`, so the slot can only be taken by a
+                // caller that skipped it; don't start a second server on the same port
+                if llama.is_some() {
+                    return Err(AppError::Busy("Another llama-server was started during the spawn retry".to_string()));
+                }
+                attempt += 1;
+            }
+            Err((e, _)) => {
+                log.write(&LogLine::new("genhat", None, format!("spawn failed: {e}")));
+                return Err(match e {
+                    AppError::SpawnFailed(msg) => AppError::SpawnFailed(format!("{msg}; command: {command}")),
+                    e => e,
+                });
+            }
+        }
+    };
+
+    let pid = child.id();
+    log.write(&LogLine::new("genhat", Some(pid), format!("spawned pid: {}", pid)));
+    orphans::record(app, "llama", pid, &exe);
+    *state.server_config.lock().unwrap() = Some(config.clone());
+    *state.launch_config.lock().unwrap() = Some(requested);
+    *state.llama_host.lock().unwrap() = host;
+    // Callers hold the `llama` lock
+    *state.loading.lock().unwrap() = Some(pid);
+    let health_url = health_url(state);
+    let ready_timeout = ready_timeout(state, &model_path);
+
+    let stage = Arc::new(AtomicU8::new(LoadStage::Starting as u8));
+    emit_load_stage(app, &model_name, LoadStage::Starting);
+
+    // Redirect stdout/stderr to the log file (llama.cpp writes most load progress to stderr)
+    if let Some(stdout) = child.stdout.take() {
+        spawn_log_reader(app, "stdout", stdout, pid, structured, &model_name, &stage);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        spawn_log_reader(app, "stderr", stderr, pid, structured, &model_name, &stage);
+    }
+
+    // "ready" is only reported once /health answers; an early exit is reported as failed.
+    // The task then keeps watching the process until the app stops it, so a crash (during
+    // load or later) leaves a crash report.
+    let app = app.clone();
+    let model_label = model_path.to_string_lossy().to_string();
+    tauri::async_runtime::spawn(async move {
+        let deadline = std::time::Instant::now() + ready_timeout;
+        let mut loaded = false;
+        while !loaded && std::time::Instant::now() < deadline {
+            if !llama_still_running(&app, pid) {
+                break;
+            }
+            loaded = wait_for_ready(&health_url, std::time::Duration::from_secs(1)).await;
+        }
+        let superseded = {
+            let state = app.state::<AppState>();
+            let _llama = state.llama.lock().unwrap();
+            let mut loading = state.loading.lock().unwrap();
+            let superseded = *loading != Some(pid);
+            if !superseded {
+                *loading = None;
+            }
+            superseded
+        };
+        // A cancelled or replaced load has already been reported
+        if !superseded {
+            let outcome = if loaded { LoadStage::Ready } else { LoadStage::Failed };
+            advance_load_stage(&app, &model_name, &stage, outcome);
+            if loaded {
+                slot_cache::prime_in_background(&app);
+            }
+        }
+
+        // Only a server that finished loading is pinged; a stuck load is the ready timeout's job
+        let mut keepalive = loaded.then(|| keepalive::Monitor::new(pid, health_url));
+        loop {
+            match llama_status(&app, pid) {
+                LlamaStatus::Running => {
+                    if let Some(monitor) = keepalive.as_mut() {
+                        monitor.tick(&app).await;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_secs(CRASH_POLL_SECS)).await
+                }
+                LlamaStatus::Exited(status) => {
+                    // Let the log readers drain the pipes so the report has the final lines
+                    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+                    crash_reports::record(&app, pid, &model_label, status);
+                    return;
+                }
+                LlamaStatus::Gone => return,
+            }
+        }
+    });
+
+    Ok((llama, child))
+}
+
+// ---- Commands ----
+
+#[tauri::command]
+fn get_cpu_features() -> CpuFeatures {
+    detect_cpu_features()
+}
+
+/// Whether a scanned file may be offered as a model: not a dotfile, not under a hidden
+/// directory below `root`, and not empty (half-copied or placeholder files).
+fn is_visible_model_file(root: &Path, path: &Path) -> bool {
+    let hidden = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+    !hidden && std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() > 0)
+}
+
+/// Display names for `paths` under `root`: the shortest trailing part of each path
+/// relative to `root` that no other path ends with (`s3gen.gguf`, or `a/x/s3gen.gguf` next
+/// to `b/x/s3gen.gguf`), so every listed name is distinct.
+fn display_names(root: &Path, paths: &[PathBuf]) -> Vec<String> {
+    let components: Vec<Vec<String>> = paths
+        .iter()
+        .map(|p| {
+            p.strip_prefix(root)
+                .unwrap_or(p)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect()
+        })
+        .collect();
+    let tail = |parts: &[String], k: usize| parts[parts.len().saturating_sub(k)..].to_vec();
+    components
+        .iter()
+        .enumerate()
+        .map(|(i, parts)| {
+            let k = (1..parts.len())
+                .find(|&k| {
+                    let own = tail(parts, k);
+                    components.iter().enumerate().all(|(j, other)| j == i || tail(other, k) != own)
+                })
+                .unwrap_or(parts.len());
+            tail(parts, k).join("/")
+        })
+        .collect()
+}
+
+/// Whether the file name `name` is an LLM GGUF rather than a part of a TTS pack.
+fn is_llm_file_name(name: &str) -> bool {
+    name.ends_with(".gguf") && !(name.starts_with("t3_") || name.starts_with("s3gen") || name.starts_with("ve_"))
+}
+
+#[tauri::command]
+fn list_models(state: State<AppState>) -> Vec<ModelFile> {
+    scan_models(&state)
+}
+
+/// Folder levels below the models dir searched for LLM GGUFs.
+const LLM_SCAN_DEPTH: usize = 3;
+
+/// The LLMs `list_models` lists under `dir` (split models as their shard 1, TTS pack parts
+/// left out), sorted by path, each with its display name (see `display_names`).
+fn llm_files(dir: &Path) -> Vec<(PathBuf, String)> {
+    let mut seen_splits = std::collections::HashSet::new();
+    let paths: Vec<PathBuf> = scan_model_files(dir, LLM_SCAN_DEPTH)
+        .into_iter()
+        .filter(|path| path.file_name().and_then(|s| s.to_str()).is_some_and(is_llm_file_name))
+        // Collapse split GGUFs into one entry pointing at shard 1
+        .filter_map(|path| match gguf::split_set(&path) {
+            Some(split) => seen_splits.insert(split.first.clone()).then_some(split.first),
+            None => Some(path),
+        })
+        .collect();
+    let names = display_names(dir, &paths);
+    paths.into_iter().zip(names).collect()
+}
+
+/// The `list_models` entries. Files with the same name in different folders are named by
+/// their distinguishing relative path, so every name is distinct.
+fn scan_models(state: &AppState) -> Vec<ModelFile> {
+    let (pinned, aliases, categories) = {
+        let settings = state.settings.lock().unwrap();
+        (
+            settings.pinned_models.clone(),
+            settings.model_aliases.clone(),
+            settings.model_categories.clone(),
+        )
+    };
+    let mut models: Vec<ModelFile> = llm_files(&get_models_dir())
+        .into_iter()
+        .map(|(path, name)| {
+            let split = gguf::split_set(&path);
+            let category = categories.get(&*path.to_string_lossy()).copied();
+            let category = category.unwrap_or_else(|| category::detect(state, &path));
+            let path = path.to_string_lossy().to_string();
+            ModelFile {
+                name,
+                pinned: pinned.contains(&path),
+                alias: aliases.get(&path).cloned(),
+                category,
+                shards: split.as_ref().map(|s| s.count),
+                unusable_reason: split.as_ref().and_then(missing_shards_reason),
+                path,
+            }
+        })
+        .collect();
+    // Stable sort: pinned first, otherwise path order is kept
+    models.sort_by_key(|m| !m.pinned);
+    gguf_cache::save(state);
+    models
+}
+
+/// The path of the model `list_models` lists under `name` in `dir`, if any. A bare file
+/// name that several listed models share is ambiguous and rejected with their names.
+fn model_by_name(dir: &Path, name: &str) -> Result<Option<String>, AppError> {
+    let files = llm_files(dir);
+    if let Some((path, _)) = files.iter().find(|(_, listed)| listed == name) {
+        return Ok(Some(path.to_string_lossy().to_string()));
+    }
+    // A unique file name is its own display name, so any match left is a shared one
+    let candidates: Vec<&str> = files
+        .iter()
+        .filter(|(path, _)| path.file_name().and_then(|n| n.to_str()) == Some(name))
+        .map(|(_, listed)| listed.as_str())
+        .collect();
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+    Err(AppError::InvalidInput(format!(
+        "Model name {name} is ambiguous; use one of: {}",
+        candidates.join(", ")
+    )))
+}
+
+/// Adds a model to the pinned set in settings. Pins of since-deleted files are left in
+/// place and simply never match in `list_models`.
+#[tauri::command]
+fn pin_model(app: AppHandle, state: State<AppState>, path: String) -> Result<(), String> {
+    if !Path::new(&path).is_file() {
+        return Err(format!("Model file not found: {}", path));
+    }
+    let mut settings = state.settings.lock().unwrap();
+    if !settings.pinned_models.contains(&path) {
+        settings.pinned_models.push(path);
+        settings::save(&app, &settings)?;
+    }
+    Ok(())
+}
+
+/// Names `path` for display and for `switch_model`. An empty alias removes it; an alias
+/// already used by another model is rejected.
+#[tauri::command]
+fn set_model_alias(app: AppHandle, state: State<AppState>, path: String, alias: String) -> Result<(), String> {
+    let alias = alias.trim().to_string();
+    let mut settings = state.settings.lock().unwrap();
+    if alias.is_empty() {
+        settings.model_aliases.remove(&path);
+    } else {
+        if !Path::new(&path).is_file() {
+            return Err(format!("Model file not found: {}", path));
+        }
+        if let Some((other, _)) = settings.model_aliases.iter().find(|(p, a)| **a == alias && **p != path) {
+            return Err(format!("Alias '{alias}' is already used by {other}"));
+        }
+        settings.model_aliases.insert(path, alias);
+    }
+    settings::save(&app, &settings)
+}
+
+/// Stores extra llama-server arguments, appended verbatim after the managed ones on the
+/// next start. Flags GenHat manages itself (`-m`, `--port`, ...) are rejected.
+#[tauri::command]
+fn set_llama_extra_args(app: AppHandle, state: State<AppState>, args: Vec<String>) -> Result<(), String> {
+    validate_extra_args(&args)?;
+    let mut settings = state.settings.lock().unwrap();
+    settings.llama_extra_args = args;
+    settings::save(&app, &settings)
+}
+
+/// Overrides the detected category for `path`; `None` reverts to detection.
+#[tauri::command]
+fn set_model_category(
+    app: AppHandle,
+    state: State<AppState>,
+    path: String,
+    category: Option<category::ModelCategory>,
+) -> Result<(), String> {
+    let mut settings = state.settings.lock().unwrap();
+    match category {
+        Some(category) => settings.model_categories.insert(path, category),
+        None => settings.model_categories.remove(&path),
+    };
+    settings::save(&app, &settings)
+}
+
+/// The launch options remembered for `path` (path, alias or shard), if any.
+#[tauri::command]
+fn get_model_config(state: State<AppState>, path: String) -> Result<Option<settings::ModelConfig>, String> {
+    let (_, key) = resolve_model_path(&state, path).map_err(|e| e.to_string())?;
+    Ok(state.settings.lock().unwrap().model_configs.get(&key).cloned())
+}
+
+/// Remembers launch options for `path` without starting it, as if `switch_model` had last
+/// been called with them. Checked like a `switch_model` config.
+#[tauri::command]
+fn set_model_config(
+    app: AppHandle,
+    state: State<AppState>,
+    path: String,
+    config: settings::ModelConfig,
+) -> Result<(), String> {
+    let (_, key) = resolve_model_path(&state, path).map_err(|e| e.to_string())?;
+    config.to_launch().validate(&state).map_err(|e| e.to_string())?;
+    let mut settings = state.settings.lock().unwrap();
+    settings.model_configs.insert(key, config);
+    settings::save(&app, &settings)
+}
+
+/// Forgets the launch options remembered for `path`, so it starts with the defaults again.
+/// Works for a model that no longer exists. Returns false if none were remembered.
+#[tauri::command]
+fn clear_model_config(app: AppHandle, state: State<AppState>, path: String) -> Result<bool, String> {
+    let key = resolve_model_path(&state, path.clone()).map_or(path, |(_, key)| key);
+    let mut settings = state.settings.lock().unwrap();
+    if settings.model_configs.remove(&key).is_none() {
+        return Ok(false);
+    }
+    settings::save(&app, &settings)?;
+    Ok(true)
+}
+
+#[tauri::command]
+fn unpin_model(app: AppHandle, state: State<AppState>, path: String) -> Result<(), String> {
+    let mut settings = state.settings.lock().unwrap();
+    let before = settings.pinned_models.len();
+    settings.pinned_models.retain(|p| *p != path);
+    if settings.pinned_models.len() != before {
+        settings::save(&app, &settings)?;
+    }
+    Ok(())
+}
+
+/// Optional `tts-manifest.json` in a TTS model folder naming the sibling files
+/// explicitly, e.g. `{ "vae": "ve_fp16.gguf", "clip": "t3_cfg-q8_0.gguf" }`.
+#[derive(Default, serde::Deserialize)]
+struct TtsManifest {
+    vae: Option<String>,
+    clip: Option<String>,
+}
+
+/// A sibling tts-inference needs next to the s3gen model: (label, file prefix, name of the
+/// original pack's file, preferred when several files match the prefix).
+const TTS_VAE: (&str, &str, &str) = ("VAE", "ve_", "ve_fp32-f16.gguf");
+const TTS_CLIP: (&str, &str, &str) = ("CLIP", "t3_", "t3_cfg-q4_k_m.gguf");
+
+/// Finds one sibling: the manifest entry if given, otherwise a `<prefix>*.gguf` file
+/// (the original pack's name first, then alphabetical).
+fn find_tts_sibling(dir: &Path, manifest: Option<&str>, (_, prefix, preferred): (&str, &str, &str)) -> Option<PathBuf> {
+    if let Some(name) = manifest {
+        return Some(dir.join(name)).filter(|p| p.is_file());
+    }
+    if dir.join(preferred).is_file() {
+        return Some(dir.join(preferred));
+    }
+    let mut matches: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            name.starts_with(prefix) && name.ends_with(".gguf")
+        })
+        .collect();
+    matches.sort();
+    matches.into_iter().next()
+}
+
+/// Resolves the VAE and CLIP siblings for the s3gen model in `dir`. On failure returns a
+/// description of each missing sibling (manifest name or searched pattern).
+fn resolve_tts_siblings(dir: &Path) -> Result<(PathBuf, PathBuf), Vec<String>> {
+    let manifest: TtsManifest = std::fs::read_to_string(dir.join("tts-manifest.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let describe = |manifest: Option<&str>, (label, prefix, _): (&str, &str, &str)| match manifest {
+        Some(name) => format!("{label} ({name}, from tts-manifest.json)"),
+        None => format!("{label} ({prefix}*.gguf)"),
+    };
+
+    let vae = find_tts_sibling(dir, manifest.vae.as_deref(), TTS_VAE);
+    let clip = find_tts_sibling(dir, manifest.clip.as_deref(), TTS_CLIP);
+    match (vae, clip) {
+        (Some(vae), Some(clip)) => Ok((vae, clip)),
+        (vae, clip) => {
+            let mut missing = Vec::new();
+            if vae.is_none() {
+                missing.push(describe(manifest.vae.as_deref(), TTS_VAE));
+            }
+            if clip.is_none() {
+                missing.push(describe(manifest.clip.as_deref(), TTS_CLIP));
+            }
+            Err(missing)
+        }
+    }
+}
+
+fn missing_tts_siblings(dir: &Path) -> Vec<String> {
+    resolve_tts_siblings(dir).err().unwrap_or_default()
+}
+
+/// Folder next to a TTS model holding reference clips for voice cloning (`--ref_wav`).
+const TTS_VOICES_DIR: &str = "voices";
+/// Formats tts-inference can load as a reference clip.
+const VOICE_EXTENSIONS: &[&str] = &["wav", "flac", "mp3", "ogg"];
+
+/// Reference clips in `dir`'s voices folder, or `None` when there is no such folder.
+fn count_voices(dir: &Path) -> Option<usize> {
+    let entries = std::fs::read_dir(dir.join(TTS_VOICES_DIR)).ok()?;
+    let count = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            p.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| VOICE_EXTENSIONS.iter().any(|v| e.eq_ignore_ascii_case(v)))
+        })
+        .count();
+    Some(count)
+}
+
+/// Folder levels below the models dir searched for TTS packs (a pack in
+/// `models/tts/chatterbox/` is two levels down).
+const TTS_SCAN_DEPTH: usize = 3;
+
+/// Visible files (see `is_visible_model_file`) in `root` and up to `depth` levels of
+/// subfolders, skipping hidden ones. Sorted, so packs list in a stable order.
+fn scan_model_files(root: &Path, depth: usize) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, level)) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // file_type() doesn't follow symlinks, so a linked folder can't loop the scan
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if is_dir {
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                if !hidden && level < depth {
+                    dirs.push((path, level + 1));
+                }
+            } else if is_visible_model_file(root, &path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Every TTS pack under the models dir, up to `TTS_SCAN_DEPTH` folders deep: each `s3gen*`
+/// GGUF is one selectable model, checked for its siblings and voices in its own folder.
+#[tauri::command]
+fn list_audio_models() -> Vec<AudioModel> {
+    let dir = get_models_dir();
+    let packs: Vec<PathBuf> = scan_model_files(&dir, TTS_SCAN_DEPTH)
+        .into_iter()
+        .filter(|path| {
+            let name = path.file_name().and_then(|s| s.to_str()).unwrap_or_default();
+            name.starts_with("s3gen") && path.extension().and_then(|s| s.to_str()) == Some("gguf")
+        })
+        .collect();
+    // Packs in different folders often share the s3gen file name
+    let names = display_names(&dir, &packs);
+    packs
+        .iter()
+        .zip(names)
+        .map(|(path, name)| {
+            let model_dir = path.parent().unwrap_or(Path::new(""));
+            let missing = missing_tts_siblings(model_dir);
+            AudioModel {
+                name,
+                usable: missing.is_empty(),
+                missing,
+                voice_count: count_voices(model_dir),
+                path: path.to_string_lossy().to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Whether the bundled (or `GENHAT_TTS_EXE`) tts-inference binary can be found, so the UI
+/// can hide TTS features on builds that don't ship it.
+#[tauri::command]
+fn tts_available(state: State<AppState>) -> bool {
+    tts_exe(&state).is_ok()
+}
+
+/// How long `reinitialize_tts` waits for `tts-inference --help`; the onedir bundle unpacks
+/// its Python runtime before argparse answers.
+const TTS_PROBE_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Clone, serde::Serialize)]
+struct TtsStatus {
+    /// Freshly resolved tts-inference path; `None` when none was found.
+    path: Option<String>,
+    /// The binary ran and printed its argparse usage for `--help`.
+    valid: bool,
+    /// The resolved path differs from the one cached before.
+    changed: bool,
+    /// First line of the `--help` output.
+    usage: Option<String>,
+    /// Why no binary was found or the probe failed.
+    error: Option<String>,
+}
+
+/// Blocking: resolves tts-inference from scratch and runs it with `--help` in the TTS slot.
+fn probe_tts(app: &AppHandle) -> Result<TtsStatus, String> {
+    let state = app.state::<AppState>();
+    let Ok(_reserved) = reserve_tts(&state.tts_reserved) else {
+        return Err("TTS busy: a speech generation is running".to_string());
+    };
+    let previous = state.binaries.lock().unwrap().tts.take();
+    let mut status = TtsStatus {
+        path: None,
+        valid: false,
+        changed: false,
+        usage: None,
+        error: None,
+    };
+    let exe = match resolve_tts_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            status.changed = previous.is_some();
+            status.error = Some(e);
+            return Ok(status);
+        }
+    };
+    status.path = Some(exe.to_string_lossy().to_string());
+    status.changed = previous.as_ref() != Some(&exe);
+
+    // Same working directory as a real run, so the onedir bundle finds its libraries
+    let mut cmd = Command::new(&exe);
+    cmd.current_dir(exe.parent().unwrap_or(Path::new("."))).arg("--help");
+    let timeout = Some(std::time::Duration::from_secs(TTS_PROBE_TIMEOUT_SECS));
+    let record = |child: &Child| orphans::record(app, "tts", child.id(), &exe);
+    match run_in_slot(&state.tts, cmd, timeout, record, None) {
+        Ok(output) => {
+            let usage = output.stdout.lines().find(|l| l.trim_start().starts_with("usage:"));
+            status.usage = usage.map(|l| l.trim().to_string());
+            status.valid = output.status.success() && usage.is_some();
+            if !status.valid {
+                status.error = Some(format!(
+                    "tts-inference --help exited with {} and no usage text\nStderr: {}",
+                    output.status,
+                    output.stderr.trim()
+                ));
+            }
+        }
+        Err(SlotError::Busy) => return Err("TTS busy: a speech generation is running".to_string()),
+        Err(SlotError::Spawn(e)) => status.error = Some(format!("Failed to run {}: {}", exe.display(), e)),
+        Err(SlotError::Cancelled) => status.error = Some("The probe was cancelled".to_string()),
+        Err(SlotError::Timeout) => {
+            status.error = Some(format!("tts-inference --help did not exit within {TTS_PROBE_TIMEOUT_SECS}s"))
+        }
+        Err(SlotError::Wait(e)) => status.error = Some(format!("Failed to wait for tts-inference: {e}")),
+    }
+    // A binary that doesn't run isn't cached, so the next TTS call searches again
+    if status.valid {
+        state.binaries.lock().unwrap().tts = Some(exe);
+    }
+    Ok(status)
+}
+
+/// Re-resolves tts-inference after an app or bundle update (the onedir folder can move),
+/// checks that it starts by running it with `--help`, and caches the path if it does.
+/// Failures are reported in the status; rejects only while speech is being generated.
+#[tauri::command]
+async fn reinitialize_tts(app: AppHandle) -> Result<TtsStatus, String> {
+    tauri::async_runtime::spawn_blocking(move || probe_tts(&app))
+        .await
+        .map_err(|e| format!("TTS probe task failed: {e}"))?
+}
+
+/// Whether a llama-server binary can be found (bundled or `GENHAT_LLAMA_EXE`).
+#[tauri::command]
+fn llm_available(state: State<AppState>) -> bool {
+    llama_exe(&state).is_ok()
+}
+
+/// Opens the OS file manager at `path`: the file is selected on Windows and macOS, while
+/// Linux (`xdg-open`) can only open the containing folder. Only paths under the models dir,
+/// the app data dir or the temp dir (TTS output) are accepted.
+#[tauri::command]
+fn reveal_in_file_manager(app: AppHandle, path: String) -> Result<(), String> {
+    let path = std::fs::canonicalize(&path).map_err(|e| format!("Path not found: {} ({})", path, e))?;
+    let roots = [Some(get_models_dir()), app.path().app_data_dir().ok(), Some(std::env::temp_dir())];
+    let known = roots
+        .into_iter()
+        .flatten()
+        .filter_map(|r| std::fs::canonicalize(r).ok())
+        .any(|r| path.starts_with(r));
+    if !known {
+        return Err(format!("Not inside a GenHat directory: {}", path.display()));
+    }
+
+    #[cfg(windows)]
+    let mut cmd = {
+        use std::os::windows::process::CommandExt;
+        let mut c = Command::new("explorer");
+        // explorer doesn't understand the verbatim `\\?\` prefix canonicalize adds
+        let shown = path.display().to_string();
+        // explorer parses its own command line and wants `/select,"<path>"`; `arg` would quote
+        // the whole argument instead, which breaks paths with spaces. Paths can't contain `"`.
+        c.raw_arg(format!("/select,\"{}\"", shown.trim_start_matches(r"\\?\")));
+        c
+    };
+    #[cfg(not(windows))]
+    let mut cmd = if cfg!(target_os = "macos") {
+        let mut c = Command::new("open");
+        c.arg("-R").arg(&path);
+        c
+    } else {
+        let mut c = Command::new("xdg-open");
+        c.arg(if path.is_dir() { &path } else { path.parent().unwrap_or(&path) });
+        c
+    };
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to open file manager: {e}"))?;
+    // explorer's exit code is meaningless, so don't wait on it; just reap it off-thread
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Accepts a model path, an alias, or any shard of a split model; returns the path to pass
+/// to llama-server together with its display string.
+fn resolve_model_path(state: &AppState, model_path: String) -> Result<(PathBuf, String), AppError> {
+    // Accept an alias or a listed model's name in place of the path
+    let model_path = if Path::new(&model_path).exists() {
+        model_path
+    } else {
+        let resolved = state.settings.lock().unwrap().resolve_model(&model_path);
+        if resolved == model_path && !Path::new(&resolved).exists() {
+            model_by_name(&get_models_dir(), &model_path)?.unwrap_or(resolved)
+        } else {
+            resolved
+        }
+    };
+    let path = PathBuf::from(&model_path);
+    if !path.exists() {
+        return Err(AppError::ModelNotFound(format!("Model file not found: {}", model_path)));
+    }
+    // Split GGUF: llama-server takes shard 1 and loads the others itself
+    if let Some(split) = gguf::split_set(&path) {
+        if let Some(reason) = missing_shards_reason(&split) {
+            return Err(AppError::ModelNotFound(format!("{reason} for split model {}", model_path)));
+        }
+        let first = split.first.to_string_lossy().to_string();
+        return Ok((split.first, first));
+    }
+    Ok((path, model_path))
+}
+
+/// Clears `AppState.switching` (or another in-progress flag) when the run ends, however it ends.
+struct SwitchGuard<'a>(&'a AtomicBool);
+
+impl Drop for SwitchGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Kills `child` and polls until it has exited, so its port is free before the next
+/// server binds. Returns the child if it is still alive after `KILL_TIMEOUT_SECS`.
+fn kill_and_confirm(mut child: Child) -> Result<(), Child> {
+    let _ = child.kill();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(KILL_TIMEOUT_SECS);
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => return Ok(()),
+            // Can't be polled (already reaped elsewhere); nothing left to wait for
+            Err(_) => return Ok(()),
+            Ok(None) if std::time::Instant::now() >= deadline => return Err(child),
+            Ok(None) => std::thread::sleep(std::time::Duration::from_millis(50)),
+        }
+    }
+}
+
+/// Replaces the running llama-server (if any) with one for `path` and records it as the
+/// current model. Returns the new server's pid; readiness is reported asynchronously.
+/// A call overlapping another restart (e.g. a double-clicked model) is rejected with
+/// `busy` rather than racing it for the port.
+fn restart_llama(
+    app: &AppHandle,
+    state: &AppState,
+    path: PathBuf,
+    model_path: &str,
+    config: &LlamaConfig,
+) -> Result<u32, AppError> {
+    if state.switching.swap(true, Ordering::SeqCst) {
+        return Err(AppError::Busy("Model switch already in progress".to_string()));
+    }
+    let _switching = SwitchGuard(&state.switching);
+    replace_llama(app, state, path, model_path, config)
+}
+
+/// `restart_llama` for a caller that already holds the `switching` flag.
+fn replace_llama(
+    app: &AppHandle,
+    state: &AppState,
+    path: PathBuf,
+    model_path: &str,
+    config: &LlamaConfig,
+) -> Result<u32, AppError> {
+    let mut guard = state.llama.lock().unwrap();
+    if let Some(child) = guard.take() {
+        let pid = child.id();
+        if let Err(child) = kill_and_confirm(child) {
+            // Still tracked, so a later stop or shutdown can try again
+            *guard = Some(child);
+            return Err(AppError::SpawnFailed(format!(
+                "Previous llama-server (pid {pid}) did not exit within {KILL_TIMEOUT_SECS}s"
+            )));
+        }
+    }
+    state.current_model.lock().unwrap().take();
+    let (mut guard, child) = spawn_llama_process(app, state, guard, path, config)?;
+    let pid = child.id();
+    *guard = Some(child);
+    *state.current_model.lock().unwrap() = Some(model_path.to_string());
+    *state.server_mode.lock().unwrap() = config.mode;
+    *state.model_external.lock().unwrap() = false;
+    metrics::reset(state);
+    Ok(pid)
+}
+
+/// Restarts llama-server with `model_path` (path, alias or any shard of a split model).
+/// `config` carries every launch option (see `LlamaConfig`); omitted fields keep the
+/// defaults. A passed config is remembered for the model; without one, the remembered
+/// one is used. Everything is validated before the running server is killed.
+/// Runs on a blocking thread: killing the old server and the spawn retries can take seconds.
+#[tauri::command]
+async fn switch_model(app: AppHandle, model_path: String, config: Option<LlamaConfig>) -> Result<String, AppError> {
+    let handle = app.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || switch(&handle, model_path, config))
+        .await
+        .map_err(|e| AppError::Other(format!("Model switch task failed: {e}")))
+        .and_then(|r| r);
+    error::report(&app, "switch_model", result)
+}
+
+/// Blocking body of `switch_model`.
+fn switch(app: &AppHandle, model_path: String, config: Option<LlamaConfig>) -> Result<String, AppError> {
+    let state = app.state::<AppState>();
+    let explicit = config.is_some();
+    let config = config.unwrap_or_else(|| remembered_config(&state, &model_path));
+    let (path, model_path) = check_switch(app, &state, model_path, &config)?;
+    restart_llama(app, &state, path, &model_path, &config)?;
+    if explicit {
+        remember_model_config(app, &state, &model_path, &config);
+    }
+    remember_last_model(app, &state, model_path);
+    Ok("server started".to_string())
+}
+
+/// `switch_model`'s checks before anything is stopped: resolves `model_path` and rejects
+/// incompatible models and invalid configs. Memory shortfalls and config warnings only warn.
+fn check_switch(
+    app: &AppHandle,
+    state: &AppState,
+    model_path: String,
+    config: &LlamaConfig,
+) -> Result<(PathBuf, String), AppError> {
+    let (path, model_path) = resolve_model_path(state, model_path)?;
+    // Predictable load failures (too-new GGUF) get a readable error instead of a crash
+    let compat = compat::check(state, &path);
+    if !compat.compatible {
+        return Err(AppError::Incompatible(compat.reason.unwrap_or_default()));
+    }
+    // The estimate is rough, so a shortfall only warns; the load itself decides. With
+    // auto_gpu_layers the split is only known at spawn time, where it is fitted to the VRAM
+    let gpu_layers = memory::gpu_layers(config, &state.settings.lock().unwrap().llama_extra_args);
+    let estimate = (!config.auto_gpu_layers).then(|| memory::estimate(state, &path, config.ctx_size(), gpu_layers));
+    match estimate {
+        Some(Ok(estimate)) if !estimate.warnings.is_empty() => {
+            eprintln!("Memory warning for {model_path}: {}", estimate.warnings.join("; "));
+            let _ = app.emit("model-memory-warning", estimate);
+        }
+        Some(Ok(_)) | None => {}
+        Some(Err(e)) => eprintln!("Memory estimate failed for {model_path}: {e}"),
+    }
+    config.validate(state)?;
+    for warning in config.warnings() {
+        eprintln!("Launch config warning for {model_path}: {warning}");
+    }
+    Ok((path, model_path))
+}
+
+/// The launch config remembered for `model` (path, alias or shard; see
+/// `Settings::model_configs`), or the defaults.
+fn remembered_config(state: &AppState, model: &str) -> LlamaConfig {
+    let Ok((_, key)) = resolve_model_path(state, model.to_string()) else {
+        return LlamaConfig::default();
+    };
+    let settings = state.settings.lock().unwrap();
+    settings.model_configs.get(&key).map_or_else(LlamaConfig::default, |c| c.to_launch())
+}
+
+/// Remembers the per-model part of `config` for the next start of `model_path` without one.
+fn remember_model_config(app: &AppHandle, state: &AppState, model_path: &str, config: &LlamaConfig) {
+    let mut settings = state.settings.lock().unwrap();
+    settings
+        .model_configs
+        .insert(model_path.to_string(), settings::ModelConfig::from_launch(config));
+    if let Err(e) = settings::save(app, &settings) {
+        eprintln!("Failed to remember the config for {model_path}: {e}");
+    }
+}
+
+/// Records `model_path` as `last_model` for the next launch.
+fn remember_last_model(app: &AppHandle, state: &AppState, model_path: String) {
+    let mut settings = state.settings.lock().unwrap();
+    if settings.last_model.as_deref() != Some(&model_path) {
+        settings.last_model = Some(model_path);
+        if let Err(e) = settings::save(app, &settings) {
+            eprintln!("Failed to record last model: {e}");
+        }
+    }
+}
+
+/// `switch_model` that doesn't take no for an answer: waits (up to
+/// `FORCE_SWITCH_WAIT_SECS`) for a switch or cancel already in progress instead of
+/// rejecting, aborts every in-flight chat request, then kills the running or still-loading
+/// server and starts `model_path`, all while holding the `switching` flag so nothing can
+/// interleave. Each step emits `model-force-switch`; the model is checked first, so an
+/// invalid request leaves the current server running.
+#[tauri::command]
+async fn force_switch_model(
+    app: AppHandle,
+    model_path: String,
+    config: Option<LlamaConfig>,
+) -> Result<String, AppError> {
+    let handle = app.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || force_switch(&handle, model_path, config))
+        .await
+        .map_err(|e| AppError::Other(format!("Model switch task failed: {e}")))
+        .and_then(|r| r);
+    error::report(&app, "force_switch_model", result)
+}
+
+/// Blocking body of `force_switch_model`.
+fn force_switch(app: &AppHandle, model_path: String, config: Option<LlamaConfig>) -> Result<String, AppError> {
+    let state = app.state::<AppState>();
+    let explicit = config.is_some();
+    let config = config.unwrap_or_else(|| remembered_config(&state, &model_path));
+    let (path, model_path) = check_switch(app, &state, model_path, &config)?;
+    let event = |phase, pid, was_loading, aborted| {
+        let _ = app.emit(
+            "model-force-switch",
+            ForceSwitchEvent {
+                model: model_path.clone(),
+                phase,
+                pid,
+                was_loading,
+                aborted,
+            },
+        );
+    };
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(FORCE_SWITCH_WAIT_SECS);
+    let mut waited = false;
+    while state.switching.swap(true, Ordering::SeqCst) {
+        if std::time::Instant::now() >= deadline {
+            return Err(AppError::Busy(format!(
+                "Another model switch didn't finish within {FORCE_SWITCH_WAIT_SECS}s"
+            )));
+        }
+        if !waited {
+            event(ForceSwitchPhase::Waiting, None, false, 0);
+            waited = true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    let _switching = SwitchGuard(&state.switching);
+
+    // Queued requests were waiting for this server; they fail rather than run on the next one
+    let aborted = {
+        let generations = state.generations.lock().unwrap();
+        for abort in generations.values() {
+            abort.abort(chat::AbortReason::ModelSwitch);
+        }
+        generations.len()
+    };
+    event(ForceSwitchPhase::Aborting, None, false, aborted);
+
+    let old = {
+        let guard = state.llama.lock().unwrap();
+        guard.as_ref().map(|c| (c.id(), *state.loading.lock().unwrap() == Some(c.id())))
+    };
+    if let Some((pid, was_loading)) = old {
+        event(ForceSwitchPhase::Stopping, Some(pid), was_loading, 0);
+    }
+    let pid = replace_llama(app, &state, path, &model_path, &config)?;
+    event(ForceSwitchPhase::Started, Some(pid), false, 0);
+    if explicit {
+        remember_model_config(app, &state, &model_path, &config);
+    }
+    remember_last_model(app, &state, model_path.clone());
+    Ok("server started".to_string())
+}
+
+/// Restarts the current model with the config it was requested with, so profile and
+/// settings changes made since (e.g. `max_tokens`) apply. An external model is reloaded
+/// from its path as-is; otherwise the model is resolved again like `switch_model` does.
+fn reload_llama(app: &AppHandle, state: &AppState) -> Result<u32, AppError> {
+    let model = state
+        .current_model
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| AppError::ServerNotRunning("No model is loaded".to_string()))?;
+    let config = state.launch_config.lock().unwrap().clone().unwrap_or_default();
+    let external = *state.model_external.lock().unwrap();
+    let (path, model) = if external {
+        (PathBuf::from(&model), model)
+    } else {
+        resolve_model_path(state, model)?
+    };
+    config.validate(state)?;
+    let pid = restart_llama(app, state, path, &model, &config)?;
+    *state.model_external.lock().unwrap() = external;
+    Ok(pid)
+}
+
+/// Re-spawns the loaded model with the latest effective config: the options it was
+/// loaded with, with params re-filled from its current profile and settings. Emits the
+/// usual `model-loading` events; readiness is reported asynchronously as for `switch_model`.
+#[tauri::command]
+fn reload_current_model(app: AppHandle, state: State<AppState>) -> Result<String, AppError> {
+    let result = reload_llama(&app, &state).map(|_| "server restarted".to_string());
+    error::report(&app, "reload_current_model", result)
+}
+
+/// Loads a GGUF picked with a file dialog from anywhere on disk, e.g. to try a download
+/// without copying it into the models folder. The path must be absolute and name a
+/// readable `.gguf`. The model is external: it isn't recorded as `last_model`, so the next
+/// launch starts the usual model again. Uses the default launch options in chat mode.
+#[tauri::command]
+fn load_model_from_path(app: AppHandle, state: State<AppState>, path: String) -> Result<String, AppError> {
+    let result = (|| {
+        let picked = Path::new(&path);
+        if !picked.is_absolute() {
+            return Err(AppError::InvalidInput(format!("Expected an absolute path, got {path}")));
+        }
+        let picked = picked
+            .canonicalize()
+            .map_err(|e| AppError::ModelNotFound(format!("Model file not found: {path} ({e})")))?;
+        let is_gguf = picked
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("gguf"));
+        if !picked.is_file() || !is_gguf {
+            return Err(AppError::InvalidInput(format!("Not a .gguf file: {}", picked.display())));
+        }
+        // Opens the file and checks the magic, so unreadable or mislabeled files fail here
+        gguf::read_version(&picked).map_err(AppError::InvalidInput)?;
+        let picked = match gguf::split_set(&picked) {
+            Some(split) => {
+                if let Some(reason) = missing_shards_reason(&split) {
+                    return Err(AppError::ModelNotFound(format!("{reason} for split model {}", picked.display())));
+                }
+                split.first
+            }
+            None => picked,
+        };
+        let compat = compat::check(&state, &picked);
+        if !compat.compatible {
+            return Err(AppError::Incompatible(compat.reason.unwrap_or_default()));
+        }
+
+        let model_path = picked.to_string_lossy().to_string();
+        let config = remembered_config(&state, &model_path);
+        restart_llama(&app, &state, picked, &model_path, &config)?;
+        *state.model_external.lock().unwrap() = true;
+        Ok("server started".to_string())
+    })();
+    error::report(&app, "load_model_from_path", result)
+}
+
+#[derive(serde::Serialize)]
+struct CurrentModel {
+    path: String,
+    mode: ServerMode,
+    /// Loaded with `load_model_from_path`; not remembered across launches.
+    external: bool,
+    /// Missed the keep-alive's consecutive `/health` pings and hasn't answered since.
+    unresponsive: bool,
+}
+
+/// The model the running llama-server was started with, or `None` when none is running.
+#[tauri::command]
+fn get_current_model(state: State<AppState>) -> Option<CurrentModel> {
+    let path = state.current_model.lock().unwrap().clone()?;
+    let pid = state.llama.lock().unwrap().as_ref().map(|c| c.id());
+    Some(CurrentModel {
+        path,
+        mode: *state.server_mode.lock().unwrap(),
+        external: *state.model_external.lock().unwrap(),
+        unresponsive: pid.is_some() && *state.unresponsive.lock().unwrap() == pid,
+    })
+}
+
+#[derive(serde::Serialize)]
+struct ActiveConfig {
+    #[serde(flatten)]
+    config: LlamaConfig,
+    /// The exact command line it was spawned with, for reproducing a failed load by hand.
+    command_line: Option<String>,
+}
+
+/// The `LlamaConfig` the running llama-server was spawned with, including a `max_tokens`
+/// taken from settings, plus its full command line, for bug reports and for showing the
+/// current settings. `None` when no server is running.
+#[tauri::command]
+fn get_active_config(state: State<AppState>) -> Option<ActiveConfig> {
+    state.llama.lock().unwrap().as_ref()?;
+    let config = state.server_config.lock().unwrap().clone()?;
+    Some(ActiveConfig {
+        config,
+        command_line: state.server_command.lock().unwrap().clone(),
+    })
+}
+
+/// Context window per request of the running llama-server, for bounding the token counter.
+/// Asks `/props` (`n_ctx` of the default slot settings); while that is unavailable (still
+/// loading, older build) falls back to the launch `ctx_size` split across its slots.
+#[tauri::command]
+async fn get_context_limit(state: State<'_, AppState>) -> Result<u32, String> {
+    if state.llama.lock().unwrap().is_none() {
+        return Err("No model is running".to_string());
+    }
+    let fallback = state
+        .server_config
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|c| c.ctx_size() / c.parallel_slots.unwrap_or(1).max(1))
+        .unwrap_or(llama_config::CTX_SIZE);
+    let props = async {
+        let resp = reqwest::Client::new()
+            .get(llama_url(&state, "/props"))
+            .timeout(std::time::Duration::from_secs(2))
+            .send()
+            .await
+            .ok()?;
+        let json: serde_json::Value = resp.error_for_status().ok()?.json().await.ok()?;
+        let n_ctx = json["default_generation_settings"]["n_ctx"].as_u64().or(json["n_ctx"].as_u64())?;
+        u32::try_from(n_ctx).ok().filter(|&n| n > 0)
+    };
+    Ok(props.await.unwrap_or(fallback))
+}
+
+#[derive(serde::Serialize)]
+struct BindAddress {
+    /// The running server's `--host`; the `bind_host` setting when none is running.
+    host: String,
+    port: u16,
+    /// URL for other devices, or `None` when the server only listens on loopback.
+    lan_url: Option<String>,
+    running: bool,
+}
+
+/// This machine's address on the network used for the default route. Connecting a UDP
+/// socket only picks a route; nothing is sent.
+fn lan_ip() -> Option<IpAddr> {
+    let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).ok()?;
+    Some(socket.local_addr().ok()?.ip()).filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+}
+
+/// Address llama-server listens on, with a URL other devices on the network can use
+/// (e.g. for a QR code) when it isn't loopback-only.
+#[tauri::command]
+fn get_bind_address(state: State<AppState>) -> BindAddress {
+    let running = state.llama.lock().unwrap().is_some();
+    let host = if running {
+        *state.llama_host.lock().unwrap()
+    } else {
+        state.settings.lock().unwrap().bind_host()
+    };
+    let lan_host = match host {
+        h if h.is_loopback() => None,
+        h if h.is_unspecified() => lan_ip(),
+        h => Some(h),
+    };
+    BindAddress {
+        host: host.to_string(),
+        port: LLAMA_PORT,
+        lan_url: lan_host.map(|h| format!("http://{}", SocketAddr::new(h, LLAMA_PORT))),
+        running,
+    }
+}
+
+/// What `shutdown_all` stopped.
+#[derive(Clone, serde::Serialize)]
+struct ShutdownSummary {
+    llama: bool,
+    tts: bool,
+    whisper: bool,
+    /// The embedding server of `start_rag_pair`.
+    embed: bool,
+    /// In-flight chat generations that were aborted.
+    generations: usize,
+}
+
+/// Aborts in-flight chat requests, then kills and reaps llama-server (both the chat and the
+/// RAG embedding one), tts-inference and whisper-cli, so no inference process outlives the app. Also run on `RunEvent::Exit`.
+fn shutdown(app: &AppHandle) -> ShutdownSummary {
+    let state = app.state::<AppState>();
+    let generations = {
+        let generations = state.generations.lock().unwrap();
+        generations.values().for_each(|abort| abort.abort(chat::AbortReason::Shutdown));
+        generations.len()
+    };
+    let llama = kill_slot(&state.llama);
+    state.current_model.lock().unwrap().take();
+    let summary = ShutdownSummary {
+        llama,
+        tts: kill_slot(&state.tts),
+        whisper: kill_slot(&state.whisper),
+        embed: rag::stop(&state),
+        generations,
+    };
+    orphans::clear(app);
+    // LogWriter writes straight to the file, so a closing marker is all that's left to do
+    if llama {
+        let structured = state.settings.lock().unwrap().structured_logs;
+        if let Ok(mut log) = LogWriter::open(app, structured) {
+            log.write(&LogLine::new("genhat", None, "--- shutdown ---"));
+        }
+    }
+    summary
+}
+
+/// Stops every subsystem at once (see `shutdown`).
+#[tauri::command]
+fn shutdown_all(app: AppHandle) -> ShutdownSummary {
+    shutdown(&app)
+}
+
+/// Kills a llama-server that is still loading (hasn't answered `/health`), leaving no model
+/// loaded, and emits `model-load-cancelled`. Returns false when nothing is loading,
+/// including a load that finished first. Rejected while a switch is still spawning the
+/// server; `switch_model` returns as soon as it has, so retry then.
+#[tauri::command]
+fn cancel_model_load(app: AppHandle, state: State<AppState>) -> Result<bool, String> {
+    if state.switching.swap(true, Ordering::SeqCst) {
+        return Err("Model switch already in progress; cancel once the server has started".to_string());
+    }
+    let _switching = SwitchGuard(&state.switching);
+    let mut guard = state.llama.lock().unwrap();
+    let mut loading = state.loading.lock().unwrap();
+    let child = match (guard.take(), *loading) {
+        (Some(child), Some(pid)) if child.id() == pid => child,
+        (other, _) => {
+            *guard = other;
+            return Ok(false);
+        }
+    };
+    let pid = child.id();
+    if let Err(child) = kill_and_confirm(child) {
+        *guard = Some(child);
+        return Err(format!("llama-server (pid {pid}) did not exit within {KILL_TIMEOUT_SECS}s"));
+    }
+    *loading = None;
+    drop(loading);
+    let model = state.current_model.lock().unwrap().take();
+    drop(guard);
+    let _ = app.emit("model-load-cancelled", LoadCancelledEvent { model, pid });
+    Ok(true)
+}
+
+#[tauri::command]
+fn stop_llama(state: State<AppState>) {
+    if let Some(mut child) = state.llama.lock().unwrap().take() {
+        let _ = child.kill();
+    }
+    state.current_model.lock().unwrap().take();
+}
+
+// Each option is a separate IPC argument so existing callers keep working.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn generate_speech(
+    app: tauri::AppHandle,
+    model_path: String,
+    input: Option<String>,
+    timeout_secs: Option<u64>,
+    output_path: Option<String>,
+    stream: Option<bool>,
+    normalize: Option<bool>,
+    trim_silence: Option<bool>,
+    markup: Option<bool>,
+    input_file: Option<String>,
+) -> Result<SpeechResult, AppError> {
+    let handle = app.clone();
+    let options = SpeechOptions {
+        stream: stream.unwrap_or(false),
+        markup: markup.unwrap_or(false),
+        post: wav::PostProcess {
+            normalize: normalize.unwrap_or(false),
+            trim_silence: trim_silence.unwrap_or(false),
+        },
+    };
+    // Very long text can come from a file instead of the IPC message
+    let input = match (input, input_file) {
+        (Some(_), Some(_)) => Err(AppError::InvalidInput("Pass either input or input_file, not both".to_string())),
+        (Some(text), None) => Ok(text),
+        (None, Some(path)) => std::fs::read_to_string(&path)
+            .map_err(|e| AppError::InvalidInput(format!("Cannot read input file {path}: {e}"))),
+        (None, None) => Err(AppError::InvalidInput("No input text: pass input or input_file".to_string())),
+    };
+    let result = match input {
+        Ok(input) => speak(app, model_path, input, timeout_secs, output_path, options).await,
+        Err(e) => Err(e),
+    };
+    error::report(&handle, "generate_speech", result)
+}
+
+async fn speak(
+    app: tauri::AppHandle,
+    model_path: String,
+    input: String,
+    timeout_secs: Option<u64>,
+    output_path: Option<String>,
+    options: SpeechOptions,
+) -> Result<SpeechResult, AppError> {
+    let SpeechOptions { stream, markup, post } = options;
+    let job = TtsJob::new(&app, &model_path, timeout_secs)?;
+
+    // Prepare Output Path: the caller's destination ("Save as…"), or a temp file for preview
+    let timestamp = unix_millis();
+    let is_preview = output_path.is_none();
+    let output_file = match output_path {
+        Some(p) => validate_output_path(Path::new(&p)).map_err(AppError::InvalidInput)?,
+        None => std::env::temp_dir().join(format!("genhat_tts_{}.wav", timestamp)),
+    };
+
+    // Identical requests are deterministic; streamed ones want their chunk events
+    let cache_limit = if stream { None } else { tts_cache::limit(&app.state::<AppState>()) };
+    let cache_key = cache_limit
+        .map(|_| tts_cache::cache_key(&[&job.s3_path, &job.vae_path, &job.clip_path], &input, markup, post));
+    if let Some(info) = cache_key.as_deref().and_then(|key| tts_cache::get(&app, key, &output_file)) {
+        return Ok(SpeechResult {
+            path: output_file.to_string_lossy().to_string(),
+            duration_secs: info.duration_secs(),
+            sample_rate: info.sample_rate,
+            channels: info.channels,
+            cached: true,
+        });
+    }
+
+    let state = app.state::<AppState>();
+    let _reserved = reserve_tts(&state.tts_reserved)?;
+    // A preview file that never becomes a result is deleted like the parts
+    let mut preview = TempFiles::default();
+    if is_preview {
+        preview.0.push(output_file.clone());
+    }
+
+    // Markup turns pauses into silence parts between separately synthesized text
+    let mut segments = if markup {
+        markup::parse(&input)
+    } else {
+        vec![markup::Segment::Text(input)]
+    };
+    if stream {
+        // tts-inference can't emit audio incrementally, so stream by sentence chunks: one
+        // run per chunk, each announced as soon as its WAV is ready, then join them.
+        segments = segments
+            .into_iter()
+            .flat_map(|segment| match segment {
+                markup::Segment::Text(text) => split_sentences(&text, TTS_CHUNK_CHARS)
+                    .into_iter()
+                    .map(markup::Segment::Text)
+                    .collect(),
+                pause => vec![pause],
+            })
+            .collect();
+    }
+    // Silence is written in the format of the preceding speech, so leading pauses are dropped
+    let first_text = segments.iter().position(|s| matches!(s, markup::Segment::Text(_)));
+    let segments = segments.split_off(
+        first_text.ok_or_else(|| AppError::InvalidInput("The input has no text to speak".to_string()))?,
+    );
+
+    let info = if let (false, [markup::Segment::Text(text)]) = (stream, segments.as_slice()) {
+        job.run(&app, text, &output_file, None).await?;
+        wav::post_process(&output_file, post)
+            .map_err(|e| AppError::ProcessFailed(format!("Failed to post-process TTS audio: {e}")))?
+    } else {
+        let mut parts = TempFiles::default();
+        let mut last_speech: Option<wav::WavInfo> = None;
+        for (index, segment) in segments.iter().enumerate() {
+            let part = std::env::temp_dir().join(format!("genhat_tts_{}_part{}.wav", timestamp, index));
+            // Tracked before the run, so a half-written part goes too
+            parts.0.push(part.clone());
+            let info = match segment {
+                markup::Segment::Text(text) => {
+                    job.run(&app, text, &part, None).await?;
+                    // Per part, so each streamed part sounds like the joined result
+                    let info = wav::post_process(&part, post)
+                        .map_err(|e| AppError::ProcessFailed(format!("Failed to post-process TTS audio: {e}")))?;
+                    last_speech = Some(info.clone());
+                    info
+                }
+                markup::Segment::Pause(ms) => {
+                    let like = last_speech.as_ref().expect("segments start with text");
+                    wav::write_silence(like, *ms, &part)
+                        .map_err(|e| AppError::ProcessFailed(format!("Failed to write pause: {e}")))?
+                }
+            };
+            if stream {
+                let _ = app.emit(
+                    "tts-chunk",
+                    TtsChunk {
+                        index,
+                        total: segments.len(),
+                        path: part.to_string_lossy().to_string(),
+                        duration_secs: info.duration_secs(),
+                    },
+                );
+            }
+        }
+        let info = wav::concat_wavs(&parts.0, &output_file)
+            .map_err(|e| AppError::ProcessFailed(format!("Failed to join TTS chunks: {e}")))?;
+        // The UI may still be playing streamed parts; markup-only parts were never shown
+        if stream {
+            parts.keep();
+        }
+        info
+    };
+
+    preview.keep();
+    let result = SpeechResult {
+        path: output_file.to_string_lossy().to_string(), // The absolute path to the wav file
+        duration_secs: info.duration_secs(),
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+        cached: false,
+    };
+    if let (Some(key), Some(max_bytes)) = (&cache_key, cache_limit) {
+        if let Err(e) = tts_cache::put(&app, key, &output_file, max_bytes) {
+            eprintln!("Failed to cache speech: {e}");
+        }
+    }
+    if stream {
+        let _ = app.emit("tts-complete", result.clone());
+    }
+    Ok(result)
+}
+
+/// Temp files of a `speak` run, deleted when the guard drops, so a run that fails or is
+/// cancelled part way leaves none behind. `keep` hands them over instead.
+#[derive(Default)]
+struct TempFiles(Vec<PathBuf>);
+
+impl TempFiles {
+    fn keep(mut self) {
+        self.0.clear();
+    }
+}
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// How `speak` builds its audio, beyond the text and output location.
+struct SpeechOptions {
+    /// Synthesize sentence chunks and announce each with `tts-chunk`.
+    stream: bool,
+    /// Interpret pause tags and strip other markup (see `markup::parse`).
+    markup: bool,
+    post: wav::PostProcess,
+}
+
+/// Target length of one streamed TTS chunk, in characters.
+const TTS_CHUNK_CHARS: usize = 250;
+
+/// Longer text is passed to tts-inference in a file: Windows caps a whole command line at
+/// 32767 UTF-16 units, and an overlong one fails to spawn with an unhelpful error.
+const TTS_TEXT_FILE_CHARS: usize = 8000;
+
+#[derive(Clone, serde::Serialize)]
+struct TtsChunk {
+    index: usize,
+    total: usize,
+    path: String,
+    duration_secs: f64,
+}
+
+/// Splits text into chunks of whole sentences of up to `max_chars` characters. A single
+/// sentence longer than that is split at word boundaries.
+fn split_sentences(text: &str, max_chars: usize) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        current.push(ch);
+        // A terminator only ends a sentence before whitespace ("3.5" and "e.g." stay whole)
+        let ends = ch == '\n' || (matches!(ch, '.' | '!' | '?') && chars.peek().map_or(true, |c| c.is_whitespace()));
+        if ends && !current.trim().is_empty() {
+            sentences.push(current.trim().to_string());
+            current.clear();
+        }
+    }
+    if !current.trim().is_empty() {
+        sentences.push(current.trim().to_string());
+    }
+
+    let mut chunks: Vec<String> = Vec::new();
+    let mut chunk = String::new();
+    for sentence in sentences {
+        for word in sentence.split_whitespace() {
+            if !chunk.is_empty() && chunk.chars().count() + 1 + word.chars().count() > max_chars {
+                chunks.push(std::mem::take(&mut chunk));
+            }
+            if !chunk.is_empty() {
+                chunk.push(' ');
+            }
+            chunk.push_str(word);
+        }
+        // Prefer breaking after a sentence once the chunk is reasonably full
+        if chunk.chars().count() >= max_chars / 2 {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Reserves tts-inference for a whole speech job (every part of a `generate_speech`,
+/// every item of a batch), so another job can't start a run between two of its runs and
+/// make the next one fail. Released when the guard drops; a second job gets `Busy`.
+fn reserve_tts(reserved: &AtomicBool) -> Result<SwitchGuard<'_>, AppError> {
+    if reserved.swap(true, Ordering::SeqCst) {
+        return Err(AppError::Busy("TTS busy: another speech generation is already running".to_string()));
+    }
+    Ok(SwitchGuard(reserved))
+}
+
+/// Fixed inputs of a tts-inference run.
+struct TtsJob {
+    exe: PathBuf,
+    s3_path: PathBuf,
+    vae_path: PathBuf,
+    clip_path: PathBuf,
+    timeout_secs: u64,
+}
+
+impl TtsJob {
+    /// Resolves the tts-inference binary and the s3gen model's VAE/CLIP siblings.
+    fn new(app: &AppHandle, model_path: &str, timeout_secs: Option<u64>) -> Result<TtsJob, AppError> {
+        // Resolve Exe
+        let exe = tts_exe(&app.state::<AppState>()).map_err(AppError::SpawnFailed)?;
+
+        // Resolve model files
+        let s3_path = PathBuf::from(model_path);
+        if !s3_path.exists() {
+            return Err(AppError::ModelNotFound(format!("Model path not found: {:?}", s3_path)));
+        }
+        let parent = s3_path.parent().unwrap_or(Path::new(""));
+
+        // Siblings: ve_* (VAE) and t3_* (CLIP), or the names in tts-manifest.json
+        let (vae_path, clip_path) = resolve_tts_siblings(parent).map_err(|missing| {
+            AppError::MissingComponent(format!(
+                "Sibling TTS model(s) not found in {:?}: {}",
+                parent,
+                missing.join(", ")
+            ))
+        })?;
+
+        Ok(TtsJob {
+            exe,
+            s3_path,
+            vae_path,
+            clip_path,
+            timeout_secs: timeout_secs.unwrap_or(TTS_TIMEOUT_SECS),
+        })
+    }
+
+    /// Synthesizes `text` into `output` and returns the validated WAV header. Text longer
+    /// than `TTS_TEXT_FILE_CHARS` goes through a temp file (`--text-file`) instead of the
+    /// command line, falling back to `--text` for bundles built before that flag existed.
+    /// `on_line` sees the process output as it runs.
+    async fn run(
+        &self,
+        app: &AppHandle,
+        text: &str,
+        output: &Path,
+        on_line: Option<LineObserver>,
+    ) -> Result<wav::WavInfo, AppError> {
+        if text.chars().count() > TTS_TEXT_FILE_CHARS {
+            let text_file = std::env::temp_dir().join(format!("genhat_tts_text_{}.txt", unix_millis()));
+            std::fs::write(&text_file, text)
+                .map_err(|e| AppError::Io(format!("Failed to write {}: {}", text_file.display(), e)))?;
+            let result = self
+                .run_with(app, "--text-file", text_file.as_os_str(), output, on_line.clone())
+                .await;
+            let _ = std::fs::remove_file(&text_file);
+            match result {
+                // argparse rejects the unknown flag (and the then-missing --text) before loading models
+                Err(AppError::ProcessFailed(msg))
+                    if msg.contains("unrecognized arguments: --text-file")
+                        || msg.contains("arguments are required: --text") =>
+                {
+                    eprintln!("tts-inference doesn't support --text-file; passing the text on the command line");
+                }
+                other => return other,
+            }
+        }
+        self.run_with(app, "--text", std::ffi::OsStr::new(text), output, on_line).await
+    }
+
+    async fn run_with(
+        &self,
+        app: &AppHandle,
+        text_flag: &str,
+        text: &std::ffi::OsStr,
+        output: &Path,
+        on_line: Option<LineObserver>,
+    ) -> Result<wav::WavInfo, AppError> {
+        // work dir should be the exe's dir so it finds its internal libs (it's a one-dir bundle)
+        let cwd = self.exe.parent().unwrap_or(Path::new("."));
+
+        let mut cmd = Command::new(&self.exe);
+        cmd.current_dir(cwd)
+            .arg(text_flag)
+            .arg(text)
+            .arg("--output")
+            .arg(output)
+            .arg("--model_gguf")
+            .arg(&self.s3_path)
+            .arg("--vae_gguf")
+            .arg(&self.vae_path)
+            .arg("--clip_gguf")
+            .arg(&self.clip_path);
+
+        // Only one tts-inference may run at a time: the onedir bundle shares a working
+        // directory and scratch files, so overlapping runs corrupt each other's output.
+        // Jobs hold `reserve_tts` across their runs; the slot is the per-process backstop.
+        let timeout_secs = self.timeout_secs;
+        let exe = self.exe.clone();
+        let app = app.clone();
+        let output_status = tauri::async_runtime::spawn_blocking(move || {
+            let state = app.state::<AppState>();
+            let timeout = Some(std::time::Duration::from_secs(timeout_secs));
+            let record = |child: &Child| orphans::record(&app, "tts", child.id(), &exe);
+            run_in_slot(&state.tts, cmd, timeout, record, on_line).map_err(|e| match e {
+                SlotError::Busy => AppError::Busy("TTS busy: another speech generation is already running".to_string()),
+                SlotError::Spawn(e) => {
+                    AppError::SpawnFailed(format!("Failed to spawn tts executable '{}': {}", exe.display(), e))
+                }
+                SlotError::Cancelled => AppError::Cancelled("Speech generation cancelled".to_string()),
+                SlotError::Timeout => {
+                    AppError::Timeout(format!("TTS process timed out after {timeout_secs}s and was killed"))
+                }
+                SlotError::Wait(e) => AppError::ProcessFailed(format!("Failed to wait for tts process: {e}")),
+            })
+        })
+        .await
+        .map_err(|e| AppError::Other(format!("TTS task failed: {e}")))??;
+
+        if !output_status.status.success() {
+            return Err(AppError::ProcessFailed(format!(
+                "TTS process failed: {}\nStdout: {}",
+                output_status.stderr, output_status.stdout
+            )));
+        }
+
+        // tts-inference can exit 0 without writing usable audio (e.g. on empty input). Read
+        // the header back so the UI gets duration without decoding, and so a missing, empty
+        // or corrupt file is reported with the process output instead of as a playable path.
+        let info = if output.is_file() {
+            wav::read_wav_info(output).map_err(|e| format!("TTS produced an invalid WAV file: {e}"))
+        } else {
+            Err(format!("TTS process exited successfully but wrote no file at {}", output.display()))
+        };
+        info.map_err(|e| {
+            AppError::InvalidOutput(format!("{e}\nStderr: {}\nStdout: {}", output_status.stderr, output_status.stdout))
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct SpeechItem {
+    text: String,
+    /// Destination file; a temp file when omitted.
+    output_path: Option<String>,
+}
+
+/// What a tts-inference run is doing, from the lines it prints.
+#[derive(Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TtsStage {
+    /// Spawned, nothing printed yet.
+    Starting,
+    /// Converting the GGUF models to the runtime format (first run per model).
+    Preparing,
+    Loading,
+    Generating,
+    /// Inference finished; writing the WAV.
+    Saving,
+}
+
+/// One progress update a tts-inference output line carries.
+enum TtsOutput {
+    Stage(TtsStage),
+    /// A tqdm bar of the current stage, in percent.
+    Percent(u8),
+}
+
+fn parse_tts_output(line: &str) -> Option<TtsOutput> {
+    let line = line.trim();
+    let stage = [
+        ("Preparing models", TtsStage::Preparing),
+        ("Loading model weights", TtsStage::Loading),
+        ("Generating audio", TtsStage::Generating),
+        ("Inference took", TtsStage::Saving),
+    ];
+    if let Some((_, stage)) = stage.iter().find(|(prefix, _)| line.starts_with(prefix)) {
+        return Some(TtsOutput::Stage(*stage));
+    }
+    // tqdm: "Sampling:  45%|████▌     | 450/1000 [00:09<00:11, 48.1it/s]"
+    let (head, _) = line.split_once("%|")?;
+    let digits = head.rsplit(|c: char| !c.is_ascii_digit()).next()?;
+    digits.parse::<u8>().ok().map(|p| TtsOutput::Percent(p.min(100)))
+}
+
+#[derive(Clone, serde::Serialize)]
+struct SpeechBatchProgress {
+    index: usize,
+    total: usize,
+    stage: TtsStage,
+    /// Progress bar of the current stage, when tts-inference draws one.
+    percent: Option<u8>,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct SpeechBatchItemDone {
+    index: usize,
+    total: usize,
+    path: String,
+    duration_secs: f64,
+}
+
+/// Emits `tts-batch-progress` for item `index` whenever its stage or percentage changes.
+fn batch_progress_observer(app: &AppHandle, index: usize, total: usize) -> LineObserver {
+    let emit = {
+        let app = app.clone();
+        move |stage: TtsStage, percent: Option<u8>| {
+            let _ = app.emit("tts-batch-progress", SpeechBatchProgress { index, total, stage, percent });
+        }
+    };
+    emit(TtsStage::Starting, None);
+    let last = Mutex::new((TtsStage::Starting, None));
+    Arc::new(move |line: &str| {
+        let mut last = last.lock().unwrap();
+        let next = match parse_tts_output(line) {
+            Some(TtsOutput::Stage(stage)) => (stage, None),
+            Some(TtsOutput::Percent(percent)) => (last.0, Some(percent)),
+            None => return,
+        };
+        if next != *last {
+            *last = next;
+            emit(next.0, next.1);
+        }
+    })
+}
+
+/// Synthesizes `items` one after another in a single call. While an item renders,
+/// `tts-batch-progress` reports its stage and tqdm percentage; each finished item emits
+/// `tts-batch-item-done` with its path. Returns the output paths in item order.
+/// `stop_speech_batch` ends the batch once the current item is done, and `cancel_speech`
+/// kills the current item; either way the paths finished so far are returned.
+#[tauri::command]
+async fn generate_speech_batch(
+    app: AppHandle,
+    model_path: String,
+    items: Vec<SpeechItem>,
+    timeout_secs: Option<u64>,
+    normalize: Option<bool>,
+    trim_silence: Option<bool>,
+) -> Result<Vec<String>, AppError> {
+    let state = app.state::<AppState>();
+    if state.speech_batch.swap(true, Ordering::SeqCst) {
+        let busy = Err(AppError::Busy("Another speech batch is already running".to_string()));
+        return error::report(&app, "generate_speech_batch", busy);
+    }
+    let _running = SwitchGuard(&state.speech_batch);
+    state.speech_batch_stop.store(false, Ordering::SeqCst);
+
+    let result = async {
+        let job = TtsJob::new(&app, &model_path, timeout_secs)?;
+        let _reserved = reserve_tts(&state.tts_reserved)?;
+        let post = wav::PostProcess {
+            normalize: normalize.unwrap_or(false),
+            trim_silence: trim_silence.unwrap_or(false),
+        };
+        // Check every item up front rather than failing hours into a long batch
+        let timestamp = unix_millis();
+        let outputs = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                if item.text.trim().is_empty() {
+                    return Err(AppError::InvalidInput(format!("Item {i} has no text")));
+                }
+                match &item.output_path {
+                    Some(p) => validate_output_path(Path::new(p)).map_err(AppError::InvalidInput),
+                    None => Ok(std::env::temp_dir().join(format!("genhat_tts_{}_item{}.wav", timestamp, i))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut paths = Vec::new();
+        for (index, (item, output)) in items.iter().zip(&outputs).enumerate() {
+            if state.speech_batch_stop.load(Ordering::SeqCst) {
+                break;
+            }
+            let observer = batch_progress_observer(&app, index, items.len());
+            match job.run(&app, &item.text, output, Some(observer)).await {
+                Ok(_) => {}
+                Err(AppError::Cancelled(_)) => break,
+                Err(e) => return Err(e),
+            }
+            let info = wav::post_process(output, post)
+                .map_err(|e| AppError::ProcessFailed(format!("Failed to post-process TTS audio: {e}")))?;
+            let path = output.to_string_lossy().to_string();
+            let _ = app.emit(
+                "tts-batch-item-done",
+                SpeechBatchItemDone {
+                    index,
+                    total: items.len(),
+                    path: path.clone(),
+                    duration_secs: info.duration_secs(),
+                },
+            );
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+    .await;
+    error::report(&app, "generate_speech_batch", result)
+}
+
+#[tauri::command]
+fn cancel_speech(state: State<AppState>) -> bool {
+    kill_slot(&state.tts)
+}
+
+/// Lets the running `generate_speech_batch` finish its current item, then return. Returns
+/// false when no batch is running.
+#[tauri::command]
+fn stop_speech_batch(state: State<AppState>) -> bool {
+    let running = state.speech_batch.load(Ordering::SeqCst);
+    if running {
+        state.speech_batch_stop.store(true, Ordering::SeqCst);
+    }
+    running
+}
+
+#[tauri::command]
+async fn transcribe_audio(
+    app: tauri::AppHandle,
+    audio_path: String,
+    model_path: Option<String>,
+) -> Result<String, AppError> {
+    let handle = app.clone();
+    let result = transcribe(app, audio_path, model_path).await;
+    error::report(&handle, "transcribe_audio", result)
+}
+
+async fn transcribe(app: tauri::AppHandle, audio_path: String, model_path: Option<String>) -> Result<String, AppError> {
+    let audio = PathBuf::from(&audio_path);
+    if !audio.is_file() {
+        return Err(AppError::InvalidInput(format!("Audio file not found: {}", audio_path)));
+    }
+
+    let model = match model_path {
+        Some(p) => PathBuf::from(p),
+        None => find_whisper_model().ok_or_else(|| {
+            AppError::ModelNotFound(format!("No whisper model (ggml-*.bin) found in {}", get_models_dir().display()))
+        })?,
+    };
+    if !model.is_file() {
+        return Err(AppError::ModelNotFound(format!("Whisper model not found: {}", model.display())));
+    }
+
+    let exe = whisper_exe(&app.state::<AppState>()).map_err(AppError::SpawnFailed)?;
+    let cwd = exe.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+    let mut cmd = Command::new(&exe);
+    cmd.current_dir(&cwd)
+        .arg("-m")
+        .arg(&model)
+        .arg("-f")
+        .arg(&audio)
+        .arg("--no-timestamps")
+        .arg("--no-prints");
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        let record = |child: &Child| orphans::record(&app, "whisper", child.id(), &exe);
+        let SlotOutput { status, stdout, stderr } = run_in_slot(&state.whisper, cmd, None, record, None).map_err(|e| match e {
+            SlotError::Busy => AppError::Busy("A transcription is already running".to_string()),
+            SlotError::Spawn(e) => {
+                AppError::SpawnFailed(format!("Failed to spawn whisper executable '{}': {}", exe.display(), e))
+            }
+            SlotError::Cancelled => AppError::Cancelled("Transcription cancelled".to_string()),
+            SlotError::Timeout => AppError::Timeout("Transcription timed out".to_string()),
+            SlotError::Wait(e) => AppError::ProcessFailed(format!("Failed to wait for whisper process: {e}")),
+        })?;
+
+        if !status.success() {
+            return Err(AppError::ProcessFailed(format!("Whisper process failed: {}\nStdout: {}", stderr, stdout)));
+        }
+
+        let transcript = stdout
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(transcript)
+    })
+    .await
+    .map_err(|e| AppError::Other(format!("Transcription task failed: {e}")))?
+}
+
+#[tauri::command]
+fn cancel_transcription(state: State<AppState>) -> bool {
+    kill_slot(&state.whisper)
+}
+
+/// Lazy start for `autostart: false`: if no llama-server is running, starts the
+/// `startup_model()` pick (with the usual `model-loading` events), then waits until the
+/// server answers `/health`. A no-op when autostart is on. The start holds `switching` like
+/// any switch: it waits (up to `FORCE_SWITCH_WAIT_SECS`) for one in progress and then uses
+/// the server it left, failing with `busy` if it doesn't let go.
+async fn ensure_llama_started(app: &AppHandle) -> Result<(), AppError> {
+    let state = app.state::<AppState>();
+    if state.settings.lock().unwrap().autostart {
+        return Ok(());
+    }
+
+    let handle = app.clone();
+    let (pid, fallback) = tauri::async_runtime::spawn_blocking(move || {
+        let state = handle.state::<AppState>();
+        let running = || state.llama.lock().unwrap().as_ref().map(|c| c.id());
+        if let Some(pid) = running() {
+            // Already running, or still loading from an earlier lazy start
+            return Ok((pid, None));
+        }
+        // A concurrent switch or lazy start usually leaves a server behind that can be used
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(FORCE_SWITCH_WAIT_SECS);
+        while state.switching.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+            if std::time::Instant::now() >= deadline {
+                return Err(AppError::Busy(format!(
+                    "A model switch didn't finish within {FORCE_SWITCH_WAIT_SECS}s"
+                )));
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        let _switching = SwitchGuard(&state.switching);
+        // The switch or start that held the flag may have left one running
+        if let Some(pid) = running() {
+            return Ok((pid, None));
+        }
+        let settings = state.settings.lock().unwrap().clone();
+        let pick = startup_model(&settings)
+            .ok_or_else(|| AppError::ModelNotFound(format!("No model found in {}", get_models_dir().display())))?;
+        let model = pick.path.to_string_lossy().to_string();
+        let mut config = remembered_config(&state, &model);
+        config.mode = ServerMode::Chat;
+        let pid = replace_llama(&handle, &state, pick.path, &model, &config)?;
+        Ok::<_, AppError>((pid, pick.fallback))
+    })
+    .await
+    .map_err(|e| AppError::Other(format!("Model start task failed: {e}")))??;
+
+    wait_until_ready(app, pid).await?;
+    // Only the call that started the server reports its fallback
+    if let Some(fallback) = fallback {
+        let _ = app.emit("fallback-model-loaded", fallback);
+    }
+    Ok(())
+}
+
+/// Waits until the llama-server with `pid` answers the readiness probe, failing early if it
+/// exits. Allows the current model's `ready_timeout`.
+async fn wait_until_ready(app: &AppHandle, pid: u32) -> Result<(), AppError> {
+    let (health_url, timeout) = {
+        let state = app.state::<AppState>();
+        let model = state.current_model.lock().unwrap().clone().unwrap_or_default();
+        (health_url(&state), ready_timeout(&state, Path::new(&model)))
+    };
+    let deadline = std::time::Instant::now() + timeout;
+    while std::time::Instant::now() < deadline {
+        if wait_for_ready(&health_url, std::time::Duration::from_secs(1)).await {
+            return Ok(());
+        }
+        if !llama_still_running(app, pid) {
+            if app.state::<AppState>().llama.lock().unwrap().is_none() {
+                return Err(AppError::Cancelled("Model load was cancelled".to_string()));
+            }
+            let command = app.state::<AppState>().server_command.lock().unwrap().clone().unwrap_or_default();
+            return Err(AppError::SpawnFailed(format!(
+                "llama-server exited while loading the model; command: {command}"
+            )));
+        }
+    }
+    Err(AppError::Timeout(format!(
+        "llama-server did not become ready within {}s",
+        timeout.as_secs()
+    )))
+}
+
+/// Records how auto-start ended and emits `app-ready`. The outcomes known during setup are
+/// emitted before the frontend can listen, so it should also call `get_startup_status`.
+fn finish_startup(app: &AppHandle, status: StartupStatus) {
+    *app.state::<AppState>().startup.lock().unwrap() = Some(status.clone());
+    let _ = app.emit("app-ready", status);
+}
+
+/// How the launch auto-start ended, or `None` while the startup model is still loading
+/// (`app-ready` follows).
+#[tauri::command]
+fn get_startup_status(state: State<AppState>) -> Option<StartupStatus> {
+    state.startup.lock().unwrap().clone()
+}
+
+/// Model to auto-start: the `default_model` setting, then the last-used model, then
+/// `LFM-1.2B-INT8.gguf`, then the first non-TTS GGUF in the models dir. Anything but the
+/// first of these that is configured (or the bundled model) counts as a fallback.
+fn startup_model(settings: &settings::Settings) -> Option<StartupPick> {
+    let dir = get_models_dir();
+    let default_path = dir.join("LFM-1.2B-INT8.gguf");
+    let configured: Vec<PathBuf> = [settings.default_model.as_deref(), settings.last_model.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(|m| PathBuf::from(settings.resolve_model(m)))
+        .collect();
+    let expected = configured.first().unwrap_or(&default_path).clone();
+    let path = configured
+        .into_iter()
+        .find(|p| p.is_file())
+        .or_else(|| default_path.exists().then(|| default_path.clone()))
+        .or_else(|| first_available_model(&dir))?;
+    let fallback = (path != expected).then(|| {
+        let fallback = ModelFallback {
+            requested: expected.to_string_lossy().to_string(),
+            loaded: path.to_string_lossy().to_string(),
+        };
+        println!("{} not found, starting {} instead", fallback.requested, fallback.loaded);
+        fallback
+    });
+    Some(StartupPick { path, fallback })
+}
+
+/// The first GGUF in `dir` that isn't a TTS model.
+fn first_available_model(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir).ok().and_then(|mut entries| {
+        entries.find_map(|e| {
+            e.ok().map(|ent| ent.path()).filter(|p| {
+                let is_gguf = p.extension().map(|s| s == "gguf").unwrap_or(false);
+                let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
+                is_gguf
+                    && !name.starts_with("t3_")
+                    && !name.starts_with("s3gen")
+                    && !name.starts_with("ve_")
+                    && is_visible_model_file(dir, p)
+            })
+        })
+    })
+}
+
+fn main() {
+    tauri::Builder::default()
+        .manage(AppState {
+            llama: Mutex::new(None),
+            current_model: Mutex::new(None),
+            server_mode: Mutex::new(ServerMode::Chat),
+            model_external: Mutex::new(false),
+            whisper: Mutex::new(None),
+            tts: Mutex::new(None),
+            tts_reserved: AtomicBool::new(false),
+            speech_batch: AtomicBool::new(false),
+            speech_batch_stop: AtomicBool::new(false),
+            rag_embed: Mutex::new(None),
+            settings: Mutex::new(settings::Settings::default()),
+            generations: Mutex::new(std::collections::HashMap::new()),
+            generation_defaults: Mutex::new(chat::GenParams::default()),
+            server_config: Mutex::new(None),
+            server_command: Mutex::new(None),
+            launch_config: Mutex::new(None),
+            backends: Mutex::new(None),
+            llama_version: Mutex::new(None),
+            server_features: Mutex::new(None),
+            llama_log: Mutex::new(std::collections::VecDeque::new()),
+            metrics: Mutex::new(std::collections::VecDeque::new()),
+            session: Mutex::new(metrics::SessionTotals::default()),
+            binaries: Mutex::new(BinaryPaths::default()),
+            llama_host: Mutex::new(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            downloads: Mutex::new(std::collections::HashMap::new()),
+            chat_queue: queue::RequestQueue::default(),
+            switching: AtomicBool::new(false),
+            loading: Mutex::new(None),
+            startup: Mutex::new(None),
+            unresponsive: Mutex::new(None),
+            gguf_metadata: Mutex::new(gguf_cache::CacheState::default()),
+            probe_lock: tokio::sync::Mutex::new(()),
+            orphans_lock: Mutex::new(()),
+        })
+        .setup(|app| {
+            let loaded = settings::load(app.handle());
+            let autostart = loaded.autostart;
+            let preferred = startup_model(&loaded);
+            *app.state::<AppState>().settings.lock().unwrap() = loaded;
+            *app.state::<AppState>().gguf_metadata.lock().unwrap() = gguf_cache::load(app.handle());
+            // A crashed session's server would still hold the port
+            let reaped = orphans::reap(app.handle());
+            if reaped > 0 {
+                // Give the OS a moment to release the port
+                std::thread::sleep(std::time::Duration::from_millis(500));
+            }
+            let dir = get_models_dir();
+            let status = |outcome, model: Option<&String>, error: Option<String>| StartupStatus {
+                outcome,
+                model: model.cloned(),
+                error,
+                models_dir: dir.to_string_lossy().to_string(),
+                fallback: None,
+            };
+
+            if !autostart {
+                println!("Auto-start disabled, no model loaded at launch.");
+                finish_startup(app.handle(), status(StartupOutcome::Disabled, None, None));
+            } else if let Some(pick) = preferred {
+                let model = pick.path.to_string_lossy().to_string();
+                let state = app.state::<AppState>();
+                let guard = state.llama.lock().unwrap();
+                let config = remembered_config(&state, &model);
+                let spawned = spawn_llama_process(app.handle(), &state, guard, pick.path, &config);
+                match spawned {
+                    Ok((mut guard, child)) => {
+                        let pid = child.id();
+                        guard.replace(child);
+                        state.current_model.lock().unwrap().replace(model.clone());
+                        let handle = app.handle().clone();
+                        let pending = StartupStatus {
+                            fallback: pick.fallback,
+                            ..status(StartupOutcome::Ready, Some(&model), None)
+                        };
+                        tauri::async_runtime::spawn(async move {
+                            let status = match wait_until_ready(&handle, pid).await {
+                                Ok(()) => {
+                                    if let Some(fallback) = &pending.fallback {
+                                        let _ = handle.emit("fallback-model-loaded", fallback.clone());
+                                    }
+                                    pending
+                                }
+                                Err(e) => StartupStatus {
+                                    outcome: StartupOutcome::Failed,
+                                    error: Some(e.to_string()),
+                                    ..pending
+                                },
+                            };
+                            finish_startup(&handle, status);
+                        });
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to auto-start llama-server: {e}");
+                        finish_startup(app.handle(), status(StartupOutcome::Failed, Some(&model), Some(e.to_string())));
+                    }
+                }
+            } else {
+                println!("No valid LLM models found in {}, server not started automatically.", dir.display());
+                finish_startup(app.handle(), status(StartupOutcome::NoModels, None, None));
+            }
+
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            stop_llama,
+            cancel_model_load,
+            load_model_from_path,
+            get_current_model,
+            get_startup_status,
+            get_active_config,
+            get_context_limit,
+            model_params::get_model_params,
+            model_params::validate_params_file,
+            get_bind_address,
+            download::download_model_resumable,
+            download::cancel_download,
+            disk::get_disk_space,
+            shutdown_all,
+            list_models,
+            pin_model,
+            set_model_alias,
+            set_model_category,
+            get_model_config,
+            set_model_config,
+            clear_model_config,
+            set_llama_extra_args,
+            unpin_model,
+            list_audio_models,
+            reveal_in_file_manager,
+            tts_available,
+            reinitialize_tts,
+            llm_available,
+            switch_model,
+            force_switch_model,
+            reload_current_model,
+            generate_speech,
+            generate_speech_batch,
+            cancel_speech,
+            stop_speech_batch,
+            get_cpu_features,
+            refresh_binary_paths,
+            backends::list_backends,
+            backends::get_llama_version,
+            capabilities::get_capabilities,
+            diagnostics::get_diagnostics,
+            transcribe_audio,
+            cancel_transcription,
+            chat::chat_completion,
+            chat::chat_completion_verbose,
+            chat::chat_completion_raw,
+            chat::continue_generation,
+            queue::get_queue_status,
+            metrics::get_recent_metrics,
+            metrics::get_session_stats,
+            metrics::reset_session_stats,
+            chat::get_param_schema,
+            chat::get_default_params,
+            chat::update_generation_defaults,
+            compat::check_compatibility,
+            memory::estimate_memory,
+            chat::set_system_prompt,
+            chat::get_system_prompt,
+            slot_cache::clear_slot_cache,
+            slot_cache::list_slot_caches,
+            slot_cache::save_slot_cache,
+            slot_cache::load_slot_cache,
+            slot_cache::delete_slot_cache,
+            chat::chat_completion_stream,
+            chat::complete,
+            chat::complete_stream,
+            chat::abort_generation,
+            chat::tokenize,
+            model_test::test_model,
+            model_test::probe_load_time,
+            model_test::measure_ttft,
+            preflight::validate_config,
+            presets::list_presets,
+            presets::apply_preset,
+            rag::rag_embed,
+            rag::rag_query,
+            rag::start_rag_pair,
+            rag::stop_rag_pair,
+            extract::extract_text,
+            rag_index::build_index,
+            rag_index::delete_index,
+            rag_index::list_indexes,
+            rag_index::search_index,
+            rerank::rerank,
+            response_cache::clear_cache,
+            tts_cache::clear_tts_cache,
+            gguf_cache::refresh_model_metadata,
+            llama_log::get_llama_log,
+            llama_log::get_llama_log_path,
+            settings::set_structured_logs,
+            settings::set_default_model,
+            settings::set_autostart,
+            settings::set_max_tokens,
+            settings::set_bind_host,
+            settings::set_remote_endpoint,
+            settings::set_keepalive,
+            settings::set_readiness_check,
+            settings::set_tts_cache,
+            crash_reports::list_crash_reports,
+            conversation::save_conversation,
+            conversation::load_conversation,
+            conversation::list_conversations,
+            conversation::delete_conversation,
+            conversation::export_conversation,
+            backup::export_app_data,
+            backup::import_app_data,
+        ])
+        .build(tauri::generate_context!())
+        .expect("error building tauri app")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                shutdown(app_handle);
+            }
+        });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh, empty folder under the system temp dir.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("genhat-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch(root: &Path, relative: &str) {
+        let path = root.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"GGUF").unwrap();
+    }
+
+    #[test]
+    fn identically_named_ggufs_get_distinct_names() {
+        let root = temp_dir("display-names");
+        for file in ["a/x/s3gen.gguf", "b/x/s3gen.gguf", "c/s3gen.gguf", "d/s3gen_other.gguf", "s3gen.gguf"] {
+            touch(&root, file);
+        }
+        let files = scan_model_files(&root, TTS_SCAN_DEPTH);
+        let names = display_names(&root, &files);
+        let named: Vec<(String, String)> = files
+            .iter()
+            .map(|p| p.strip_prefix(&root).unwrap().to_string_lossy().replace('\\', "/"))
+            .zip(names)
+            .collect();
+        let expected = [
+            ("a/x/s3gen.gguf", "a/x/s3gen.gguf"),
+            ("b/x/s3gen.gguf", "b/x/s3gen.gguf"),
+            ("c/s3gen.gguf", "c/s3gen.gguf"),
+            ("d/s3gen_other.gguf", "s3gen_other.gguf"),
+            ("s3gen.gguf", "s3gen.gguf"),
+        ];
+        for (file, name) in expected {
+            assert!(named.contains(&(file.to_string(), name.to_string())), "{file} in {named:?}");
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn tts_reservation_spans_the_whole_job() {
+        let reserved = AtomicBool::new(false);
+        let job = reserve_tts(&reserved).unwrap();
+        // Between the job's runs the slot is empty; the reservation still keeps others out
+        for _part in 0..3 {
+            assert!(matches!(reserve_tts(&reserved), Err(AppError::Busy(_))));
+        }
+        drop(job);
+        let next = reserve_tts(&reserved);
+        assert!(next.is_ok());
+        drop(next);
+        assert!(!reserved.load(Ordering::SeqCst));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn an_occupied_slot_rejects_a_second_process() {
+        let slot = Mutex::new(Some(Command::new("sleep").arg("10").spawn().unwrap()));
+        let second = run_in_slot(&slot, Command::new("true"), None, |_| {}, None);
+        assert!(matches!(second, Err(SlotError::Busy)));
+        assert!(kill_slot(&slot));
+        let output = run_in_slot(&slot, Command::new("true"), None, |_| {}, None);
+        assert!(output.is_ok_and(|o| o.status.success()));
+        assert!(slot.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn display_names_of_unique_files_are_file_names() {
+        let root = Path::new("/models");
+        let paths = [root.join("qwen/model.gguf"), root.join("llama/other.gguf")];
+        assert_eq!(display_names(root, &paths), vec!["model.gguf", "other.gguf"]);
+    }
+
+    #[test]
+    fn same_named_llm_ggufs_in_different_folders() {
+        let root = temp_dir("model-by-name");
+        for file in ["qwen/model.gguf", "llama/model.gguf", "chat.gguf", "sub/nested.gguf", "tts/s3gen.gguf"] {
+            touch(&root, file);
+        }
+        std::fs::write(root.join("empty.gguf"), b"").unwrap();
+
+        let names: Vec<String> = llm_files(&root).into_iter().map(|(_, name)| name).collect();
+        assert_eq!(names, ["chat.gguf", "llama/model.gguf", "qwen/model.gguf", "nested.gguf"]);
+
+        let path = |file: &str| Some(root.join(file).to_string_lossy().to_string());
+        assert_eq!(model_by_name(&root, "chat.gguf").unwrap(), path("chat.gguf"));
+        assert_eq!(model_by_name(&root, "nested.gguf").unwrap(), path("sub/nested.gguf"));
+        assert_eq!(model_by_name(&root, "qwen/model.gguf").unwrap(), path("qwen/model.gguf"));
+        match model_by_name(&root, "model.gguf") {
+            Err(AppError::InvalidInput(message)) => {
+                assert!(message.contains("llama/model.gguf") && message.contains("qwen/model.gguf"), "{message}");
+            }
+            other => panic!("expected an ambiguous-name error, got {other:?}"),
+        }
+        for name in ["missing.gguf", "s3gen.gguf", "empty.gguf", "../chat.gguf", "sub", ""] {
+            assert_eq!(model_by_name(&root, name).unwrap(), None, "{name}");
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+// Lightweight prosody markup for `generate_speech(markup: true)`. tts-inference has no
+// prosody controls, so pauses become inserted silence and every other tag is dropped.
+//
+// Recognized: `[pause 500ms]` / `[pause 1.5s]` (a bare `[pause]` is 500 ms), SSML `<break time="500ms"/>` and
+// `<break strength="strong"/>`. Other `<...>` / `[...]` tags are stripped (their inner
+// text is kept), and the XML entities `&amp; &lt; &gt; &quot; &apos;` are decoded.
+
+#[derive(Debug, PartialEq)]
+pub enum Segment {
+    Text(String),
+    /// Silence in milliseconds.
+    Pause(u32),
+}
+
+/// Longest pause a single tag may ask for.
+const MAX_PAUSE_MS: u32 = 10_000;
+
+/// Default `<break/>` and `[pause]` length (SSML "medium").
+const DEFAULT_BREAK_MS: u32 = 500;
+
+/// `500ms`, `1s`, `1.5s`, or a bare number of milliseconds.
+fn parse_duration(s: &str) -> Option<u32> {
+    let s = s.trim();
+    let ms = if let Some(v) = s.strip_suffix("ms") {
+        v.trim().parse::<f64>().ok()?
+    } else if let Some(v) = s.strip_suffix('s') {
+        v.trim().parse::<f64>().ok()? * 1000.0
+    } else {
+        s.parse::<f64>().ok()?
+    };
+    (ms.is_finite() && ms >= 0.0).then(|| (ms as u32).min(MAX_PAUSE_MS))
+}
+
+/// Value of `name="..."` (or single-quoted) inside a tag body.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!("{name}="))? + name.len() + 1;
+    let rest = &tag[start..];
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let rest = &rest[1..];
+    Some(&rest[..rest.find(quote)?])
+}
+
+/// Pause requested by a tag body (without its brackets), if it is a pause tag.
+fn pause_tag(tag: &str) -> Option<u32> {
+    let tag = tag.trim().trim_end_matches('/').trim();
+    let (name, rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+    match name.to_ascii_lowercase().as_str() {
+        "pause" if rest.is_empty() => Some(DEFAULT_BREAK_MS),
+        "pause" => parse_duration(rest),
+        "break" => {
+            if let Some(time) = attribute(rest, "time") {
+                return parse_duration(time);
+            }
+            Some(match attribute(rest, "strength") {
+                Some("none") => 0,
+                Some("x-weak") => 100,
+                Some("weak") => 250,
+                Some("strong") => 750,
+                Some("x-strong") => 1000,
+                _ => DEFAULT_BREAK_MS,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Splits marked-up input into text to speak and pauses. Whitespace inside text is
+/// collapsed, whitespace-only text is dropped and adjacent pauses are merged. An
+/// unterminated `<` or `[` is kept as literal text.
+pub fn parse(input: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut rest = input;
+
+    let flush = |text: &mut String, segments: &mut Vec<Segment>| {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let decoded = decode_entities(&words.join(" "));
+        if !decoded.is_empty() {
+            segments.push(Segment::Text(decoded));
+        }
+        text.clear();
+    };
+
+    while let Some(open) = rest.find(['<', '[']) {
+        let close = if rest.as_bytes()[open] == b'<' { '>' } else { ']' };
+        let Some(len) = rest[open + 1..].find(close) else {
+            // Literal bracket; later tags still count
+            text.push_str(&rest[..=open]);
+            rest = &rest[open + 1..];
+            continue;
+        };
+        text.push_str(&rest[..open]);
+        let tag = &rest[open + 1..open + 1 + len];
+        rest = &rest[open + len + 2..];
+
+        match pause_tag(tag) {
+            Some(ms) => {
+                flush(&mut text, &mut segments);
+                match segments.last_mut() {
+                    Some(Segment::Pause(prev)) => *prev = (*prev + ms).min(MAX_PAUSE_MS),
+                    _ => segments.push(Segment::Pause(ms)),
+                }
+            }
+            // Unknown tags are dropped; a space keeps words on either side apart
+            None => text.push(' '),
+        }
+    }
+    text.push_str(rest);
+    flush(&mut text, &mut segments);
+    segments.retain(|s| *s != Segment::Pause(0));
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Segment {
+        Segment::Text(s.to_string())
+    }
+
+    #[test]
+    fn pause_tags_become_pauses() {
+        assert_eq!(parse("Hi [pause 300ms] there"), [text("Hi"), Segment::Pause(300), text("there")]);
+        assert_eq!(parse("a [pause 1.5s] b"), [text("a"), Segment::Pause(1500), text("b")]);
+        assert_eq!(parse("a [PAUSE] b"), [text("a"), Segment::Pause(DEFAULT_BREAK_MS), text("b")]);
+        assert_eq!(parse("a [pause 1 minute] b"), [text("a b")]);
+        assert_eq!(parse("[pause 99s]"), [Segment::Pause(MAX_PAUSE_MS)]);
+    }
+
+    #[test]
+    fn ssml_breaks_become_pauses() {
+        assert_eq!(parse(r#"a<break time="250ms"/>b"#), [text("a"), Segment::Pause(250), text("b")]);
+        assert_eq!(parse("a <break time='2s'> b"), [text("a"), Segment::Pause(2000), text("b")]);
+        assert_eq!(parse(r#"a <break strength="strong" /> b"#), [text("a"), Segment::Pause(750), text("b")]);
+        assert_eq!(parse("a <break/> b"), [text("a"), Segment::Pause(DEFAULT_BREAK_MS), text("b")]);
+        assert_eq!(parse(r#"a <break strength="none"/> b"#), [text("a"), text("b")]);
+    }
+
+    #[test]
+    fn adjacent_pauses_merge() {
+        assert_eq!(parse("a [pause 200ms] [pause 300ms] b"), [text("a"), Segment::Pause(500), text("b")]);
+        assert_eq!(parse("[pause 8s][pause 8s]"), [Segment::Pause(MAX_PAUSE_MS)]);
+    }
+
+    #[test]
+    fn unknown_tags_are_dropped_but_their_text_is_kept() {
+        assert_eq!(parse("<speak>Hello <emphasis>big</emphasis>world</speak>"), [text("Hello big world")]);
+        assert_eq!(parse("one [laughs] two"), [text("one two")]);
+        assert_eq!(parse("  spread \n out  "), [text("spread out")]);
+        assert!(parse("<speak> </speak>").is_empty());
+    }
+
+    #[test]
+    fn entities_are_decoded_once() {
+        assert_eq!(parse("a &lt;b&gt; &amp; &quot;c&quot; &apos;d&apos;"), [text("a <b> & \"c\" 'd'")]);
+        assert_eq!(parse("&amp;lt;"), [text("&lt;")]);
+    }
+
+    #[test]
+    fn unterminated_brackets_stay_literal() {
+        assert_eq!(parse("1 < 2"), [text("1 < 2")]);
+        assert_eq!(parse("see [1"), [text("see [1")]);
+        assert_eq!(parse("1 < 2 [pause 100ms] ok"), [text("1 < 2"), Segment::Pause(100), text("ok")]);
+        assert_eq!(parse("é [ ü <"), [text("é [ ü <")]);
+    }
+}
@@ -0,0 +1,289 @@
+// Pre-load memory estimate: model weights (the GGUF files' size) plus the KV cache for
+// the chosen context, computed from the header's attention metadata and split between
+// RAM and VRAM by the number of offloaded layers. Compute buffers vary by backend and
+// batch size, so they're a flat allowance and every figure is approximate.
+
+use std::path::Path;
+use std::process::Command;
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::{self, AppError};
+use crate::llama_config::{LlamaConfig, CTX_SIZE};
+use crate::{backends, gguf, gguf_cache, resolve_model_path, AppState};
+
+/// Rough allowance for llama.cpp's compute/scratch buffers, charged to the device that
+/// runs the graph (the GPU once any layer is offloaded).
+const COMPUTE_BUFFER_BYTES: u64 = 512 << 20;
+
+/// VRAM `fit_gpu_layers` leaves free: this much, or a tenth of the free VRAM if more, for
+/// the display, other programs and the estimate's error.
+const AUTO_GPU_MIN_HEADROOM_BYTES: u64 = 512 << 20;
+
+/// llama-server's default KV cache type is f16.
+const KV_BYTES_PER_ELEMENT: u64 = 2;
+
+const NOTE: &str = "Approximate: weights are the file size, the KV cache assumes an f16 cache, and compute buffers are a flat 512 MiB allowance";
+
+#[derive(Clone, serde::Serialize)]
+pub struct MemoryEstimate {
+    pub path: String,
+    pub ctx_size: u32,
+    pub gpu_layers: u32,
+    /// `<arch>.block_count`; `None` if the header doesn't say.
+    pub layer_count: Option<u64>,
+    /// Model weights (all shards of a split model).
+    pub weights_bytes: u64,
+    /// KV cache at `ctx_size`; `None` when the header lacks attention metadata.
+    pub kv_cache_bytes: Option<u64>,
+    pub estimated_ram_bytes: u64,
+    pub estimated_vram_bytes: u64,
+    /// Memory the OS reports as available, when it can be read.
+    pub available_ram_bytes: Option<u64>,
+    /// Free VRAM summed over the GPUs `list_backends` reports; only probed when offloading.
+    pub free_vram_bytes: Option<u64>,
+    /// Estimates exceeding what's available, for a pre-load warning. A missing KV figure
+    /// isn't a warning; see `kv_cache_bytes`.
+    pub warnings: Vec<String>,
+    pub note: &'static str,
+}
+
+/// Per-token KV cache size: K and V for every layer and KV head.
+fn kv_bytes_per_token(metadata: &gguf::GgufMetadata, layers: u64) -> Option<u64> {
+    let arch = metadata.architecture()?;
+    let get = |key: &str| metadata.get_uint(&format!("{arch}.{key}"));
+    let heads = get("attention.head_count").filter(|&h| h > 0)?;
+    // Per-layer head counts are stored as arrays, which the reader skips; assume no GQA then
+    let kv_heads = get("attention.head_count_kv").unwrap_or(heads);
+    let head_dim = get("embedding_length")? / heads;
+    let key_len = get("attention.key_length").unwrap_or(head_dim);
+    let value_len = get("attention.value_length").unwrap_or(head_dim);
+    Some(layers * kv_heads * (key_len + value_len) * KV_BYTES_PER_ELEMENT)
+}
+
+/// `MemAvailable` on Linux, free + inactive pages on macOS, free physical memory on Windows.
+fn available_ram_bytes() -> Option<u64> {
+    if cfg!(target_os = "linux") {
+        let info = std::fs::read_to_string("/proc/meminfo").ok()?;
+        let line = info.lines().find(|l| l.starts_with("MemAvailable:"))?;
+        let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    } else if cfg!(target_os = "macos") {
+        let out = Command::new("vm_stat").output().ok()?;
+        let text = String::from_utf8_lossy(&out.stdout);
+        // "Mach Virtual Memory Statistics: (page size of 16384 bytes)"
+        let page_size: u64 = text.split("page size of ").nth(1)?.split_whitespace().next()?.parse().ok()?;
+        let pages = |label: &str| -> u64 {
+            text.lines()
+                .find_map(|l| l.strip_prefix(label))
+                .and_then(|v| v.trim().trim_end_matches('.').parse().ok())
+                .unwrap_or(0)
+        };
+        Some((pages("Pages free:") + pages("Pages inactive:") + pages("Pages speculative:")) * page_size)
+    } else if cfg!(windows) {
+        let out = Command::new("powershell")
+            .args(["-NoProfile", "-Command", "(Get-CimInstance Win32_OperatingSystem).FreePhysicalMemory"])
+            .output()
+            .ok()?;
+        let kb: u64 = String::from_utf8_lossy(&out.stdout).trim().parse().ok()?;
+        Some(kb * 1024)
+    } else {
+        None
+    }
+}
+
+/// Peak resident set of process `pid`: `VmHWM` on Linux, `PeakWorkingSet64` on Windows.
+/// macOS has no per-process peak outside the process itself, so the current RSS is used.
+pub(crate) fn peak_rss_bytes(pid: u32) -> Option<u64> {
+    if cfg!(target_os = "linux") {
+        let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+        let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+        let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    } else if cfg!(target_os = "macos") {
+        let out = Command::new("ps").args(["-o", "rss=", "-p", &pid.to_string()]).output().ok()?;
+        let kb: u64 = String::from_utf8_lossy(&out.stdout).trim().parse().ok()?;
+        Some(kb * 1024)
+    } else if cfg!(windows) {
+        let out = Command::new("powershell")
+            .args(["-NoProfile", "-Command", &format!("(Get-Process -Id {pid}).PeakWorkingSet64")])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&out.stdout).trim().parse().ok()
+    } else {
+        None
+    }
+}
+
+pub(crate) fn gib(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / (1u64 << 30) as f64)
+}
+
+/// Blocking: reads the header, may query free memory and probe `--list-devices` (cached).
+pub(crate) fn estimate(state: &AppState, path: &Path, ctx_size: u32, gpu_layers: u32) -> Result<MemoryEstimate, String> {
+    let files = gguf::split_set(path).map(|s| s.shards).unwrap_or_else(|| vec![path.to_path_buf()]);
+    let weights_bytes = files
+        .iter()
+        .map(|f| std::fs::metadata(f).map(|m| m.len()).map_err(|e| format!("Failed to read {}: {}", f.display(), e)))
+        .sum::<Result<u64, String>>()?;
+
+    let metadata = gguf_cache::read_metadata(state, path)?;
+    let layer_count = metadata
+        .architecture()
+        .and_then(|arch| metadata.get_uint(&format!("{arch}.block_count")));
+    let kv_cache_bytes = layer_count
+        .and_then(|layers| kv_bytes_per_token(&metadata, layers))
+        .map(|per_token| per_token * ctx_size as u64);
+
+    // Share of repeating layers on the GPU; weights and KV cache follow the layers
+    let offloaded = match layer_count {
+        Some(layers) if layers > 0 => (gpu_layers as u64).min(layers) as f64 / layers as f64,
+        _ if gpu_layers > 0 => 1.0,
+        _ => 0.0,
+    };
+    let total = weights_bytes + kv_cache_bytes.unwrap_or(0);
+    let on_gpu = (total as f64 * offloaded) as u64;
+    let (compute_ram, compute_vram) = if gpu_layers > 0 { (0, COMPUTE_BUFFER_BYTES) } else { (COMPUTE_BUFFER_BYTES, 0) };
+    let estimated_ram_bytes = total - on_gpu + compute_ram;
+    let estimated_vram_bytes = on_gpu + compute_vram;
+
+    let available_ram_bytes = available_ram_bytes();
+    let free_vram_bytes = if gpu_layers > 0 {
+        backends::gpu_devices(state)
+            .ok()
+            .and_then(|devices| devices.iter().map(|d| d.free_vram_mb).sum::<Option<u64>>())
+            .map(|mb| mb << 20)
+    } else {
+        None
+    };
+
+    let mut warnings = Vec::new();
+    if let Some(available) = available_ram_bytes.filter(|&a| estimated_ram_bytes > a) {
+        warnings.push(format!(
+            "Needs about {} of RAM but only {} is available; expect heavy swapping or a failed load",
+            gib(estimated_ram_bytes),
+            gib(available)
+        ));
+    }
+    if let Some(free) = free_vram_bytes.filter(|&f| estimated_vram_bytes > f) {
+        warnings.push(format!(
+            "Needs about {} of VRAM but only {} is free; offload fewer layers",
+            gib(estimated_vram_bytes),
+            gib(free)
+        ));
+    }
+
+    Ok(MemoryEstimate {
+        path: path.to_string_lossy().to_string(),
+        ctx_size,
+        gpu_layers,
+        layer_count,
+        weights_bytes,
+        kv_cache_bytes,
+        estimated_ram_bytes,
+        estimated_vram_bytes,
+        available_ram_bytes,
+        free_vram_bytes,
+        warnings,
+        note: NOTE,
+    })
+}
+
+/// The layer count `auto_gpu_layers` picked, and what it was based on.
+#[derive(Clone, serde::Serialize)]
+pub struct GpuLayerFit {
+    pub gpu_layers: u32,
+    /// `<arch>.block_count`.
+    pub layer_count: u64,
+    /// Free VRAM summed over the GPUs, probed just before the launch; `None` without a GPU.
+    pub free_vram_bytes: Option<u64>,
+    /// Estimated VRAM per offloaded layer (its share of the weights and KV cache).
+    pub layer_bytes: u64,
+    pub headroom_bytes: u64,
+}
+
+/// Most layers of `path` that fit in the free VRAM with `ctx_size` tokens of context,
+/// leaving headroom. The memory estimate with every layer offloaded gives the per-layer
+/// cost; when all fit, the output layer is offloaded too (`block_count + 1`). Blocking:
+/// re-probes `--list-devices`, so call it once the previous server has exited.
+pub(crate) fn fit_gpu_layers(state: &AppState, path: &Path, ctx_size: u32) -> Result<GpuLayerFit, String> {
+    let free_vram_bytes = backends::probe_gpu_devices(state)?
+        .iter()
+        .map(|d| d.free_vram_mb.map(|mb| mb << 20))
+        .sum::<Option<u64>>()
+        .filter(|&free| free > 0);
+    let full = estimate(state, path, ctx_size, u32::MAX)?;
+    let layer_count = full
+        .layer_count
+        .filter(|&l| l > 0)
+        .ok_or("The model header has no layer count")?;
+    let layer_bytes = (full.estimated_vram_bytes - COMPUTE_BUFFER_BYTES).div_ceil(layer_count);
+    let headroom_bytes = free_vram_bytes
+        .map_or(AUTO_GPU_MIN_HEADROOM_BYTES, |free| (free / 10).max(AUTO_GPU_MIN_HEADROOM_BYTES));
+    let budget = free_vram_bytes
+        .unwrap_or(0)
+        .saturating_sub(headroom_bytes + COMPUTE_BUFFER_BYTES);
+    let fits = (budget / layer_bytes.max(1)).min(layer_count);
+    let gpu_layers = if fits == layer_count { layer_count + 1 } else { fits };
+    Ok(GpuLayerFit {
+        gpu_layers: u32::try_from(gpu_layers).unwrap_or(u32::MAX),
+        layer_count,
+        free_vram_bytes,
+        layer_bytes,
+        headroom_bytes,
+    })
+}
+
+/// GPU layer count passed through `llama_extra_args` (`-ngl N`, `--n-gpu-layers N`,
+/// `--gpu-layers N`, or `--flag=N`).
+pub(crate) fn gpu_layers_from_args(args: &[String]) -> Option<u32> {
+    const FLAGS: [&str; 3] = ["-ngl", "--n-gpu-layers", "--gpu-layers"];
+    let mut value = None;
+    for (i, arg) in args.iter().enumerate() {
+        if FLAGS.contains(&arg.as_str()) {
+            value = args.get(i + 1).map(String::as_str);
+        } else if let Some((flag, v)) = arg.split_once('=') {
+            if FLAGS.contains(&flag) {
+                value = Some(v);
+            }
+        }
+    }
+    // llama.cpp treats out-of-range values (e.g. 999) as "all layers"
+    value.and_then(|v| v.parse().ok())
+}
+
+/// Layers a server started with `config` offloads: extra args come last on the command
+/// line, so their `-ngl` wins over `config.gpu_layers`; 0 when neither sets one.
+pub(crate) fn gpu_layers(config: &LlamaConfig, extra_args: &[String]) -> u32 {
+    gpu_layers_from_args(extra_args)
+        .or(config.gpu_layers.and_then(|l| u32::try_from(l).ok()))
+        .unwrap_or(0)
+}
+
+/// Estimates RAM and VRAM needed to load `path` (path, alias or split shard) with
+/// `ctx_size` tokens of context (default `CTX_SIZE`) and `gpu_layers` layers offloaded
+/// (default: the `-ngl` in `llama_extra_args`, else 0).
+#[tauri::command]
+pub async fn estimate_memory(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    ctx_size: Option<u32>,
+    gpu_layers: Option<u32>,
+) -> Result<MemoryEstimate, AppError> {
+    let result = async {
+        let (path, _) = resolve_model_path(&state, path)?;
+        let gpu_layers = gpu_layers
+            .unwrap_or_else(|| gpu_layers_from_args(&state.settings.lock().unwrap().llama_extra_args).unwrap_or(0));
+        let ctx_size = ctx_size.unwrap_or(CTX_SIZE);
+        if ctx_size == 0 {
+            return Err(AppError::InvalidInput("ctx_size must be at least 1".to_string()));
+        }
+        let handle = app.clone();
+        tauri::async_runtime::spawn_blocking(move || estimate(&handle.state::<AppState>(), &path, ctx_size, gpu_layers))
+            .await
+            .map_err(|e| AppError::Other(format!("Memory estimate failed: {e}")))?
+            .map_err(AppError::Io)
+    }
+    .await;
+    error::report(&app, "estimate_memory", result)
+}
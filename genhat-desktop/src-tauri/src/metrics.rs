@@ -0,0 +1,152 @@
+// Per-request timing metrics. llama-server reports a `timings` object with every
+// completion (the final chunk when streaming); the last `HISTORY_LEN` of them are kept in
+// memory for `get_recent_metrics`. Token counts are also summed into per-session totals
+// for `get_session_stats`; a session starts at launch, on a model switch or on
+// `reset_session_stats`.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::State;
+
+use crate::{unix_millis, AppState};
+
+/// Requests kept in the rolling history.
+const HISTORY_LEN: usize = 100;
+
+/// Most recent request metrics, oldest first, capped at `HISTORY_LEN`.
+pub type MetricsHistory = Mutex<VecDeque<RequestMetrics>>;
+
+/// Running totals since the session started (see `reset`).
+pub type Session = Mutex<SessionTotals>;
+
+pub struct SessionTotals {
+    started: Instant,
+    /// Epoch milliseconds of `started`.
+    started_at: u64,
+    requests: u64,
+    prompt_tokens: u64,
+    generated_tokens: u64,
+}
+
+impl Default for SessionTotals {
+    fn default() -> Self {
+        SessionTotals {
+            started: Instant::now(),
+            started_at: unix_millis(),
+            requests: 0,
+            prompt_tokens: 0,
+            generated_tokens: 0,
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct SessionStats {
+    /// Epoch milliseconds when the session started.
+    pub since: u64,
+    pub uptime_secs: u64,
+    /// Local requests that reported timings (cached, aborted and remote ones don't).
+    pub requests: u64,
+    /// Prompt tokens evaluated; tokens reused from the slot cache aren't counted.
+    pub prompt_tokens: u64,
+    pub generated_tokens: u64,
+}
+
+/// llama-server's `timings` object. Missing fields (older builds) default to 0.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Timings {
+    /// Prompt tokens evaluated (excludes tokens reused from the slot's cache).
+    pub prompt_n: u64,
+    pub prompt_ms: f64,
+    pub prompt_per_second: f64,
+    /// Tokens generated.
+    pub predicted_n: u64,
+    pub predicted_ms: f64,
+    pub predicted_per_second: f64,
+}
+
+impl Timings {
+    /// Parses the `timings` field of a completion response or stream chunk.
+    pub fn from_response(json: &serde_json::Value) -> Option<Timings> {
+        json.get("timings").and_then(|t| serde_json::from_value(t.clone()).ok())
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct RequestMetrics {
+    /// Epoch milliseconds when the response completed.
+    pub timestamp: u64,
+    /// Model path the server was running, if known.
+    pub model: Option<String>,
+    pub stream: bool,
+    /// `-b` / `-ub` the server was launched with, for comparing prompt throughput across
+    /// settings; `None` = llama-server's default (2048 / 512).
+    pub batch_size: Option<u32>,
+    pub ubatch_size: Option<u32>,
+    pub timings: Timings,
+}
+
+/// Starts a new session: zeroes the totals and the uptime.
+pub fn reset(state: &AppState) {
+    *state.session.lock().unwrap() = SessionTotals::default();
+}
+
+/// Appends a finished request to the history and adds it to the session totals.
+pub fn record(state: &AppState, stream: bool, timings: &Timings) {
+    let model = state.current_model.lock().unwrap().clone();
+    let (batch_size, ubatch_size) = state
+        .server_config
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or((None, None), |c| (c.batch_size, c.ubatch_size));
+    {
+        let mut session = state.session.lock().unwrap();
+        session.requests += 1;
+        session.prompt_tokens += timings.prompt_n;
+        session.generated_tokens += timings.predicted_n;
+    }
+    let mut history = state.metrics.lock().unwrap();
+    if history.len() == HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(RequestMetrics {
+        timestamp: unix_millis(),
+        model,
+        stream,
+        batch_size,
+        ubatch_size,
+        timings: timings.clone(),
+    });
+}
+
+/// Timings of the most recent chat requests (up to `limit`, default all kept), oldest
+/// first. Cached and aborted responses have no timings and aren't listed.
+#[tauri::command]
+pub fn get_recent_metrics(state: State<AppState>, limit: Option<usize>) -> Vec<RequestMetrics> {
+    let history = state.metrics.lock().unwrap();
+    let skip = history.len().saturating_sub(limit.unwrap_or(HISTORY_LEN));
+    history.iter().skip(skip).cloned().collect()
+}
+
+/// Token totals of this session (since launch, the last model switch or
+/// `reset_session_stats`) across chat and raw completions, with its uptime.
+#[tauri::command]
+pub fn get_session_stats(state: State<AppState>) -> SessionStats {
+    let session = state.session.lock().unwrap();
+    SessionStats {
+        since: session.started_at,
+        uptime_secs: session.started.elapsed().as_secs(),
+        requests: session.requests,
+        prompt_tokens: session.prompt_tokens,
+        generated_tokens: session.generated_tokens,
+    }
+}
+
+/// Zeroes the session totals and restarts the uptime.
+#[tauri::command]
+pub fn reset_session_stats(state: State<AppState>) {
+    reset(&state);
+}
@@ -0,0 +1,179 @@
+// Per-model sampling profiles: a `GenParams` JSON file next to the model
+// (`<model>.params.json`, e.g. `qwen-7b.params.json` for `qwen-7b.gguf`). llama-server is
+// started with the caller's params, then the profile, then the settings (`max_tokens`),
+// then the built-in defaults; `get_model_params` reports where each value came from.
+// Loading ignores unknown keys; `validate_params_file` reports them (and every other
+// mistake) with a line number for hand-edited files.
+
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+
+use crate::chat::{GenParams, PARAM_DEFS};
+use crate::error::{self, AppError};
+use crate::{resolve_model_path, AppState};
+
+#[derive(Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamSource {
+    /// `PARAM_DEFS` default (or unset / off when it has none).
+    Default,
+    ModelProfile,
+    Settings,
+}
+
+#[derive(serde::Serialize)]
+pub struct ResolvedParam {
+    pub name: &'static str,
+    /// `None` when the sampler is off (no default and nothing sets it).
+    pub value: Option<serde_json::Value>,
+    pub source: ParamSource,
+}
+
+#[derive(serde::Serialize)]
+pub struct ResolvedParams {
+    pub path: String,
+    pub profile_path: String,
+    pub profile_exists: bool,
+    /// In `PARAM_DEFS` order.
+    pub params: Vec<ResolvedParam>,
+}
+
+pub fn profile_path(model: &Path) -> PathBuf {
+    model.with_extension("params.json")
+}
+
+/// The model's profile; `Ok(None)` when it has none.
+pub fn load_profile(model: &Path) -> Result<Option<GenParams>, String> {
+    let path = profile_path(model);
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let params: GenParams = serde_json::from_str(&json).map_err(|e| format!("{} is not valid: {}", path.display(), e))?;
+    params
+        .validate()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(Some(params))
+}
+
+/// 1-based line of the first `"key"` in `json`, as " (line N)" for error messages.
+fn at_line(json: &str, key: &str) -> String {
+    let quoted = format!("\"{key}\"");
+    json.find(&quoted)
+        .map(|pos| format!(" (line {})", json[..pos].lines().count().max(1)))
+        .unwrap_or_default()
+}
+
+/// Parses a profile strictly: unlike `load_profile`, keys that aren't parameters are errors.
+fn parse_strict(path: &Path) -> Result<GenParams, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    // serde_json's messages already end in "at line X column Y"
+    let value: serde_json::Value =
+        serde_json::from_str(&json).map_err(|e| format!("{} is not valid JSON: {}", path.display(), e))?;
+    let object = value
+        .as_object()
+        .ok_or_else(|| format!("{} must contain a JSON object of parameters", path.display()))?;
+    if let Some(key) = object.keys().find(|k| !PARAM_DEFS.iter().any(|d| d.name == k.as_str())) {
+        let known: Vec<&str> = PARAM_DEFS.iter().map(|d| d.name).collect();
+        return Err(format!(
+            "{}: unknown parameter '{key}'{} (expected one of {})",
+            path.display(),
+            at_line(&json, key),
+            known.join(", ")
+        ));
+    }
+    let params: GenParams = serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))?;
+    params.validate().map_err(|e| {
+        // Range errors start with the field name
+        let field = e.split_whitespace().next().unwrap_or_default();
+        format!("{}: {}{}", path.display(), e, at_line(&json, field))
+    })?;
+    Ok(params)
+}
+
+/// Settings that act as launch params.
+fn settings_params(state: &AppState) -> GenParams {
+    GenParams {
+        max_tokens: state.settings.lock().unwrap().max_tokens,
+        ..GenParams::default()
+    }
+}
+
+/// Launch params for `model` (before built-in defaults, which `LlamaConfig::to_args` fills
+/// in): `caller` fields win, then the model profile, then the settings. A broken profile
+/// is skipped with a warning rather than blocking the load.
+pub fn launch_params(state: &AppState, model: &Path, caller: GenParams) -> GenParams {
+    let profile = load_profile(model).unwrap_or_else(|e| {
+        eprintln!("Ignoring model params profile: {e}");
+        None
+    });
+    caller
+        .or_defaults(&profile.unwrap_or_default())
+        .or_defaults(&settings_params(state))
+}
+
+/// Fields set in `params`, by name.
+fn set_fields(params: &GenParams) -> serde_json::Map<String, serde_json::Value> {
+    match serde_json::to_value(params) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    }
+}
+
+/// Every sampling parameter llama-server would start with for `path` (path, alias or
+/// shard) when `switch_model` sets none, with its source, for "inherited" / "overridden"
+/// badges while editing the model's profile.
+#[tauri::command]
+pub fn get_model_params(app: AppHandle, state: State<AppState>, path: String) -> Result<ResolvedParams, AppError> {
+    let result = resolve(&state, path);
+    error::report(&app, "get_model_params", result)
+}
+
+fn resolve(state: &AppState, path: String) -> Result<ResolvedParams, AppError> {
+    let (model, _) = resolve_model_path(state, path)?;
+    let profile = load_profile(&model).map_err(AppError::InvalidInput)?;
+    Ok(resolve_with(state, &model, &profile_path(&model), profile))
+}
+
+/// Checks a hand-edited profile at `path` (it needn't sit next to a model yet): rejects bad
+/// JSON, unknown keys, wrong types and out-of-range values, naming the line. When it is
+/// valid, returns what llama-server would start with under it, as `get_model_params` does;
+/// `path` in the result is the model the file name belongs to (`<name>.gguf`).
+#[tauri::command]
+pub fn validate_params_file(state: State<AppState>, path: String) -> Result<ResolvedParams, String> {
+    let file = PathBuf::from(&path);
+    let profile = parse_strict(&file)?;
+    let model = match path.strip_suffix(".params.json") {
+        Some(stem) => PathBuf::from(format!("{stem}.gguf")),
+        None => file.with_extension("gguf"),
+    };
+    Ok(resolve_with(&state, &model, &file, Some(profile)))
+}
+
+fn resolve_with(state: &AppState, model: &Path, profile_file: &Path, profile: Option<GenParams>) -> ResolvedParams {
+    let layers = [
+        (ParamSource::ModelProfile, set_fields(&profile.clone().unwrap_or_default())),
+        (ParamSource::Settings, set_fields(&settings_params(state))),
+        (ParamSource::Default, set_fields(&GenParams::builtin())),
+    ];
+    let params = PARAM_DEFS
+        .iter()
+        .map(|def| {
+            let found = layers
+                .iter()
+                .find_map(|(source, fields)| fields.get(def.name).map(|v| (*source, v.clone())));
+            ResolvedParam {
+                name: def.name,
+                value: found.as_ref().map(|(_, v)| v.clone()),
+                source: found.map_or(ParamSource::Default, |(source, _)| source),
+            }
+        })
+        .collect();
+    ResolvedParams {
+        path: model.to_string_lossy().to_string(),
+        profile_path: profile_file.to_string_lossy().to_string(),
+        profile_exists: profile.is_some(),
+        params,
+    }
+}
@@ -0,0 +1,415 @@
+// End-to-end model check: loads a model if it isn't already running, sends a trivial
+// prompt, times the answer and puts the previously loaded model back. `probe_load_time`
+// does only the loading half, for comparing cold-load times, and `measure_ttft` times the
+// running server's first token separately from its generation rate.
+
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use tauri::{AppHandle, Manager, State};
+
+use crate::chat::{self, not_reachable, ChatMessage, GenParams};
+use crate::error::{self, AppError};
+use crate::llama_config::LlamaConfig;
+use crate::memory;
+use crate::metrics::Timings;
+use crate::{
+    kill_and_confirm, queue, remembered_config, resolve_model_path, restart_llama, wait_until_ready, AppState,
+    ServerMode, KILL_TIMEOUT_SECS,
+};
+
+const TEST_PROMPT: &str = "Reply with the single word OK.";
+const TEST_MAX_TOKENS: i32 = 16;
+
+/// `measure_ttft` prompt size when none is given, in approximate tokens.
+const TTFT_DEFAULT_PROMPT_TOKENS: usize = 512;
+const TTFT_MAX_PROMPT_TOKENS: usize = 32_768;
+/// Tokens generated for the rate after the first one.
+const TTFT_MAX_TOKENS: i32 = 64;
+/// About 10 tokens in common tokenizers; repeated to the requested prompt length.
+const TTFT_FILLER: &str = "The quick brown fox jumps over the lazy dog. ";
+
+#[derive(Clone, serde::Serialize)]
+pub struct ModelTestReport {
+    pub model: String,
+    /// The model answered with a non-empty response.
+    pub success: bool,
+    /// Why the test failed, when `success` is false.
+    pub error: Option<String>,
+    /// True if the model had to be loaded for the test (and the previous one restored).
+    pub switched: bool,
+    /// Time until `/health` answered; `None` when the running server was reused.
+    pub load_ms: Option<u64>,
+    /// Round trip of the test prompt.
+    pub response_ms: Option<u64>,
+    pub response: Option<String>,
+    pub completion_tokens: Option<u64>,
+    /// Generation speed as reported by llama-server, else tokens over the round trip.
+    pub tokens_per_sec: Option<f64>,
+    /// Set if the previously loaded model could not be started again.
+    pub restore_error: Option<String>,
+}
+
+struct PromptOutcome {
+    response: String,
+    completion_tokens: Option<u64>,
+    tokens_per_sec: Option<f64>,
+}
+
+async fn send_test_prompt(state: &AppState) -> Result<PromptOutcome, AppError> {
+    let messages = [ChatMessage {
+        role: "user".into(),
+        content: TEST_PROMPT.into(),
+    }];
+    let params = GenParams {
+        temperature: Some(0.0),
+        max_tokens: Some(TEST_MAX_TOKENS),
+        ..GenParams::default()
+    };
+    let resp = reqwest::Client::new()
+        .post(chat::completions_url(state))
+        .json(&chat::request_body(&messages, &params, false))
+        .send()
+        .await
+        .map_err(not_reachable)?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(AppError::ServerError(format!("llama-server returned {status}: {text}")));
+    }
+
+    let json: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| AppError::ServerError(format!("Invalid response from llama-server: {e}")))?;
+    Ok(PromptOutcome {
+        response: json["choices"][0]["message"]["content"].as_str().unwrap_or_default().trim().to_string(),
+        completion_tokens: json["usage"]["completion_tokens"].as_u64(),
+        tokens_per_sec: json["timings"]["predicted_per_second"].as_f64(),
+    })
+}
+
+/// The model a test takes the server from, with the launch options to start it again.
+struct Previous {
+    model: Option<String>,
+    config: LlamaConfig,
+}
+
+/// Records what is running before a test replaces it. Refused while a switch or load is in
+/// progress, since the test would kill a server that isn't settled yet.
+fn take_over(state: &AppState) -> Result<Previous, AppError> {
+    if state.switching.load(Ordering::SeqCst) || state.loading.lock().unwrap().is_some() {
+        return Err(AppError::Busy("A model switch or load is in progress; try again once it finishes".to_string()));
+    }
+    let model = state.current_model.lock().unwrap().clone();
+    let config = state.launch_config.lock().unwrap().clone();
+    let config = match (config, &model) {
+        (Some(config), _) => config,
+        (None, Some(model)) => remembered_config(state, model),
+        (None, None) => LlamaConfig::default(),
+    };
+    Ok(Previous { model, config })
+}
+
+/// Starts a llama-server for `path` off the async runtime (spawning blocks on retries).
+async fn load(app: &AppHandle, path: std::path::PathBuf, model: String, config: LlamaConfig) -> Result<u32, AppError> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        restart_llama(&handle, &handle.state::<AppState>(), path, &model, &config)
+    })
+    .await
+    .map_err(|e| AppError::Other(format!("Model start task failed: {e}")))?
+}
+
+/// The test server: default launch options in chat mode.
+fn test_config() -> LlamaConfig {
+    LlamaConfig {
+        mode: ServerMode::Chat,
+        ..LlamaConfig::default()
+    }
+}
+
+/// Kills the running llama-server and waits for it to exit, off the async runtime.
+async fn stop(app: &AppHandle) -> Result<(), AppError> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = handle.state::<AppState>();
+        let mut guard = state.llama.lock().unwrap();
+        if let Some(child) = guard.take() {
+            let pid = child.id();
+            if let Err(child) = kill_and_confirm(child) {
+                // Still tracked, so a later stop or shutdown can try again
+                *guard = Some(child);
+                return Err(AppError::SpawnFailed(format!(
+                    "llama-server (pid {pid}) did not exit within {KILL_TIMEOUT_SECS}s"
+                )));
+            }
+        }
+        state.current_model.lock().unwrap().take();
+        Ok(())
+    })
+    .await
+    .map_err(|e| AppError::Other(format!("Model stop task failed: {e}")))?
+}
+
+/// Starts the previous model again with its launch options, or stops the server if nothing
+/// was running before. Returns why that failed.
+async fn restore(app: &AppHandle, state: &AppState, previous: Previous) -> Option<String> {
+    let restored = match previous.model {
+        Some(prev) => match resolve_model_path(state, prev) {
+            Ok((path, prev)) => load(app, path, prev, previous.config).await.map(|_| ()),
+            Err(e) => Err(e),
+        },
+        None => stop(app).await,
+    };
+    restored.err().map(|e| {
+        eprintln!("Failed to restore the previous model: {e}");
+        e.to_string()
+    })
+}
+
+/// Loads `path` (path, alias or split shard) unless it's already running in chat mode,
+/// sends a short prompt and reports whether a non-empty answer came back, with timings.
+/// A model swapped out for the test is started again afterwards with the launch options it
+/// ran with; if nothing was running, the test server is stopped. Refused with `busy` while a
+/// switch or load is in progress.
+#[tauri::command]
+pub async fn test_model(app: AppHandle, state: State<'_, AppState>, path: String) -> Result<ModelTestReport, AppError> {
+    let result = async {
+        let (path, model) = resolve_model_path(&state, path)?;
+        let current = state.current_model.lock().unwrap().clone();
+        let current_mode = *state.server_mode.lock().unwrap();
+        let switched = current.as_deref() != Some(model.as_str()) || current_mode != ServerMode::Chat;
+        let previous = if switched { Some(take_over(&state)?) } else { None };
+
+        let mut report = ModelTestReport {
+            model: model.clone(),
+            success: false,
+            error: None,
+            switched,
+            load_ms: None,
+            response_ms: None,
+            response: None,
+            completion_tokens: None,
+            tokens_per_sec: None,
+            restore_error: None,
+        };
+
+        let started = Instant::now();
+        let ready = if switched {
+            match load(&app, path, model, test_config()).await {
+                Ok(pid) => wait_until_ready(&app, pid).await,
+                Err(e) => Err(e),
+            }
+        } else {
+            let pid = state.llama.lock().unwrap().as_ref().map(|c| c.id());
+            match pid {
+                Some(pid) => wait_until_ready(&app, pid).await,
+                None => Err(AppError::ServerNotRunning("llama-server is not running".to_string())),
+            }
+        };
+        if switched {
+            report.load_ms = Some(started.elapsed().as_millis() as u64);
+        }
+
+        match ready {
+            Ok(()) => {
+                let sent = Instant::now();
+                match send_test_prompt(&state).await {
+                    Ok(outcome) => {
+                        let elapsed = sent.elapsed();
+                        report.response_ms = Some(elapsed.as_millis() as u64);
+                        report.success = !outcome.response.is_empty();
+                        if !report.success {
+                            report.error = Some("The model returned an empty response".to_string());
+                        }
+                        report.tokens_per_sec = outcome.tokens_per_sec.or_else(|| {
+                            let secs = elapsed.as_secs_f64();
+                            outcome.completion_tokens.filter(|_| secs > 0.0).map(|n| n as f64 / secs)
+                        });
+                        report.completion_tokens = outcome.completion_tokens;
+                        report.response = Some(outcome.response);
+                    }
+                    Err(e) => report.error = Some(e.to_string()),
+                }
+            }
+            Err(e) => report.error = Some(e.to_string()),
+        }
+
+        if let Some(previous) = previous {
+            report.restore_error = restore(&app, &state, previous).await;
+        }
+        Ok(report)
+    }
+    .await;
+    error::report(&app, "test_model", result)
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct LoadProbe {
+    pub model: String,
+    /// From spawning llama-server to its first successful `/health` answer.
+    pub load_ms: u64,
+    /// Peak resident memory of the server once loaded (see `memory::peak_rss_bytes`);
+    /// `None` when it can't be read on this platform.
+    pub peak_memory_bytes: Option<u64>,
+    /// Set if the previously loaded model could not be started again.
+    pub restore_error: Option<String>,
+}
+
+/// Measures the cold-load time of `path` (path, alias or split shard): stops the running
+/// server, starts `path` with default launch options, times it until `/health` answers,
+/// reads its peak memory and stops it again, then restores the previous model with its
+/// launch options. Refused with `busy` while a switch or load is in progress. Probes
+/// queue behind each other so measurements never share RAM. A failed load rejects (after
+/// restoring) rather than returning a partial probe.
+#[tauri::command]
+pub async fn probe_load_time(app: AppHandle, state: State<'_, AppState>, path: String) -> Result<LoadProbe, AppError> {
+    let result = async {
+        let (path, model) = resolve_model_path(&state, path)?;
+        let _probe = state.probe_lock.lock().await;
+        let previous = take_over(&state)?;
+
+        // Free the running model's memory before timing, so the load doesn't compete for it
+        stop(&app).await?;
+        let started = Instant::now();
+        let measured = match load(&app, path, model.clone(), test_config()).await {
+            Ok(pid) => wait_until_ready(&app, pid).await.map(|()| {
+                let load_ms = started.elapsed().as_millis() as u64;
+                (load_ms, memory::peak_rss_bytes(pid))
+            }),
+            Err(e) => Err(e),
+        };
+        let stopped = stop(&app).await;
+
+        let restore_error = restore(&app, &state, previous).await;
+        stopped?;
+        let (load_ms, peak_memory_bytes) = measured?;
+        Ok(LoadProbe {
+            model,
+            load_ms,
+            peak_memory_bytes,
+            restore_error,
+        })
+    }
+    .await;
+    error::report(&app, "probe_load_time", result)
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct TtftReport {
+    pub model: String,
+    /// Prompt tokens llama-server evaluated; the prompt is sized by estimate, so this can
+    /// differ from the requested length.
+    pub prompt_tokens: Option<u64>,
+    /// From sending the request to the first streamed token.
+    pub ttft_ms: u64,
+    /// Prompt processing as timed by llama-server (excludes HTTP and scheduling).
+    pub prompt_ms: Option<f64>,
+    pub prompt_per_second: Option<f64>,
+    pub generated_tokens: u64,
+    /// Generation rate after the first token: llama-server's, else streamed tokens over
+    /// the time since the first one.
+    pub tokens_per_sec: Option<f64>,
+}
+
+/// A prompt of about `tokens` tokens.
+fn ttft_prompt(tokens: usize) -> String {
+    let mut prompt = String::from("Continue this text:\n\n");
+    prompt.push_str(&TTFT_FILLER.repeat(tokens.div_ceil(10)));
+    prompt
+}
+
+/// Measures time-to-first-token on the running chat server: streams a prompt of about
+/// `prompt_len` tokens (default 512, max 32768) with the prompt cache off, so the whole
+/// prompt is processed, and reports the wait for the first token alongside the server's
+/// prompt timings and the generation rate of the up to 64 tokens that follow. Takes a turn
+/// in the chat queue so concurrent requests don't skew it. A prompt longer than the
+/// context is rejected by llama-server (`server_error`).
+#[tauri::command]
+pub async fn measure_ttft(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    prompt_len: Option<usize>,
+) -> Result<TtftReport, AppError> {
+    let result = async {
+        let prompt_len = prompt_len.unwrap_or(TTFT_DEFAULT_PROMPT_TOKENS);
+        if prompt_len == 0 || prompt_len > TTFT_MAX_PROMPT_TOKENS {
+            return Err(AppError::InvalidInput(format!(
+                "prompt_len must be between 1 and {TTFT_MAX_PROMPT_TOKENS} (got {prompt_len})"
+            )));
+        }
+        if state.llama.lock().unwrap().is_none() {
+            return Err(AppError::ServerNotRunning("No model is running".to_string()));
+        }
+        if *state.server_mode.lock().unwrap() != ServerMode::Chat {
+            return Err(AppError::InvalidInput("measure_ttft needs a server in chat mode".to_string()));
+        }
+        let model = state.current_model.lock().unwrap().clone().unwrap_or_default();
+
+        let messages = [ChatMessage {
+            role: "user".into(),
+            content: ttft_prompt(prompt_len),
+        }];
+        let params = GenParams {
+            temperature: Some(0.0),
+            max_tokens: Some(TTFT_MAX_TOKENS),
+            ..GenParams::default()
+        };
+        let mut body = chat::request_body(&messages, &params, true);
+        body["cache_prompt"] = serde_json::json!(false);
+
+        let _ticket = queue::enter(&app, &state, None).await;
+        let sent = Instant::now();
+        let mut resp = reqwest::Client::new()
+            .post(chat::completions_url(&state))
+            .json(&body)
+            .send()
+            .await
+            .map_err(not_reachable)?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(AppError::ServerError(format!("llama-server returned {status}: {text}")));
+        }
+
+        let mut buffer = Vec::new();
+        let mut first = None;
+        let mut last = sent;
+        let mut generated_tokens = 0u64;
+        let mut timings = None;
+        let interrupted = |e: reqwest::Error| AppError::ServerError(format!("Stream interrupted: {e}"));
+        'stream: while let Some(chunk) = resp.chunk().await.map_err(interrupted)? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(payload) = chat::next_sse_payload(&mut buffer) {
+                if payload == "[DONE]" {
+                    break 'stream;
+                }
+                let Ok(json) = serde_json::from_str::<serde_json::Value>(&payload) else { continue };
+                if let Some(t) = Timings::from_response(&json) {
+                    timings = Some(t);
+                }
+                if json["choices"][0]["delta"]["content"].as_str().is_some_and(|c| !c.is_empty()) {
+                    last = Instant::now();
+                    first.get_or_insert(last);
+                    generated_tokens += 1;
+                }
+            }
+        }
+
+        let first = first.ok_or_else(|| AppError::ServerError("The model generated no tokens".to_string()))?;
+        let secs = (last - first).as_secs_f64();
+        let measured = (generated_tokens > 1 && secs > 0.0).then(|| (generated_tokens - 1) as f64 / secs);
+        Ok(TtftReport {
+            model,
+            prompt_tokens: timings.as_ref().map(|t| t.prompt_n),
+            ttft_ms: (first - sent).as_millis() as u64,
+            prompt_ms: timings.as_ref().map(|t| t.prompt_ms),
+            prompt_per_second: timings.as_ref().map(|t| t.prompt_per_second),
+            generated_tokens,
+            tokens_per_sec: timings.map(|t| t.predicted_per_second).or(measured),
+        })
+    }
+    .await;
+    error::report(&app, "measure_ttft", result)
+}
@@ -0,0 +1,169 @@
+// Inference processes left behind by a crashed session. Every spawned llama-server,
+// tts-inference and whisper-cli is recorded in <app data>/child-processes.json with the
+// pid of the app that started it; on the next launch, recorded processes whose app is gone
+// but which are still alive *and* still run the same binary are killed, so a stale server
+// can't keep holding the port. Another GenHat instance that is still running keeps its
+// processes.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::{AppHandle, Manager};
+
+use crate::{write_file_atomic, AppState};
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct ChildRecord {
+    pid: u32,
+    exe: String,
+    /// Pid of the app that spawned it; `None` in files written before owners were recorded.
+    #[serde(default)]
+    owner: Option<u32>,
+}
+
+/// `<subsystem>:<app pid>` (subsystems: `llama`, `embed`, `tts`, `whisper`) → that app's last
+/// spawned process. Each subsystem runs at most one process per app, so a new spawn simply
+/// replaces the previous record.
+type Registry = BTreeMap<String, ChildRecord>;
+
+fn registry_key(kind: &str) -> String {
+    format!("{kind}:{}", std::process::id())
+}
+
+fn registry_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path().app_data_dir().ok().map(|d| d.join("child-processes.json"))
+}
+
+fn load(app: &AppHandle) -> Registry {
+    registry_path(app)
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Runs `f` holding the registry lock, so concurrent spawns' load-modify-save cycles don't
+/// overwrite each other's records.
+fn with_registry<T>(app: &AppHandle, f: impl FnOnce() -> T) -> T {
+    let state = app.state::<AppState>();
+    let _guard = state.orphans_lock.lock().unwrap_or_else(|e| e.into_inner());
+    f()
+}
+
+fn save(app: &AppHandle, registry: &Registry) {
+    let Some(path) = registry_path(app) else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(registry) {
+        if let Err(e) = write_file_atomic(&path, json.as_bytes()) {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Records a just-spawned child of `kind`.
+pub fn record(app: &AppHandle, kind: &str, pid: u32, exe: &Path) {
+    with_registry(app, || {
+        let mut registry = load(app);
+        registry.insert(
+            registry_key(kind),
+            ChildRecord {
+                pid,
+                exe: exe.to_string_lossy().to_string(),
+                owner: Some(std::process::id()),
+            },
+        );
+        save(app, &registry);
+    })
+}
+
+/// Forgets this app's records, after a clean shutdown has reaped every child. Records of
+/// other running instances stay.
+pub fn clear(app: &AppHandle) {
+    with_registry(app, || {
+        let mut registry = load(app);
+        let own = std::process::id();
+        registry.retain(|_, child| child.owner != Some(own));
+        save(app, &registry);
+    })
+}
+
+/// File name of the executable running as `pid`, or `None` if no such process exists.
+fn process_exe_name(pid: u32) -> Option<String> {
+    if cfg!(windows) {
+        // CSV row: "llama-server.exe","1234","Console","1","52,000 K"
+        let out = Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {pid}"), "/FO", "CSV", "/NH"])
+            .output()
+            .ok()?;
+        let text = String::from_utf8_lossy(&out.stdout);
+        let name = text.lines().next()?.split(',').next()?.trim_matches('"');
+        Some(name.to_string()).filter(|n| n.contains('.'))
+    } else if cfg!(target_os = "linux") {
+        let exe = std::fs::read_link(format!("/proc/{pid}/exe")).ok()?;
+        Some(exe.file_name()?.to_string_lossy().to_string())
+    } else {
+        let out = Command::new("ps").args(["-p", &pid.to_string(), "-o", "comm="]).output().ok()?;
+        let comm = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        Some(Path::new(&comm).file_name()?.to_string_lossy().to_string()).filter(|n| !n.is_empty())
+    }
+}
+
+/// Whether `owner` is a GenHat that is still running, i.e. its records aren't orphans. Our
+/// own pid counts as gone: `reap` runs before this session records anything, so a record
+/// with it comes from an earlier session whose pid was reused.
+fn owner_running(owner: u32) -> bool {
+    if owner == std::process::id() {
+        return false;
+    }
+    let Some(running) = process_exe_name(owner) else { return false };
+    let own_name = std::env::current_exe().ok().and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()));
+    // When our own name is unknown, a live owner is given the benefit of the doubt
+    own_name.map_or(true, |own| running.eq_ignore_ascii_case(&own))
+}
+
+fn kill_pid(pid: u32) -> bool {
+    let status = if cfg!(windows) {
+        Command::new("taskkill").args(["/PID", &pid.to_string(), "/F"]).status()
+    } else {
+        Command::new("kill").args(["-9", &pid.to_string()]).status()
+    };
+    status.is_ok_and(|s| s.success())
+}
+
+/// Kills processes recorded by a session that has ended (its app pid no longer runs
+/// GenHat) and that are still running the binary they were started from (matched by
+/// executable file name, so a reused pid belonging to some other program is left alone).
+/// Records of running instances are kept. Returns how many were killed. Call before
+/// auto-start. Holds the registry lock throughout, so a record made meanwhile isn't lost
+/// when the survivors are saved.
+pub fn reap(app: &AppHandle) -> usize {
+    with_registry(app, || reap_locked(app))
+}
+
+fn reap_locked(app: &AppHandle) -> usize {
+    let (running_apps, orphans): (Registry, Registry) =
+        load(app).into_iter().partition(|(_, child)| child.owner.is_some_and(owner_running));
+    let mut killed = 0;
+    for (key, child) in &orphans {
+        let kind = key.split(':').next().unwrap_or(key);
+        let expected = Path::new(&child.exe).file_name().map(|n| n.to_string_lossy().to_string());
+        let running = process_exe_name(child.pid);
+        // Case-insensitive for Windows image names
+        let same = matches!((&running, &expected), (Some(r), Some(e)) if r.eq_ignore_ascii_case(e));
+        if same {
+            if kill_pid(child.pid) {
+                eprintln!("Killed orphaned {kind} process {} ({})", child.pid, child.exe);
+                killed += 1;
+            } else {
+                eprintln!("Failed to kill orphaned {kind} process {}", child.pid);
+            }
+        }
+    }
+    if !orphans.is_empty() {
+        save(app, &running_apps);
+    }
+    killed
+}
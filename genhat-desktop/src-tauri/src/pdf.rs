@@ -0,0 +1,1058 @@
+// Plain-text extraction from PDF files, for `extract_text`. Hand-written because no PDF
+// crate is available to the build, so it deliberately covers a small subset: unencrypted
+// files whose objects are found by scanning for `N G obj` (plain or packed in object
+// streams), a page tree reachable from a root `Pages` node, FlateDecode streams, the
+// text operators (`Tf Tj TJ ' " Td TD Tm T* ET`) and ToUnicode CMaps with `bfchar` and
+// string-valued `bfrange` entries, else Latin-1. Anything else (other filters, form
+// XObjects, orphaned pages, encryption) yields no text or an error rather than a guess;
+// every length and offset read from the file is bounds-checked, and the tests feed it
+// mutated and pathological files.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+
+/// Largest decoded stream; larger ones are skipped rather than inflated.
+const MAX_STREAM_BYTES: u64 = 64 << 20;
+/// Reference chains and page-tree levels beyond this are treated as broken.
+const MAX_DEPTH: usize = 32;
+/// `TJ` offsets (thousandths of an em) at or beyond this much leftwards are read as a space.
+const TJ_SPACE: f64 = 200.0;
+
+type Dict = BTreeMap<String, Obj>;
+/// A page dictionary and its resources, which may be inherited from the page tree.
+type Page<'a> = (&'a Dict, Option<&'a Dict>);
+
+#[derive(Clone)]
+enum Obj {
+    Null,
+    Num(f64),
+    Str(Vec<u8>),
+    Name(String),
+    Array(Vec<Obj>),
+    Dict(Dict),
+    Ref(u32),
+    /// A bare keyword: a content stream operator, `true` / `false`, or a stray token.
+    Keyword(String),
+}
+
+impl Obj {
+    fn as_dict(&self) -> Option<&Dict> {
+        match self {
+            Obj::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    fn as_num(&self) -> Option<f64> {
+        match self {
+            Obj::Num(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn as_name(&self) -> Option<&str> {
+        match self {
+            Obj::Name(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0')
+}
+
+fn is_delimiter(b: u8) -> bool {
+    matches!(b, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+/// Tokenizer and object parser over PDF syntax, shared by the file body, object streams,
+/// content streams and CMaps.
+struct Lexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    /// Starts at `pos`, or at the end if the data is shorter (offsets come from the file).
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Lexer { data, pos: pos.min(data.len()) }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_space(&mut self) {
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while self.peek().is_some_and(|b| b != b'\n' && b != b'\r') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn regular(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| !is_whitespace(b) && !is_delimiter(b)) {
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    fn literal_string(&mut self) -> Vec<u8> {
+        // Opening paren already consumed
+        let mut out = Vec::new();
+        let mut depth = 1;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'(' => {
+                    depth += 1;
+                    out.push(b);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    out.push(b);
+                }
+                b'\\' => {
+                    let Some(e) = self.peek() else { break };
+                    self.pos += 1;
+                    match e {
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'b' => out.push(8),
+                        b'f' => out.push(12),
+                        b'0'..=b'7' => {
+                            let mut value = (e - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(d @ b'0'..=b'7') => {
+                                        value = value * 8 + (d - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            out.push(value as u8);
+                        }
+                        // Line continuation
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        other => out.push(other),
+                    }
+                }
+                other => out.push(other),
+            }
+        }
+        out
+    }
+
+    fn hex_string(&mut self) -> Vec<u8> {
+        // Opening angle bracket already consumed
+        let mut digits = Vec::new();
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            if b == b'>' {
+                break;
+            }
+            if let Some(d) = (b as char).to_digit(16) {
+                digits.push(d as u8);
+            }
+        }
+        if digits.len() % 2 == 1 {
+            digits.push(0);
+        }
+        digits.chunks(2).map(|p| p[0] << 4 | p[1]).collect()
+    }
+
+    fn name(&mut self) -> String {
+        // Slash already consumed; `#xx` escapes a byte
+        let raw = self.regular();
+        let mut out = Vec::with_capacity(raw.len());
+        let mut i = 0;
+        while i < raw.len() {
+            let hex = raw.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+            match (raw[i], hex) {
+                (b'#', Some(byte)) => {
+                    out.push(byte);
+                    i += 3;
+                }
+                (b, _) => {
+                    out.push(b);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&out).to_string()
+    }
+
+    /// The next object, or `None` at the end of the data or on a closing `]` / `>>`.
+    fn object(&mut self, depth: usize) -> Option<Obj> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.skip_space();
+        let b = self.peek()?;
+        match b {
+            b'(' => {
+                self.pos += 1;
+                Some(Obj::Str(self.literal_string()))
+            }
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                let mut dict = BTreeMap::new();
+                loop {
+                    self.skip_space();
+                    match self.peek() {
+                        None => break,
+                        Some(b'>') => {
+                            // `>>`, or a lone `>` at the end of damaged data
+                            self.pos = (self.pos + 2).min(self.data.len());
+                            break;
+                        }
+                        Some(b'/') => {
+                            self.pos += 1;
+                            let key = self.name();
+                            let value = self.object(depth + 1).unwrap_or(Obj::Null);
+                            dict.insert(key, value);
+                        }
+                        // Malformed entry: skip a token so parsing keeps moving
+                        Some(_) => {
+                            if self.object(depth + 1).is_none() {
+                                self.pos += 1;
+                            }
+                        }
+                    }
+                }
+                Some(Obj::Dict(dict))
+            }
+            b'<' => {
+                self.pos += 1;
+                Some(Obj::Str(self.hex_string()))
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_space();
+                    match self.peek() {
+                        None => break,
+                        Some(b']') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some(_) => match self.object(depth + 1) {
+                            Some(item) => items.push(item),
+                            None => self.pos += 1,
+                        },
+                    }
+                }
+                Some(Obj::Array(items))
+            }
+            b']' | b'>' | b')' | b'{' | b'}' => None,
+            b'/' => {
+                self.pos += 1;
+                Some(Obj::Name(self.name()))
+            }
+            _ => {
+                let word = self.regular();
+                if word.is_empty() {
+                    return None;
+                }
+                let word = String::from_utf8_lossy(word).to_string();
+                if let Ok(n) = word.parse::<f64>() {
+                    // `12 0 R` is a reference
+                    let save = self.pos;
+                    self.skip_space();
+                    let generation = self.regular();
+                    let is_int = |w: &[u8]| !w.is_empty() && w.iter().all(u8::is_ascii_digit);
+                    if is_int(word.as_bytes()) && is_int(generation) {
+                        self.skip_space();
+                        if self.regular() == b"R" {
+                            return Some(Obj::Ref(n as u32));
+                        }
+                    }
+                    self.pos = save;
+                    return Some(Obj::Num(n));
+                }
+                Some(if word == "null" { Obj::Null } else { Obj::Keyword(word) })
+            }
+        }
+    }
+}
+
+struct PdfObject {
+    value: Obj,
+    /// Raw (still encoded) stream data.
+    stream: Option<Vec<u8>>,
+}
+
+/// A length or offset read from the file, if it is a whole number that can index `data`.
+fn file_offset(n: f64, data: &[u8]) -> Option<usize> {
+    (n >= 0.0 && n.fract() == 0.0 && n <= data.len() as f64).then_some(n as usize)
+}
+
+fn find(data: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?.windows(needle.len()).position(|w| w == needle).map(|i| i + from)
+}
+
+/// Object number of an `N G obj` header whose `obj` keyword starts at `at`.
+fn object_header(data: &[u8], at: usize) -> Option<u32> {
+    if data.get(at + 3).is_some_and(|&b| !is_whitespace(b) && !is_delimiter(b)) {
+        return None;
+    }
+    let mut i = at;
+    let skip_space = |i: &mut usize| {
+        let start = *i;
+        while *i > 0 && is_whitespace(data[*i - 1]) {
+            *i -= 1;
+        }
+        *i < start
+    };
+    let digits = |i: &mut usize| {
+        let end = *i;
+        while *i > 0 && data[*i - 1].is_ascii_digit() {
+            *i -= 1;
+        }
+        (*i < end).then(|| std::str::from_utf8(&data[*i..end]).ok()).flatten()
+    };
+    if !skip_space(&mut i) {
+        return None;
+    }
+    digits(&mut i)?;
+    if !skip_space(&mut i) {
+        return None;
+    }
+    let number = digits(&mut i)?;
+    if i > 0 && !is_whitespace(data[i - 1]) && !is_delimiter(data[i - 1]) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Every `N G obj … endobj` in the file, later definitions (incremental updates) winning.
+fn scan_objects(data: &[u8]) -> HashMap<u32, PdfObject> {
+    let mut objects = HashMap::new();
+    let mut pos = 0;
+    while let Some(at) = find(data, b"obj", pos) {
+        pos = at + 3;
+        let Some(number) = object_header(data, at) else { continue };
+        let mut lexer = Lexer::new(data, at + 3);
+        let Some(value) = lexer.object(0) else { continue };
+        let start = lexer.pos;
+        lexer.skip_space();
+        let mut stream = None;
+        if lexer.regular() == b"stream" {
+            let mut begin = lexer.pos;
+            if data.get(begin) == Some(&b'\r') {
+                begin += 1;
+            }
+            if data.get(begin) == Some(&b'\n') {
+                begin += 1;
+            }
+            let declared = value
+                .as_dict()
+                .and_then(|d| d.get("Length"))
+                .and_then(Obj::as_num)
+                .and_then(|n| file_offset(n, data))
+                .and_then(|len| begin.checked_add(len))
+                .filter(|&end| end <= data.len());
+            let end = declared
+                .filter(|&end| {
+                    let mut after = Lexer::new(data, end);
+                    after.skip_space();
+                    data.get(after.pos..).is_some_and(|rest| rest.starts_with(b"endstream"))
+                })
+                .or_else(|| {
+                    // Indirect or wrong /Length: up to `endstream`, minus its end of line
+                    let mut end = find(data, b"endstream", begin)?;
+                    while end > begin && matches!(data[end - 1], b'\r' | b'\n') {
+                        end -= 1;
+                    }
+                    Some(end)
+                });
+            if let Some(end) = end {
+                stream = Some(data[begin..end].to_vec());
+                pos = end;
+            }
+        } else {
+            pos = start;
+        }
+        objects.insert(number, PdfObject { value, stream });
+    }
+    objects
+}
+
+/// The decoded data of a stream whose dictionary is `dict`; `None` for filters other
+/// than FlateDecode.
+fn decode_stream(dict: &Dict, raw: &[u8]) -> Option<Vec<u8>> {
+    let filters: Vec<&str> = match dict.get("Filter") {
+        None => Vec::new(),
+        Some(Obj::Name(n)) => vec![n],
+        Some(Obj::Array(items)) => items.iter().filter_map(Obj::as_name).collect(),
+        Some(_) => return None,
+    };
+    let mut data = raw.to_vec();
+    for filter in filters {
+        if filter != "FlateDecode" {
+            return None;
+        }
+        let mut out = Vec::new();
+        let result = flate2::read::ZlibDecoder::new(&data[..])
+            .take(MAX_STREAM_BYTES)
+            .read_to_end(&mut out);
+        // Streams with a damaged end still yield what came before the damage
+        if result.is_err() && out.is_empty() {
+            return None;
+        }
+        data = out;
+    }
+    Some(data)
+}
+
+struct Document {
+    objects: HashMap<u32, PdfObject>,
+}
+
+impl Document {
+    fn parse(data: &[u8]) -> Document {
+        let mut objects = scan_objects(data);
+        // Objects packed in object streams (PDF 1.5+), unless also defined directly
+        let mut packed = Vec::new();
+        for object in objects.values() {
+            let Some(dict) = object.value.as_dict() else { continue };
+            if dict.get("Type").and_then(Obj::as_name) != Some("ObjStm") {
+                continue;
+            }
+            let Some(decoded) = object.stream.as_deref().and_then(|s| decode_stream(dict, s)) else { continue };
+            let count = dict.get("N").and_then(Obj::as_num).and_then(|n| file_offset(n, &decoded)).unwrap_or(0);
+            let Some(first) = dict.get("First").and_then(Obj::as_num).and_then(|n| file_offset(n, &decoded)) else {
+                continue;
+            };
+            let mut header = Lexer::new(&decoded, 0);
+            for _ in 0..count {
+                let (Some(Obj::Num(number)), Some(Obj::Num(offset))) = (header.object(0), header.object(0)) else {
+                    break;
+                };
+                let Some(at) = file_offset(offset, &decoded).and_then(|offset| first.checked_add(offset)) else {
+                    continue;
+                };
+                if let Some(value) = Lexer::new(&decoded, at).object(0) {
+                    packed.push((number as u32, value));
+                }
+            }
+        }
+        for (number, value) in packed {
+            objects.entry(number).or_insert(PdfObject { value, stream: None });
+        }
+        Document { objects }
+    }
+
+    /// `obj`, or what it refers to.
+    fn resolve<'a>(&'a self, mut obj: &'a Obj) -> &'a Obj {
+        for _ in 0..MAX_DEPTH {
+            match obj {
+                Obj::Ref(n) => match self.objects.get(n) {
+                    Some(target) => obj = &target.value,
+                    None => return &Obj::Null,
+                },
+                _ => return obj,
+            }
+        }
+        &Obj::Null
+    }
+
+    fn dict<'a>(&'a self, obj: Option<&'a Obj>) -> Option<&'a Dict> {
+        self.resolve(obj?).as_dict()
+    }
+
+    /// Decoded data of the stream object `obj` refers to.
+    fn stream(&self, obj: &Obj) -> Option<Vec<u8>> {
+        let Obj::Ref(n) = obj else { return None };
+        let object = self.objects.get(n)?;
+        decode_stream(object.value.as_dict()?, object.stream.as_deref()?)
+    }
+
+    /// The pages in reading order; none without a root page tree node.
+    fn pages(&self) -> Vec<Page<'_>> {
+        let type_of = |d: &Dict| d.get("Type").and_then(Obj::as_name).map(str::to_string);
+        // Incremental updates can leave stale trees behind; the largest one is the document
+        let root = self
+            .objects
+            .values()
+            .filter_map(|o| o.value.as_dict())
+            .filter(|d| type_of(d).as_deref() == Some("Pages") && !d.contains_key("Parent"))
+            .max_by_key(|d| d.get("Count").and_then(Obj::as_num).unwrap_or(0.0) as u64);
+        let mut pages = Vec::new();
+        if let Some(root) = root {
+            self.collect_pages(root, None, &mut pages, &mut HashSet::new(), 0);
+        }
+        pages
+    }
+
+    fn collect_pages<'a>(
+        &'a self,
+        node: &'a Dict,
+        inherited: Option<&'a Dict>,
+        pages: &mut Vec<Page<'a>>,
+        visited: &mut HashSet<u32>,
+        depth: usize,
+    ) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let resources = self.dict(node.get("Resources")).or(inherited);
+        let Some(Obj::Array(kids)) = node.get("Kids").map(|k| self.resolve(k)) else {
+            pages.push((node, resources));
+            return;
+        };
+        for kid in kids {
+            if let Obj::Ref(n) = kid {
+                if !visited.insert(*n) {
+                    continue;
+                }
+            }
+            if let Some(child) = self.dict(Some(kid)) {
+                self.collect_pages(child, resources, pages, visited, depth + 1);
+            }
+        }
+    }
+}
+
+/// A font's byte → text mapping.
+#[derive(Default)]
+struct Font {
+    /// From the ToUnicode CMap; `None` for simple fonts without one.
+    cmap: Option<HashMap<u32, String>>,
+    /// Bytes per character code: 2 for composite (Type0) fonts unless the CMap says 1.
+    code_len: usize,
+}
+
+fn utf16_text(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks(2).map(|p| u16::from_be_bytes([p[0], *p.get(1).unwrap_or(&0)])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn code_of(bytes: &[u8]) -> u32 {
+    bytes.iter().take(4).fold(0, |code, &b| code << 8 | b as u32)
+}
+
+/// `bfchar` / `bfrange` mappings of a ToUnicode CMap, and the code length of its
+/// code space.
+fn parse_cmap(data: &[u8]) -> (HashMap<u32, String>, Option<usize>) {
+    let mut map = HashMap::new();
+    let mut code_len = None;
+    let mut lexer = Lexer::new(data, 0);
+    let mut operands: Vec<Obj> = Vec::new();
+    while lexer.pos < data.len() {
+        let Some(obj) = lexer.object(0) else {
+            lexer.pos += 1;
+            continue;
+        };
+        let Obj::Keyword(op) = &obj else {
+            operands.push(obj);
+            continue;
+        };
+        match op.as_str() {
+            "endcodespacerange" => {
+                if let Some(Obj::Str(low)) = operands.first() {
+                    code_len = Some(low.len().clamp(1, 4));
+                }
+            }
+            "endbfchar" => {
+                for pair in operands.chunks(2) {
+                    if let [Obj::Str(src), Obj::Str(dst)] = pair {
+                        map.insert(code_of(src), utf16_text(dst));
+                    }
+                }
+            }
+            "endbfrange" => {
+                for triple in operands.chunks(3) {
+                    // Array destinations (one string per code) aren't supported
+                    let [Obj::Str(low), Obj::Str(high), Obj::Str(first)] = triple else { continue };
+                    let (low, high) = (code_of(low), code_of(high));
+                    if high < low || high - low > 0xFFFF {
+                        continue;
+                    }
+                    let mut units: Vec<u16> =
+                        first.chunks(2).map(|p| u16::from_be_bytes([p[0], *p.get(1).unwrap_or(&0)])).collect();
+                    for code in low..=high {
+                        map.insert(code, String::from_utf16_lossy(&units));
+                        if let Some(last) = units.last_mut() {
+                            *last = last.wrapping_add(1);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        operands.clear();
+    }
+    (map, code_len)
+}
+
+impl Document {
+    fn font(&self, font: &Dict) -> Font {
+        let composite = font.get("Subtype").and_then(Obj::as_name) == Some("Type0");
+        let mut result = Font {
+            cmap: None,
+            code_len: if composite { 2 } else { 1 },
+        };
+        if let Some(data) = font.get("ToUnicode").and_then(|t| self.stream(t)) {
+            let (map, code_len) = parse_cmap(&data);
+            if let Some(len) = code_len {
+                result.code_len = len;
+            }
+            result.cmap = Some(map);
+        }
+        result
+    }
+}
+
+impl Font {
+    fn decode(&self, bytes: &[u8], out: &mut String) {
+        match &self.cmap {
+            Some(map) => {
+                for code in bytes.chunks(self.code_len.max(1)) {
+                    if let Some(text) = map.get(&code_of(code)) {
+                        out.push_str(text);
+                    }
+                }
+            }
+            // Composite fonts without a ToUnicode map use glyph ids; nothing readable
+            None if self.code_len > 1 => {}
+            // Latin-1 covers WinAnsi / Standard text well enough for search
+            None => out.extend(bytes.iter().map(|&b| b as char).filter(|c| !c.is_control() || *c == '\t')),
+        }
+    }
+}
+
+fn push_break(out: &mut String, separator: char) {
+    if !out.is_empty() && !out.ends_with(|c: char| c == '\n' || (separator == ' ' && c == ' ')) {
+        out.push(separator);
+    }
+}
+
+/// The text a page's content streams draw.
+fn page_text(doc: &Document, page: &Dict, resources: Option<&Dict>) -> String {
+    let contents: Vec<Obj> = match page.get("Contents") {
+        Some(Obj::Array(items)) => items.clone(),
+        Some(Obj::Ref(n)) => match doc.objects.get(n).map(|o| &o.value) {
+            Some(Obj::Array(items)) => items.clone(),
+            _ => vec![Obj::Ref(*n)],
+        },
+        _ => Vec::new(),
+    };
+    let mut data = Vec::new();
+    for part in &contents {
+        if let Some(stream) = doc.stream(part) {
+            data.extend(stream);
+            data.push(b'\n');
+        }
+    }
+
+    let font_dicts = resources.and_then(|r| doc.dict(r.get("Font")));
+    let mut fonts: HashMap<String, Font> = HashMap::new();
+    let mut current: Option<String> = None;
+    let mut out = String::new();
+    let mut last_y: Option<f64> = None;
+    let mut operands: Vec<Obj> = Vec::new();
+    let mut lexer = Lexer::new(&data, 0);
+
+    while lexer.pos < data.len() {
+        let Some(obj) = lexer.object(0) else {
+            lexer.pos += 1;
+            continue;
+        };
+        let Obj::Keyword(op) = obj else {
+            operands.push(obj);
+            continue;
+        };
+        let show = |bytes: &[u8], fonts: &mut HashMap<String, Font>, out: &mut String| {
+            let name = current.clone().unwrap_or_default();
+            let font = fonts.entry(name.clone()).or_insert_with(|| {
+                font_dicts
+                    .and_then(|f| doc.dict(f.get(&name)))
+                    .map(|f| doc.font(f))
+                    .unwrap_or_else(|| Font {
+                        cmap: None,
+                        code_len: 1,
+                    })
+            });
+            font.decode(bytes, out);
+        };
+        match op.as_str() {
+            "Tf" => {
+                if let Some(Obj::Name(name)) = operands.iter().rev().nth(1) {
+                    current = Some(name.clone());
+                }
+            }
+            "Tj" | "'" | "\"" => {
+                if op != "Tj" {
+                    push_break(&mut out, '\n');
+                }
+                if let Some(Obj::Str(s)) = operands.last() {
+                    show(s, &mut fonts, &mut out);
+                }
+            }
+            "TJ" => {
+                if let Some(Obj::Array(items)) = operands.last() {
+                    for item in items {
+                        match item {
+                            Obj::Str(s) => show(s, &mut fonts, &mut out),
+                            Obj::Num(n) if -n >= TJ_SPACE => push_break(&mut out, ' '),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            "Td" | "TD" => {
+                let ty = operands.last().and_then(Obj::as_num).unwrap_or(0.0);
+                push_break(&mut out, if ty.abs() > 0.01 { '\n' } else { ' ' });
+            }
+            "Tm" => {
+                let y = operands.last().and_then(Obj::as_num);
+                let same_line = matches!((y, last_y), (Some(y), Some(last)) if (y - last).abs() < 0.01);
+                push_break(&mut out, if same_line { ' ' } else { '\n' });
+                last_y = y;
+            }
+            "T*" | "ET" => push_break(&mut out, '\n'),
+            // Inline image: skip its binary data up to `EI`
+            "ID" => {
+                let mut at = lexer.pos;
+                while let Some(ei) = find(&data, b"EI", at) {
+                    let before = ei.checked_sub(1).map_or(true, |i| is_whitespace(data[i]));
+                    let after = data.get(ei + 2).map_or(true, |&b| is_whitespace(b));
+                    at = ei + 2;
+                    if before && after {
+                        break;
+                    }
+                }
+                lexer.pos = at;
+            }
+            _ => {}
+        }
+        operands.clear();
+    }
+    tidy(&out)
+}
+
+/// Collapses runs of spaces and drops blank lines.
+fn tidy(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether a trailer (classic, or the dictionary of a cross-reference stream) names an
+/// `/Encrypt` dictionary.
+fn is_encrypted(doc: &Document, data: &[u8]) -> bool {
+    let xref_stream = doc.objects.values().any(|o| {
+        o.value
+            .as_dict()
+            .is_some_and(|d| d.get("Type").and_then(Obj::as_name) == Some("XRef") && d.contains_key("Encrypt"))
+    });
+    let mut pos = 0;
+    let mut trailer = false;
+    while let Some(at) = find(data, b"trailer", pos) {
+        pos = at + 7;
+        let dict = Lexer::new(data, pos).object(0);
+        trailer |= dict.as_ref().and_then(Obj::as_dict).is_some_and(|d| d.contains_key("Encrypt"));
+    }
+    xref_stream || trailer
+}
+
+/// The text of each page of the PDF `data`, in order; empty for pages without text
+/// (scanned or image-only pages).
+pub fn page_texts(data: &[u8]) -> Result<Vec<String>, String> {
+    if !data.starts_with(b"%PDF-") && find(&data[..data.len().min(1024)], b"%PDF-", 0).is_none() {
+        return Err("Not a PDF file (no %PDF- header)".to_string());
+    }
+    let doc = Document::parse(data);
+    if is_encrypted(&doc, data) {
+        return Err("The PDF is encrypted; remove the password protection first".to_string());
+    }
+    let pages = doc.pages();
+    if pages.is_empty() {
+        return Err("The PDF has no pages, or its page tree is damaged".to_string());
+    }
+    Ok(pages.into_iter().map(|(page, resources)| page_text(&doc, page, resources)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A PDF of `objects` (numbered from 1) followed by `trailer`. No xref table: the
+    /// reader scans for objects, and so do these fixtures.
+    fn pdf(objects: &[Vec<u8>], trailer: &str) -> Vec<u8> {
+        let mut out = b"%PDF-1.5\n".to_vec();
+        for (i, body) in objects.iter().enumerate() {
+            out.extend(format!("{} 0 obj\n", i + 1).as_bytes());
+            out.extend(body);
+            out.extend(b"\nendobj\n");
+        }
+        out.extend(format!("trailer\n{trailer}\n%%EOF\n").as_bytes());
+        out
+    }
+
+    fn stream_with_length(dict: &str, data: &[u8], length: &str) -> Vec<u8> {
+        let mut out = format!("<< {dict} /Length {length} >>\nstream\n").into_bytes();
+        out.extend(data);
+        out.extend(b"\nendstream");
+        out
+    }
+
+    fn stream(dict: &str, data: &[u8]) -> Vec<u8> {
+        stream_with_length(dict, data, &data.len().to_string())
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    const SIMPLE_CONTENT: &[u8] = b"BT /F1 12 Tf 72 700 Td (Hello,) Tj [(wor) -20 (ld)] TJ 0 -14 Td (caf\\351) Tj ET";
+
+    /// Two pages in Helvetica, uncompressed; the second draws nothing. The content stream
+    /// claims to be `length` bytes long.
+    fn simple_with_length(length: &str) -> Vec<u8> {
+        pdf(
+            &[
+                b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+                b"<< /Type /Pages /Kids [3 0 R 5 0 R] /Count 2 >>".to_vec(),
+                b"<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 4 0 R >> >> /Contents 6 0 R >>".to_vec(),
+                b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_vec(),
+                b"<< /Type /Page /Parent 2 0 R >>".to_vec(),
+                stream_with_length("", SIMPLE_CONTENT, length),
+            ],
+            "<< /Root 1 0 R >>",
+        )
+    }
+
+    fn simple() -> Vec<u8> {
+        simple_with_length(&SIMPLE_CONTENT.len().to_string())
+    }
+
+    /// A PDF 1.5 file: page and font packed in a compressed object stream, text drawn with
+    /// two-byte codes of a Type0 font mapped through a ToUnicode CMap.
+    fn packed() -> Vec<u8> {
+        let cmap = b"begincmap 1 begincodespacerange <0000> <FFFF> endcodespacerange \
+            2 beginbfchar <0001> <0048> <0002> <0069> endbfchar \
+            1 beginbfrange <0010> <0012> <0041> endbfrange endcmap";
+        let content = b"BT /F1 10 Tf <00010002> Tj 0 -12 Td <001000110012> Tj ET";
+        let packed_objects = [
+            "<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 7 0 R >> >> /Contents 3 0 R >>",
+            "<< /Type /Font /Subtype /Type0 /BaseFont /X /ToUnicode 4 0 R >>",
+        ];
+        let header = format!("6 0 7 {} ", packed_objects[0].len() + 1);
+        let body = format!("{header}{}\n{}", packed_objects[0], packed_objects[1]);
+        let objstm = stream(
+            &format!("/Type /ObjStm /N 2 /First {} /Filter /FlateDecode", header.len()),
+            &zlib(body.as_bytes()),
+        );
+        pdf(
+            &[
+                b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+                b"<< /Type /Pages /Kids [6 0 R] /Count 1 >>".to_vec(),
+                stream("/Filter /FlateDecode", &zlib(content)),
+                stream("/Filter /FlateDecode", &zlib(cmap)),
+                objstm,
+            ],
+            "<< /Root 1 0 R >>",
+        )
+    }
+
+    #[test]
+    fn simple_pages() {
+        let pages = page_texts(&simple()).unwrap();
+        assert_eq!(pages, vec!["Hello,world\ncafé".to_string(), String::new()]);
+    }
+
+    #[test]
+    fn object_streams_and_to_unicode() {
+        assert_eq!(page_texts(&packed()).unwrap(), vec!["Hi\nABC".to_string()]);
+    }
+
+    #[test]
+    fn rejects_non_pdf_encrypted_and_pageless_files() {
+        assert!(page_texts(b"PK\x03\x04 not a pdf").unwrap_err().contains("Not a PDF"));
+        let encrypted = pdf(&[b"<< /Filter /Standard >>".to_vec()], "<< /Root 2 0 R /Encrypt 1 0 R >>");
+        assert!(page_texts(&encrypted).unwrap_err().contains("encrypted"));
+        let pageless = pdf(&[b"<< /Type /Catalog >>".to_vec()], "<< /Root 1 0 R >>");
+        assert!(page_texts(&pageless).unwrap_err().contains("no pages"));
+    }
+
+    #[test]
+    fn bogus_stream_lengths_fall_back_to_endstream() {
+        for length in ["-5", "3.5", "7", "1e30", "99999999999999999999", "18446744073709551615", "(text)", "9 0 R"] {
+            let pages = page_texts(&simple_with_length(length)).unwrap();
+            assert_eq!(pages[0], "Hello,world\ncafé", "/Length {length}");
+        }
+    }
+
+    #[test]
+    fn hostile_object_stream_offsets() {
+        let cases = [("2", "1e300", "0"), ("1e300", "0", "0"), ("1", "0", "18446744073709551615"), ("1", "-1", "-1")];
+        for (n, first, offset) in cases {
+            let body = format!("3 {offset} << /Type /Page >>");
+            let objstm = stream(&format!("/Type /ObjStm /N {n} /First {first}"), body.as_bytes());
+            let data = pdf(&[b"<< /Type /Pages /Kids [] /Count 0 >>".to_vec(), objstm], "<< >>");
+            // Parses without panicking; whatever it finds is not a usable page tree
+            let _ = page_texts(&data);
+        }
+    }
+
+    #[test]
+    fn truncated_and_damaged_files_do_not_panic() {
+        for data in [simple(), packed()] {
+            for len in 0..data.len() {
+                let _ = page_texts(&data[..len]);
+            }
+            // Flip bytes through the file, one at a time
+            for at in (0..data.len()).step_by(7) {
+                let mut damaged = data.clone();
+                damaged[at] ^= 0x5a;
+                let _ = page_texts(&damaged);
+            }
+        }
+        let _ = page_texts(b"%PDF-1.4\n1 0 obj\n<< /Type /Pages /Kids [2 0 R] >");
+        let _ = page_texts(b"%PDF-1.4\n1 0 obj\n<< /Length 5 >>\nstream\n");
+        let _ = page_texts(b"%PDF-1.4\n1 0 obj\n((((((\\");
+    }
+
+    /// xorshift64: a fixed seed, so a failing mutation can be replayed.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n.max(1) as u64) as usize
+        }
+    }
+
+    /// Fragments that steer mutations into the parser's less travelled paths.
+    const TOKENS: &[&str] = &[
+        "<<", ">>", "[", "]", "(", ")", "\\", "<", ">", "/", "%", "0 0 R", "1 0 R", "2 0 R", " obj ", "endobj",
+        "stream\n", "endstream", "/Length 99999", "/Length -1", "/N 1000000", "/First -1", "/Filter /FlateDecode",
+        "/Type /Pages", "/Kids [2 0 R]", "/Type /ObjStm", "-1e309", "1e309", "Tj", "TJ", "Tf", "Td", "Tm", "ID",
+        "EI", "beginbfrange", "endbfrange", "<0000> <FFFF>", "#", "#zz",
+    ];
+
+    fn mutate(rng: &mut Rng, data: &mut Vec<u8>) {
+        for _ in 0..1 + rng.below(4) {
+            let at = rng.below(data.len() + 1);
+            match rng.below(5) {
+                0 if at < data.len() => data[at] = rng.next() as u8,
+                1 => {
+                    let token = TOKENS[rng.below(TOKENS.len())].as_bytes();
+                    data.splice(at..at, token.iter().copied());
+                }
+                2 => {
+                    let end = (at + rng.below(64)).min(data.len());
+                    data.drain(at..end);
+                }
+                3 => {
+                    let end = (at + rng.below(256)).min(data.len());
+                    let copy = data[at..end].to_vec();
+                    let to = rng.below(data.len() + 1);
+                    data.splice(to..to, copy);
+                }
+                _ => data.truncate(at),
+            }
+        }
+    }
+
+    #[test]
+    fn random_mutations_do_not_panic() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for fixture in [simple(), packed()] {
+            for _ in 0..1500 {
+                let mut data = fixture.clone();
+                mutate(&mut rng, &mut data);
+                let _ = page_texts(&data);
+            }
+        }
+        // Garbage behind a valid header
+        for _ in 0..300 {
+            let mut data = b"%PDF-1.7\n".to_vec();
+            for _ in 0..rng.below(2000) {
+                data.push(rng.next() as u8);
+            }
+            let _ = page_texts(&data);
+        }
+    }
+
+    #[test]
+    fn pathological_structures_terminate() {
+        let deep_array = format!("%PDF-1.4\n1 0 obj\n{}\nendobj", "[".repeat(100_000));
+        assert!(page_texts(deep_array.as_bytes()).is_err());
+        let deep_dict = format!("%PDF-1.4\n1 0 obj\n{}\nendobj", "<< /A ".repeat(50_000));
+        assert!(page_texts(deep_dict.as_bytes()).is_err());
+
+        // Reference cycles, and a page tree that contains itself
+        let cyclic = pdf(
+            &[
+                b"2 0 R".to_vec(),
+                b"1 0 R".to_vec(),
+                b"<< /Type /Pages /Kids [3 0 R 4 0 R 1 0 R] /Count 2 >>".to_vec(),
+                b"<< /Type /Page /Contents 2 0 R /Resources 1 0 R >>".to_vec(),
+            ],
+            "<< /Root 3 0 R >>",
+        );
+        assert_eq!(page_texts(&cyclic).unwrap(), vec![String::new()]);
+
+        // A bfrange covering every two-byte code, and a content stream whose inline image never ends
+        let cmap = b"1 begincodespacerange <0000> <FFFF> endcodespacerange 1 beginbfrange <0000> <FFFF> <0041> endbfrange";
+        let content = b"BT /F1 10 Tf <0000> Tj ET BI /W 1 ID \xff\xfe EI-not-the-end";
+        let data = pdf(
+            &[
+                b"<< /Type /Pages /Kids [2 0 R] /Count 1 >>".to_vec(),
+                b"<< /Type /Page /Resources << /Font << /F1 3 0 R >> >> /Contents 4 0 R >>".to_vec(),
+                b"<< /Type /Font /Subtype /Type0 /ToUnicode 5 0 R >>".to_vec(),
+                stream("", content),
+                stream("", cmap),
+            ],
+            "<< >>",
+        );
+        assert_eq!(page_texts(&data).unwrap(), vec!["A".to_string()]);
+
+        // Unsupported filters and array-valued bfranges give no text instead of a guess
+        let unsupported = pdf(
+            &[
+                b"<< /Type /Pages /Kids [2 0 R] /Count 1 >>".to_vec(),
+                b"<< /Type /Page /Contents 3 0 R >>".to_vec(),
+                stream("/Filter /LZWDecode", b"BT (hidden) Tj ET"),
+            ],
+            "<< >>",
+        );
+        assert_eq!(page_texts(&unsupported).unwrap(), vec![String::new()]);
+        // Pages outside a page tree aren't collected
+        let orphan = pdf(&[b"<< /Type /Page /Contents 2 0 R >>".to_vec(), stream("", b"BT (x) Tj ET")], "<< >>");
+        assert!(page_texts(&orphan).unwrap_err().contains("no pages"));
+    }
+}
@@ -0,0 +1,116 @@
+// Dry run of `switch_model`: every check it makes before killing the running server,
+// plus binary resolution, collected into a report instead of stopping at the first
+// failure. Nothing is spawned, so a settings "test configuration" button answers at once.
+
+use tauri::{AppHandle, Manager};
+
+use crate::compat::{self, CompatReport};
+use crate::error::{self, AppError};
+use crate::llama_config::LlamaConfig;
+use crate::memory::{self, MemoryEstimate};
+use crate::{get_models_dir, llama_exe, resolve_model_path, AppState};
+
+#[derive(Clone, serde::Serialize)]
+pub struct PreflightCheck {
+    /// `model`, `config`, `binary` or `compatibility`.
+    pub name: &'static str,
+    pub passed: bool,
+    /// Why it failed, or a note on a pass (e.g. the resolved binary).
+    pub detail: Option<String>,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct ConfigValidation {
+    /// Resolved model path (first shard of a split model), when it could be resolved.
+    pub model_path: Option<String>,
+    /// Every check passed, so `switch_model` with the same arguments would spawn the server.
+    pub valid: bool,
+    /// `model`, `config`, `binary`, then `compatibility`, which is absent (like `compat` and
+    /// `memory`) when the model can't be resolved.
+    pub checks: Vec<PreflightCheck>,
+    pub compat: Option<CompatReport>,
+    pub memory: Option<MemoryEstimate>,
+    /// Memory shortfalls (or why the estimate failed) and `LlamaConfig::warnings`. Like
+    /// `switch_model`, these don't make the config invalid.
+    pub warnings: Vec<String>,
+}
+
+fn check(name: &'static str, result: Result<Option<String>, String>) -> PreflightCheck {
+    match result {
+        Ok(detail) => PreflightCheck { name, passed: true, detail },
+        Err(detail) => PreflightCheck { name, passed: false, detail: Some(detail) },
+    }
+}
+
+/// Blocking: may read GGUF headers and probe the llama-server binary (both cached).
+fn run(state: &AppState, model_path: String, config: &LlamaConfig) -> ConfigValidation {
+    let mut report = ConfigValidation {
+        model_path: None,
+        valid: false,
+        checks: Vec::new(),
+        compat: None,
+        memory: None,
+        warnings: Vec::new(),
+    };
+    report.checks.push(check("config", config.validate(state).map(|_| None).map_err(|e| e.to_string())));
+    report.checks.push(check(
+        "binary",
+        llama_exe(state).map(|exe| Some(exe.to_string_lossy().to_string())),
+    ));
+
+    let path = match resolve_model_path(state, model_path) {
+        Ok((path, model)) => {
+            let outside = get_models_dir()
+                .canonicalize()
+                .ok()
+                .zip(path.canonicalize().ok())
+                .is_some_and(|(root, p)| !p.starts_with(root));
+            let note = outside.then(|| "Outside the models folder; it won't be listed or restored at startup".to_string());
+            report.checks.insert(0, check("model", Ok(note)));
+            report.model_path = Some(model);
+            path
+        }
+        Err(e) => {
+            report.checks.insert(0, check("model", Err(e.to_string())));
+            return report;
+        }
+    };
+
+    let compat = compat::check(state, &path);
+    report.checks.push(check(
+        "compatibility",
+        if compat.compatible { Ok(None) } else { Err(compat.reason.clone().unwrap_or_default()) },
+    ));
+    report.compat = Some(compat);
+
+    let gpu_layers = memory::gpu_layers(config, &state.settings.lock().unwrap().llama_extra_args);
+    match memory::estimate(state, &path, config.ctx_size(), gpu_layers) {
+        Ok(estimate) => {
+            report.warnings = estimate.warnings.clone();
+            report.memory = Some(estimate);
+        }
+        Err(e) => report.warnings.push(format!("Memory estimate failed: {e}")),
+    }
+
+    report.warnings.extend(config.warnings());
+    report.valid = report.checks.iter().all(|c| c.passed);
+    report
+}
+
+/// Runs `switch_model`'s pre-flight checks for `model_path` (path, alias or shard) and
+/// `config` without stopping the running server or spawning a new one. Failed checks are
+/// reported in the result; the command itself only rejects if the check task fails.
+#[tauri::command]
+pub async fn validate_config(
+    app: AppHandle,
+    model_path: String,
+    config: Option<LlamaConfig>,
+) -> Result<ConfigValidation, AppError> {
+    let handle = app.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        run(&handle.state::<AppState>(), model_path, &config.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| AppError::Other(format!("Config validation failed: {e}")));
+    error::report(&app, "validate_config", result)
+}
@@ -0,0 +1,104 @@
+// Presets: a model bundled with sampling params and a system prompt, one JSON file per
+// preset in <app data>/presets/<name>.json. The file name (without .json) is the preset name.
+
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+use crate::chat::GenParams;
+use crate::error::{self, AppError};
+use crate::llama_config::LlamaConfig;
+use crate::{get_models_dir, settings, AppState};
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct Preset {
+    /// Taken from the file name; any `name` in the file is ignored.
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Model path, alias, or a path relative to the models dir.
+    pub model: String,
+    /// Launch options passed to `switch_model` (context size, GPU layers, …).
+    #[serde(default)]
+    pub config: Option<LlamaConfig>,
+    /// Server-wide sampling defaults; shorthand for (and overriding) `config.params`.
+    #[serde(default)]
+    pub params: Option<GenParams>,
+    /// Replaces the configured system prompt when set; left unchanged otherwise.
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+}
+
+fn presets_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Cannot resolve app data directory: {e}"))?
+        .join("presets");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+fn read_preset(path: &Path) -> Result<Preset, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut preset: Preset =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid preset {}: {}", path.display(), e))?;
+    preset.name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    Ok(preset)
+}
+
+/// All presets, sorted by name. Files that don't parse are skipped with a warning.
+#[tauri::command]
+pub fn list_presets(app: AppHandle) -> Result<Vec<Preset>, String> {
+    let dir = presets_dir(&app)?;
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut presets: Vec<Preset> = entries
+        .map_while(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("json"))
+        .filter_map(|p| read_preset(&p).map_err(|e| eprintln!("Skipping preset: {e}")).ok())
+        .collect();
+    presets.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(presets)
+}
+
+/// Loads the preset's model with its params (via `switch_model`) and, if the preset has
+/// one, makes its system prompt the configured one.
+#[tauri::command]
+pub async fn apply_preset(app: AppHandle, state: State<'_, AppState>, name: String) -> Result<String, AppError> {
+    let preset = (|| {
+        // Names become file names, so keep them to a conservative character set
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ' ') {
+            return Err(AppError::InvalidInput(format!("Invalid preset name: {name}")));
+        }
+        let path = presets_dir(&app)?.join(format!("{name}.json"));
+        if !path.is_file() {
+            return Err(AppError::InvalidInput(format!("Preset not found: {name}")));
+        }
+        Ok(read_preset(&path)?)
+    })();
+    let preset = error::report(&app, "apply_preset", preset)?;
+
+    let relative = get_models_dir().join(&preset.model);
+    let model = if Path::new(&preset.model).is_relative() && relative.exists() {
+        relative.to_string_lossy().to_string()
+    } else {
+        preset.model
+    };
+    // switch_model reports its own failures on `app-error`
+    let mut config = preset.config.unwrap_or_default();
+    if let Some(params) = preset.params {
+        config.params = params;
+    }
+    let message = crate::switch_model(app.clone(), model, Some(config)).await?;
+
+    if let Some(prompt) = preset.system_prompt {
+        let saved = {
+            let mut settings = state.settings.lock().unwrap();
+            settings.system_prompt = if prompt.trim().is_empty() { None } else { Some(prompt) };
+            settings::save(&app, &settings).map_err(AppError::Other)
+        };
+        error::report(&app, "apply_preset", saved)?;
+    }
+    Ok(message)
+}
@@ -0,0 +1,127 @@
+// Chat request queue. A llama-server started with a single slot (the default) handles one
+// completion at a time, and overlapping requests from several panels interleave in that
+// slot, so chat requests take turns through a one-permit semaphore. With
+// `parallel_slots` > 1 the server schedules its own slots and requests pass straight through.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::AppState;
+
+pub struct RequestQueue {
+    slot: Semaphore,
+    queued: AtomicUsize,
+    in_flight: AtomicUsize,
+}
+
+impl Default for RequestQueue {
+    fn default() -> Self {
+        RequestQueue {
+            slot: Semaphore::new(1),
+            queued: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl RequestQueue {
+    /// No chat request is waiting or being generated.
+    pub fn is_idle(&self) -> bool {
+        self.queued.load(Ordering::SeqCst) == 0 && self.in_flight.load(Ordering::SeqCst) == 0
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct QueueStatus {
+    /// Requests waiting for the slot.
+    pub queued: usize,
+    /// Requests being generated (at most 1 while `serialized`).
+    pub in_flight: usize,
+    /// Requests take turns: the running server has a single slot.
+    pub serialized: bool,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct QueueEvent {
+    request_id: Option<String>,
+    /// `queued` when a request starts waiting, `started` when a waiting request gets the slot.
+    state: &'static str,
+    #[serde(flatten)]
+    status: QueueStatus,
+}
+
+/// Held for the duration of a request; releases the slot and the in-flight count on drop.
+pub struct Ticket<'a> {
+    queue: &'a RequestQueue,
+    _permit: Option<SemaphorePermit<'a>>,
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        self.queue.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts a request as queued while it waits, including when the wait is abandoned.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn serialized(state: &AppState) -> bool {
+    let slots = state.server_config.lock().unwrap().as_ref().and_then(|c| c.parallel_slots);
+    slots.unwrap_or(1) <= 1
+}
+
+fn status(state: &AppState) -> QueueStatus {
+    QueueStatus {
+        queued: state.chat_queue.queued.load(Ordering::SeqCst),
+        in_flight: state.chat_queue.in_flight.load(Ordering::SeqCst),
+        serialized: serialized(state),
+    }
+}
+
+fn emit(app: &AppHandle, state: &AppState, request_id: Option<&str>, event: &'static str) {
+    let _ = app.emit(
+        "chat-queue",
+        QueueEvent {
+            request_id: request_id.map(str::to_string),
+            state: event,
+            status: status(state),
+        },
+    );
+}
+
+/// Waits for the slot when the server has a single one. A `chat-queue` event is emitted
+/// only if the request actually has to wait (`queued`) and once it gets the slot
+/// (`started`). Dropping the future while waiting (an abort) leaves the queue.
+pub async fn enter<'a>(app: &AppHandle, state: &'a AppState, request_id: Option<&str>) -> Ticket<'a> {
+    let queue = &state.chat_queue;
+    let mut waited = false;
+    let permit = if !serialized(state) {
+        None
+    } else if let Ok(permit) = queue.slot.try_acquire() {
+        Some(permit)
+    } else {
+        queue.queued.fetch_add(1, Ordering::SeqCst);
+        let _waiting = Waiting(&queue.queued);
+        emit(app, state, request_id, "queued");
+        waited = true;
+        Some(queue.slot.acquire().await.expect("chat queue semaphore is never closed"))
+    };
+    queue.in_flight.fetch_add(1, Ordering::SeqCst);
+    if waited {
+        emit(app, state, request_id, "started");
+    }
+    Ticket { queue, _permit: permit }
+}
+
+/// Chat requests waiting for and holding the server's slot, for a "waiting for model…" indicator.
+#[tauri::command]
+pub fn get_queue_status(state: State<AppState>) -> QueueStatus {
+    status(&state)
+}
@@ -0,0 +1,387 @@
+// Local retrieval-augmented chat: an embedding model runs in a second llama-server on
+// `EMBED_PORT` next to the chat model on `LLAMA_PORT`, so questions can be embedded,
+// matched against the caller's document embeddings, and answered from the best passages
+// without unloading either model. `rag_query` takes documents the caller embedded with
+// `rag_embed`; `rag_index` keeps persisted indexes built on the same server.
+
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::chat::{self, ChatMessage, GenParams};
+use crate::error::{self, AppError};
+use crate::llama_log::{LogLine, LogWriter};
+use crate::{orphans, AppState, ServerMode};
+
+/// Port of the embedding llama-server. It always binds loopback, whatever `bind_host` says.
+pub const EMBED_PORT: u16 = 8082;
+/// Passages fed to the chat model when `rag_query` gets no `top_k`.
+pub(crate) const DEFAULT_TOP_K: usize = 4;
+
+/// The embedding llama-server started by `start_rag_pair`.
+pub struct EmbedServer {
+    child: Child,
+    /// Model path, as `resolve_model_path` returns it.
+    model: String,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct RagPair {
+    pub chat_model: String,
+    pub embed_model: String,
+    pub embed_port: u16,
+    pub embed_pid: u32,
+}
+
+/// A passage the caller has already embedded with `rag_embed`.
+#[derive(Clone, serde::Deserialize)]
+pub struct RagDocument {
+    /// Caller's identifier (file, chunk, …), passed back in the sources.
+    #[serde(default)]
+    pub id: Option<String>,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct RagSource {
+    /// Position of the document in the request's `documents`.
+    pub index: usize,
+    pub id: Option<String>,
+    pub text: String,
+    /// Cosine similarity to the question, -1 to 1.
+    pub score: f32,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct RagAnswer {
+    pub answer: String,
+    /// The passages given to the chat model, most similar first; `[n]` citations in the
+    /// answer refer to position n (1-based) here.
+    pub sources: Vec<RagSource>,
+}
+
+fn embed_url(path: &str) -> String {
+    format!("http://127.0.0.1:{EMBED_PORT}{path}")
+}
+
+/// Kills the embedding server, if one is running. Returns whether one was.
+pub fn stop(state: &AppState) -> bool {
+    match state.rag_embed.lock().unwrap().take() {
+        Some(mut server) => {
+            let _ = server.child.kill();
+            let _ = server.child.wait();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Model of the embedding server, if it is still running.
+pub(crate) fn running_embed_model(state: &AppState) -> Option<String> {
+    let mut guard = state.rag_embed.lock().unwrap();
+    let server = guard.as_mut()?;
+    match server.child.try_wait() {
+        Ok(None) => Some(server.model.clone()),
+        _ => {
+            guard.take();
+            None
+        }
+    }
+}
+
+/// Copies the embedding server's output into the llama log as stream `embed`.
+fn spawn_log_reader(app: &AppHandle, pipe: impl std::io::Read + Send + 'static, pid: u32, structured: bool) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        if let Ok(mut log) = LogWriter::open(&app, structured) {
+            for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                let entry = LogLine::new("embed", Some(pid), line);
+                log.write(&entry);
+                let _ = app.emit("llama-log", entry);
+            }
+        }
+    });
+}
+
+/// Starts `path` in embedding mode on `EMBED_PORT`, with its remembered config, replacing
+/// a previous embedding server.
+fn spawn_embed_server(app: &AppHandle, state: &AppState, path: &Path, model: &str) -> Result<u32, AppError> {
+    stop(state);
+    let exe = crate::llama_exe(state).map_err(AppError::SpawnFailed)?;
+    if std::net::TcpListener::bind(("127.0.0.1", EMBED_PORT)).is_err() {
+        return Err(AppError::PortInUse(EMBED_PORT));
+    }
+    let mut config = crate::remembered_config(state, model);
+    config.mode = ServerMode::Embedding;
+    let mut args = vec![
+        "-m".to_string(),
+        path.to_string_lossy().to_string(),
+        "--port".to_string(),
+        EMBED_PORT.to_string(),
+        "--host".to_string(),
+        "127.0.0.1".to_string(),
+    ];
+    args.extend(config.to_args());
+
+    let structured = state.settings.lock().unwrap().structured_logs;
+    if let Ok(mut log) = LogWriter::open(app, structured) {
+        log.write(&LogLine::new("genhat", None, "--- embedding llama-server start ---"));
+        log.write(&LogLine::new("genhat", None, format!("command: {}", crate::command_line(&exe, &args))));
+    }
+    let work_dir = exe
+        .parent()
+        .ok_or_else(|| AppError::SpawnFailed(format!("llama-server path has no folder: {}", exe.display())))?;
+    let mut child = Command::new(&exe)
+        .args(&args)
+        .current_dir(work_dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| AppError::SpawnFailed(format!("Failed to start llama-server '{}': {}", exe.display(), e)))?;
+    let pid = child.id();
+    orphans::record(app, "embed", pid, &exe);
+    if let Some(stdout) = child.stdout.take() {
+        spawn_log_reader(app, stdout, pid, structured);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        spawn_log_reader(app, stderr, pid, structured);
+    }
+    *state.rag_embed.lock().unwrap() = Some(EmbedServer {
+        child,
+        model: model.to_string(),
+    });
+    Ok(pid)
+}
+
+/// Waits for the embedding server to answer `/health`, failing early if it exits.
+async fn wait_for_embed(state: &AppState, path: &Path) -> Result<(), AppError> {
+    let timeout = crate::ready_timeout(state, path);
+    let deadline = std::time::Instant::now() + timeout;
+    while std::time::Instant::now() < deadline {
+        if crate::wait_for_ready(&embed_url("/health"), std::time::Duration::from_secs(1)).await {
+            return Ok(());
+        }
+        if running_embed_model(state).is_none() {
+            return Err(AppError::SpawnFailed(
+                "The embedding llama-server exited while loading the model".to_string(),
+            ));
+        }
+    }
+    Err(AppError::Timeout(format!(
+        "The embedding llama-server did not become ready within {}s",
+        timeout.as_secs()
+    )))
+}
+
+/// Loads `chat_path` as the chat model (via `switch_model`, unless it is already running
+/// in chat mode) and `embed_path` in a second llama-server in embedding mode on port 8082,
+/// then waits until both are ready. Each uses its remembered launch options. The two
+/// models share the GPU, so leave VRAM for both.
+#[tauri::command]
+pub async fn start_rag_pair(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    chat_path: String,
+    embed_path: String,
+) -> Result<RagPair, AppError> {
+    let result = async {
+        let (_, chat_model) = crate::resolve_model_path(&state, chat_path)?;
+        let (embed_file, embed_model) = crate::resolve_model_path(&state, embed_path)?;
+        if chat_model == embed_model {
+            return Err(AppError::InvalidInput(
+                "The chat and embedding models must be different files".to_string(),
+            ));
+        }
+
+        let loaded = state.current_model.lock().unwrap().as_deref() == Some(chat_model.as_str())
+            && *state.server_mode.lock().unwrap() == ServerMode::Chat;
+        if !loaded {
+            let mut config = crate::remembered_config(&state, &chat_model);
+            // Only pass a config (which would be remembered) when the mode has to change
+            let config = (config.mode != ServerMode::Chat).then(|| {
+                config.mode = ServerMode::Chat;
+                config
+            });
+            crate::switch_model(app.clone(), chat_model.clone(), config).await?;
+        }
+
+        let embed_pid = match running_embed_model(&state) {
+            Some(model) if model == embed_model => state.rag_embed.lock().unwrap().as_ref().map(|s| s.child.id()),
+            _ => None,
+        };
+        let embed_pid = match embed_pid {
+            Some(pid) => pid,
+            None => spawn_embed_server(&app, &state, &embed_file, &embed_model)?,
+        };
+
+        let chat_pid = state.llama.lock().unwrap().as_ref().map(|c| c.id());
+        let chat_pid = chat_pid.ok_or_else(|| AppError::ServerNotRunning("llama-server is not running".to_string()))?;
+        crate::wait_until_ready(&app, chat_pid).await?;
+        wait_for_embed(&state, &embed_file).await?;
+        Ok(RagPair {
+            chat_model,
+            embed_model,
+            embed_port: EMBED_PORT,
+            embed_pid,
+        })
+    }
+    .await;
+    error::report(&app, "start_rag_pair", result)
+}
+
+/// Stops the embedding server started by `start_rag_pair`; the chat model keeps running.
+/// Returns whether one was running.
+#[tauri::command]
+pub fn stop_rag_pair(state: State<AppState>) -> bool {
+    stop(&state)
+}
+
+/// Embeds `texts` with the embedding server, one vector per text in order.
+pub(crate) async fn embed(state: &AppState, texts: &[String]) -> Result<Vec<Vec<f32>>, AppError> {
+    if running_embed_model(state).is_none() {
+        return Err(AppError::ServerNotRunning(
+            "No embedding model is running; start one with start_rag_pair".to_string(),
+        ));
+    }
+    let resp = reqwest::Client::new()
+        .post(embed_url("/v1/embeddings"))
+        .json(&serde_json::json!({ "input": texts }))
+        .send()
+        .await
+        .map_err(|e| AppError::ServerNotRunning(format!("The embedding llama-server is not reachable: {e}")))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(AppError::ServerError(format!("Embedding llama-server returned {status}: {text}")));
+    }
+    let json: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| AppError::ServerError(format!("Invalid response from the embedding llama-server: {e}")))?;
+
+    // `{ data: [{ index, embedding }] }`, not necessarily in input order
+    let data = json["data"]
+        .as_array()
+        .ok_or_else(|| AppError::ServerError("Embedding response had no data".to_string()))?;
+    let mut vectors = vec![None; texts.len()];
+    for item in data {
+        let index = item["index"].as_u64().map(|i| i as usize);
+        let vector: Option<Vec<f32>> = item["embedding"]
+            .as_array()
+            .and_then(|v| v.iter().map(|x| x.as_f64().map(|x| x as f32)).collect());
+        if let (Some(index), Some(vector)) = (index, vector) {
+            if let Some(slot) = vectors.get_mut(index) {
+                *slot = Some(vector);
+            }
+        }
+    }
+    vectors
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| AppError::ServerError("Embedding response is missing vectors".to_string()))
+}
+
+/// Embeds `texts` with the model started by `start_rag_pair`, for building the
+/// `documents` of `rag_query`. Returns one vector per text, in order.
+#[tauri::command]
+pub async fn rag_embed(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    texts: Vec<String>,
+) -> Result<Vec<Vec<f32>>, AppError> {
+    let result = async {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        embed(&state, &texts).await
+    }
+    .await;
+    error::report(&app, "rag_embed", result)
+}
+
+pub(crate) fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 {
+        0.0
+    } else {
+        dot / denom
+    }
+}
+
+/// System message that numbers the passages for citation.
+fn context_prompt(sources: &[RagSource]) -> String {
+    let mut prompt = String::from(
+        "Answer the user's question using only the passages below. Cite the passages you use \
+         by their number, like [1]. If the passages don't contain the answer, say so.\n",
+    );
+    for (i, source) in sources.iter().enumerate() {
+        prompt.push_str(&format!("\n[{}] {}\n", i + 1, source.text.trim()));
+    }
+    prompt
+}
+
+/// Answers `question` from `documents`: embeds the question, picks the `top_k` (default
+/// 4) documents most similar to it, and asks the chat model with those passages as
+/// context. Documents must be embedded by the same model (`rag_embed`); a vector of
+/// another length is rejected.
+#[tauri::command]
+pub async fn rag_query(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    question: String,
+    documents: Vec<RagDocument>,
+    top_k: Option<usize>,
+    params: Option<GenParams>,
+) -> Result<RagAnswer, AppError> {
+    let result = async {
+        if question.trim().is_empty() {
+            return Err(AppError::InvalidInput("The question is empty".to_string()));
+        }
+        if documents.is_empty() {
+            return Err(AppError::InvalidInput("No documents to search".to_string()));
+        }
+        let query = embed(&state, std::slice::from_ref(&question)).await?.remove(0);
+        if let Some((i, doc)) = documents.iter().enumerate().find(|(_, d)| d.embedding.len() != query.len()) {
+            return Err(AppError::InvalidInput(format!(
+                "Document {i} has a {}-dimensional embedding, but the embedding model produces {}; re-embed it with rag_embed",
+                doc.embedding.len(),
+                query.len()
+            )));
+        }
+
+        let mut sources: Vec<RagSource> = documents
+            .into_iter()
+            .enumerate()
+            .map(|(index, doc)| RagSource {
+                index,
+                score: cosine(&query, &doc.embedding),
+                id: doc.id,
+                text: doc.text,
+            })
+            .collect();
+        sources.sort_by(|a, b| b.score.total_cmp(&a.score));
+        sources.truncate(top_k.unwrap_or(DEFAULT_TOP_K).max(1));
+
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: context_prompt(&sources),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: question,
+            },
+        ];
+        let reply = chat::complete_chat(&app, &state, messages, params, None, None, None, None).await?;
+        Ok(RagAnswer {
+            answer: reply.text,
+            sources,
+        })
+    }
+    .await;
+    error::report(&app, "rag_query", result)
+}
@@ -0,0 +1,391 @@
+// Persisted document indexes for RAG: documents are split into overlapping chunks,
+// embedded with the embedding server of `start_rag_pair`, and stored with their vectors in
+// <app data>/rag-indexes/<name>.json (plain JSON, so an index can be inspected by hand).
+// Search is brute force: the query is compared against every chunk by cosine similarity,
+// which is fine for the few thousand chunks a personal knowledge base has.
+
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::{self, AppError};
+use crate::{rag, unix_millis, write_file_atomic, AppState};
+
+/// Bumped when the file layout changes; other versions are rejected rather than misread.
+const INDEX_FORMAT: u32 = 1;
+/// Target chunk length in characters (a few hundred tokens for most models).
+const CHUNK_CHARS: usize = 1000;
+/// Characters shared by consecutive chunks, so a passage cut at a boundary stays findable.
+const CHUNK_OVERLAP: usize = 200;
+/// Chunks embedded per `/v1/embeddings` request.
+const EMBED_BATCH: usize = 32;
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Clone, serde::Deserialize)]
+pub struct Document {
+    /// Defaults to the document's position in the request.
+    #[serde(default)]
+    pub id: Option<String>,
+    pub text: String,
+    /// Anything the caller wants back with search hits (title, path, …).
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct IndexStats {
+    pub name: String,
+    /// Embedding model the vectors came from (as `resolve_model_path` returns it).
+    pub embed_model: String,
+    pub dimensions: usize,
+    pub documents: usize,
+    pub chunks: usize,
+    /// Size of the index file.
+    #[serde(default)]
+    pub bytes: u64,
+    pub created_at: u64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct DocumentEntry {
+    id: String,
+    metadata: Option<serde_json::Value>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ChunkEntry {
+    /// Position in `IndexFile.documents`.
+    document: usize,
+    /// Position of the chunk within its document.
+    chunk: usize,
+    text: String,
+    embedding: Vec<f32>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct IndexFile {
+    format: u32,
+    stats: IndexStats,
+    documents: Vec<DocumentEntry>,
+    chunks: Vec<ChunkEntry>,
+}
+
+/// Just the stats, for `list_indexes`; the vectors are skipped rather than kept.
+#[derive(serde::Deserialize)]
+struct IndexHeader {
+    format: u32,
+    stats: IndexStats,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct IndexHit {
+    pub document_id: String,
+    pub chunk: usize,
+    pub text: String,
+    /// Cosine similarity to the query, -1 to 1.
+    pub score: f32,
+    pub metadata: Option<serde_json::Value>,
+}
+
+fn index_dir(app: &AppHandle) -> Result<PathBuf, AppError> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Io(format!("Failed to resolve app data dir: {e}")))?
+        .join("rag-indexes");
+    std::fs::create_dir_all(&dir).map_err(|e| AppError::Io(format!("Failed to create {}: {}", dir.display(), e)))?;
+    Ok(dir)
+}
+
+/// Names become file names: letters, digits, `-` and `_` only.
+fn index_path(app: &AppHandle, name: &str) -> Result<PathBuf, AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid index name '{name}': use up to {MAX_NAME_LEN} letters, digits, '-' or '_'"
+        )));
+    }
+    Ok(index_dir(app)?.join(format!("{name}.json")))
+}
+
+/// Splits `text` into chunks of about `CHUNK_CHARS` characters overlapping by
+/// `CHUNK_OVERLAP`, ending at whitespace where there is some in the second half.
+fn chunk_text(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = (start + CHUNK_CHARS).min(chars.len());
+        if end < chars.len() {
+            if let Some(space) = (start + CHUNK_CHARS / 2..end).rev().find(|&i| chars[i].is_whitespace()) {
+                end = space;
+            }
+        }
+        let chunk: String = chars[start..end].iter().collect();
+        if !chunk.trim().is_empty() {
+            chunks.push(chunk.trim().to_string());
+        }
+        if end == chars.len() {
+            break;
+        }
+        start = end.saturating_sub(CHUNK_OVERLAP).max(start + 1);
+    }
+    chunks
+}
+
+fn no_embed_server() -> AppError {
+    AppError::ServerNotRunning("No embedding model is running; start one with start_rag_pair".to_string())
+}
+
+/// Chunks and embeds `documents` with the running embedding model and saves them as index
+/// `name`, replacing an index of that name. Needs `start_rag_pair` first.
+#[tauri::command]
+pub async fn build_index(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+    documents: Vec<Document>,
+) -> Result<IndexStats, AppError> {
+    let result = build(&app, &state, name, documents).await;
+    error::report(&app, "build_index", result)
+}
+
+async fn build(app: &AppHandle, state: &AppState, name: String, documents: Vec<Document>) -> Result<IndexStats, AppError> {
+    let path = index_path(app, &name)?;
+    if documents.is_empty() {
+        return Err(AppError::InvalidInput("No documents to index".to_string()));
+    }
+    let embed_model = rag::running_embed_model(state).ok_or_else(no_embed_server)?;
+
+    let mut entries = Vec::with_capacity(documents.len());
+    let mut pending = Vec::new();
+    for (i, doc) in documents.into_iter().enumerate() {
+        for (chunk, text) in chunk_text(&doc.text).into_iter().enumerate() {
+            pending.push((entries.len(), chunk, text));
+        }
+        entries.push(DocumentEntry {
+            id: doc.id.unwrap_or_else(|| i.to_string()),
+            metadata: doc.metadata,
+        });
+    }
+    if pending.is_empty() {
+        return Err(AppError::InvalidInput("The documents contain no text".to_string()));
+    }
+
+    let mut chunks = Vec::with_capacity(pending.len());
+    for batch in pending.chunks(EMBED_BATCH) {
+        let texts: Vec<String> = batch.iter().map(|(_, _, text)| text.clone()).collect();
+        let vectors = rag::embed(state, &texts).await?;
+        for ((document, chunk, text), embedding) in batch.iter().cloned().zip(vectors) {
+            chunks.push(ChunkEntry {
+                document,
+                chunk,
+                text,
+                embedding,
+            });
+        }
+    }
+    let dimensions = chunks[0].embedding.len();
+
+    let mut index = IndexFile {
+        format: INDEX_FORMAT,
+        stats: IndexStats {
+            name,
+            embed_model,
+            dimensions,
+            documents: entries.len(),
+            chunks: chunks.len(),
+            bytes: 0,
+            created_at: unix_millis(),
+        },
+        documents: entries,
+        chunks,
+    };
+    let json = serde_json::to_string(&index).map_err(|e| AppError::Other(e.to_string()))?;
+    // A rebuild replaces the old index only once the new one is completely written
+    write_file_atomic(&path, json.as_bytes())
+        .map_err(|e| AppError::Io(format!("Failed to write {}: {}", path.display(), e)))?;
+    index.stats.bytes = json.len() as u64;
+    Ok(index.stats)
+}
+
+fn read_index(app: &AppHandle, name: &str) -> Result<IndexFile, AppError> {
+    let path = index_path(app, name)?;
+    if !path.is_file() {
+        return Err(AppError::InvalidInput(format!("Index not found: {name}")));
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| AppError::Io(format!("Failed to read {}: {}", path.display(), e)))?;
+    let index: IndexFile = serde_json::from_str(&json)
+        .map_err(|e| AppError::InvalidOutput(format!("Failed to parse {}: {}", path.display(), e)))?;
+    if index.format != INDEX_FORMAT {
+        return Err(AppError::Incompatible(format!("Index {name} has unsupported format {}", index.format)));
+    }
+    Ok(index)
+}
+
+/// The `top_k` (default 4) chunks of index `name` most similar to `query`, best first.
+/// The query is embedded by the running embedding model, which must be the one the index
+/// was built with.
+#[tauri::command]
+pub async fn search_index(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+    query: String,
+    top_k: Option<usize>,
+) -> Result<Vec<IndexHit>, AppError> {
+    let result = search(&app, &state, &name, query, top_k).await;
+    error::report(&app, "search_index", result)
+}
+
+async fn search(
+    app: &AppHandle,
+    state: &AppState,
+    name: &str,
+    query: String,
+    top_k: Option<usize>,
+) -> Result<Vec<IndexHit>, AppError> {
+    let index = read_index(app, name)?;
+    let running = rag::running_embed_model(state).ok_or_else(no_embed_server)?;
+    if running != index.stats.embed_model {
+        return Err(AppError::Incompatible(format!(
+            "Index {name} was built with {}, but the embedding server runs {running}",
+            index.stats.embed_model
+        )));
+    }
+    let query = rag::embed(state, std::slice::from_ref(&query)).await?.remove(0);
+    if query.len() != index.stats.dimensions {
+        return Err(AppError::Incompatible(format!(
+            "The query embedding has {} dimensions, but index {name} has {}",
+            query.len(),
+            index.stats.dimensions
+        )));
+    }
+
+    let mut scored: Vec<(f32, &ChunkEntry)> =
+        index.chunks.iter().map(|c| (rag::cosine(&query, &c.embedding), c)).collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.truncate(top_k.unwrap_or(rag::DEFAULT_TOP_K).max(1));
+    Ok(scored
+        .into_iter()
+        .filter_map(|(score, c)| {
+            let doc = index.documents.get(c.document)?;
+            Some(IndexHit {
+                document_id: doc.id.clone(),
+                chunk: c.chunk,
+                text: c.text.clone(),
+                score,
+                metadata: doc.metadata.clone(),
+            })
+        })
+        .collect())
+}
+
+/// Stats of the saved indexes, newest first. Unreadable files are skipped with a warning.
+#[tauri::command]
+pub fn list_indexes(app: AppHandle) -> Result<Vec<IndexStats>, AppError> {
+    let result = list(&app);
+    error::report(&app, "list_indexes", result)
+}
+
+fn list(app: &AppHandle) -> Result<Vec<IndexStats>, AppError> {
+    let dir = index_dir(app)?;
+    let entries =
+        std::fs::read_dir(&dir).map_err(|e| AppError::Io(format!("Failed to read {}: {}", dir.display(), e)))?;
+    let mut indexes = Vec::new();
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let header = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str::<IndexHeader>(&json).map_err(|e| e.to_string()));
+        match header {
+            Ok(header) if header.format == INDEX_FORMAT => {
+                let mut stats = header.stats;
+                stats.bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                indexes.push(stats);
+            }
+            Ok(header) => eprintln!("Skipping {}: unsupported format {}", path.display(), header.format),
+            Err(e) => eprintln!("Skipping unreadable index {}: {}", path.display(), e),
+        }
+    }
+    indexes.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+    Ok(indexes)
+}
+
+/// Deletes index `name`. Returns false if there was none.
+#[tauri::command]
+pub fn delete_index(app: AppHandle, name: String) -> Result<bool, AppError> {
+    let result = index_path(&app, &name).and_then(|path| {
+        if !path.is_file() {
+            return Ok(false);
+        }
+        std::fs::remove_file(&path).map_err(|e| AppError::Io(format!("Failed to delete {}: {}", path.display(), e)))?;
+        Ok(true)
+    });
+    error::report(&app, "delete_index", result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn char_count(chunks: &[String]) -> Vec<usize> {
+        chunks.iter().map(|c| c.chars().count()).collect()
+    }
+
+    #[test]
+    fn blank_text_has_no_chunks() {
+        assert!(chunk_text("").is_empty());
+        assert!(chunk_text(" \n\t ").is_empty());
+        assert_eq!(chunk_text("  short text \n"), ["short text"]);
+    }
+
+    #[test]
+    fn text_of_exactly_one_chunk_is_not_split() {
+        let text = "x".repeat(CHUNK_CHARS);
+        assert_eq!(chunk_text(&text), [text]);
+    }
+
+    #[test]
+    fn text_past_the_chunk_size_overlaps_the_next_chunk() {
+        let text: String = (0..=CHUNK_CHARS).map(|i| char::from(b'a' + (i % 26) as u8)).collect();
+        let chunks = chunk_text(&text);
+        assert_eq!(char_count(&chunks), [CHUNK_CHARS, CHUNK_OVERLAP + 1]);
+        assert_eq!(chunks[1], text[CHUNK_CHARS - CHUNK_OVERLAP..]);
+    }
+
+    #[test]
+    fn chunks_end_at_whitespace_and_cover_every_word() {
+        let words: Vec<String> = (0..1000).map(|i| format!("w{i}")).collect();
+        let chunks = chunk_text(&words.join(" "));
+        assert!(chunks.len() > 1);
+        for (chunk, next) in chunks.iter().zip(&chunks[1..]) {
+            assert!(chunk.chars().count() <= CHUNK_CHARS);
+            // Whole words only, and the next chunk starts inside this one's tail
+            assert!(chunk.split(' ').all(|w| words.iter().any(|word| word == w)), "{chunk}");
+            let head: String = next.chars().take(CHUNK_OVERLAP / 2).collect();
+            assert!(chunk.contains(&head), "{head}");
+        }
+        for word in &words {
+            assert!(chunks.iter().any(|c| c.split(' ').any(|w| w == word)), "{word}");
+        }
+    }
+
+    #[test]
+    fn multibyte_text_splits_on_char_boundaries() {
+        let dense = "é".repeat(CHUNK_CHARS * 2 + 7);
+        let chunks = chunk_text(&dense);
+        assert_eq!(char_count(&chunks)[..2], [CHUNK_CHARS, CHUNK_CHARS]);
+        assert!(chunks.iter().all(|c| c.chars().all(|ch| ch == 'é')));
+
+        let mixed = "日本語 🙂🙂 ".repeat(CHUNK_CHARS / 3);
+        let chunks = chunk_text(&mixed);
+        assert!(chunks.len() > 1);
+        assert!(char_count(&chunks).iter().all(|&n| n <= CHUNK_CHARS));
+        assert!(chunks.iter().all(|c| c.starts_with(['日', '本', '語', '🙂'])));
+    }
+}
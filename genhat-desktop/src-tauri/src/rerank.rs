@@ -0,0 +1,85 @@
+// Reranking: scores candidate passages against a query via llama-server's /rerank
+// endpoint. Needs a reranker GGUF loaded with `switch_model(mode: "rerank")`.
+
+use tauri::{AppHandle, State};
+
+use crate::chat::not_reachable;
+use crate::error::{self, AppError};
+use crate::{AppState, ServerMode};
+
+#[derive(Clone, serde::Serialize)]
+pub struct RankScore {
+    /// Position of the document in the request's `documents`.
+    pub index: usize,
+    pub score: f32,
+}
+
+/// Parses llama-server's `{ results: [{ index, relevance_score }] }` (older builds return
+/// the bare array) into scores sorted by relevance, highest first.
+fn parse_scores(json: &serde_json::Value) -> Result<Vec<RankScore>, AppError> {
+    let results = json
+        .get("results")
+        .unwrap_or(json)
+        .as_array()
+        .ok_or_else(|| AppError::ServerError("llama-server rerank response had no results".to_string()))?;
+    let mut scores: Vec<RankScore> = results
+        .iter()
+        .filter_map(|r| {
+            Some(RankScore {
+                index: r["index"].as_u64()? as usize,
+                score: r["relevance_score"].as_f64()? as f32,
+            })
+        })
+        .collect();
+    scores.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(scores)
+}
+
+async fn send_rerank(state: &AppState, query: &str, documents: &[String]) -> Result<Vec<RankScore>, AppError> {
+    let resp = reqwest::Client::new()
+        .post(crate::llama_url(state, "/rerank"))
+        .json(&serde_json::json!({ "query": query, "documents": documents }))
+        .send()
+        .await
+        .map_err(not_reachable)?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(AppError::ServerError(format!("llama-server returned {status}: {text}")));
+    }
+
+    let json: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| AppError::ServerError(format!("Invalid response from llama-server: {e}")))?;
+    parse_scores(&json)
+}
+
+/// Ranks `documents` by relevance to `query`. Returns one entry per document, most
+/// relevant first; `index` refers back into `documents`.
+#[tauri::command]
+pub async fn rerank(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    query: String,
+    documents: Vec<String>,
+) -> Result<Vec<RankScore>, AppError> {
+    let result = async {
+        if documents.is_empty() {
+            return Ok(Vec::new());
+        }
+        if state.current_model.lock().unwrap().is_none() {
+            return Err(AppError::ServerNotRunning("llama-server is not running".to_string()));
+        }
+        if *state.server_mode.lock().unwrap() != ServerMode::Rerank {
+            return Err(AppError::WrongMode(
+                "The loaded model is not running as a reranker; load a reranker GGUF with switch_model(mode: \"rerank\")"
+                    .to_string(),
+            ));
+        }
+        send_rerank(&state, &query, &documents).await
+    }
+    .await;
+    error::report(&app, "rerank", result)
+}
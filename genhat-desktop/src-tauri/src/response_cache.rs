@@ -0,0 +1,102 @@
+// On-disk cache of chat responses for deterministic requests, stored in
+// <app data>/cache/responses/<sha256>.json.
+
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::chat::GenParams;
+use crate::llama_config::LlamaConfig;
+use crate::{unix_millis, write_file_atomic};
+
+/// Oldest entries beyond this count are evicted after each insert.
+const MAX_ENTRIES: usize = 200;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CacheEntry {
+    response: String,
+    created_at: u64,
+}
+
+fn cache_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Cannot resolve app data directory: {e}"))?
+        .join("cache")
+        .join("responses");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// Only requests whose output can't vary between runs are cacheable:
+/// greedy sampling (temperature 0) or a fixed seed (-1 asks for a random one).
+pub fn is_deterministic(params: &GenParams) -> bool {
+    params.temperature == Some(0.0) || params.seed.is_some_and(|seed| seed >= 0)
+}
+
+/// Hashes the model path, the config and extra flags the server was launched with (LoRA
+/// adapters, chat template, sampling defaults, …, all of which change the output of the same
+/// request) and the full request body (messages, sampling params, logit bias, ...).
+pub fn cache_key(model: &str, config: Option<&LlamaConfig>, extra_args: &[String], body: &serde_json::Value) -> String {
+    let material = serde_json::json!({
+        "model": model,
+        "config": config,
+        "extra_args": extra_args,
+        "body": body,
+    });
+    let digest = Sha256::digest(material.to_string().as_bytes());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn get(app: &AppHandle, key: &str) -> Option<String> {
+    let path = cache_dir(app).ok()?.join(format!("{key}.json"));
+    let json = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str::<CacheEntry>(&json).ok().map(|e| e.response)
+}
+
+pub fn put(app: &AppHandle, key: &str, response: &str) -> Result<(), String> {
+    let dir = cache_dir(app)?;
+    let entry = CacheEntry {
+        response: response.to_string(),
+        created_at: unix_millis(),
+    };
+    let json = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+    write_file_atomic(&dir.join(format!("{key}.json")), json.as_bytes())
+        .map_err(|e| format!("Failed to write cache entry: {e}"))?;
+    evict(&dir);
+    Ok(())
+}
+
+/// Drops the oldest entries (by modification time) until at most MAX_ENTRIES remain.
+fn evict(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|e| {
+            let modified = e.metadata().ok()?.modified().ok()?;
+            Some((modified, e.path()))
+        })
+        .collect();
+    if files.len() <= MAX_ENTRIES {
+        return;
+    }
+    files.sort_by_key(|(modified, _)| *modified);
+    let excess = files.len() - MAX_ENTRIES;
+    for (_, path) in files.into_iter().take(excess) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Deletes every cached response. Returns how many entries were removed.
+#[tauri::command]
+pub fn clear_cache(app: AppHandle) -> Result<usize, String> {
+    let dir = cache_dir(&app)?;
+    let mut removed = 0;
+    for entry in std::fs::read_dir(&dir).map_err(|e| e.to_string())?.flatten() {
+        if std::fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,363 @@
+// Persistent user settings, stored as settings.json in the Tauri app data dir.
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, State};
+
+use crate::category::ModelCategory;
+use crate::chat::GenParams;
+use crate::error::{self, AppError};
+use crate::llama_config::LlamaConfig;
+use crate::{write_file_atomic, AppState};
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Prepended as a system message to chat requests that don't carry their own.
+    pub system_prompt: Option<String>,
+    /// Write the llama log as JSONL (`{timestamp, stream, pid, message}` per line).
+    pub structured_logs: bool,
+    /// Model paths favorited with `pin_model`, listed first by `list_models`.
+    pub pinned_models: Vec<String>,
+    /// Model path → display alias (`set_model_alias`). Aliases are unique.
+    pub model_aliases: BTreeMap<String, String>,
+    /// Model path → category, overriding detection in `list_models`.
+    pub model_categories: BTreeMap<String, ModelCategory>,
+    /// Model path → launch options last passed to `switch_model` (or `set_model_config`),
+    /// used when it is started without a config.
+    pub model_configs: BTreeMap<String, ModelConfig>,
+    /// Appended verbatim to the llama-server command line (`set_llama_extra_args`).
+    pub llama_extra_args: Vec<String>,
+    /// Model (path or alias) started at launch, ahead of `last_model` and the built-in pick.
+    pub default_model: Option<String>,
+    /// Model most recently loaded through `switch_model`.
+    pub last_model: Option<String>,
+    /// Start a model at launch at all. Defaults to true.
+    pub autostart: bool,
+    /// Server-wide token limit (`-n`) when `switch_model` params don't set one; `None` = 256.
+    pub max_tokens: Option<i32>,
+    /// llama-server `--host`; `None` = 127.0.0.1 (this machine only).
+    pub bind_host: Option<String>,
+    /// OpenAI-compatible server `chat_completion` uses while no local llama-server is
+    /// running; `None` (the default) keeps chat strictly local.
+    pub remote_endpoint: Option<RemoteEndpoint>,
+    /// Reuse audio of identical non-streamed `generate_speech` requests (see `tts_cache`).
+    pub tts_cache: bool,
+    /// TTS cache size limit in MiB; `None` = 500.
+    pub tts_cache_max_mb: Option<u64>,
+    /// Seconds a starting llama-server gets to become ready before the load counts as
+    /// failed, before the per-GiB allowance for large models; `None` = 120.
+    pub ready_timeout_secs: Option<u64>,
+    /// Readiness probe path; `None` = `/health`.
+    pub health_path: Option<String>,
+    /// `/health` pings that catch a llama-server which is running but hung (see `keepalive`).
+    pub keepalive: KeepAlive,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct KeepAlive {
+    /// Off by default.
+    pub enabled: bool,
+    /// Seconds between pings while idle; at least 5.
+    pub interval_secs: u64,
+    /// Consecutive missed pings before the server counts as unresponsive; at least 1.
+    pub failure_threshold: u32,
+    /// Restart an unresponsive server with the same model and config.
+    pub restart: bool,
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        KeepAlive {
+            enabled: false,
+            interval_secs: 30,
+            failure_threshold: 3,
+            restart: false,
+        }
+    }
+}
+
+/// The per-model part of a `LlamaConfig`: what is worth remembering for each model.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ModelConfig {
+    pub ctx_size: Option<u32>,
+    pub gpu_layers: Option<i32>,
+    pub auto_gpu_layers: bool,
+    /// Sampling params as passed to `switch_model`, before the profile and settings.
+    pub params: GenParams,
+}
+
+impl ModelConfig {
+    pub fn from_launch(config: &LlamaConfig) -> ModelConfig {
+        ModelConfig {
+            ctx_size: config.ctx_size,
+            gpu_layers: config.gpu_layers,
+            auto_gpu_layers: config.auto_gpu_layers,
+            params: config.params.clone(),
+        }
+    }
+
+    /// A launch config with these options and the defaults for everything else.
+    pub fn to_launch(&self) -> LlamaConfig {
+        LlamaConfig {
+            ctx_size: self.ctx_size,
+            gpu_layers: self.gpu_layers,
+            auto_gpu_layers: self.auto_gpu_layers,
+            params: self.params.clone(),
+            ..LlamaConfig::default()
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RemoteEndpoint {
+    /// API root the `/chat/completions` path is appended to, e.g. `http://192.168.1.20:8080/v1`.
+    pub base_url: String,
+    /// Sent as `Authorization: Bearer <key>`.
+    pub api_key: Option<String>,
+    /// `model` field of each request, for servers that host several.
+    pub model: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            system_prompt: None,
+            structured_logs: false,
+            pinned_models: Vec::new(),
+            model_aliases: BTreeMap::new(),
+            model_categories: BTreeMap::new(),
+            model_configs: BTreeMap::new(),
+            llama_extra_args: Vec::new(),
+            default_model: None,
+            last_model: None,
+            autostart: true,
+            max_tokens: None,
+            bind_host: None,
+            remote_endpoint: None,
+            tts_cache: false,
+            tts_cache_max_mb: None,
+            ready_timeout_secs: None,
+            health_path: None,
+            keepalive: KeepAlive::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Maps an alias to its model path; anything else is returned unchanged.
+    pub fn resolve_model(&self, name: &str) -> String {
+        self.model_aliases
+            .iter()
+            .find(|(_, alias)| *alias == name)
+            .map(|(path, _)| path.clone())
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// The `bind_host` address, falling back to loopback when unset or (from a hand-edited
+    /// file) unparsable.
+    pub fn bind_host(&self) -> IpAddr {
+        let loopback = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        match self.bind_host.as_deref().map(str::parse::<IpAddr>) {
+            None => loopback,
+            Some(Ok(host)) => host,
+            Some(Err(_)) => {
+                eprintln!("Ignoring invalid bind_host setting; binding llama-server to {loopback}");
+                loopback
+            }
+        }
+    }
+}
+
+pub fn settings_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path().app_data_dir().ok().map(|d| d.join("settings.json"))
+}
+
+/// Loads settings, falling back to defaults when the file is missing or unreadable. A file
+/// that doesn't parse is renamed to settings.json.bad first, so the next save can't
+/// overwrite what the user wrote, and the problem is reported on `app-error`.
+pub fn load(app: &AppHandle) -> Settings {
+    let Some(path) = settings_path(app) else { return Settings::default() };
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Settings::default(),
+        Err(e) => {
+            let error = AppError::Io(format!("Failed to read {}: {}; using default settings", path.display(), e));
+            eprintln!("{error}");
+            let _ = error::report::<()>(app, "load_settings", Err(error));
+            return Settings::default();
+        }
+    };
+    match serde_json::from_str(&json) {
+        Ok(settings) => settings,
+        Err(e) => {
+            let bad = path.with_extension("json.bad");
+            let error = match std::fs::rename(&path, &bad) {
+                Ok(()) => AppError::InvalidInput(format!(
+                    "{} is not valid settings ({e}); it was moved to {} and the defaults are in use",
+                    path.display(),
+                    bad.display()
+                )),
+                Err(rename) => AppError::Io(format!(
+                    "{} is not valid settings ({e}) and could not be moved aside ({rename}); \
+                     the defaults are in use and the next change will overwrite it",
+                    path.display()
+                )),
+            };
+            eprintln!("{error}");
+            let _ = error::report::<()>(app, "load_settings", Err(error));
+            Settings::default()
+        }
+    }
+}
+
+pub fn save(app: &AppHandle, settings: &Settings) -> Result<(), String> {
+    let path = settings_path(app).ok_or("Cannot resolve app data directory")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    write_file_atomic(&path, json.as_bytes()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Sets the model started at launch (path or alias); `None` or an empty string clears it.
+#[tauri::command]
+pub fn set_default_model(app: AppHandle, state: State<AppState>, path: Option<String>) -> Result<(), String> {
+    let mut settings = state.settings.lock().unwrap();
+    let path = path.filter(|p| !p.trim().is_empty());
+    if let Some(p) = &path {
+        if !std::path::Path::new(&settings.resolve_model(p)).is_file() {
+            return Err(format!("Model file not found: {}", p));
+        }
+    }
+    settings.default_model = path;
+    save(&app, &settings)
+}
+
+/// Enables or disables starting a model at launch.
+#[tauri::command]
+pub fn set_autostart(app: AppHandle, state: State<AppState>, enabled: bool) -> Result<(), String> {
+    let mut settings = state.settings.lock().unwrap();
+    settings.autostart = enabled;
+    save(&app, &settings)
+}
+
+/// Sets the startup token limit (-1 = unlimited; `None` restores the built-in 256).
+/// Takes effect the next time llama-server is started.
+#[tauri::command]
+pub fn set_max_tokens(app: AppHandle, state: State<AppState>, value: Option<i32>) -> Result<(), String> {
+    let check = GenParams {
+        max_tokens: value,
+        ..GenParams::default()
+    };
+    check.validate()?;
+    let mut settings = state.settings.lock().unwrap();
+    settings.max_tokens = value;
+    save(&app, &settings)
+}
+
+/// Sets the address llama-server listens on (an IP such as `0.0.0.0` for every interface,
+/// or one LAN address); `None` or an empty string restores 127.0.0.1. Anything other than
+/// loopback exposes the unauthenticated server to the network. Takes effect the next time
+/// llama-server is started.
+#[tauri::command]
+pub fn set_bind_host(app: AppHandle, state: State<AppState>, host: Option<String>) -> Result<(), String> {
+    let host = host.map(|h| h.trim().to_string()).filter(|h| !h.is_empty());
+    if let Some(h) = &host {
+        h.parse::<IpAddr>().map_err(|_| format!("Invalid bind address: {h} (expected an IP address such as 0.0.0.0)"))?;
+    }
+    let mut settings = state.settings.lock().unwrap();
+    settings.bind_host = host;
+    save(&app, &settings)
+}
+
+/// Sets the remote chat fallback (see `Settings::remote_endpoint`); `None` turns it off.
+/// `base_url` must be http(s); a trailing slash is dropped and an empty key or model is unset.
+#[tauri::command]
+pub fn set_remote_endpoint(app: AppHandle, state: State<AppState>, endpoint: Option<RemoteEndpoint>) -> Result<(), String> {
+    let endpoint = match endpoint {
+        Some(e) => {
+            let base_url = e.base_url.trim().trim_end_matches('/').to_string();
+            if !(base_url.starts_with("http://") || base_url.starts_with("https://")) || base_url.contains(char::is_whitespace) {
+                return Err(format!("Invalid remote endpoint URL: {} (expected http:// or https://)", e.base_url));
+            }
+            let non_empty = |v: Option<String>| v.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+            Some(RemoteEndpoint {
+                base_url,
+                api_key: non_empty(e.api_key),
+                model: non_empty(e.model),
+            })
+        }
+        None => None,
+    };
+    let mut settings = state.settings.lock().unwrap();
+    settings.remote_endpoint = endpoint;
+    save(&app, &settings)
+}
+
+/// Turns the TTS cache on or off and sets its size limit in MiB (`None` = 500). The limit
+/// is applied from the next stored file; `clear_tts_cache` empties it.
+#[tauri::command]
+pub fn set_tts_cache(app: AppHandle, state: State<AppState>, enabled: bool, max_mb: Option<u64>) -> Result<(), String> {
+    if max_mb == Some(0) {
+        return Err("TTS cache limit must be at least 1 MiB".to_string());
+    }
+    let mut settings = state.settings.lock().unwrap();
+    settings.tts_cache = enabled;
+    settings.tts_cache_max_mb = max_mb;
+    save(&app, &settings)
+}
+
+/// Sets how llama-server readiness is checked: the base timeout in seconds (10–3600) and
+/// the probe path (must start with `/`). `None` or an empty path restores the default
+/// (120s, `/health`). Takes effect the next time llama-server is started.
+#[tauri::command]
+pub fn set_readiness_check(
+    app: AppHandle,
+    state: State<AppState>,
+    timeout_secs: Option<u64>,
+    health_path: Option<String>,
+) -> Result<(), String> {
+    if let Some(secs) = timeout_secs {
+        if !(10..=3600).contains(&secs) {
+            return Err(format!("Readiness timeout must be 10–3600 seconds (got {secs})"));
+        }
+    }
+    let health_path = health_path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(p) = &health_path {
+        if !p.starts_with('/') || p.contains(char::is_whitespace) {
+            return Err(format!("Invalid readiness probe path: {p} (expected e.g. /health)"));
+        }
+    }
+    let mut settings = state.settings.lock().unwrap();
+    settings.ready_timeout_secs = timeout_secs;
+    settings.health_path = health_path;
+    save(&app, &settings)
+}
+
+/// Configures the llama-server keep-alive (see `Settings::keepalive`); `None` restores the
+/// defaults (off). Applies to the running server from its next ping.
+#[tauri::command]
+pub fn set_keepalive(app: AppHandle, state: State<AppState>, keepalive: Option<KeepAlive>) -> Result<(), String> {
+    let keepalive = keepalive.unwrap_or_default();
+    if keepalive.interval_secs < 5 {
+        return Err(format!("Keep-alive interval must be at least 5 seconds (got {})", keepalive.interval_secs));
+    }
+    if keepalive.failure_threshold < 1 {
+        return Err("Keep-alive failure threshold must be at least 1".to_string());
+    }
+    let mut settings = state.settings.lock().unwrap();
+    settings.keepalive = keepalive;
+    save(&app, &settings)
+}
+
+/// Switches the llama log format. Takes effect the next time llama-server is started.
+#[tauri::command]
+pub fn set_structured_logs(app: AppHandle, state: State<AppState>, enabled: bool) -> Result<(), String> {
+    let mut settings = state.settings.lock().unwrap();
+    settings.structured_logs = enabled;
+    save(&app, &settings)
+}
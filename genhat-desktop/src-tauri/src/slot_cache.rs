@@ -0,0 +1,342 @@
+// System-prompt prefix caching. Chat requests are sent with `cache_prompt`, so a slot keeps
+// the KV cache of its last prompt and a request starting with the same injected system
+// prompt only evaluates what follows it. Once a server is ready (and whenever the system
+// prompt changes) the prefix is primed with a system-only request. With `slot_save_path`
+// set on a single-slot server, the primed slot is also saved to
+// <slot_save_path>/genhat-slot-<hash>.bin per model file and system prompt, and restored
+// instead of re-primed when the same combination starts again.
+//
+// Named caches (`save_slot_cache` / `load_slot_cache`) snapshot slot 0 at any point, e.g.
+// after a long document was read in, as <slot folder>/<name>.bin with a <name>.json
+// sidecar naming the model. The slot folder is `slot_save_path`, else
+// <app data>/slot-caches, which every server is started with.
+
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+use crate::chat::{self, ChatMessage, GenParams};
+use crate::error::{self, AppError};
+use crate::{llama_url, queue, unix_millis, AppState, ServerMode};
+
+/// Saved slots GenHat writes; `clear_slot_cache` deletes only these.
+const FILE_PREFIX: &str = "genhat-slot-";
+
+/// Longest accepted named-cache name.
+const MAX_NAME_LEN: usize = 64;
+
+/// Size and modification time (ms since the epoch) of the model file, so a cache for
+/// replaced weights is told apart.
+fn model_stamp(model: &Path) -> (Option<u64>, Option<u64>) {
+    let meta = std::fs::metadata(model).ok();
+    let modified = meta
+        .as_ref()
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64);
+    (meta.map(|m| m.len()), modified)
+}
+
+/// Names the saved slot for `model` (path, size and mtime, so replaced weights miss) and
+/// `system_prompt`. llama-server resolves it inside its `--slot-save-path`.
+fn slot_file_name(model: &Path, system_prompt: &str) -> String {
+    let (size, modified) = model_stamp(model);
+    let material = serde_json::json!({
+        "model": model.to_string_lossy(),
+        "size": size,
+        "modified": modified,
+        "system_prompt": system_prompt,
+    });
+    let digest = Sha256::digest(material.to_string().as_bytes());
+    let hash: String = digest.iter().take(16).map(|b| format!("{b:02x}")).collect();
+    format!("{FILE_PREFIX}{hash}.bin")
+}
+
+/// Folder servers save slots to when `slot_save_path` isn't set, created on first use.
+pub fn default_dir(app: &AppHandle) -> Option<PathBuf> {
+    let dir = app.path().app_data_dir().ok()?.join("slot-caches");
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+/// The slot folder of the running (or last) server: its `slot_save_path`, else the default.
+fn server_dir(app: &AppHandle, state: &AppState) -> Option<PathBuf> {
+    let configured = state.server_config.lock().unwrap().as_ref().and_then(|c| c.slot_save_path.clone());
+    configured.map(PathBuf::from).or_else(|| default_dir(app))
+}
+
+/// Asks llama-server to save or restore slot 0 as `filename`; returns its JSON reply.
+async fn slot_action(state: &AppState, action: &str, filename: &str) -> Result<serde_json::Value, String> {
+    let resp = reqwest::Client::new()
+        .post(llama_url(state, &format!("/slots/0?action={action}")))
+        .json(&serde_json::json!({ "filename": filename }))
+        .send()
+        .await
+        .map_err(|e| format!("llama-server is not reachable: {e}"))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("Slot {action} failed ({status}): {text}"));
+    }
+    resp.json().await.map_err(|e| format!("Invalid slot {action} response: {e}"))
+}
+
+/// Evaluates the system prompt on the running chat server so the next request reuses its
+/// KV cache, restoring or saving the slot file when `slot_save_path` is set. Does nothing
+/// without a system prompt or a loaded chat server. Takes a turn in the chat queue.
+async fn prime(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let Some(system_prompt) = state.settings.lock().unwrap().system_prompt.clone() else {
+        return Ok(());
+    };
+    let ready = state.llama.lock().unwrap().is_some() && state.loading.lock().unwrap().is_none();
+    let Some(model) = state.current_model.lock().unwrap().clone().filter(|_| ready) else {
+        return Ok(());
+    };
+    if *state.server_mode.lock().unwrap() != ServerMode::Chat {
+        return Ok(());
+    }
+    let (save_path, single_slot) = {
+        let config = state.server_config.lock().unwrap();
+        let config = config.as_ref();
+        (
+            config.and_then(|c| c.slot_save_path.clone()),
+            config.and_then(|c| c.parallel_slots).unwrap_or(1) <= 1,
+        )
+    };
+    // With several slots a request may land in any of them, so only one slot is saved
+    let filename = save_path
+        .filter(|_| single_slot)
+        .map(|dir| (PathBuf::from(dir), slot_file_name(Path::new(&model), &system_prompt)));
+
+    let _ticket = queue::enter(app, &state, None).await;
+    if let Some((dir, name)) = &filename {
+        if dir.join(name).is_file() {
+            match slot_action(&state, "restore", name).await {
+                Ok(_) => return Ok(()),
+                Err(e) => eprintln!("Restoring the saved system prompt cache failed, priming instead: {e}"),
+            }
+        }
+    }
+
+    let messages = [ChatMessage {
+        role: "system".into(),
+        content: system_prompt,
+    }];
+    let params = GenParams {
+        max_tokens: Some(1),
+        ..GenParams::default()
+    };
+    let mut body = chat::request_body(&messages, &params, false);
+    if single_slot {
+        body["id_slot"] = serde_json::json!(0);
+    }
+    let resp = reqwest::Client::new()
+        .post(chat::completions_url(&state))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("llama-server is not reachable: {e}"))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("Priming the system prompt failed ({status}): {text}"));
+    }
+
+    if let Some((_, name)) = &filename {
+        slot_action(&state, "save", name).await?;
+    }
+    Ok(())
+}
+
+/// Sidecar of a named cache (<name>.json next to <name>.bin).
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SlotCacheInfo {
+    pub name: String,
+    /// Model the cache was saved with; loading it into another model is rejected.
+    pub model: String,
+    pub model_size: Option<u64>,
+    pub model_modified: Option<u64>,
+    /// Tokens in the saved context (`n_saved`).
+    pub tokens: Option<u64>,
+    /// Size of the `.bin` file.
+    pub bytes: u64,
+    /// Unix epoch milliseconds.
+    pub created_at: u64,
+}
+
+/// Names become file names: letters, digits, `-` and `_` only.
+fn check_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && !name.starts_with(FILE_PREFIX);
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid slot cache name '{name}': use up to {MAX_NAME_LEN} letters, digits, '-' or '_'"
+        )));
+    }
+    Ok(())
+}
+
+/// The running chat server's model, or why slots can't be saved or restored now.
+fn running_model(state: &AppState) -> Result<String, AppError> {
+    if state.llama.lock().unwrap().is_none() || state.loading.lock().unwrap().is_some() {
+        return Err(AppError::ServerNotRunning("No model is loaded".to_string()));
+    }
+    if *state.server_mode.lock().unwrap() != ServerMode::Chat {
+        return Err(AppError::InvalidInput("Slot caches need a server in chat mode".to_string()));
+    }
+    Ok(state.current_model.lock().unwrap().clone().unwrap_or_default())
+}
+
+fn read_info(dir: &Path, name: &str) -> Option<SlotCacheInfo> {
+    let json = std::fs::read_to_string(dir.join(format!("{name}.json"))).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Named slot caches in the current slot folder, newest first. Only entries with both the
+/// `.bin` file and its sidecar are listed.
+#[tauri::command]
+pub fn list_slot_caches(app: AppHandle, state: State<AppState>) -> Result<Vec<SlotCacheInfo>, String> {
+    let dir = server_dir(&app, &state).ok_or("Cannot resolve the slot cache folder")?;
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut caches: Vec<SlotCacheInfo> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path.file_stem()?.to_str()?.to_string();
+            let is_sidecar = path.extension().is_some_and(|e| e == "json");
+            (is_sidecar && dir.join(format!("{name}.bin")).is_file())
+                .then(|| read_info(&dir, &name))
+                .flatten()
+        })
+        .collect();
+    caches.sort_by_key(|c| std::cmp::Reverse(c.created_at));
+    Ok(caches)
+}
+
+/// Saves slot 0 of the running chat server (its whole current context) as `name`,
+/// replacing a cache of that name. Waits for its turn in the chat queue, so an in-flight
+/// reply finishes first.
+#[tauri::command]
+pub async fn save_slot_cache(app: AppHandle, state: State<'_, AppState>, name: String) -> Result<SlotCacheInfo, AppError> {
+    let result = async {
+        check_name(&name)?;
+        let model = running_model(&state)?;
+        let dir = server_dir(&app, &state).ok_or_else(|| AppError::Io("Cannot resolve the slot cache folder".into()))?;
+        let file = format!("{name}.bin");
+
+        let _ticket = queue::enter(&app, &state, None).await;
+        let reply = slot_action(&state, "save", &file).await.map_err(AppError::ServerError)?;
+        let (model_size, model_modified) = model_stamp(Path::new(&model));
+        let info = SlotCacheInfo {
+            name: name.clone(),
+            model,
+            model_size,
+            model_modified,
+            tokens: reply["n_saved"].as_u64(),
+            bytes: std::fs::metadata(dir.join(&file)).map(|m| m.len()).unwrap_or(0),
+            created_at: unix_millis(),
+        };
+        let sidecar = dir.join(format!("{name}.json"));
+        let json = serde_json::to_string_pretty(&info).map_err(|e| AppError::Other(e.to_string()))?;
+        std::fs::write(&sidecar, json).map_err(|e| AppError::Io(format!("Failed to write {}: {}", sidecar.display(), e)))?;
+        Ok(info)
+    }
+    .await;
+    error::report(&app, "save_slot_cache", result)
+}
+
+/// Restores the named cache into slot 0, so the next request that starts with the cached
+/// context skips reprocessing it. Rejects with `model_changed` when the cache was saved
+/// with another model (or the same file has since been replaced), since llama-server would
+/// load the foreign KV data without complaint.
+#[tauri::command]
+pub async fn load_slot_cache(app: AppHandle, state: State<'_, AppState>, name: String) -> Result<SlotCacheInfo, AppError> {
+    let result = async {
+        check_name(&name)?;
+        let model = running_model(&state)?;
+        let dir = server_dir(&app, &state).ok_or_else(|| AppError::Io("Cannot resolve the slot cache folder".into()))?;
+        let info = read_info(&dir, &name)
+            .filter(|_| dir.join(format!("{name}.bin")).is_file())
+            .ok_or_else(|| AppError::InvalidInput(format!("No slot cache named '{name}' in {}", dir.display())))?;
+        if info.model != model || (info.model_size, info.model_modified) != model_stamp(Path::new(&model)) {
+            return Err(AppError::ModelChanged(format!(
+                "Slot cache '{name}' was saved with {}, but {model} is loaded; switch to that model to restore it",
+                info.model
+            )));
+        }
+
+        let _ticket = queue::enter(&app, &state, None).await;
+        slot_action(&state, "restore", &format!("{name}.bin"))
+            .await
+            .map_err(AppError::ServerError)?;
+        Ok(info)
+    }
+    .await;
+    error::report(&app, "load_slot_cache", result)
+}
+
+/// Deletes the named cache and its sidecar. Returns false if there was none.
+#[tauri::command]
+pub fn delete_slot_cache(app: AppHandle, state: State<AppState>, name: String) -> Result<bool, String> {
+    check_name(&name).map_err(|e| e.to_string())?;
+    let dir = server_dir(&app, &state).ok_or("Cannot resolve the slot cache folder")?;
+    let bin = dir.join(format!("{name}.bin"));
+    let existed = bin.is_file();
+    for path in [bin, dir.join(format!("{name}.json"))] {
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(existed)
+}
+
+/// Runs `prime` as a background task; failures are only logged, since requests still work
+/// (just without the head start).
+pub fn prime_in_background(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = prime(&app).await {
+            eprintln!("System prompt cache: {e}");
+        }
+    });
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct SlotCacheCleanup {
+    pub dir: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Deletes the primed system-prompt slots (`genhat-slot-*.bin`) in `path`, or in the
+/// current slot folder when `path` is omitted. Named caches and other files are kept.
+#[tauri::command]
+pub fn clear_slot_cache(app: AppHandle, state: State<AppState>, path: Option<String>) -> Result<SlotCacheCleanup, String> {
+    let dir = path
+        .or_else(|| server_dir(&app, &state).map(|d| d.to_string_lossy().to_string()))
+        .ok_or("Cannot resolve the slot cache folder")?;
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("Failed to read {dir}: {e}"))?;
+    let mut cleanup = SlotCacheCleanup {
+        dir: dir.clone(),
+        files: 0,
+        bytes: 0,
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(FILE_PREFIX) || !name.ends_with(".bin") {
+            continue;
+        }
+        let len = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        match std::fs::remove_file(entry.path()) {
+            Ok(()) => {
+                cleanup.files += 1;
+                cleanup.bytes += len;
+            }
+            Err(e) => eprintln!("Failed to delete {}: {}", entry.path().display(), e),
+        }
+    }
+    Ok(cleanup)
+}
@@ -0,0 +1,120 @@
+// Opt-in cache of generated speech, stored as <app data>/tts-cache/<sha256>.wav.
+// tts-inference runs with its fixed default seed and no reference voice, so the same text,
+// model files and options always produce the same audio; `generate_speech` copies a hit to
+// its output instead of synthesizing again. Streamed runs aren't cached (their chunks are
+// the point). The oldest files are evicted once the folder exceeds the size limit.
+
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::wav::{self, PostProcess, WavInfo};
+use crate::AppState;
+
+/// Size limit when `tts_cache_max_mb` is unset.
+pub const DEFAULT_MAX_MB: u64 = 500;
+
+fn cache_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Cannot resolve app data directory: {e}"))?
+        .join("tts-cache");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// The size limit in bytes while the cache is enabled, `None` while it is off.
+pub fn limit(state: &AppState) -> Option<u64> {
+    let settings = state.settings.lock().unwrap();
+    settings
+        .tts_cache
+        .then(|| settings.tts_cache_max_mb.unwrap_or(DEFAULT_MAX_MB) << 20)
+}
+
+/// Hashes the text and options with each model file's path, size and modification time,
+/// so replacing a model (same name, new weights) misses.
+pub fn cache_key(model_files: &[&Path], text: &str, markup: bool, post: PostProcess) -> String {
+    let files: Vec<serde_json::Value> = model_files
+        .iter()
+        .map(|path| {
+            let meta = std::fs::metadata(path).ok();
+            let modified = meta
+                .as_ref()
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs());
+            serde_json::json!({
+                "path": path.to_string_lossy(),
+                "len": meta.map(|m| m.len()),
+                "modified": modified,
+            })
+        })
+        .collect();
+    let material = serde_json::json!({
+        "files": files,
+        "text": text,
+        "markup": markup,
+        "normalize": post.normalize,
+        "trim_silence": post.trim_silence,
+    });
+    let digest = Sha256::digest(material.to_string().as_bytes());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Copies the cached audio for `key` to `output`; `None` on a miss or an unreadable entry.
+pub fn get(app: &AppHandle, key: &str, output: &Path) -> Option<WavInfo> {
+    let cached = cache_dir(app).ok()?.join(format!("{key}.wav"));
+    if !cached.is_file() {
+        return None;
+    }
+    std::fs::copy(&cached, output).ok()?;
+    // Touched so eviction drops the least recently used files first
+    if let Ok(file) = std::fs::File::options().append(true).open(&cached) {
+        let _ = file.set_modified(std::time::SystemTime::now());
+    }
+    wav::read_wav_info(output).ok()
+}
+
+/// Stores a copy of `audio` under `key`, then evicts down to `max_bytes`.
+pub fn put(app: &AppHandle, key: &str, audio: &Path, max_bytes: u64) -> Result<(), String> {
+    let dir = cache_dir(app)?;
+    std::fs::copy(audio, dir.join(format!("{key}.wav"))).map_err(|e| format!("Failed to write cache entry: {e}"))?;
+    evict(&dir, max_bytes);
+    Ok(())
+}
+
+/// Drops the oldest files (by modification time) until the folder fits in `max_bytes`.
+fn evict(dir: &Path, max_bytes: u64) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    let mut files: Vec<(std::time::SystemTime, u64, PathBuf)> = entries
+        .flatten()
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            Some((meta.modified().ok()?, meta.len(), e.path()))
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort_by_key(|(modified, _, _)| *modified);
+    for (_, len, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        if std::fs::remove_file(path).is_ok() {
+            total -= len;
+        }
+    }
+}
+
+/// Deletes every cached speech file. Returns how many were removed.
+#[tauri::command]
+pub fn clear_tts_cache(app: AppHandle) -> Result<usize, String> {
+    let dir = cache_dir(&app)?;
+    let mut removed = 0;
+    for entry in std::fs::read_dir(&dir).map_err(|e| e.to_string())?.flatten() {
+        if std::fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
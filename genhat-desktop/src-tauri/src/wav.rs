@@ -0,0 +1,281 @@
+// Minimal RIFF/WAVE header reading for files written by tts-inference, plus joining and
+// simple post-processing (peak normalization, silence trimming) of their samples.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Largest `fmt ` chunk accepted: the 18-byte WAVEFORMATEX plus up to 64 bytes of extension
+/// (WAVE_FORMAT_EXTENSIBLE uses 22). A bigger size field means a corrupt or hostile file.
+const MAX_FMT_LEN: u32 = 18 + 64;
+
+#[derive(Clone)]
+pub struct WavInfo {
+    /// `1` = integer PCM, `3` = IEEE float (resolved from the subformat of extensible files).
+    pub format_tag: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub block_align: u16,
+    pub bits_per_sample: u16,
+    /// Size of the `data` chunk in bytes.
+    pub data_len: u32,
+    /// File offset of the first sample byte.
+    pub data_offset: u64,
+}
+
+impl WavInfo {
+    pub fn duration_secs(&self) -> f64 {
+        let bytes_per_sec = self.sample_rate as f64 * self.block_align as f64;
+        if bytes_per_sec == 0.0 {
+            0.0
+        } else {
+            self.data_len as f64 / bytes_per_sec
+        }
+    }
+}
+
+/// Reads the `fmt ` and `data` chunk headers without loading the samples.
+/// Fails on anything that isn't a plausible, non-empty WAV file.
+pub fn read_wav_info(path: &Path) -> Result<WavInfo, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+
+    let mut riff = [0u8; 12];
+    file.read_exact(&mut riff)
+        .map_err(|_| format!("{} is too short to be a WAV file", path.display()))?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(format!("{} is not a RIFF/WAVE file", path.display()));
+    }
+
+    let mut fmt: Option<(u16, u16, u32, u16, u16)> = None;
+    loop {
+        let mut header = [0u8; 8];
+        if file.read_exact(&mut header).is_err() {
+            return Err(format!("{} has no data chunk", path.display()));
+        }
+        let id = &header[0..4];
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+        if id == b"fmt " {
+            if !(16..=MAX_FMT_LEN).contains(&size) {
+                return Err(format!("{} has an invalid fmt chunk ({size} bytes)", path.display()));
+            }
+            let mut body = vec![0u8; size as usize];
+            file.read_exact(&mut body)
+                .map_err(|_| format!("{} has a truncated fmt chunk", path.display()))?;
+            if size & 1 == 1 {
+                let _ = file.read_exact(&mut [0u8; 1]);
+            }
+            // WAVE_FORMAT_EXTENSIBLE carries the real format tag at the start of its subformat GUID
+            let mut format_tag = u16::from_le_bytes([body[0], body[1]]);
+            if format_tag == 0xFFFE && body.len() >= 26 {
+                format_tag = u16::from_le_bytes([body[24], body[25]]);
+            }
+            fmt = Some((
+                format_tag,
+                u16::from_le_bytes([body[2], body[3]]),
+                u32::from_le_bytes([body[4], body[5], body[6], body[7]]),
+                u16::from_le_bytes([body[12], body[13]]),
+                u16::from_le_bytes([body[14], body[15]]),
+            ));
+        } else if id == b"data" {
+            let (format_tag, channels, sample_rate, block_align, bits_per_sample) =
+                fmt.ok_or_else(|| format!("{} has a data chunk before its fmt chunk", path.display()))?;
+            if channels == 0 || sample_rate == 0 || block_align == 0 {
+                return Err(format!("{} has an invalid format header", path.display()));
+            }
+            if size == 0 {
+                return Err(format!("{} contains no audio samples", path.display()));
+            }
+            let data_offset = file
+                .stream_position()
+                .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+            let file_len = file
+                .metadata()
+                .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?
+                .len();
+            if file_len < data_offset + size as u64 {
+                return Err(format!(
+                    "{} is truncated: the data chunk claims {} bytes but only {} follow",
+                    path.display(),
+                    size,
+                    file_len - data_offset
+                ));
+            }
+            return Ok(WavInfo {
+                format_tag,
+                channels,
+                sample_rate,
+                block_align,
+                bits_per_sample,
+                data_len: size,
+                data_offset,
+            });
+        } else {
+            // Skip unknown chunks (LIST, fact, ...). Chunks are padded to an even size.
+            let skip = size as u64 + (size as u64 & 1);
+            std::io::copy(&mut (&mut file).take(skip), &mut std::io::sink())
+                .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        }
+    }
+}
+
+/// Joins WAV files with identical formats into `output`: the first file's header (fmt and
+/// any other chunks before `data`) followed by every file's samples, with sizes patched.
+pub fn concat_wavs(parts: &[std::path::PathBuf], output: &Path) -> Result<WavInfo, String> {
+    let first = parts.first().ok_or("No WAV files to join")?;
+    let infos = parts.iter().map(|p| read_wav_info(p)).collect::<Result<Vec<_>, _>>()?;
+    let base = &infos[0];
+    if let Some((path, _)) = parts.iter().zip(&infos).find(|(_, i)| {
+        (i.channels, i.sample_rate, i.block_align) != (base.channels, base.sample_rate, base.block_align)
+    }) {
+        return Err(format!("{} has a different audio format than {}", path.display(), first.display()));
+    }
+    let total: u64 = infos.iter().map(|i| i.data_len as u64).sum();
+    let total = u32::try_from(total).map_err(|_| "Joined audio exceeds the 4 GB WAV limit".to_string())?;
+
+    let write_err = |e: std::io::Error| format!("Cannot write {}: {}", output.display(), e);
+    let mut out = std::fs::File::create(output).map_err(write_err)?;
+    // Header up to (not including) the data chunk's id and size
+    let mut header = vec![0u8; (base.data_offset - 8) as usize];
+    std::fs::File::open(first)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .map_err(|e| format!("Cannot read {}: {}", first.display(), e))?;
+    out.write_all(&header).map_err(write_err)?;
+    out.write_all(b"data").map_err(write_err)?;
+    out.write_all(&total.to_le_bytes()).map_err(write_err)?;
+
+    for (path, info) in parts.iter().zip(&infos) {
+        let mut file = std::fs::File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        file.seek(SeekFrom::Start(info.data_offset))
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        std::io::copy(&mut file.take(info.data_len as u64), &mut out).map_err(write_err)?;
+    }
+    if total & 1 == 1 {
+        out.write_all(&[0]).map_err(write_err)?;
+    }
+    // RIFF size covers everything after the first 8 bytes
+    let riff_len = out.stream_position().map_err(write_err)? - 8;
+    out.seek(SeekFrom::Start(4)).map_err(write_err)?;
+    out.write_all(&(riff_len as u32).to_le_bytes()).map_err(write_err)?;
+    drop(out);
+
+    read_wav_info(output)
+}
+
+/// Optional clean-up applied to generated speech. Both off by default.
+#[derive(Clone, Copy, Default)]
+pub struct PostProcess {
+    /// Scale so the loudest sample peaks at `NORMALIZE_PEAK`.
+    pub normalize: bool,
+    /// Cut leading/trailing audio quieter than `SILENCE_THRESHOLD`, keeping `SILENCE_PAD_MS`.
+    pub trim_silence: bool,
+}
+
+/// Normalization target, about -1 dBFS, leaving headroom against clipping on playback.
+const NORMALIZE_PEAK: f32 = 0.89;
+
+/// Samples below this amplitude (about -40 dBFS) count as silence.
+const SILENCE_THRESHOLD: f32 = 0.01;
+
+/// Silence kept around the trimmed audio so words aren't clipped and joined chunks still pause.
+const SILENCE_PAD_MS: u32 = 100;
+
+/// Decodes the samples to `[-1, 1]` floats. Supports 16-bit PCM and 32-bit float.
+fn decode_samples(info: &WavInfo, bytes: &[u8]) -> Result<Vec<f32>, String> {
+    match (info.format_tag, info.bits_per_sample) {
+        (1, 16) => Ok(bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect()),
+        (3, 32) => Ok(bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()),
+        (tag, bits) => Err(format!("Unsupported WAV sample format (format {tag}, {bits}-bit)")),
+    }
+}
+
+fn encode_samples(info: &WavInfo, samples: &[f32]) -> Vec<u8> {
+    match info.bits_per_sample {
+        16 => samples
+            .iter()
+            .flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0).round() as i16).to_le_bytes())
+            .collect(),
+        _ => samples.iter().flat_map(|s| s.to_le_bytes()).collect(),
+    }
+}
+
+/// Frame range left after trimming silence from both ends; the whole range if it's all silent.
+fn audible_frames(samples: &[f32], channels: usize, sample_rate: u32) -> std::ops::Range<usize> {
+    let frames = samples.len() / channels;
+    let loud = |f: &usize| samples[f * channels..(f + 1) * channels].iter().any(|s| s.abs() > SILENCE_THRESHOLD);
+    let (Some(first), Some(last)) = ((0..frames).find(loud), (0..frames).rev().find(loud)) else {
+        return 0..frames;
+    };
+    let pad = (sample_rate * SILENCE_PAD_MS / 1000) as usize;
+    first.saturating_sub(pad)..(last + 1 + pad).min(frames)
+}
+
+/// Applies `options` to the WAV at `path` in place, rewriting it with a canonical
+/// 44-byte header. A no-op when both options are off.
+pub fn post_process(path: &Path, options: PostProcess) -> Result<WavInfo, String> {
+    let info = read_wav_info(path)?;
+    if !options.normalize && !options.trim_silence {
+        return Ok(info);
+    }
+
+    let mut bytes = vec![0u8; info.data_len as usize];
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+    file.seek(SeekFrom::Start(info.data_offset))
+        .and_then(|_| file.read_exact(&mut bytes))
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    drop(file);
+    let mut samples = decode_samples(&info, &bytes)?;
+
+    let channels = info.channels as usize;
+    if options.trim_silence {
+        let frames = audible_frames(&samples, channels, info.sample_rate);
+        samples = samples[frames.start * channels..frames.end * channels].to_vec();
+    }
+    if options.normalize {
+        let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        // Leave (near-)silent audio alone rather than amplifying noise
+        if peak > SILENCE_THRESHOLD {
+            let gain = NORMALIZE_PEAK / peak;
+            samples.iter_mut().for_each(|s| *s *= gain);
+        }
+    }
+
+    write_canonical(path, &info, &encode_samples(&info, &samples))?;
+    read_wav_info(path)
+}
+
+/// Writes `ms` of digital silence to `output` in the same format as `like`.
+pub fn write_silence(like: &WavInfo, ms: u32, output: &Path) -> Result<WavInfo, String> {
+    let frames = (like.sample_rate as u64 * ms as u64 / 1000).max(1);
+    let data = vec![0u8; (frames * like.block_align as u64) as usize];
+    write_canonical(output, like, &data)?;
+    read_wav_info(output)
+}
+
+/// Writes `data` as a WAV with a canonical 44-byte header in `info`'s format.
+fn write_canonical(path: &Path, info: &WavInfo, data: &[u8]) -> Result<(), String> {
+    let byte_rate = info.sample_rate * info.block_align as u32;
+    let mut out = Vec::with_capacity(44 + data.len() + 1);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data.len() as u32 + (data.len() as u32 & 1)).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&info.format_tag.to_le_bytes());
+    out.extend_from_slice(&info.channels.to_le_bytes());
+    out.extend_from_slice(&info.sample_rate.to_le_bytes());
+    out.extend_from_slice(&byte_rate.to_le_bytes());
+    out.extend_from_slice(&info.block_align.to_le_bytes());
+    out.extend_from_slice(&info.bits_per_sample.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() & 1 == 1 {
+        out.push(0);
+    }
+    std::fs::write(path, out).map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
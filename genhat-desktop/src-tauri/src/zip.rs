@@ -0,0 +1,317 @@
+// Minimal ZIP archives: writing stored (uncompressed) entries for backups, and reading
+// stored or deflated entries, which covers backups as well as DOCX / PPTX files. No ZIP64,
+// so at most 65535 entries and 4 GiB.
+
+use std::io::Read;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR: u32 = 0x0605_4b50;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+/// General purpose flag bit 11: names are UTF-8.
+const UTF8_NAMES: u16 = 1 << 11;
+/// ZIP 2.0, the oldest version with folders.
+const ZIP_VERSION: u16 = 20;
+/// DOS date of 1980-01-01, the earliest a ZIP entry can carry.
+const DOS_EPOCH_DATE: u16 = (1 << 5) | 1;
+/// Largest entry `read_zip` inflates, so a ZIP bomb can't exhaust memory.
+const MAX_ENTRY_BYTES: usize = 256 << 20;
+
+/// CRC-32 (IEEE), as ZIP entries carry.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Header fields shared by the local and central headers, from "version needed" through
+/// "extra field length".
+fn put_entry_fields(buf: &mut Vec<u8>, crc: u32, size: u32, name_len: u16) {
+    buf.extend(ZIP_VERSION.to_le_bytes());
+    buf.extend(UTF8_NAMES.to_le_bytes());
+    buf.extend(METHOD_STORED.to_le_bytes());
+    buf.extend(0u16.to_le_bytes()); // time
+    buf.extend(DOS_EPOCH_DATE.to_le_bytes());
+    buf.extend(crc.to_le_bytes());
+    buf.extend(size.to_le_bytes()); // compressed
+    buf.extend(size.to_le_bytes()); // uncompressed
+    buf.extend(name_len.to_le_bytes());
+    buf.extend(0u16.to_le_bytes()); // extra field length
+}
+
+/// A ZIP archive of `entries`, stored uncompressed. No ZIP64: at most 65535 entries, 4 GiB.
+pub fn write_zip(entries: &[(String, Vec<u8>)]) -> Result<Vec<u8>, String> {
+    let too_large = || "Too much data for a ZIP archive".to_string();
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in entries {
+        let offset = u32::try_from(out.len()).map_err(|_| too_large())?;
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let name_len = u16::try_from(name.len()).map_err(|_| format!("Entry name too long: {name}"))?;
+        let crc = crc32(data);
+
+        out.extend(LOCAL_HEADER.to_le_bytes());
+        put_entry_fields(&mut out, crc, size, name_len);
+        out.extend(name.as_bytes());
+        out.extend(data);
+
+        central.extend(CENTRAL_HEADER.to_le_bytes());
+        central.extend(ZIP_VERSION.to_le_bytes()); // version made by
+        put_entry_fields(&mut central, crc, size, name_len);
+        central.extend(0u16.to_le_bytes()); // comment length
+        central.extend(0u16.to_le_bytes()); // disk number
+        central.extend(0u16.to_le_bytes()); // internal attributes
+        central.extend(0u32.to_le_bytes()); // external attributes
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+    }
+    let count = u16::try_from(entries.len()).map_err(|_| too_large())?;
+    let central_offset = u32::try_from(out.len()).map_err(|_| too_large())?;
+    let central_size = u32::try_from(central.len()).map_err(|_| too_large())?;
+    out.extend(central);
+    out.extend(END_OF_CENTRAL_DIR.to_le_bytes());
+    out.extend(0u16.to_le_bytes()); // this disk
+    out.extend(0u16.to_le_bytes()); // disk with the central directory
+    out.extend(count.to_le_bytes());
+    out.extend(count.to_le_bytes());
+    out.extend(central_size.to_le_bytes());
+    out.extend(central_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes()); // comment length
+    Ok(out)
+}
+
+fn truncated() -> String {
+    "The archive is truncated".to_string()
+}
+
+/// `at + len`, as an offset into the archive. Every offset and length comes from the file,
+/// so the sum is checked rather than trusted.
+fn offset(at: usize, len: usize) -> Result<usize, String> {
+    at.checked_add(len).ok_or_else(truncated)
+}
+
+/// The `len` bytes at `at`.
+fn slice_at(bytes: &[u8], at: usize, len: usize) -> Result<&[u8], String> {
+    bytes.get(at..offset(at, len)?).ok_or_else(truncated)
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Result<u16, String> {
+    slice_at(bytes, at, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Result<u32, String> {
+    slice_at(bytes, at, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Raw DEFLATE data of an entry whose central header says it inflates to `size` bytes.
+fn inflate(data: &[u8], size: usize) -> Result<Vec<u8>, String> {
+    if size > MAX_ENTRY_BYTES {
+        return Err(format!("entry is larger than {} MiB", MAX_ENTRY_BYTES >> 20));
+    }
+    let mut out = Vec::with_capacity(size);
+    flate2::read::DeflateDecoder::new(data)
+        .take(MAX_ENTRY_BYTES as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| format!("corrupt compressed data ({e})"))?;
+    if out.len() > MAX_ENTRY_BYTES {
+        return Err(format!("entry is larger than {} MiB", MAX_ENTRY_BYTES >> 20));
+    }
+    Ok(out)
+}
+
+/// Entries of a ZIP archive, inflated and CRC-checked. Stored and deflated entries are
+/// supported (what `export_app_data` and office suites write); folder entries are dropped,
+/// and so are entries `wanted` rejects, before they are inflated.
+pub fn read_zip(bytes: &[u8], wanted: impl Fn(&str) -> bool) -> Result<Vec<(String, Vec<u8>)>, String> {
+    // The end record is the last 22 bytes, unless a comment (up to 64 KiB) follows it
+    let last = bytes.len().checked_sub(22).ok_or("The file is too short to be a ZIP archive")?;
+    let end = (last.saturating_sub(u16::MAX as usize)..=last)
+        .rev()
+        .find(|&i| u32_at(bytes, i) == Ok(END_OF_CENTRAL_DIR))
+        .ok_or("The file is not a ZIP archive")?;
+    let count = u16_at(bytes, offset(end, 10)?)?;
+    let mut at = u32_at(bytes, offset(end, 16)?)? as usize;
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        if u32_at(bytes, at)? != CENTRAL_HEADER {
+            return Err("The archive's central directory is corrupt".to_string());
+        }
+        // The fixed part of a central header is 46 bytes; check it once, then read fields
+        let header = slice_at(bytes, at, 46)?;
+        let flags = u16_at(header, 8)?;
+        let method = u16_at(header, 10)?;
+        let crc = u32_at(header, 16)?;
+        let size = u32_at(header, 20)? as usize;
+        let uncompressed = u32_at(header, 24)? as usize;
+        let name_len = u16_at(header, 28)? as usize;
+        let extra_len = u16_at(header, 30)? as usize;
+        let comment_len = u16_at(header, 32)? as usize;
+        let local = u32_at(header, 42)? as usize;
+        let name_start = offset(at, 46)?;
+        let name = String::from_utf8_lossy(slice_at(bytes, name_start, name_len)?).to_string();
+        at = offset(offset(offset(name_start, name_len)?, extra_len)?, comment_len)?;
+
+        if name.ends_with('/') || !wanted(&name) {
+            continue;
+        }
+        if flags & 1 == 1 {
+            return Err(format!("{name} is encrypted"));
+        }
+        if u32_at(bytes, local)? != LOCAL_HEADER {
+            return Err(format!("The local header of {name} is corrupt"));
+        }
+        // The local header has its own name and extra lengths, which needn't match the central ones
+        let name_len = u16_at(bytes, offset(local, 26)?)? as usize;
+        let extra_len = u16_at(bytes, offset(local, 28)?)? as usize;
+        let data_start = offset(offset(offset(local, 30)?, name_len)?, extra_len)?;
+        let raw = slice_at(bytes, data_start, size)?;
+        let data = match method {
+            METHOD_STORED => raw.to_vec(),
+            METHOD_DEFLATE => inflate(raw, uncompressed).map_err(|e| format!("{name}: {e}"))?,
+            _ => return Err(format!("{name} uses unsupported compression method {method}")),
+        };
+        if crc32(&data) != crc {
+            return Err(format!("{name} is corrupt (CRC mismatch)"));
+        }
+        entries.push((name, data));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A one-entry archive holding `name` deflated, as office suites write them.
+    fn deflated_zip(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        let packed = encoder.finish().unwrap();
+        let fields = |buf: &mut Vec<u8>| {
+            buf.extend(ZIP_VERSION.to_le_bytes());
+            buf.extend(0u16.to_le_bytes()); // flags
+            buf.extend(METHOD_DEFLATE.to_le_bytes());
+            buf.extend(0u16.to_le_bytes());
+            buf.extend(DOS_EPOCH_DATE.to_le_bytes());
+            buf.extend(crc32(data).to_le_bytes());
+            buf.extend((packed.len() as u32).to_le_bytes());
+            buf.extend((data.len() as u32).to_le_bytes());
+            buf.extend((name.len() as u16).to_le_bytes());
+            buf.extend(0u16.to_le_bytes());
+        };
+        let mut out = LOCAL_HEADER.to_le_bytes().to_vec();
+        fields(&mut out);
+        out.extend(name.as_bytes());
+        out.extend(&packed);
+        let central_offset = out.len() as u32;
+        out.extend(CENTRAL_HEADER.to_le_bytes());
+        out.extend(ZIP_VERSION.to_le_bytes());
+        fields(&mut out);
+        out.extend([0u8; 10]); // comment length, disk, internal and external attributes
+        out.extend(0u32.to_le_bytes()); // local header offset
+        out.extend(name.as_bytes());
+        let central_size = out.len() as u32 - central_offset;
+        out.extend(END_OF_CENTRAL_DIR.to_le_bytes());
+        out.extend([0u8; 4]);
+        out.extend(1u16.to_le_bytes());
+        out.extend(1u16.to_le_bytes());
+        out.extend(central_size.to_le_bytes());
+        out.extend(central_offset.to_le_bytes());
+        out.extend(0u16.to_le_bytes());
+        out
+    }
+
+    fn sample() -> Vec<(String, Vec<u8>)> {
+        vec![
+            ("settings.json".to_string(), br#"{"theme":"dark"}"#.to_vec()),
+            ("conversations/".to_string(), Vec::new()),
+            ("conversations/a b.json".to_string(), "héllo".as_bytes().to_vec()),
+            ("empty".to_string(), Vec::new()),
+        ]
+    }
+
+    #[test]
+    fn crc32_matches_the_reference_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn stored_round_trip() {
+        let bytes = write_zip(&sample()).unwrap();
+        let entries = read_zip(&bytes, |_| true).unwrap();
+        let expected: Vec<_> = sample().into_iter().filter(|(name, _)| !name.ends_with('/')).collect();
+        assert_eq!(entries, expected);
+        let only_json = read_zip(&bytes, |name| name.ends_with(".json")).unwrap();
+        assert_eq!(only_json.len(), 2);
+    }
+
+    #[test]
+    fn reads_deflated_entries_and_trailing_comments() {
+        let text = "word/document.xml ".repeat(100);
+        let mut bytes = deflated_zip("word/document.xml", text.as_bytes());
+        assert_eq!(read_zip(&bytes, |_| true).unwrap(), vec![("word/document.xml".to_string(), text.into_bytes())]);
+        // An archive comment after the end record
+        let len = bytes.len();
+        bytes[len - 2..].copy_from_slice(&5u16.to_le_bytes());
+        bytes.extend(b"notes");
+        assert_eq!(read_zip(&bytes, |_| true).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_corrupt_entries() {
+        let mut bytes = write_zip(&sample()).unwrap();
+        let at = bytes.windows(6).position(|w| w == "héllo".as_bytes()).unwrap();
+        bytes[at] = b'H';
+        assert!(read_zip(&bytes, |_| true).unwrap_err().contains("CRC mismatch"));
+        // Entries that aren't wanted aren't checked either
+        assert!(read_zip(&bytes, |name| name == "settings.json").is_ok());
+
+        assert!(read_zip(b"short", |_| true).unwrap_err().contains("too short"));
+        assert!(read_zip(&[0u8; 100], |_| true).unwrap_err().contains("not a ZIP"));
+    }
+
+    #[test]
+    fn hostile_offsets_and_lengths_are_errors() {
+        let bytes = write_zip(&sample()).unwrap();
+        let end = bytes.len() - 22;
+        let central = u32_at(&bytes, end + 16).unwrap() as usize;
+        // (position, field width) pairs pointing at every offset or length the reader follows
+        let fields = [
+            (end + 16, 4),     // central directory offset
+            (central + 20, 4), // compressed size
+            (central + 28, 2), // name length
+            (central + 30, 2), // extra length
+            (central + 32, 2), // comment length
+            (central + 42, 4), // local header offset
+            (26, 2),           // local name length
+            (28, 2),           // local extra length
+        ];
+        for (at, width) in fields {
+            let mut damaged = bytes.clone();
+            damaged[at..at + width].fill(0xFF);
+            assert!(read_zip(&damaged, |_| true).is_err(), "field at {at}");
+        }
+        // More entries than the directory holds
+        let mut damaged = bytes.clone();
+        damaged[end + 10..end + 12].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(read_zip(&damaged, |_| true).is_err());
+    }
+
+    #[test]
+    fn truncated_archives_are_errors() {
+        for bytes in [write_zip(&sample()).unwrap(), deflated_zip("a.txt", &[7u8; 4000])] {
+            for len in 0..bytes.len() {
+                assert!(read_zip(&bytes[..len], |_| true).is_err(), "{len} of {} bytes", bytes.len());
+            }
+        }
+    }
+}
@@ -4,15 +4,48 @@ import { invoke, convertFileSrc } from "@tauri-apps/api/core";
 interface ModelFile {
   name: string;
   path: string;
+  pinned: boolean;
+  alias: string | null;
+  category: "chat" | "code" | "embedding" | "vision" | "tts" | "unknown";
+  shards: number | null;
+  unusable_reason: string | null;
+}
+
+interface AudioModel {
+  name: string;
+  path: string;
+  usable: boolean;
+  missing: string[];
+}
+
+interface SpeechResult {
+  path: string;
+  duration_secs: number;
+  sample_rate: number;
+  channels: number;
+}
+
+// Shape of the typed errors returned by backend commands.
+interface AppError {
+  code: string;
+  message: string;
+}
+
+function errorMessage(e: unknown): string {
+  if (typeof e === "object" && e !== null && "message" in e) {
+    return (e as AppError).message;
+  }
+  return String(e);
 }
 
 function App() {
   const [models, setModels] = useState<ModelFile[]>([]);
   const [selectedModel, setSelectedModel] = useState("");
   
-  const [audioModels, setAudioModels] = useState<ModelFile[]>([]);
+  const [audioModels, setAudioModels] = useState<AudioModel[]>([]);
   const [selectedAudioModel, setSelectedAudioModel] = useState("None");
   const [audioOutput, setAudioOutput] = useState("");
+  const [ttsAvailable, setTtsAvailable] = useState(false);
 
   const [prompt, setPrompt] = useState("");
   const [response, setResponse] = useState("");
@@ -28,11 +61,16 @@ function App() {
       })
       .catch(console.error);
 
-    invoke<ModelFile[]>("list_audio_models")
+    invoke<AudioModel[]>("list_audio_models")
       .then((list) => {
         setAudioModels(list);
       })
       .catch(console.error);
+
+    // Builds without the bundled tts-inference binary hide the audio picker
+    invoke<boolean>("tts_available")
+      .then(setTtsAvailable)
+      .catch(console.error);
   }, []);
 
   const handleModelChange = async (path: string) => {
@@ -43,7 +81,7 @@ function App() {
       alert(`Switched to model: ${path}`);
     } catch (err) {
       console.error(err);
-      alert("Failed to switch model");
+      alert(`Failed to switch model: ${errorMessage(err)}`);
     }
   };
 
@@ -56,14 +94,14 @@ function App() {
       // Audio Mode Check
       if (selectedAudioModel && selectedAudioModel !== "None") {
          try {
-           const path = await invoke<string>("generate_speech", {
+           const result = await invoke<SpeechResult>("generate_speech", {
              modelPath: selectedAudioModel,
              input: prompt,
            });
-           setAudioOutput(convertFileSrc(path));
+           setAudioOutput(convertFileSrc(result.path));
          } catch (e) {
            console.error(e);
-           setResponse(`Error generating audio: ${e}`);
+           setResponse(`Error generating audio: ${errorMessage(e)}`);
          }
          setLoading(false);
          return;
@@ -152,7 +190,7 @@ function App() {
           </select>
         </div>
 
-        <div>
+        {ttsAvailable && <div>
           <label htmlFor="audio-select" style={{ display: 'block', marginBottom: '5px' }}>Audio Model:</label>
           <select
             id="audio-select"
@@ -163,12 +201,17 @@ function App() {
           >
             <option value="None">None (Text Chat)</option>
             {audioModels.map((m) => (
-              <option key={m.path} value={m.path}>
+              <option
+                key={m.path}
+                value={m.path}
+                disabled={!m.usable}
+                title={m.usable ? undefined : `Missing: ${m.missing.join(", ")}`}
+              >
                 {m.name}
               </option>
             ))}
           </select>
-        </div>
+        </div>}
       </div>
 
       <textarea